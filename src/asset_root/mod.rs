@@ -0,0 +1,312 @@
+use amethyst::error::Error;
+use std::{
+    env,
+    ffi::OsString,
+    fmt,
+    path::{Path, PathBuf},
+};
+
+/// Environment variable naming the asset directory, checked after `--assets`.
+pub const ASSETS_ENV_VAR: &str = "AMETHYST_2D_PLAYGROUND_ASSETS";
+/// Directory below the XDG data directories an installed game keeps its assets in.
+const DATA_DIR_NAME: &str = "amethyst-2d-playground/resources";
+/// Directory below the config and data directories of the user the game keeps their files in.
+const USER_DIR_NAME: &str = "amethyst-2d-playground";
+/// File every asset directory holds, telling it apart from unrelated directories of the same name.
+const MARKER_FILE: &str = "config/pong.ron";
+
+/// Where a candidate asset directory came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AssetRootOrigin {
+    CommandLine,
+    Environment,
+    Executable,
+    DataDir,
+    DevCheckout,
+}
+
+impl fmt::Display for AssetRootOrigin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::CommandLine => write!(f, "--assets"),
+            Self::Environment => write!(f, "${}", ASSETS_ENV_VAR),
+            Self::Executable => write!(f, "next to the executable"),
+            Self::DataDir => write!(f, "data directory"),
+            Self::DevCheckout => write!(f, "development checkout"),
+        }
+    }
+}
+
+/// Locations the asset directory is looked for, in the order they are tried. A directory given on
+/// the command line or in the environment is used as is: if it holds no assets, the game fails
+/// instead of quietly falling back to another installation.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AssetRoots {
+    pub command_line: Option<PathBuf>,
+    pub environment: Option<PathBuf>,
+    /// `resources` next to the executable, for a build copied around as a whole.
+    pub executable: Option<PathBuf>,
+    /// `XDG_DATA_HOME` followed by the `XDG_DATA_DIRS`, for an installed game.
+    pub data_dirs: Vec<PathBuf>,
+    /// `resources` of the checkout the game was built from, for `cargo run` and the tests.
+    pub dev_checkout: Option<PathBuf>,
+}
+
+impl AssetRoots {
+    /// Collects the locations from the environment of the process, with the directory given by
+    /// `--assets`.
+    pub fn from_env(command_line: Option<PathBuf>) -> Self {
+        Self {
+            command_line,
+            environment: env::var_os(ASSETS_ENV_VAR).filter(|dir| !dir.is_empty()).map(PathBuf::from),
+            executable: env::current_exe()
+                .ok()
+                .and_then(|exe| exe.parent().map(|dir| dir.join("resources"))),
+            data_dirs: xdg_data_dirs(
+                env::var_os("XDG_DATA_HOME"),
+                env::var_os("XDG_DATA_DIRS"),
+                env::var_os("HOME"),
+            )
+            .into_iter()
+            .map(|dir| dir.join(DATA_DIR_NAME))
+            .collect(),
+            dev_checkout: option_env!("CARGO_MANIFEST_DIR").map(|dir| Path::new(dir).join("resources")),
+        }
+    }
+
+    /// Every candidate in the order they are tried, stopping at the explicit ones.
+    pub fn candidates(&self) -> Vec<(AssetRootOrigin, &Path)> {
+        if let Some(dir) = &self.command_line {
+            return vec![(AssetRootOrigin::CommandLine, dir)];
+        }
+        if let Some(dir) = &self.environment {
+            return vec![(AssetRootOrigin::Environment, dir)];
+        }
+        let mut candidates = Vec::new();
+        if let Some(dir) = &self.executable {
+            candidates.push((AssetRootOrigin::Executable, dir.as_path()));
+        }
+        candidates.extend(self.data_dirs.iter().map(|dir| (AssetRootOrigin::DataDir, dir.as_path())));
+        if let Some(dir) = &self.dev_checkout {
+            candidates.push((AssetRootOrigin::DevCheckout, dir.as_path()));
+        }
+        candidates
+    }
+
+    /// The absolute path of the first candidate holding the assets. The error lists every location
+    /// tried.
+    pub fn resolve(&self) -> Result<PathBuf, Error> {
+        let candidates = self.candidates();
+        for (origin, dir) in &candidates {
+            if dir.join(MARKER_FILE).is_file() {
+                let dir = dunce::canonicalize(dir)?;
+                log::debug!("Using the assets in {} ({})", dir.display(), origin);
+                return Ok(dir);
+            }
+        }
+        let mut message = format!("no assets found, looked for {} in:", MARKER_FILE);
+        for (origin, dir) in &candidates {
+            message.push_str(&format!("\n  {} ({})", dir.display(), origin));
+        }
+        Err(Error::from_string(message))
+    }
+}
+
+/// Writable directories of the user playing. The asset directory may belong to an installation the
+/// user cannot write to, and is replaced as a whole on updates.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UserDirs {
+    /// Settings and bindings changed in the menus.
+    pub config: PathBuf,
+    /// Saves and replays.
+    pub data: PathBuf,
+}
+
+impl UserDirs {
+    /// The directories of the user running the process, `None` if the environment names no home.
+    pub fn from_env() -> Option<Self> {
+        user_dirs(
+            env::var_os("XDG_CONFIG_HOME"),
+            env::var_os("XDG_DATA_HOME"),
+            env::var_os("HOME"),
+            env::var_os("APPDATA"),
+        )
+    }
+
+    /// Directories next to `assets_dir`, for an environment without a home.
+    pub fn next_to(assets_dir: &Path) -> Self {
+        let app_root = assets_dir.parent().unwrap_or(assets_dir);
+        Self {
+            config: app_root.join("config"),
+            data: app_root.to_path_buf(),
+        }
+    }
+}
+
+/// The config and data directories of the user on this platform: below `APPDATA` on Windows,
+/// `Library/Application Support` on macOS, and the XDG config and data homes elsewhere.
+fn user_dirs(
+    config_home: Option<OsString>,
+    data_home: Option<OsString>,
+    home: Option<OsString>,
+    app_data: Option<OsString>,
+) -> Option<UserDirs> {
+    let (config, data) = if cfg!(windows) {
+        let app_data = PathBuf::from(app_data.filter(|dir| !dir.is_empty())?);
+        (app_data.clone(), app_data)
+    } else if cfg!(target_os = "macos") {
+        let support = Path::new(&home.filter(|dir| !dir.is_empty())?).join("Library/Application Support");
+        (support.clone(), support)
+    } else {
+        (
+            xdg_home(config_home, home.as_ref(), ".config")?,
+            xdg_home(data_home, home.as_ref(), ".local/share")?,
+        )
+    };
+    Some(UserDirs {
+        config: config.join(USER_DIR_NAME),
+        data: data.join(USER_DIR_NAME),
+    })
+}
+
+/// The XDG base directory set in `dir`, or `default` below the `home` directory. Relative
+/// directories are invalid and ignored, see the XDG Base Directory Specification.
+fn xdg_home(dir: Option<OsString>, home: Option<&OsString>, default: &str) -> Option<PathBuf> {
+    match dir.map(PathBuf::from).filter(|dir| dir.is_absolute()) {
+        Some(dir) => Some(dir),
+        None => home.filter(|home| !home.is_empty()).map(|home| Path::new(home).join(default)),
+    }
+}
+
+/// The XDG base directories for data, most important first, see the XDG Base Directory
+/// Specification.
+fn xdg_data_dirs(data_home: Option<OsString>, data_dirs: Option<OsString>, home: Option<OsString>) -> Vec<PathBuf> {
+    let mut dirs: Vec<PathBuf> = xdg_home(data_home, home.as_ref(), ".local/share").into_iter().collect();
+    let data_dirs = data_dirs
+        .filter(|dirs| !dirs.is_empty())
+        .unwrap_or_else(|| "/usr/local/share:/usr/share".into());
+    dirs.extend(env::split_paths(&data_dirs).filter(|dir| dir.is_absolute()));
+    dirs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    /// An empty directory below the temporary directory, with the assets marker if `assets`.
+    fn temp_dir(name: &str, assets: bool) -> PathBuf {
+        let dir = crate::test_harness::temp_path(name);
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("config")).expect("failed to create temporary directory");
+        if assets {
+            fs::write(dir.join(MARKER_FILE), "()").expect("failed to write marker file");
+        }
+        dir
+    }
+
+    #[test]
+    fn dev_checkout_holds_assets() {
+        let assets_dir = AssetRoots::default().resolve();
+        assert!(assets_dir.is_err());
+        let dev_checkout = Path::new(env!("CARGO_MANIFEST_DIR")).join("resources");
+        let assets_dir = AssetRoots {
+            dev_checkout: Some(dev_checkout.clone()),
+            ..AssetRoots::default()
+        }
+        .resolve()
+        .expect("no assets in the checkout");
+        assert_eq!(assets_dir, dunce::canonicalize(dev_checkout).expect("valid path"));
+    }
+
+    #[test]
+    fn first_candidate_with_assets_wins() {
+        let empty = temp_dir("empty-assets", false);
+        let installed = temp_dir("installed-assets", true);
+        let roots = AssetRoots {
+            executable: Some(empty.clone()),
+            data_dirs: vec![empty.join("missing"), installed.clone()],
+            dev_checkout: Some(Path::new(env!("CARGO_MANIFEST_DIR")).join("resources")),
+            ..AssetRoots::default()
+        };
+        let expected = dunce::canonicalize(&installed).expect("valid path");
+        assert_eq!(roots.resolve().expect("assets not found"), expected);
+        let _ = fs::remove_dir_all(empty);
+        let _ = fs::remove_dir_all(installed);
+    }
+
+    #[test]
+    fn explicit_assets_dir_does_not_fall_back() {
+        let empty = temp_dir("explicit-assets", false);
+        let roots = AssetRoots {
+            environment: Some(empty.clone()),
+            dev_checkout: Some(Path::new(env!("CARGO_MANIFEST_DIR")).join("resources")),
+            ..AssetRoots::default()
+        };
+        let message = roots.resolve().expect_err("empty directory has no assets").to_string();
+        assert!(message.contains(&empty.display().to_string()));
+        assert!(message.contains(ASSETS_ENV_VAR));
+        assert!(!message.contains("development checkout"));
+        let _ = fs::remove_dir_all(empty);
+    }
+
+    #[test]
+    fn error_lists_every_location_tried() {
+        let roots = AssetRoots {
+            executable: Some(PathBuf::from("/nowhere/bin/resources")),
+            data_dirs: vec![PathBuf::from("/nowhere/share/amethyst-2d-playground/resources")],
+            dev_checkout: Some(PathBuf::from("/nowhere/src/resources")),
+            ..AssetRoots::default()
+        };
+        let message = roots.resolve().expect_err("no assets anywhere").to_string();
+        assert_eq!(
+            message,
+            "no assets found, looked for config/pong.ron in:\n  \
+             /nowhere/bin/resources (next to the executable)\n  \
+             /nowhere/share/amethyst-2d-playground/resources (data directory)\n  \
+             /nowhere/src/resources (development checkout)"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn xdg_data_home_comes_first() {
+        let dirs = xdg_data_dirs(Some("/data/home".into()), Some("/a:relative:/b".into()), Some("/home/u".into()));
+        assert_eq!(dirs, vec![PathBuf::from("/data/home"), PathBuf::from("/a"), PathBuf::from("/b")]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn relative_xdg_data_home_is_ignored() {
+        let dirs = xdg_data_dirs(Some("relative".into()), Some("/a".into()), Some("/home/u".into()));
+        assert_eq!(dirs, vec![PathBuf::from("/home/u/.local/share"), PathBuf::from("/a")]);
+    }
+
+    #[cfg(all(unix, not(target_os = "macos")))]
+    #[test]
+    fn user_dirs_follow_xdg() {
+        let dirs = user_dirs(Some("/config".into()), Some("relative".into()), Some("/home/u".into()), None);
+        assert_eq!(
+            dirs,
+            Some(UserDirs {
+                config: PathBuf::from("/config/amethyst-2d-playground"),
+                data: PathBuf::from("/home/u/.local/share/amethyst-2d-playground"),
+            })
+        );
+        assert_eq!(user_dirs(None, Some("/data".into()), None, None), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn xdg_defaults_without_environment() {
+        let dirs = xdg_data_dirs(None, Some("".into()), Some("/home/u".into()));
+        assert_eq!(
+            dirs,
+            vec![
+                PathBuf::from("/home/u/.local/share"),
+                PathBuf::from("/usr/local/share"),
+                PathBuf::from("/usr/share"),
+            ]
+        );
+    }
+}
@@ -57,12 +57,12 @@ pub fn set_sink_volume(world: &mut World, volume: f32) {
     sink.set_volume(volume);
 }
 
-/// Plays the bounce sound when a ball hits a side or a paddle.
-pub fn play_bounce(sounds: &Sounds, storage: &AssetStorage<Source>, output: Option<&Output>) {
+/// Plays the bounce sound at the given volume when a ball hits a side or a paddle.
+pub fn play_bounce(sounds: &Sounds, storage: &AssetStorage<Source>, output: Option<&Output>, volume: f32) {
     if let Some(output) = output.as_ref() {
         if let Some(sound) = storage.get(&sounds.bounce_sfx) {
             if cfg!(not(test)) {
-                output.play_once(sound, 1.0);
+                output.play_once(sound, volume);
             }
         }
     }
@@ -0,0 +1,114 @@
+use amethyst::{
+    error::Error,
+    input::{BindingTypes, Bindings},
+};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// The input types of the game. A bindings file naming an axis or action the game does not know
+/// fails to load, instead of leaving a paddle without input.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct GameBindings;
+
+impl BindingTypes for GameBindings {
+    type Axis = AxisBinding;
+    type Action = ActionBinding;
+}
+
+/// The axes of the game, one per paddle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum AxisBinding {
+    LeftPaddle,
+    RightPaddle,
+    TopPaddle,
+    BottomPaddle,
+}
+
+impl AxisBinding {
+    /// Every axis, each of which the bindings have to bind.
+    pub const ALL: [Self; 4] = [Self::LeftPaddle, Self::RightPaddle, Self::TopPaddle, Self::BottomPaddle];
+}
+
+impl fmt::Display for AxisBinding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::LeftPaddle => write!(f, "left paddle"),
+            Self::RightPaddle => write!(f, "right paddle"),
+            Self::TopPaddle => write!(f, "top paddle"),
+            Self::BottomPaddle => write!(f, "bottom paddle"),
+        }
+    }
+}
+
+/// The actions of the game. Unlike the axes, they may be left unbound.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum ActionBinding {
+    /// Opens the pause menu during a match and closes it again, like Escape.
+    Pause,
+}
+
+impl fmt::Display for ActionBinding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Pause => write!(f, "pause"),
+        }
+    }
+}
+
+/// Checks that `bindings` bind every axis, as a paddle without one could not be moved.
+pub fn validate(bindings: &Bindings<GameBindings>) -> Result<(), Error> {
+    let missing = AxisBinding::ALL
+        .iter()
+        .filter(|axis| bindings.axis(*axis).is_none())
+        .map(ToString::to_string)
+        .collect::<Vec<_>>();
+    if missing.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::from_string(format!("no binding for the {}", missing.join(", "))))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    const TWO_PADDLES: &str = "(axes: {\
+        LeftPaddle: Emulated(pos: Key(W), neg: Key(S)), \
+        RightPaddle: Emulated(pos: Key(Up), neg: Key(Down))})";
+
+    #[test]
+    fn parse_typed_bindings() {
+        let bindings: Bindings<GameBindings> = ron::de::from_str(TWO_PADDLES).expect("failed to parse bindings");
+        assert!(bindings.axis(&AxisBinding::LeftPaddle).is_some());
+        assert!(bindings.axis(&AxisBinding::TopPaddle).is_none());
+    }
+
+    #[test]
+    fn typo_fails_to_parse() {
+        let bindings = ron::de::from_str::<Bindings<GameBindings>>(&TWO_PADDLES.replace("LeftPaddle", "LeftPadle"));
+        assert!(bindings.is_err());
+    }
+
+    #[test]
+    fn missing_axes_are_reported() {
+        let bindings: Bindings<GameBindings> = ron::de::from_str(TWO_PADDLES).expect("failed to parse bindings");
+        let message = validate(&bindings).expect_err("top and bottom paddle unbound").to_string();
+        assert_eq!(message, "no binding for the top paddle, bottom paddle");
+    }
+
+    #[test]
+    fn shipped_bindings_are_valid() -> amethyst::Result<()> {
+        use amethyst::config::Config;
+
+        let path = crate::initialize_paths()?.key_bindings;
+        validate(&Bindings::load_no_fallback(path)?)
+    }
+
+    #[test_case(AxisBinding::LeftPaddle => "left paddle")]
+    #[test_case(AxisBinding::BottomPaddle => "bottom paddle")]
+    fn axis_name(axis: AxisBinding) -> String {
+        axis.to_string()
+    }
+}
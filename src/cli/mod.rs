@@ -0,0 +1,343 @@
+use crate::{
+    asset_root::AssetRoots,
+    network::{parse_address, NetworkRole, DEFAULT_PORT},
+    GamePaths, Side,
+};
+use amethyst::{error::Error, LoggerConfig};
+use log::LevelFilter;
+use std::path::PathBuf;
+
+/// Printed by `--version`.
+pub const VERSION: &str = concat!(env!("CARGO_PKG_NAME"), " ", env!("CARGO_PKG_VERSION"));
+
+/// Printed by `--help`.
+pub const USAGE: &str = "\
+Usage: amethyst-2d-playground [OPTIONS]
+
+Options:
+  --start STATE          start in welcome (default), menu or pong
+  --assets DIR           read the assets from DIR instead of looking for them
+  --config PATH          read the pong config from PATH
+  --bindings PATH        read the key bindings from PATH
+  --display PATH         read the display config from PATH
+  --window-size WxH      open a window of W by H pixels, e.g. 1280x720
+  --headless             play a CPU match without window and audio, then quit
+  --log-level LEVEL      off, error, warn, info (default), debug or trace
+  --host [ADDRESS]       host a network match, on port 7777 of every interface by default
+  --join ADDRESS         join the network match hosted on ADDRESS
+  --peer ADDRESS         play a rollback match against the peer at ADDRESS
+  --listen ADDRESS       address a peer listens on, port 7777 of every interface by default
+  --side left|right      side a peer plays on, left by default
+  -h, --help             print this help
+  -V, --version          print the version";
+
+/// State a local game starts in, chosen with `--start`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StartState {
+    Welcome,
+    Menu,
+    /// Skips the menus and plays a match with the default settings.
+    Pong,
+}
+
+impl Default for StartState {
+    fn default() -> Self {
+        Self::Welcome
+    }
+}
+
+/// What the command line asks the binary to do.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    Run(CommandLine),
+    Help,
+    Version,
+}
+
+/// Options of a game started from the command line, see `USAGE`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CommandLine {
+    pub start: StartState,
+    /// `None` for a local game.
+    pub network: Option<NetworkRole>,
+    /// Asset directory, see `AssetRoots`.
+    pub assets_dir: Option<PathBuf>,
+    pub pong_config: Option<PathBuf>,
+    pub key_bindings: Option<PathBuf>,
+    pub display_config: Option<PathBuf>,
+    /// Width and height of the window, taking precedence over the display config and the user
+    /// settings.
+    pub window_size: Option<(u32, u32)>,
+    /// Set by `--headless` or the `headless` feature.
+    pub headless: bool,
+    pub log_level: Option<LevelFilter>,
+}
+
+impl Command {
+    /// Reads the command line arguments, without the name of the binary.
+    pub fn from_args<I: IntoIterator<Item = String>>(args: I) -> Result<Self, Error> {
+        let mut command_line = CommandLine {
+            // the `headless` feature builds a game that can not open a window at all
+            headless: cfg!(feature = "headless"),
+            ..CommandLine::default()
+        };
+        let mut start = None;
+        let mut host = None;
+        let mut join = None;
+        let mut peer = None;
+        let mut listen = None;
+        let mut side = None;
+        let mut args = args.into_iter().peekable();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "-h" | "--help" => return Ok(Self::Help),
+                "-V" | "--version" => return Ok(Self::Version),
+                "--start" => {
+                    start = match args.next().as_deref() {
+                        Some("welcome") => Some(StartState::Welcome),
+                        Some("menu") => Some(StartState::Menu),
+                        Some("pong") => Some(StartState::Pong),
+                        _ => return Err(Error::from_string("--start needs welcome, menu or pong")),
+                    }
+                }
+                "--assets" | "--config" | "--bindings" | "--display" => {
+                    let path = match args.next() {
+                        Some(path) => PathBuf::from(path),
+                        None => return Err(Error::from_string(format!("{} needs a path", arg))),
+                    };
+                    match arg.as_str() {
+                        "--assets" => command_line.assets_dir = Some(path),
+                        "--config" => command_line.pong_config = Some(path),
+                        "--bindings" => command_line.key_bindings = Some(path),
+                        _ => command_line.display_config = Some(path),
+                    }
+                }
+                "--window-size" => {
+                    let size = args.next().unwrap_or_default();
+                    command_line.window_size = Some(parse_window_size(&size)?);
+                }
+                "--headless" => command_line.headless = true,
+                "--log-level" => {
+                    let level = args.next().unwrap_or_default();
+                    match level.parse() {
+                        Ok(level) => command_line.log_level = Some(level),
+                        Err(_) => {
+                            return Err(Error::from_string(
+                                "--log-level needs off, error, warn, info, debug or trace",
+                            ))
+                        }
+                    }
+                }
+                "--host" => {
+                    let address = match args.peek() {
+                        Some(next) if !next.starts_with('-') => parse_address(&args.next().unwrap_or_default())?,
+                        _ => ([0, 0, 0, 0], DEFAULT_PORT).into(),
+                    };
+                    host = Some(address);
+                }
+                "--join" | "--peer" | "--listen" => {
+                    let address = match args.next() {
+                        Some(address) => parse_address(&address)?,
+                        None => return Err(Error::from_string(format!("{} needs a network address", arg))),
+                    };
+                    match arg.as_str() {
+                        "--join" => join = Some(address),
+                        "--peer" => peer = Some(address),
+                        _ => listen = Some(address),
+                    }
+                }
+                "--side" => {
+                    side = match args.next().as_deref() {
+                        Some("left") => Some(Side::Left),
+                        Some("right") => Some(Side::Right),
+                        _ => return Err(Error::from_string("--side needs left or right")),
+                    }
+                }
+                _ => return Err(Error::from_string(format!("unknown argument {:?}, see --help", arg))),
+            }
+        }
+
+        if peer.is_none() && (listen.is_some() || side.is_some()) {
+            return Err(Error::from_string("--listen and --side only work with --peer"));
+        }
+        command_line.network = match (host, join, peer) {
+            (Some(address), None, None) => Some(NetworkRole::Host(address)),
+            (None, Some(address), None) => Some(NetworkRole::Join(address)),
+            (None, None, Some(remote)) => Some(NetworkRole::Peer {
+                listen: listen.unwrap_or_else(|| ([0, 0, 0, 0], DEFAULT_PORT).into()),
+                remote,
+                side: side.unwrap_or(Side::Left),
+            }),
+            (None, None, None) => None,
+            _ => return Err(Error::from_string("--host, --join and --peer are exclusive")),
+        };
+        match (command_line.network, start) {
+            (Some(NetworkRole::Join(_)), _) | (Some(NetworkRole::Peer { .. }), _) if command_line.headless => {
+                return Err(Error::from_string("--headless only works for local or hosted matches"))
+            }
+            (Some(_), Some(_)) => return Err(Error::from_string("--start only works for local games")),
+            // a headless game has no menus to start in
+            (None, Some(StartState::Welcome)) | (None, Some(StartState::Menu)) if command_line.headless => {
+                return Err(Error::from_string("--headless always starts in pong"))
+            }
+            _ => {}
+        }
+        command_line.start = start.unwrap_or_default();
+        Ok(Self::Run(command_line))
+    }
+}
+
+impl CommandLine {
+    /// Paths of the game, using the asset directory and config files given on the command line.
+    pub fn game_paths(&self) -> Result<GamePaths, Error> {
+        let assets_dir = AssetRoots::from_env(self.assets_dir.clone()).resolve()?;
+        Ok(self.override_paths(GamePaths::new(assets_dir)))
+    }
+
+    /// Replaces the default config files in `paths` with the ones given on the command line.
+    pub fn override_paths(&self, paths: GamePaths) -> GamePaths {
+        GamePaths {
+            pong_config: self.pong_config.clone().unwrap_or(paths.pong_config),
+            key_bindings: self.key_bindings.clone().unwrap_or(paths.key_bindings),
+            display_config: self.display_config.clone().unwrap_or(paths.display_config),
+            ..paths
+        }
+    }
+
+    pub fn logger_config(&self) -> LoggerConfig {
+        let mut logger_config = LoggerConfig::default();
+        if let Some(level) = self.log_level {
+            logger_config.level_filter = level;
+        }
+        logger_config
+    }
+}
+
+/// Parses `WIDTHxHEIGHT`, e.g. `1280x720`.
+fn parse_window_size(text: &str) -> Result<(u32, u32), Error> {
+    let mut parts = text.splitn(2, 'x').map(str::parse::<u32>);
+    match (parts.next(), parts.next()) {
+        (Some(Ok(width)), Some(Ok(height))) if width > 0 && height > 0 => Ok((width, height)),
+        _ => Err(Error::from_string(format!("{:?} is not a window size like 1280x720", text))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+    use test_case::test_case;
+
+    fn parse_args(args: &[&str]) -> Result<CommandLine, String> {
+        match Command::from_args(args.iter().map(|arg| arg.to_string())).map_err(|e| e.to_string())? {
+            Command::Run(command_line) => Ok(command_line),
+            command => Err(format!("{:?}", command)),
+        }
+    }
+
+    fn network_role(args: &[&str]) -> Result<Option<NetworkRole>, String> {
+        parse_args(args).map(|command_line| command_line.network)
+    }
+
+    fn error<T>(message: &str) -> Result<T, String> {
+        Err(message.to_string())
+    }
+
+    fn host(ip: [u8; 4], port: u16) -> Result<Option<NetworkRole>, String> {
+        Ok(Some(NetworkRole::Host((ip, port).into())))
+    }
+
+    #[test_case(&[] => Ok(None) ; "local game")]
+    #[test_case(&["--host"] => host([0, 0, 0, 0], 7777) ; "host on default port")]
+    #[test_case(&["--headless", "--host", "127.0.0.1:9000"] => host([127, 0, 0, 1], 9000) ; "headless host")]
+    #[test_case(&["--join", "192.168.0.2"] => Ok(Some(NetworkRole::Join(([192, 168, 0, 2], 7777).into()))) ; "join")]
+    #[test_case(&["--join"] => Err("--join needs a network address".to_string()) ; "join without address")]
+    #[test_case(&["--headless"] => Ok(None) ; "headless local game")]
+    #[test_case(&["--join", "pong"] => Err("\"pong\" is not a network address".to_string()) ; "bad address")]
+    #[test_case(&["--host", "--join", "::1"] => Err("--host, --join and --peer are exclusive".into()) ; "two roles")]
+    #[test_case(&["--peer", "10.0.0.2:7000", "--side", "right"] => Ok(Some(NetworkRole::Peer {
+        listen: ([0, 0, 0, 0], 7777).into(),
+        remote: ([10, 0, 0, 2], 7000).into(),
+        side: Side::Right,
+    })) ; "peer")]
+    #[test_case(&["--peer", "10.0.0.2", "--side", "up"] => Err("--side needs left or right".to_string()) ; "bad side")]
+    #[test_case(&["--listen", "0.0.0.0:7000"] => error(
+        "--listen and --side only work with --peer"
+    ) ; "listen without peer")]
+    #[test_case(&["--host", "--side", "right"] => error(
+        "--listen and --side only work with --peer"
+    ) ; "side without peer")]
+    #[test_case(&["--headless", "--join", "::1"] => error(
+        "--headless only works for local or hosted matches"
+    ) ; "headless join")]
+    fn test_network_role_from_args(args: &[&str]) -> Result<Option<NetworkRole>, String> {
+        network_role(args)
+    }
+
+    #[test_case(&["--start", "pong"] => Ok(StartState::Pong) ; "skip the menus")]
+    #[test_case(&["--start", "menu"] => Ok(StartState::Menu) ; "main menu")]
+    #[test_case(&["--log-level", "warn"] => Ok(StartState::Welcome) ; "welcome screen by default")]
+    #[test_case(&["--start", "options"] => Err("--start needs welcome, menu or pong".to_string()) ; "unknown state")]
+    #[test_case(&["--start", "menu", "--join", "::1"] => error("--start only works for local games") ; "network game")]
+    #[test_case(&["--headless", "--start", "menu"] => Err("--headless always starts in pong".into()) ; "headless menu")]
+    fn test_start_state_from_args(args: &[&str]) -> Result<StartState, String> {
+        parse_args(args).map(|command_line| command_line.start)
+    }
+
+    #[test_case(&["--window-size", "1280x720"] => Ok(Some((1280, 720))) ; "window size")]
+    #[test_case(&[] => Ok(None) ; "size of display config")]
+    #[test_case(&["--window-size", "1280"] => Err("\"1280\" is not a window size like 1280x720".into()) ; "no height")]
+    #[test_case(&["--window-size", "0x720"] => Err("\"0x720\" is not a window size like 1280x720".into()) ; "no width")]
+    #[test_case(&["--window-size"] => Err("\"\" is not a window size like 1280x720".into()) ; "no size")]
+    fn test_window_size_from_args(args: &[&str]) -> Result<Option<(u32, u32)>, String> {
+        parse_args(args).map(|command_line| command_line.window_size)
+    }
+
+    #[test_case(&["--log-level", "debug"] => Ok(Some(LevelFilter::Debug)) ; "debug")]
+    #[test_case(&["--log-level", "OFF"] => Ok(Some(LevelFilter::Off)) ; "off")]
+    #[test_case(&[] => Ok(None) ; "logger default")]
+    #[test_case(&["--log-level", "loud"] => error("--log-level needs off, error, warn, info, debug or trace") ; "bad")]
+    fn test_log_level_from_args(args: &[&str]) -> Result<Option<LevelFilter>, String> {
+        parse_args(args).map(|command_line| command_line.log_level)
+    }
+
+    #[test_case(&["--help"] => Ok(Command::Help) ; "help")]
+    #[test_case(&["--start", "pong", "-h"] => Ok(Command::Help) ; "help after options")]
+    #[test_case(&["--host", "-h"] => Ok(Command::Help) ; "help after host")]
+    #[test_case(&["-V"] => Ok(Command::Version) ; "version")]
+    #[test_case(&["--fullscreen"] => Err("unknown argument \"--fullscreen\", see --help".into()) ; "unknown argument")]
+    fn test_command_from_args(args: &[&str]) -> Result<Command, String> {
+        Command::from_args(args.iter().map(|arg| arg.to_string())).map_err(|e| e.to_string())
+    }
+
+    #[test]
+    fn override_config_paths() {
+        let paths = crate::initialize_paths().expect("valid paths required");
+        let command_line = parse_args(&["--config", "pong.ron", "--bindings", "/tmp/input.ron"]).expect("valid args");
+        let overridden = command_line.override_paths(paths.clone());
+        assert_eq!(overridden.pong_config, Path::new("pong.ron"));
+        assert_eq!(overridden.key_bindings, Path::new("/tmp/input.ron"));
+        assert_eq!(overridden.display_config, paths.display_config);
+        assert_eq!(overridden.assets_dir, paths.assets_dir);
+    }
+
+    #[test]
+    fn assets_dir_of_game_paths() {
+        let assets_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("resources");
+        let command_line = parse_args(&["--assets", &assets_dir.to_string_lossy()]).expect("valid args");
+        let paths = command_line.game_paths().expect("assets not found");
+        assert_eq!(paths.assets_dir, dunce::canonicalize(&assets_dir).expect("valid path"));
+        assert!(paths.pong_config.is_file());
+
+        let command_line = parse_args(&["--assets", "no-such-dir"]).expect("valid args");
+        let message = command_line.game_paths().expect_err("no assets there").to_string();
+        assert!(message.contains("no-such-dir (--assets)"));
+    }
+
+    #[test]
+    fn log_level_of_logger() {
+        let command_line = parse_args(&["--log-level", "trace"]).expect("valid args");
+        assert_eq!(command_line.logger_config().level_filter, LevelFilter::Trace);
+        assert_eq!(CommandLine::default().logger_config().level_filter, LevelFilter::Info);
+    }
+}
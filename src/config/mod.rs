@@ -0,0 +1,12 @@
+mod pong_config;
+mod user_bindings;
+mod user_settings;
+
+pub use self::{
+    pong_config::{
+        ArenaConfig, BallConfig, BounceConfig, MatchRules, MultiBallConfig, PaddleConfig, PongConfig, PowerUpConfig,
+        ServeConfig, SimulationConfig,
+    },
+    user_bindings::{button_label, load_bindings, rebind, BindingSlot, UserBindings},
+    user_settings::{UserSettings, FRAME_LIMITS, RESOLUTIONS},
+};
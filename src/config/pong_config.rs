@@ -0,0 +1,247 @@
+use serde::{Deserialize, Serialize};
+
+/// Dimensions of the playing field in world units.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ArenaConfig {
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Default for ArenaConfig {
+    fn default() -> Self {
+        Self {
+            width: 160.0,
+            height: 90.0,
+        }
+    }
+}
+
+impl ArenaConfig {
+    /// Extent of the arena along `axis`: 0 for the width, 1 for the height.
+    pub fn length(&self, axis: usize) -> f32 {
+        match axis {
+            0 => self.width,
+            _ => self.height,
+        }
+    }
+}
+
+/// Size and movement speed shared by both paddles.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct PaddleConfig {
+    pub width: f32,
+    pub height: f32,
+    pub velocity: f32,
+}
+
+impl Default for PaddleConfig {
+    fn default() -> Self {
+        Self {
+            width: 4.0,
+            height: 16.0,
+            velocity: 75.0,
+        }
+    }
+}
+
+/// Size and initial velocity of a freshly spawned ball.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct BallConfig {
+    /// Only the length of the velocity is used: it is the speed balls are served with, the
+    /// direction is picked by the `ServeSystem`.
+    pub velocity: [f32; 2],
+    pub radius: f32,
+}
+
+impl Default for BallConfig {
+    fn default() -> Self {
+        Self {
+            velocity: [75.0, 50.0],
+            radius: 2.0,
+        }
+    }
+}
+
+/// When a match is decided: the first player to reach `winning_score` points with a lead of at
+/// least `win_by` points wins.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MatchRules {
+    pub winning_score: i32,
+    pub win_by: i32,
+}
+
+impl Default for MatchRules {
+    fn default() -> Self {
+        Self {
+            winning_score: 11,
+            win_by: 2,
+        }
+    }
+}
+
+/// How balls bounce off the paddles. The angle a ball leaves a paddle with depends on where it hit
+/// the paddle, and every hit speeds the ball up until the next point is scored.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct BounceConfig {
+    /// Angle in degrees a ball leaves a paddle with after hitting its very edge. Hits closer to the
+    /// centre of the paddle leave flatter.
+    pub max_angle: f32,
+    /// Share of the paddle's vertical velocity that is passed on to the ball.
+    pub spin: f32,
+    /// Factor the ball speed is multiplied with on every paddle hit.
+    pub speed_up: f32,
+    /// Speed paddle hits never speed the ball up beyond.
+    pub max_speed: f32,
+}
+
+impl Default for BounceConfig {
+    fn default() -> Self {
+        Self {
+            max_angle: 60.0,
+            spin: 0.25,
+            speed_up: 1.05,
+            max_speed: 180.0,
+        }
+    }
+}
+
+/// How a ball is served at the start of a rally.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ServeConfig {
+    /// Seconds the ball waits in the centre of the arena after a point before it is served.
+    pub countdown: f32,
+    /// Largest angle in degrees between the serve direction and the horizontal.
+    pub max_angle: f32,
+}
+
+impl Default for ServeConfig {
+    fn default() -> Self {
+        Self {
+            countdown: 1.0,
+            max_angle: 30.0,
+        }
+    }
+}
+
+/// Extra balls of the multi-ball game mode.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct MultiBallConfig {
+    /// Seconds between two extra balls joining the match.
+    pub spawn_interval: f32,
+    /// Number of balls in play no extra ball joins beyond.
+    pub max_balls: usize,
+}
+
+impl Default for MultiBallConfig {
+    fn default() -> Self {
+        Self {
+            spawn_interval: 8.0,
+            max_balls: 3,
+        }
+    }
+}
+
+/// Power-ups appearing in the arena when they are enabled in the `MatchSettings`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct PowerUpConfig {
+    /// Seconds between two power-ups appearing in the arena.
+    pub spawn_interval: f32,
+    /// Number of power-ups lying in the arena no further one appears beyond.
+    pub max_pickups: usize,
+    pub radius: f32,
+    /// Seconds the effect of a collected power-up lasts.
+    pub duration: f32,
+    /// Factor the height of the collecting player's paddle is multiplied with.
+    pub grow: f32,
+    /// Factor the height of the opponent's paddle is multiplied with.
+    pub shrink: f32,
+    /// Factor the speed of the collecting ball is multiplied with.
+    pub speed_up: f32,
+    /// Factor the speed of the collecting ball is multiplied with.
+    pub slow_down: f32,
+    /// Seconds a sticky paddle holds a ball before releasing it.
+    pub stick_time: f32,
+}
+
+impl Default for PowerUpConfig {
+    fn default() -> Self {
+        Self {
+            spawn_interval: 5.0,
+            max_pickups: 2,
+            radius: 3.0,
+            duration: 8.0,
+            grow: 1.5,
+            shrink: 0.6,
+            speed_up: 1.5,
+            slow_down: 0.6,
+            stick_time: 0.75,
+        }
+    }
+}
+
+/// Rate of the fixed simulation step the gameplay systems run at.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SimulationConfig {
+    /// Simulation steps per second.
+    pub tick_rate: f32,
+    /// Upper bound of steps per rendered frame, so a long frame can not stall the game further.
+    pub max_ticks_per_frame: u32,
+}
+
+impl Default for SimulationConfig {
+    fn default() -> Self {
+        Self {
+            tick_rate: 120.0,
+            max_ticks_per_frame: 8,
+        }
+    }
+}
+
+/// Gameplay tuning values, loaded from `resources/config/pong.ron`.
+///
+/// The `Default` implementation mirrors the shipped config file, so systems keep working when the
+/// resource was never explicitly inserted.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PongConfig {
+    pub arena: ArenaConfig,
+    pub paddle: PaddleConfig,
+    pub ball: BallConfig,
+    pub bounce: BounceConfig,
+    pub serve: ServeConfig,
+    pub multi_ball: MultiBallConfig,
+    pub power_ups: PowerUpConfig,
+    pub rules: MatchRules,
+    pub simulation: SimulationConfig,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use amethyst::config::Config;
+    use assert_approx_eq::assert_approx_eq;
+
+    #[test]
+    fn shipped_config_matches_default() -> amethyst::Result<()> {
+        let pong_config_path = crate::initialize_paths()?.pong_config;
+        let config = PongConfig::load_no_fallback(pong_config_path)?;
+        assert_eq!(config, PongConfig::default());
+        Ok(())
+    }
+
+    #[test]
+    fn missing_sections_fall_back_to_default() -> amethyst::Result<()> {
+        let config = PongConfig::load_bytes(b"(arena: (width: 320.0, height: 180.0))")?;
+        assert_approx_eq!(config.arena.width, 320.0);
+        assert_approx_eq!(config.arena.height, 180.0);
+        assert_eq!(config.paddle, PaddleConfig::default());
+        assert_eq!(config.ball, BallConfig::default());
+        assert_eq!(config.bounce, BounceConfig::default());
+        assert_eq!(config.serve, ServeConfig::default());
+        assert_eq!(config.multi_ball, MultiBallConfig::default());
+        assert_eq!(config.power_ups, PowerUpConfig::default());
+        assert_eq!(config.rules, MatchRules::default());
+        assert_eq!(config.simulation, SimulationConfig::default());
+        Ok(())
+    }
+}
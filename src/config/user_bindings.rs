@@ -0,0 +1,461 @@
+use crate::bindings::{validate, ActionBinding, AxisBinding, GameBindings};
+use amethyst::{
+    error::Error,
+    input::{Axis, Bindings, Button},
+};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fs, path::Path};
+
+/// One button of the loaded bindings the rebinding screen can reassign.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum BindingSlot {
+    /// The button moving an emulated axis towards positive values.
+    AxisPositive(AxisBinding),
+    /// The button moving an emulated axis towards negative values.
+    AxisNegative(AxisBinding),
+    /// Every binding of an action. Reassigning it leaves the action with the single new button.
+    Action(ActionBinding),
+}
+
+impl BindingSlot {
+    /// Every slot of `bindings`, sorted by name. Only emulated axes are listed: controller and
+    /// mouse axes do not consist of buttons. Of an axis driven by several devices, the emulated
+    /// part is listed.
+    pub fn all(bindings: &Bindings<GameBindings>) -> Vec<Self> {
+        let mut slots = Vec::new();
+        let mut axes = bindings.axes().copied().collect::<Vec<_>>();
+        axes.sort();
+        for axis in axes {
+            if bindings.axis(&axis).and_then(emulated_buttons).is_some() {
+                slots.push(BindingSlot::AxisPositive(axis));
+                slots.push(BindingSlot::AxisNegative(axis));
+            }
+        }
+        let mut actions = bindings.actions().copied().collect::<Vec<_>>();
+        actions.sort();
+        slots.extend(actions.into_iter().map(BindingSlot::Action));
+        slots
+    }
+
+    /// Name of the slot for a label, e.g. "LEFT PADDLE +".
+    pub fn label(&self) -> String {
+        match self {
+            BindingSlot::AxisPositive(axis) => format!("{} +", axis.to_string().to_uppercase()),
+            BindingSlot::AxisNegative(axis) => format!("{} -", axis.to_string().to_uppercase()),
+            BindingSlot::Action(action) => action.to_string().to_uppercase(),
+        }
+    }
+
+    /// The button currently assigned to the slot, `None` for an action without bindings or bound
+    /// to a combination of buttons.
+    pub fn button(&self, bindings: &Bindings<GameBindings>) -> Option<Button> {
+        match self {
+            BindingSlot::AxisPositive(axis) => bindings.axis(axis).and_then(emulated_buttons).map(|(pos, _)| pos),
+            BindingSlot::AxisNegative(axis) => bindings.axis(axis).and_then(emulated_buttons).map(|(_, neg)| neg),
+            BindingSlot::Action(action) => match bindings.action_bindings(action).next() {
+                Some([button]) => Some(*button),
+                _ => None,
+            },
+        }
+    }
+}
+
+/// The buttons of the emulated part of `axis` as `(pos, neg)`, looking into an axis combining
+/// several devices.
+fn emulated_buttons(axis: &Axis) -> Option<(Button, Button)> {
+    match axis {
+        Axis::Emulated { pos, neg } => Some((*pos, *neg)),
+        Axis::Multiple(axes) => axes.iter().find_map(emulated_buttons),
+        _ => None,
+    }
+}
+
+/// Mutable access to the buttons found by `emulated_buttons`.
+fn emulated_buttons_mut(axis: &mut Axis) -> Option<(&mut Button, &mut Button)> {
+    match axis {
+        Axis::Emulated { pos, neg } => Some((pos, neg)),
+        Axis::Multiple(axes) => axes.iter_mut().find_map(emulated_buttons_mut),
+        _ => None,
+    }
+}
+
+/// Loads the shipped bindings: the key bindings, merged with the `controller_bindings` if given.
+/// An axis bound on both devices follows whichever of them is used. Fails if an axis is left
+/// unbound, see `validate`.
+pub fn load_bindings(
+    key_bindings: &Path,
+    controller_bindings: Option<&Path>,
+) -> Result<Bindings<GameBindings>, Error> {
+    use amethyst::config::Config;
+
+    let load = |path: &Path| {
+        Bindings::<GameBindings>::load_no_fallback(path)
+            .map_err(|e| Error::from_string(format!("failed to load bindings {:?}: {}", path, e)))
+    };
+    let mut bindings = load(key_bindings)?;
+    if let Some(path) = controller_bindings {
+        merge_bindings(&mut bindings, load(path)?)?;
+    }
+    validate(&bindings).map_err(|e| Error::from_string(format!("invalid bindings {:?}: {}", key_bindings, e)))?;
+    Ok(bindings)
+}
+
+/// Adds the axes and actions of `other` to `bindings`. Axes bound in both are combined into one
+/// driven by either binding.
+fn merge_bindings(bindings: &mut Bindings<GameBindings>, other: Bindings<GameBindings>) -> Result<(), Error> {
+    for id in other.axes() {
+        let axis = other.axis(id).cloned().expect("listed axis exists");
+        let merged = match bindings.remove_axis(id) {
+            Some(Axis::Multiple(mut axes)) => {
+                axes.push(axis);
+                Axis::Multiple(axes)
+            }
+            Some(existing) => Axis::Multiple(vec![existing, axis]),
+            None => axis,
+        };
+        bindings
+            .insert_axis(*id, merged)
+            .map_err(|e| Error::from_string(format!("failed to merge axis {}: {}", id, e)))?;
+    }
+    for id in other.actions() {
+        for combination in other.action_bindings(id) {
+            bindings
+                .insert_action_binding(*id, combination.to_vec())
+                .map_err(|e| Error::from_string(format!("failed to merge action {}: {}", id, e)))?;
+        }
+    }
+    Ok(())
+}
+
+/// Name of a button for a label, e.g. "W" or "PAD 0 A".
+pub fn button_label(button: Option<Button>) -> String {
+    let label = match button {
+        Some(Button::Key(key)) => format!("{:?}", key),
+        Some(Button::ScanCode(code)) => format!("scan code {}", code),
+        Some(Button::Mouse(button)) => format!("mouse {:?}", button),
+        Some(Button::MouseWheel(direction)) => format!("wheel {:?}", direction),
+        Some(Button::Controller(controller, button)) => format!("pad {} {:?}", controller, button),
+        None => "none".to_string(),
+    };
+    label.to_uppercase()
+}
+
+/// Assigns `button` to `slot`. A slot already using `button` gets the previous button of `slot`
+/// instead, so no button ends up doing two things at once. Returns the slot swapped with.
+pub fn rebind(
+    bindings: &mut Bindings<GameBindings>,
+    slot: &BindingSlot,
+    button: Button,
+) -> Result<Option<BindingSlot>, Error> {
+    let previous = slot.button(bindings);
+    if previous == Some(button) {
+        return Ok(None);
+    }
+    let conflict = BindingSlot::all(bindings)
+        .into_iter()
+        .find(|other| other != slot && other.button(bindings) == Some(button));
+
+    let mut axes = BTreeMap::new();
+    let mut actions = BTreeMap::new();
+    let mut assignments = vec![(slot, Some(button))];
+    if let Some(conflict) = &conflict {
+        assignments.push((conflict, previous));
+    }
+    for (slot, button) in assignments {
+        match slot {
+            BindingSlot::AxisPositive(id) | BindingSlot::AxisNegative(id) => {
+                let axis = axes.entry(*id).or_insert_with(|| bindings.axis(id).cloned());
+                if let (Some((pos, neg)), Some(button)) = (axis.as_mut().and_then(emulated_buttons_mut), button) {
+                    match slot {
+                        BindingSlot::AxisPositive(_) => *pos = button,
+                        _ => *neg = button,
+                    }
+                }
+            }
+            BindingSlot::Action(id) => {
+                actions.insert(*id, button.map(|button| vec![vec![button]]).unwrap_or_default());
+            }
+        }
+    }
+    replace(
+        bindings,
+        axes.into_iter().filter_map(|(id, axis)| Some((id, axis?))),
+        actions,
+    )?;
+    Ok(conflict)
+}
+
+/// Replaces axes and actions of `bindings`. Everything replaced is removed first, so swapping
+/// buttons between them does not trip over the conflict checks of the bindings.
+fn replace<A, B>(bindings: &mut Bindings<GameBindings>, axes: A, actions: B) -> Result<(), Error>
+where
+    A: IntoIterator<Item = (AxisBinding, Axis)>,
+    B: IntoIterator<Item = (ActionBinding, Vec<Vec<Button>>)>,
+{
+    let axes = axes.into_iter().collect::<Vec<_>>();
+    let actions = actions.into_iter().collect::<Vec<_>>();
+    for (id, _) in &axes {
+        bindings.remove_axis(id);
+    }
+    for (id, _) in &actions {
+        let combinations = bindings.action_bindings(id).map(<[Button]>::to_vec).collect::<Vec<_>>();
+        for combination in combinations {
+            bindings.remove_action_binding(id, &combination);
+        }
+    }
+    for (id, axis) in axes {
+        bindings
+            .insert_axis(id, axis)
+            .map_err(|e| Error::from_string(format!("failed to bind axis {}: {}", id, e)))?;
+    }
+    for (id, combinations) in actions {
+        for combination in combinations {
+            bindings
+                .insert_action_binding(id, combination)
+                .map_err(|e| Error::from_string(format!("failed to bind action {}: {}", id, e)))?;
+        }
+    }
+    Ok(())
+}
+
+/// Bindings changed in the rebinding screen. Stored in the config directory of the user and
+/// layered over the shipped key bindings when the game starts: every axis and action listed here replaces the shipped
+/// one of the same name.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UserBindings {
+    pub axes: BTreeMap<AxisBinding, Axis>,
+    pub actions: BTreeMap<ActionBinding, Vec<Vec<Button>>>,
+}
+
+impl UserBindings {
+    /// Loads the bindings from `path`. A missing or broken file is reported to the log and no
+    /// bindings are changed then, so a fresh install starts without a bindings file.
+    pub fn load_or_default<P: AsRef<Path>>(path: P) -> Self {
+        use amethyst::config::Config;
+
+        let path = path.as_ref();
+        if !path.is_file() {
+            return Self::default();
+        }
+        Self::load_no_fallback(path).unwrap_or_else(|e| {
+            log::error!("Failed to load user bindings {:?}: {}", path, e);
+            Self::default()
+        })
+    }
+
+    /// Writes the bindings to `path`, creating the config directory of the user if necessary.
+    pub fn store<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        use amethyst::config::Config;
+
+        let path = path.as_ref();
+        if let Some(directory) = path.parent() {
+            fs::create_dir_all(directory)?;
+        }
+        self.write(path)?;
+        Ok(())
+    }
+
+    /// The axes and actions of `bindings` that differ from the shipped `defaults`.
+    pub fn changes(defaults: &Bindings<GameBindings>, bindings: &Bindings<GameBindings>) -> Self {
+        let axes = bindings
+            .axes()
+            .filter_map(|id| {
+                let axis = bindings.axis(id)?;
+                if defaults.axis(id) == Some(axis) {
+                    None
+                } else {
+                    Some((*id, axis.clone()))
+                }
+            })
+            .collect();
+        let combinations = |bindings: &Bindings<GameBindings>, id: &ActionBinding| {
+            bindings.action_bindings(id).map(<[Button]>::to_vec).collect::<Vec<_>>()
+        };
+        let actions = bindings
+            .actions()
+            .filter_map(|id| {
+                let action = combinations(bindings, id);
+                if combinations(defaults, id) == action {
+                    None
+                } else {
+                    Some((*id, action))
+                }
+            })
+            .collect();
+        Self { axes, actions }
+    }
+
+    /// Layers the user's changes over `bindings`. Changes that conflict with the other bindings,
+    /// e.g. after the shipped bindings changed, are reported to the log and the shipped bindings
+    /// stay in use.
+    pub fn apply(&self, bindings: &mut Bindings<GameBindings>) {
+        let defaults = bindings.clone();
+        let axes = self.axes.iter().map(|(id, axis)| (*id, axis.clone()));
+        let actions = self.actions.iter().map(|(id, action)| (*id, action.clone()));
+        if let Err(e) = replace(bindings, axes, actions) {
+            log::error!("Ignoring the user bindings: {}", e);
+            *bindings = defaults;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use amethyst::{config::Config, winit::VirtualKeyCode};
+    use test_case::test_case;
+
+    fn default_bindings() -> Bindings<GameBindings> {
+        let path = crate::initialize_paths().expect("valid paths required").key_bindings;
+        Bindings::load_no_fallback(path).expect("failed to load key bindings")
+    }
+
+    fn axis(id: AxisBinding) -> BindingSlot {
+        BindingSlot::AxisPositive(id)
+    }
+
+    #[test]
+    fn slots_of_every_axis_direction() {
+        let slots = BindingSlot::all(&default_bindings());
+        let labels = slots.iter().map(BindingSlot::label).collect::<Vec<_>>();
+        assert_eq!(
+            labels,
+            vec![
+                "BOTTOM PADDLE +",
+                "BOTTOM PADDLE -",
+                "LEFT PADDLE +",
+                "LEFT PADDLE -",
+                "RIGHT PADDLE +",
+                "RIGHT PADDLE -",
+                "TOP PADDLE +",
+                "TOP PADDLE -",
+            ]
+        );
+    }
+
+    #[test_case(Some(Button::Key(VirtualKeyCode::W)) => "W" ; "key")]
+    #[test_case(Some(Button::ScanCode(17)) => "SCAN CODE 17" ; "scan code")]
+    #[test_case(None => "NONE" ; "unbound")]
+    fn test_button_label(button: Option<Button>) -> String {
+        button_label(button)
+    }
+
+    #[test]
+    fn rebind_free_button() -> amethyst::Result<()> {
+        let mut bindings = default_bindings();
+        let swapped = rebind(&mut bindings, &axis(AxisBinding::LeftPaddle), Button::Key(VirtualKeyCode::Q))?;
+        assert_eq!(swapped, None);
+        assert_eq!(axis(AxisBinding::LeftPaddle).button(&bindings), Some(Button::Key(VirtualKeyCode::Q)));
+        Ok(())
+    }
+
+    #[test]
+    fn rebind_swaps_conflicting_buttons() -> amethyst::Result<()> {
+        let mut bindings = default_bindings();
+        let swapped = rebind(&mut bindings, &axis(AxisBinding::LeftPaddle), Button::Key(VirtualKeyCode::Up))?;
+        assert_eq!(swapped, Some(axis(AxisBinding::RightPaddle)));
+        assert_eq!(axis(AxisBinding::LeftPaddle).button(&bindings), Some(Button::Key(VirtualKeyCode::Up)));
+        assert_eq!(axis(AxisBinding::RightPaddle).button(&bindings), Some(Button::Key(VirtualKeyCode::W)));
+
+        // both directions of the same axis
+        let down = BindingSlot::AxisNegative(AxisBinding::LeftPaddle);
+        let swapped = rebind(&mut bindings, &down, Button::Key(VirtualKeyCode::Up))?;
+        assert_eq!(swapped, Some(axis(AxisBinding::LeftPaddle)));
+        assert_eq!(down.button(&bindings), Some(Button::Key(VirtualKeyCode::Up)));
+        assert_eq!(axis(AxisBinding::LeftPaddle).button(&bindings), Some(Button::Key(VirtualKeyCode::S)));
+        Ok(())
+    }
+
+    #[test]
+    fn rebind_action() -> amethyst::Result<()> {
+        let mut bindings = default_bindings();
+        bindings
+            .insert_action_binding(ActionBinding::Pause, vec![Button::Key(VirtualKeyCode::P)])
+            .expect("failed to bind pause");
+        let pause = BindingSlot::Action(ActionBinding::Pause);
+        assert!(BindingSlot::all(&bindings).contains(&pause));
+
+        let swapped = rebind(&mut bindings, &pause, Button::Key(VirtualKeyCode::Space))?;
+        assert_eq!(swapped, None);
+        assert_eq!(pause.button(&bindings), Some(Button::Key(VirtualKeyCode::Space)));
+        assert_eq!(bindings.action_bindings(&ActionBinding::Pause).count(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn controller_bindings_merge_into_key_bindings() -> amethyst::Result<()> {
+        let assets_dir = crate::initialize_paths().expect("valid paths required").assets_dir;
+        let mut bindings = load_bindings(
+            &assets_dir.join("config/input.ron"),
+            Some(&assets_dir.join("config/input_controller.ron")),
+        )?;
+        match bindings.axis(&AxisBinding::LeftPaddle) {
+            Some(Axis::Multiple(axes)) => match axes.as_slice() {
+                [Axis::Emulated { .. }, Axis::Controller { .. }] => {}
+                axes => panic!("expected keyboard and controller axis, got {:?}", axes),
+            },
+            axis => panic!("expected keyboard and controller axis, got {:?}", axis),
+        }
+        let slots = BindingSlot::all(&bindings);
+        assert_eq!(slots.len(), 9);
+        assert!(slots.contains(&BindingSlot::Action(ActionBinding::Pause)));
+
+        rebind(&mut bindings, &axis(AxisBinding::LeftPaddle), Button::Key(VirtualKeyCode::Q))?;
+        assert_eq!(axis(AxisBinding::LeftPaddle).button(&bindings), Some(Button::Key(VirtualKeyCode::Q)));
+        match bindings.axis(&AxisBinding::LeftPaddle) {
+            Some(Axis::Multiple(axes)) if axes.len() == 2 => {}
+            axis => panic!("rebinding dropped the controller, got {:?}", axis),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn user_bindings_layer_over_defaults() -> amethyst::Result<()> {
+        let defaults = default_bindings();
+        let mut bindings = defaults.clone();
+        rebind(&mut bindings, &axis(AxisBinding::LeftPaddle), Button::Key(VirtualKeyCode::Up))?;
+        let changes = UserBindings::changes(&defaults, &bindings);
+        assert_eq!(
+            changes.axes.keys().copied().collect::<Vec<_>>(),
+            vec![AxisBinding::LeftPaddle, AxisBinding::RightPaddle]
+        );
+        assert!(changes.actions.is_empty());
+
+        let dir = crate::test_harness::temp_path("bindings");
+        let _ = std::fs::remove_dir_all(&dir);
+        let path = dir.join("config/user_bindings.ron");
+        changes.store(&path)?;
+        let loaded = UserBindings::load_or_default(&path);
+        std::fs::remove_dir_all(&dir)?;
+        assert_eq!(loaded, changes);
+
+        let mut layered = defaults;
+        loaded.apply(&mut layered);
+        assert_eq!(axis(AxisBinding::LeftPaddle).button(&layered), Some(Button::Key(VirtualKeyCode::Up)));
+        assert_eq!(axis(AxisBinding::RightPaddle).button(&layered), Some(Button::Key(VirtualKeyCode::W)));
+        assert_eq!(UserBindings::changes(&bindings, &layered), UserBindings::default());
+        Ok(())
+    }
+
+    #[test]
+    fn conflicting_user_bindings_are_ignored() {
+        let mut user_bindings = UserBindings::default();
+        user_bindings.axes.insert(
+            AxisBinding::LeftPaddle,
+            Axis::Emulated {
+                pos: Button::Key(VirtualKeyCode::Up),
+                neg: Button::Key(VirtualKeyCode::S),
+            },
+        );
+        let mut bindings = default_bindings();
+        user_bindings.apply(&mut bindings);
+        assert_eq!(UserBindings::changes(&default_bindings(), &bindings), UserBindings::default());
+    }
+
+    #[test]
+    fn missing_file_changes_nothing() {
+        let user_bindings = UserBindings::load_or_default("this/file/does/not/exist.ron");
+        assert_eq!(user_bindings, UserBindings::default());
+    }
+}
@@ -0,0 +1,209 @@
+use amethyst::{core::frame_limiter::FrameRateLimitStrategy, error::Error, window::DisplayConfig};
+use serde::{Deserialize, Serialize};
+use std::{fs, path::Path, time::Duration};
+
+/// Window sizes offered by the options menu.
+pub const RESOLUTIONS: &[(u32, u32)] = &[(1280, 720), (1600, 900), (1920, 1080), (2560, 1440)];
+/// Frame limits offered by the options menu, `0` means unlimited.
+pub const FRAME_LIMITS: &[u32] = &[60, 144, 240, 0];
+
+/// Refresh rate the frame limiter is capped at when vsync is enabled.
+const VSYNC_FRAME_RATE: u32 = 60;
+/// Step by which the options menu changes a volume.
+const VOLUME_STEP: f32 = 0.1;
+
+/// Choices made in the options menu. Stored in the config directory of the user and applied on top
+/// of `display.ron` when the game starts.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UserSettings {
+    /// Volume of the background music between 0.0 and 1.0.
+    pub music_volume: f32,
+    /// Volume of the sound effects between 0.0 and 1.0.
+    pub sfx_volume: f32,
+    pub fullscreen: bool,
+    /// Window size, `None` keeps the dimensions from `display.ron`.
+    pub resolution: Option<(u32, u32)>,
+    pub vsync: bool,
+    /// Maximum frames per second, `0` means unlimited.
+    pub frame_limit: u32,
+}
+
+impl Default for UserSettings {
+    fn default() -> Self {
+        Self {
+            music_volume: 0.2,
+            sfx_volume: 1.0,
+            fullscreen: false,
+            resolution: None,
+            vsync: false,
+            frame_limit: 144,
+        }
+    }
+}
+
+impl UserSettings {
+    /// Loads the settings from `path`. A missing or broken file is reported to the log and the
+    /// defaults are used instead, so a fresh install starts without a settings file.
+    pub fn load_or_default<P: AsRef<Path>>(path: P) -> Self {
+        use amethyst::config::Config;
+
+        let path = path.as_ref();
+        if !path.is_file() {
+            return Self::default();
+        }
+        Self::load_no_fallback(path).unwrap_or_else(|e| {
+            log::error!("Failed to load user settings {:?}: {}", path, e);
+            Self::default()
+        })
+    }
+
+    /// Writes the settings to `path`, creating the config directory of the user if necessary.
+    pub fn store<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        use amethyst::config::Config;
+
+        let path = path.as_ref();
+        if let Some(directory) = path.parent() {
+            fs::create_dir_all(directory)?;
+        }
+        self.write(path)?;
+        Ok(())
+    }
+
+    /// Overrides the window related values of the shipped display config.
+    pub fn apply_to_display(&self, display_config: &mut DisplayConfig) {
+        if let Some(resolution) = self.resolution {
+            display_config.dimensions = Some(resolution);
+        }
+    }
+
+    /// The frame limiter strategy and rate matching these settings. The swapchain present mode is
+    /// not configurable in amethyst, so vsync caps the frame rate at the usual monitor refresh rate.
+    pub fn frame_rate_limit(&self) -> (FrameRateLimitStrategy, u32) {
+        let limit = match (self.vsync, self.frame_limit) {
+            (true, 0) => VSYNC_FRAME_RATE,
+            (true, limit) => limit.min(VSYNC_FRAME_RATE),
+            (false, limit) => limit,
+        };
+        if limit == 0 {
+            (FrameRateLimitStrategy::Unlimited, 0)
+        } else {
+            (FrameRateLimitStrategy::SleepAndYield(Duration::from_millis(2)), limit)
+        }
+    }
+
+    /// Raises a volume by one step, wrapping around to silence after full volume.
+    pub fn next_volume(volume: f32) -> f32 {
+        let next = ((volume + VOLUME_STEP) / VOLUME_STEP).round() * VOLUME_STEP;
+        if next > 1.0 + VOLUME_STEP * 0.5 {
+            0.0
+        } else {
+            next.min(1.0)
+        }
+    }
+
+    /// The resolution after `current` in `RESOLUTIONS`.
+    pub fn next_resolution(current: Option<(u32, u32)>) -> (u32, u32) {
+        let index = current.and_then(|current| RESOLUTIONS.iter().position(|r| *r == current));
+        match index {
+            Some(index) => RESOLUTIONS[(index + 1) % RESOLUTIONS.len()],
+            None => RESOLUTIONS[0],
+        }
+    }
+
+    /// The frame limit after `current` in `FRAME_LIMITS`.
+    pub fn next_frame_limit(current: u32) -> u32 {
+        let index = FRAME_LIMITS.iter().position(|limit| *limit == current);
+        match index {
+            Some(index) => FRAME_LIMITS[(index + 1) % FRAME_LIMITS.len()],
+            None => FRAME_LIMITS[0],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_approx_eq::assert_approx_eq;
+    use test_case::test_case;
+
+    #[test_case(0.0 => 10 ; "from silence")]
+    #[test_case(0.2 => 30 ; "regular step")]
+    #[test_case(0.25 => 40 ; "snaps to step")]
+    #[test_case(0.9 => 100 ; "to full volume")]
+    #[test_case(1.0 => 0 ; "wraps around")]
+    fn test_next_volume(volume: f32) -> i32 {
+        (UserSettings::next_volume(volume) * 100.0).round() as i32
+    }
+
+    #[test_case(None => (1280, 720) ; "from display config")]
+    #[test_case(Some((1280, 720)) => (1600, 900) ; "regular step")]
+    #[test_case(Some((2560, 1440)) => (1280, 720) ; "wraps around")]
+    #[test_case(Some((800, 600)) => (1280, 720) ; "unknown resolution")]
+    fn test_next_resolution(current: Option<(u32, u32)>) -> (u32, u32) {
+        UserSettings::next_resolution(current)
+    }
+
+    #[test_case(60 => 144 ; "regular step")]
+    #[test_case(0 => 60 ; "wraps around")]
+    #[test_case(75 => 60 ; "unknown limit")]
+    fn test_next_frame_limit(current: u32) -> u32 {
+        UserSettings::next_frame_limit(current)
+    }
+
+    #[test_case(false, 144 => 144 ; "limited")]
+    #[test_case(false, 0 => 0 ; "unlimited")]
+    #[test_case(true, 144 => 60 ; "vsync caps limit")]
+    #[test_case(true, 0 => 60 ; "vsync caps unlimited")]
+    #[test_case(true, 30 => 30 ; "vsync keeps lower limit")]
+    fn test_frame_rate_limit(vsync: bool, frame_limit: u32) -> u32 {
+        let settings = UserSettings {
+            vsync,
+            frame_limit,
+            ..UserSettings::default()
+        };
+        settings.frame_rate_limit().1
+    }
+
+    #[test]
+    fn resolution_overrides_display_config() {
+        let mut display_config = DisplayConfig::default();
+        display_config.dimensions = Some((1280, 720));
+
+        UserSettings::default().apply_to_display(&mut display_config);
+        assert_eq!(display_config.dimensions, Some((1280, 720)));
+
+        let settings = UserSettings {
+            resolution: Some((1920, 1080)),
+            ..UserSettings::default()
+        };
+        settings.apply_to_display(&mut display_config);
+        assert_eq!(display_config.dimensions, Some((1920, 1080)));
+    }
+
+    #[test]
+    fn write_and_load_round_trip() -> amethyst::Result<()> {
+        let dir = crate::test_harness::temp_path("settings");
+        let _ = std::fs::remove_dir_all(&dir);
+        let path = dir.join("config/user_settings.ron");
+        let settings = UserSettings {
+            music_volume: 0.5,
+            fullscreen: true,
+            resolution: Some((1920, 1080)),
+            ..UserSettings::default()
+        };
+        settings.store(&path)?;
+        let loaded = UserSettings::load_or_default(&path);
+        std::fs::remove_dir_all(&dir)?;
+
+        assert_approx_eq!(loaded.music_volume, 0.5);
+        assert_eq!(loaded, settings);
+        Ok(())
+    }
+
+    #[test]
+    fn missing_file_falls_back_to_default() {
+        let settings = UserSettings::load_or_default("this/file/does/not/exist.ron");
+        assert_eq!(settings, UserSettings::default());
+    }
+}
@@ -1,9 +1,10 @@
 use std::marker::PhantomData;
 
 use amethyst::{
-    core::{ArcThreadPool, SystemBundle, SystemDesc},
-    ecs::prelude::{Dispatcher, DispatcherBuilder, System, World, WorldExt},
+    core::{ArcThreadPool, RunNowDesc, SystemBundle, SystemDesc},
+    ecs::prelude::{Dispatcher, DispatcherBuilder, RunNow, System, World, WorldExt},
     error::Error,
+    shred::Resource,
     DataDispose, DataInit,
 };
 use amethyst_test::GameUpdate;
@@ -98,6 +99,31 @@ impl<'a, 'b> CustomGameDataBuilder<'a, 'b> {
         self
     }
 
+    /// Adds a system to the base dispatcher that runs on the main thread, after the other systems,
+    /// e.g. for input devices that may not be used from other threads.
+    #[allow(dead_code)]
+    pub fn with_base_thread_local<RD, R>(mut self, run_now_desc: RD) -> Self
+    where
+        RD: RunNowDesc<'a, 'b, R> + 'static,
+        R: for<'c> RunNow<'c> + 'b,
+    {
+        self.base_dispatcher_operations.push(Box::new(AddThreadLocal {
+            run_now_desc,
+            marker: PhantomData::<R>,
+        }));
+        self
+    }
+
+    /// Inserts a resource into the `World` before any of the dispatchers are set up.
+    pub fn with_resource<R>(mut self, resource: R) -> Self
+    where
+        R: Resource,
+    {
+        self.base_dispatcher_operations
+            .push(Box::new(AddResource { resource }));
+        self
+    }
+
     #[allow(dead_code)]
     pub fn with_running<SD, S>(
         mut self,
@@ -196,6 +222,27 @@ where
     }
 }
 
+struct AddThreadLocal<RD, R> {
+    run_now_desc: RD,
+    marker: PhantomData<R>,
+}
+
+impl<'a, 'b, RD, R> DispatcherOperation<'a, 'b> for AddThreadLocal<RD, R>
+where
+    RD: RunNowDesc<'a, 'b, R>,
+    R: for<'c> RunNow<'c> + 'b,
+{
+    fn exec(
+        self: Box<Self>,
+        world: &mut World,
+        dispatcher_builder: &mut DispatcherBuilder<'a, 'b>,
+    ) -> Result<(), Error> {
+        let system = self.run_now_desc.build(world);
+        dispatcher_builder.add_thread_local(system);
+        Ok(())
+    }
+}
+
 struct AddBundle<B> {
     bundle: B,
 }
@@ -213,3 +260,21 @@ where
         Ok(())
     }
 }
+
+struct AddResource<R> {
+    resource: R,
+}
+
+impl<'a, 'b, R> DispatcherOperation<'a, 'b> for AddResource<R>
+where
+    R: Resource,
+{
+    fn exec(
+        self: Box<Self>,
+        world: &mut World,
+        _dispatcher_builder: &mut DispatcherBuilder<'a, 'b>,
+    ) -> Result<(), Error> {
+        world.insert(self.resource);
+        Ok(())
+    }
+}
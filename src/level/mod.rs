@@ -0,0 +1,177 @@
+use crate::config::ArenaConfig;
+use amethyst::{config::Config, error::Error};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Directory below the assets directory the level files are read from.
+pub const LEVELS_DIR: &str = "prefab/levels";
+/// Levels shipped with the game, by file name without extension. The level select lists them first,
+/// followed by any other level file found in the levels directory.
+pub const LEVELS: [&str; 4] = ["classic", "pillars", "narrow_goals", "moving_walls"];
+/// Width of the goal posts next to a goal smaller than the arena height. The posts are centred on
+/// the edge of the arena, so balls bouncing off them stay out of reach of the `WinnerSystem`.
+pub const GOAL_POST_WIDTH: f32 = 2.0;
+
+/// A box the balls bounce off, as placed in a level file.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ObstacleLayout {
+    /// Centre of the obstacle at the start of a match.
+    pub position: [f32; 2],
+    pub size: [f32; 2],
+    /// Velocity a moving obstacle starts with, zero for a static one.
+    #[serde(default)]
+    pub velocity: [f32; 2],
+    /// Distance a moving obstacle travels away from `position` along each axis before it turns
+    /// around.
+    #[serde(default)]
+    pub travel: [f32; 2],
+}
+
+/// Layout of the arena a match is played in. The match uses the `Level` resource, which the
+/// `LevelSelectState` fills from the level files.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Level {
+    /// Name shown in the level select.
+    pub name: String,
+    /// Arena dimensions used instead of the configured ones.
+    pub arena: Option<ArenaConfig>,
+    /// Height of the goal in the middle of the left and right edges. Balls only score through the
+    /// goal, and bounce off the goal posts above and below it. `None` makes the whole edge a goal.
+    pub goal_height: Option<f32>,
+    pub obstacles: Vec<ObstacleLayout>,
+}
+
+impl Default for Level {
+    fn default() -> Self {
+        Self {
+            name: "CLASSIC".to_string(),
+            arena: None,
+            goal_height: None,
+            obstacles: Vec::new(),
+        }
+    }
+}
+
+impl Level {
+    /// Reads the level file called `name` from the levels directory.
+    pub fn read(assets_dir: &Path, name: &str) -> Result<Self, Error> {
+        Ok(Self::load_no_fallback(level_path(assets_dir, name))?)
+    }
+
+    /// Whether a ball at height `y` is in front of the goal of an arena of the given `height`.
+    pub fn in_goal(&self, y: f32, height: f32) -> bool {
+        self.goal_height
+            .map_or(true, |goal_height| (y - height * 0.5).abs() <= goal_height * 0.5)
+    }
+
+    /// The obstacles of the level, followed by the goal posts of an arena with the given
+    /// dimensions.
+    pub fn obstacles_with_goal_posts(&self, arena: &ArenaConfig) -> Vec<ObstacleLayout> {
+        let mut obstacles = self.obstacles.clone();
+        if let Some(goal_height) = self.goal_height {
+            let post_height = ((arena.height - goal_height) * 0.5).max(0.0);
+            if post_height > 0.0 {
+                for x in &[0.0, arena.width] {
+                    for y in &[post_height * 0.5, arena.height - post_height * 0.5] {
+                        obstacles.push(ObstacleLayout {
+                            position: [*x, *y],
+                            size: [GOAL_POST_WIDTH, post_height],
+                            velocity: [0.0, 0.0],
+                            travel: [0.0, 0.0],
+                        });
+                    }
+                }
+            }
+        }
+        obstacles
+    }
+}
+
+/// Names of the levels offered by the level select, in the order described at `LEVELS`. The shipped
+/// levels are listed even if their file is missing, the level select shows them as unavailable.
+pub fn level_names(assets_dir: &Path) -> Result<Vec<String>, Error> {
+    let mut others = Vec::new();
+    for entry in fs::read_dir(assets_dir.join(LEVELS_DIR))? {
+        let path = entry?.path();
+        if path.extension().map_or(false, |extension| extension == "ron") {
+            if let Some(name) = path.file_stem().and_then(|name| name.to_str()) {
+                if !LEVELS.contains(&name) {
+                    others.push(name.to_string());
+                }
+            }
+        }
+    }
+    others.sort();
+    Ok(LEVELS.iter().map(|name| name.to_string()).chain(others).collect())
+}
+
+/// Location of the level file called `name`.
+pub fn level_path(assets_dir: &Path, name: &str) -> PathBuf {
+    assets_dir.join(LEVELS_DIR).join(format!("{}.ron", name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use test_case::test_case;
+
+    #[test_case(None, 5.0 => true ; "whole edge")]
+    #[test_case(Some(30.0), 45.0 => true ; "centre")]
+    #[test_case(Some(30.0), 60.0 => true ; "edge of the goal")]
+    #[test_case(Some(30.0), 61.0 => false ; "above the goal")]
+    #[test_case(Some(30.0), 20.0 => false ; "below the goal")]
+    fn test_in_goal(goal_height: Option<f32>, y: f32) -> bool {
+        let level = Level {
+            goal_height,
+            ..Level::default()
+        };
+        level.in_goal(y, 90.0)
+    }
+
+    #[test]
+    fn goal_posts_fill_the_edges() {
+        let level = Level {
+            goal_height: Some(30.0),
+            ..Level::default()
+        };
+        let posts = level.obstacles_with_goal_posts(&ArenaConfig::default());
+        assert_eq!(posts.len(), 4);
+        for post in posts {
+            assert_eq!(post.size, [GOAL_POST_WIDTH, 30.0]);
+            assert!(post.position[1] == 15.0 || post.position[1] == 75.0);
+        }
+    }
+
+    #[test]
+    fn list_added_level_files_after_the_shipped_ones() -> amethyst::Result<()> {
+        let assets_dir = crate::test_harness::temp_path("level-names");
+        fs::create_dir_all(assets_dir.join(LEVELS_DIR))?;
+        for name in &["zigzag", "pillars", "arches"] {
+            Level::default().write(level_path(&assets_dir, name))?;
+        }
+        fs::write(assets_dir.join(LEVELS_DIR).join("notes.txt"), "not a level")?;
+
+        let names = level_names(&assets_dir);
+        fs::remove_dir_all(&assets_dir)?;
+
+        let mut expected = LEVELS.iter().map(|name| name.to_string()).collect::<Vec<_>>();
+        expected.extend(vec!["arches".to_string(), "zigzag".to_string()]);
+        assert_eq!(names?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn read_every_level() -> amethyst::Result<()> {
+        let assets_dir = crate::initialize_paths()?.assets_dir;
+        for name in &level_names(&assets_dir)? {
+            let level = Level::read(&assets_dir, name)?;
+            assert!(!level.name.is_empty());
+        }
+        Ok(())
+    }
+}
@@ -1,12 +1,34 @@
+mod asset_root;
 mod audio;
+mod bindings;
+mod cli;
+mod config;
 mod game_data;
+mod level;
+mod network;
+mod replay;
+mod rng;
+mod save;
 mod states;
 mod systems;
 mod test_harness;
 
-use crate::{audio::Music, systems::UiEventHandlerSystemDesc};
+use crate::{
+    asset_root::{AssetRoots, UserDirs},
+    audio::Music,
+    bindings::{AxisBinding, GameBindings},
+    cli::{Command, CommandLine, StartState},
+    config::{load_bindings, MatchRules, PongConfig, UserBindings, UserSettings},
+    network::{NetworkHost, NetworkRole, RollbackSession, UdpTransport},
+    systems::{
+        AiDifficulty, ControllerSystemDesc, HotReloadEvent, HotReloadSystemDesc, MatchOver, MenuFocusSystemDesc,
+        PointerSystemDesc, UiEventHandlerSystemDesc, WatchedFile, WatchedKind,
+    },
+};
 use amethyst::{
-    audio::{AudioBundle, DjSystemDesc},
+    assets::AssetStorage,
+    audio::{AudioBundle, DjSystemDesc, Source},
+    config::Config,
     core::{
         ecs::{Read, SystemData, World},
         frame_limiter::FrameRateLimitStrategy,
@@ -17,32 +39,26 @@ use amethyst::{
     derive::EventReader,
     ecs::{Component, DenseVecStorage},
     error::Error,
-    input::{BindingTypes, InputBundle, InputEvent, StringBindings},
+    input::{BindingTypes, Bindings, InputBundle, InputEvent},
     prelude::*,
     renderer::{
         plugins::{RenderFlat2D, RenderToWindow},
         types::DefaultBackend,
-        RenderingBundle,
+        RenderingBundle, SpriteSheet, Texture,
     },
-    ui::{RenderUi, UiBundle, UiEvent},
-    utils::{application_root_dir, fps_counter::FpsCounterBundle},
+    ui::{FontAsset, RenderUi, UiBundle, UiEvent},
+    utils::fps_counter::FpsCounterBundle,
+    window::{DisplayConfig, ScreenDimensions},
     winit::Event,
 };
 use derivative::Derivative;
+use serde::{Deserialize, Serialize};
 extern crate dunce;
 use crate::game_data::{CustomGameData, CustomGameDataBuilder};
 use sentry::integrations::panic::register_panic_handler;
-use std::{path, time::Duration};
-
-const ARENA_HEIGHT: f32 = 90.0;
-const ARENA_WIDTH: f32 = 160.0;
-const PADDLE_HEIGHT: f32 = 16.0;
-const PADDLE_WIDTH: f32 = 4.0;
-const PADDLE_VELOCITY: f32 = 75.0;
-
-const BALL_VELOCITY_X: f32 = 75.0;
-const BALL_VELOCITY_Y: f32 = 50.0;
-const BALL_RADIUS: f32 = 2.0;
+#[cfg(feature = "controller")]
+use amethyst::input::SdlEventsSystemDesc;
+use std::path;
 
 const AUDIO_MUSIC: &[&str] = &[
     "audio/Computer_Music_All-Stars_-_Wheres_My_Jetpack.ogg",
@@ -51,76 +67,202 @@ const AUDIO_MUSIC: &[&str] = &[
 const AUDIO_BOUNCE: &str = "audio/bounce.ogg";
 const AUDIO_SCORE: &str = "audio/score.ogg";
 
+const HUD_PREFAB: &str = "ui/hud.ron";
+const MENU_PREFAB: &str = "ui/menu.ron";
+const GAME_OVER_PREFAB: &str = "ui/game_over.ron";
+
 fn main() -> amethyst::Result<()> {
-    amethyst::start_logger(amethyst::LoggerConfig::default());
+    let command_line = match Command::from_args(std::env::args().skip(1))? {
+        Command::Run(command_line) => command_line,
+        Command::Help => {
+            println!("{}", cli::USAGE);
+            return Ok(());
+        }
+        Command::Version => {
+            println!("{}", cli::VERSION);
+            return Ok(());
+        }
+    };
+    amethyst::start_logger(command_line.logger_config());
 
     let sentry = sentry::init(sentry::ClientOptions::default());
     if sentry.is_enabled() {
         register_panic_handler();
     }
 
-    let mut game = build_game()?;
+    let mut game = match command_line.network {
+        None if command_line.headless => build_game(states::HeadlessState::default(), &command_line)?,
+        None => match command_line.start {
+            StartState::Welcome => build_game(states::WelcomeScreen::default(), &command_line)?,
+            StartState::Menu => build_game(states::MainMenu::default(), &command_line)?,
+            StartState::Pong => build_game(states::Pong::default(), &command_line)?,
+        },
+        Some(NetworkRole::Host(address)) => {
+            // A headless host leaves every side to the clients.
+            let local_sides: &[Side] = if command_line.headless { &[] } else { &[Side::Left] };
+            let host = NetworkHost::bind(address, local_sides)?;
+            build_game(states::HostState::new(host, command_line.headless), &command_line)?
+        }
+        Some(NetworkRole::Join(address)) => build_game(states::NetworkClientState::new(address), &command_line)?,
+        Some(NetworkRole::Peer { listen, remote, side }) => {
+            let transport = UdpTransport::bind(listen, remote)?;
+            // the left peer picks the seed of the match
+            let seed = if side == Side::Left { Some(rand::random()) } else { None };
+            let session = RollbackSession::new(side, Box::new(transport), seed);
+            build_game(states::Pong::peer(session), &command_line)?
+        }
+    };
     game.run();
     Ok(())
 }
 
-fn initialize_paths() -> Result<(path::PathBuf, path::PathBuf, path::PathBuf), Error> {
-    let app_root = initialize_app_root()?;
-    let display_config_path = app_root.join("resources/config/display.ron");
-    let key_bindings_path = {
-        if cfg!(feature = "sdl_controller") {
-            app_root.join("resources/config/input_controller.ron")
+/// Locations of the config files and the asset directory the game is started with.
+#[derive(Debug, Clone)]
+pub struct GamePaths {
+    pub display_config: path::PathBuf,
+    pub key_bindings: path::PathBuf,
+    /// Controller bindings merged into `key_bindings`, only set when built with the `controller`
+    /// feature.
+    pub controller_bindings: Option<path::PathBuf>,
+    pub pong_config: path::PathBuf,
+    /// Settings chosen in the options menu. The file only exists once the user changed something.
+    pub user_settings: path::PathBuf,
+    /// Bindings changed in the rebinding screen, layered over `key_bindings`.
+    pub user_bindings: path::PathBuf,
+    /// Directory holding the save slots.
+    pub saves_dir: path::PathBuf,
+    /// Directory the replay of the last finished match is written to.
+    pub replays_dir: path::PathBuf,
+    pub assets_dir: path::PathBuf,
+}
+
+impl GamePaths {
+    /// The paths of a game using the assets in `assets_dir`, with the files written by the game in
+    /// the directories of the user, see `UserDirs`.
+    pub fn new(assets_dir: path::PathBuf) -> Self {
+        let user_dirs = UserDirs::from_env().unwrap_or_else(|| UserDirs::next_to(&assets_dir));
+        Self::with_user_dirs(assets_dir, &user_dirs)
+    }
+
+    /// The paths of a game using the read-only assets in `assets_dir`, writing its files to
+    /// `user_dirs`.
+    pub fn with_user_dirs(assets_dir: path::PathBuf, user_dirs: &UserDirs) -> Self {
+        let controller_bindings = if cfg!(feature = "controller") {
+            Some(assets_dir.join("config/input_controller.ron"))
         } else {
-            app_root.join("resources/config/input.ron")
+            None
+        };
+        Self {
+            display_config: assets_dir.join("config/display.ron"),
+            key_bindings: assets_dir.join("config/input.ron"),
+            controller_bindings,
+            pong_config: assets_dir.join("config/pong.ron"),
+            user_settings: user_dirs.config.join("user_settings.ron"),
+            user_bindings: user_dirs.config.join("user_bindings.ron"),
+            saves_dir: user_dirs.data.join("saves"),
+            replays_dir: user_dirs.data.join("replays"),
+            assets_dir,
         }
-    };
-    let assets_dir = app_root.join("resources");
-    Ok((display_config_path, key_bindings_path, assets_dir))
-}
+    }
 
-fn initialize_app_root() -> Result<path::PathBuf, Error> {
-    let app_root = dunce::canonicalize(application_root_dir()?)?;
-    let directory = "amethyst-2d-playground";
-    if let Some(index) = app_root.to_str().unwrap_or_default().rfind("amethyst-2d-playground") {
-        let mut path = app_root.to_str().unwrap_or_default().to_string();
-        path.truncate(index + directory.len());
-        return Ok(path::PathBuf::from(path));
+    /// The shipped bindings of every input device the game was built for.
+    pub fn load_bindings(&self) -> Result<Bindings<GameBindings>, Error> {
+        load_bindings(&self.key_bindings, self.controller_bindings.as_deref())
     }
-    Ok(app_root)
 }
 
-fn build_game(
-) -> Result<CoreApplication<'static, CustomGameData<'static, 'static>, GameStateEvent, GameStateEventReader>, Error> {
-    let (display_config_path, key_bindings_path, assets_dir) = initialize_paths()?;
-    let game_data = build_game_data(display_config_path, key_bindings_path)?;
-    let game =
-        CoreApplication::<'static, CustomGameData<'static, 'static>, GameStateEvent, GameStateEventReader>::build(
-            assets_dir,
-            states::WelcomeScreen::default(),
-        )?
-        .with_frame_limit(FrameRateLimitStrategy::SleepAndYield(Duration::from_millis(2)), 144)
+/// Paths of the game, with the asset directory found by `AssetRoots`.
+fn initialize_paths() -> Result<GamePaths, Error> {
+    Ok(GamePaths::new(AssetRoots::from_env(None).resolve()?))
+}
+
+type Game = CoreApplication<'static, CustomGameData<'static, 'static>, GameStateEvent, GameStateEventReader>;
+
+/// Builds the game starting in `initial_state`. A headless game opens no window, plays no audio
+/// and runs at the simulation tick rate.
+fn build_game<S>(initial_state: S, command_line: &CommandLine) -> Result<Game, Error>
+where
+    S: State<CustomGameData<'static, 'static>, GameStateEvent> + 'static,
+{
+    let paths = command_line.game_paths()?;
+    let assets_dir = paths.assets_dir.clone();
+    let (frame_limit_strategy, frame_limit) = if command_line.headless {
+        let tick_rate = PongConfig::load_no_fallback(&paths.pong_config)?.simulation.tick_rate;
+        (FrameRateLimitStrategy::Sleep, tick_rate.max(1.0) as u32)
+    } else {
+        UserSettings::load_or_default(&paths.user_settings).frame_rate_limit()
+    };
+    let game_data = build_game_data(paths, command_line)?;
+    let game = Game::build(assets_dir, initial_state)?
+        .with_frame_limit(frame_limit_strategy, frame_limit)
         .build(game_data)?;
     Ok(game)
 }
 
 fn build_game_data(
-    display_config_path: path::PathBuf,
-    key_bindings_path: path::PathBuf,
+    paths: GamePaths,
+    command_line: &CommandLine,
 ) -> Result<CustomGameDataBuilder<'static, 'static>, Error> {
     use log::warn;
+    let game_paths = paths.clone();
+    let GamePaths {
+        display_config: display_config_path,
+        key_bindings: key_bindings_path,
+        controller_bindings: controller_bindings_path,
+        pong_config: pong_config_path,
+        user_settings: user_settings_path,
+        user_bindings: user_bindings_path,
+        assets_dir,
+        ..
+    } = paths;
+
     if key_bindings_path.as_path().exists() == false || key_bindings_path.as_path().is_file() == false {
         let path = key_bindings_path.into_os_string();
         warn!("{:?} does not exist", path);
         return Err(Error::from_string("bad key_bindings_path"));
     }
 
-    if display_config_path.as_path().exists() == false || display_config_path.as_path().is_file() == false {
+    // a headless game opens no window to configure
+    let display_config_exists = display_config_path.as_path().exists() && display_config_path.as_path().is_file();
+    if !command_line.headless && display_config_exists == false {
         let path = display_config_path.into_os_string();
         warn!("{:?} does not exist", path);
         return Err(Error::from_string("bad display_config_path"));
     }
 
-    let builder = if cfg!(test) {
+    if pong_config_path.as_path().exists() == false || pong_config_path.as_path().is_file() == false {
+        let path = pong_config_path.into_os_string();
+        warn!("{:?} does not exist", path);
+        return Err(Error::from_string("bad pong_config_path"));
+    }
+    let pong_config = PongConfig::load_no_fallback(&pong_config_path)?;
+    let user_settings = UserSettings::load_or_default(&user_settings_path);
+    let user_bindings = UserBindings::load_or_default(&user_bindings_path);
+    let mut bindings = game_paths.load_bindings()?;
+    user_bindings.apply(&mut bindings);
+
+    let mut watched_files = vec![
+        WatchedFile::new(key_bindings_path.clone(), WatchedKind::KeyBindings),
+        WatchedFile::new(pong_config_path, WatchedKind::PongConfig),
+        WatchedFile::new(assets_dir.join(HUD_PREFAB), WatchedKind::UiPrefab(HUD_PREFAB)),
+        WatchedFile::new(assets_dir.join(MENU_PREFAB), WatchedKind::UiPrefab(MENU_PREFAB)),
+    ];
+    if let Some(controller_bindings_path) = controller_bindings_path {
+        watched_files.push(WatchedFile::new(controller_bindings_path, WatchedKind::KeyBindings));
+    }
+    let display_config = if command_line.headless {
+        None
+    } else {
+        let mut display_config = DisplayConfig::load_no_fallback(&display_config_path)?;
+        user_settings.apply_to_display(&mut display_config);
+        if let Some(window_size) = command_line.window_size {
+            display_config.dimensions = Some(window_size);
+        }
+        watched_files.push(WatchedFile::new(display_config_path, WatchedKind::DisplayConfig));
+        Some(display_config)
+    };
+
+    let builder = if cfg!(test) || command_line.headless {
         CustomGameDataBuilder::default()
     } else {
         CustomGameDataBuilder::default()
@@ -132,59 +274,333 @@ fn build_game_data(
             )
     };
     let builder = builder
+        .with_resource(pong_config)
+        .with_resource(user_settings)
+        .with_resource(user_bindings)
+        .with_resource(game_paths)
+        .with_resource(MatchSettings::default())
         .with_base_bundle(TransformBundle::new())
-        .with_base_bundle(InputBundle::<StringBindings>::new().with_bindings_from_file(key_bindings_path)?)
+        .with_base_bundle(InputBundle::<GameBindings>::new().with_bindings(bindings))
+        .with_base(ControllerSystemDesc::default(), "controller_system", &["input_system"])
+        .with_base(PointerSystemDesc::default(), "pointer_system", &["input_system"])
+        .with_base(MenuFocusSystemDesc::default(), "menu_focus_system", &["input_system"])
         .with_base_bundle(FpsCounterBundle::default())
         .with_base(UiEventHandlerSystemDesc::default(), "ui_event_handler", &[])
-        .with_base_bundle(UiBundle::<StringBindings>::new())
-        .with_base_bundle(
+        .with_base(HotReloadSystemDesc::new(watched_files), "hot_reload_system", &[])
+        .with_base_bundle(UiBundle::<GameBindings>::new());
+    // SDL reports the controllers, and may only be used from the main thread
+    #[cfg(feature = "controller")]
+    let builder = if command_line.headless {
+        builder
+    } else {
+        builder.with_base_thread_local(SdlEventsSystemDesc::<GameBindings>::default())
+    };
+    let builder = match display_config {
+        Some(display_config) => builder.with_base_bundle(
             RenderingBundle::<DefaultBackend>::new()
-            // The RenderToWindow plugin provides all the scaffolding for opening a window and
-            // drawing on it
-            .with_plugin(RenderToWindow::from_config_path(display_config_path).with_clear([0.34, 0.36, 0.52, 1.0]))
-            .with_plugin(RenderFlat2D::default())
-            .with_plugin(RenderUi::default()),
-        );
+                // The RenderToWindow plugin provides all the scaffolding for opening a window and
+                // drawing on it
+                .with_plugin(RenderToWindow::from_config(display_config).with_clear([0.34, 0.36, 0.52, 1.0]))
+                .with_plugin(RenderFlat2D::default())
+                .with_plugin(RenderUi::default()),
+        ),
+        // Without renderer and audio output, the storages of the loaded assets are not set up by
+        // their processors. The HUD is laid out for a screen nobody sees.
+        None => builder
+            .with_resource(ScreenDimensions::new(1920, 1280, 1.0))
+            .with_resource(AssetStorage::<Source>::default())
+            .with_resource(AssetStorage::<Texture>::default())
+            .with_resource(AssetStorage::<SpriteSheet>::default())
+            .with_resource(AssetStorage::<FontAsset>::default()),
+    };
     Ok(builder)
 }
 
+#[derive(Debug, Clone, PartialEq)]
 pub struct Ball {
     pub velocity: [f32; 2],
     pub radius: f32,
+    /// Player whose paddle the ball touched last during the current rally.
+    pub last_hit: Option<Side>,
 }
 
 impl Component for Ball {
     type Storage = DenseVecStorage<Self>;
 }
 
-#[derive(PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Side {
     Left,
     Right,
+    Top,
+    Bottom,
+}
+
+impl Side {
+    /// Every side in the order of the players, P1 to P4.
+    pub const ALL: [Self; 4] = [Self::Left, Self::Right, Self::Top, Self::Bottom];
+
+    /// The player on the other side of the arena.
+    pub const fn opponent(self) -> Self {
+        match self {
+            Self::Left => Self::Right,
+            Self::Right => Self::Left,
+            Self::Top => Self::Bottom,
+            Self::Bottom => Self::Top,
+        }
+    }
+
+    /// Index of the player guarding this side, e.g. in the `ScoreBoard`.
+    pub const fn index(self) -> usize {
+        match self {
+            Self::Left => 0,
+            Self::Right => 1,
+            Self::Top => 2,
+            Self::Bottom => 3,
+        }
+    }
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Left => "P1",
+            Self::Right => "P2",
+            Self::Top => "P3",
+            Self::Bottom => "P4",
+        }
+    }
+
+    /// The input axis moving the paddle on this side.
+    pub const fn input_axis(self) -> AxisBinding {
+        match self {
+            Self::Left => AxisBinding::LeftPaddle,
+            Self::Right => AxisBinding::RightPaddle,
+            Self::Top => AxisBinding::TopPaddle,
+            Self::Bottom => AxisBinding::BottomPaddle,
+        }
+    }
+
+    /// The axis the paddle on this side moves along: 1 (y) for the left and right paddles, 0 (x)
+    /// for the top and bottom paddles.
+    pub const fn axis(self) -> usize {
+        match self {
+            Self::Left | Self::Right => 1,
+            Self::Top | Self::Bottom => 0,
+        }
+    }
+
+    /// Unit vector the front of the paddle on this side faces, pointing into the arena.
+    pub const fn normal(self) -> [f32; 2] {
+        match self {
+            Self::Left => [1.0, 0.0],
+            Self::Right => [-1.0, 0.0],
+            Self::Top => [0.0, -1.0],
+            Self::Bottom => [0.0, 1.0],
+        }
+    }
+}
+
+/// Who moves a paddle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PaddleController {
+    Human,
+    Cpu(AiDifficulty),
+    /// A human following the mouse cursor, or a touch on their half of the screen, with the paddle.
+    Pointer,
+}
+
+impl Default for PaddleController {
+    fn default() -> Self {
+        Self::Human
+    }
+}
+
+impl PaddleController {
+    /// The controller that follows this one when cycling through the choices in the main menu.
+    pub const fn next(self) -> Self {
+        match self {
+            Self::Human => Self::Cpu(AiDifficulty::Easy),
+            Self::Cpu(AiDifficulty::Easy) => Self::Cpu(AiDifficulty::Normal),
+            Self::Cpu(AiDifficulty::Normal) => Self::Cpu(AiDifficulty::Hard),
+            Self::Cpu(AiDifficulty::Hard) => Self::Pointer,
+            Self::Pointer => Self::Human,
+        }
+    }
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Human => "HUMAN",
+            Self::Cpu(difficulty) => difficulty.name(),
+            Self::Pointer => "POINTER",
+        }
+    }
+}
+
+/// Rules variant a match is played with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GameMode {
+    /// One ball at a time.
+    Classic,
+    /// Extra balls join the match every now and then, see `MultiBallConfig`.
+    MultiBall,
+    /// Four players guard the four walls of the arena.
+    FourPlayer,
+}
+
+impl Default for GameMode {
+    fn default() -> Self {
+        Self::Classic
+    }
+}
+
+impl GameMode {
+    /// The mode that follows this one when cycling through the choices in the main menu.
+    pub const fn next(self) -> Self {
+        match self {
+            Self::Classic => Self::MultiBall,
+            Self::MultiBall => Self::FourPlayer,
+            Self::FourPlayer => Self::Classic,
+        }
+    }
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Classic => "CLASSIC",
+            Self::MultiBall => "MULTI-BALL",
+            Self::FourPlayer => "FOUR PLAYERS",
+        }
+    }
+
+    /// The sides guarded by a paddle. The other walls of the arena bounce the balls back.
+    pub fn sides(self) -> &'static [Side] {
+        match self {
+            Self::Classic | Self::MultiBall => &Side::ALL[..2],
+            Self::FourPlayer => &Side::ALL,
+        }
+    }
+}
+
+/// Choices made in the main menu for the next match.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MatchSettings {
+    pub left: PaddleController,
+    pub right: PaddleController,
+    /// Only plays in `GameMode::FourPlayer`.
+    #[serde(default)]
+    pub top: PaddleController,
+    /// Only plays in `GameMode::FourPlayer`.
+    #[serde(default)]
+    pub bottom: PaddleController,
+    #[serde(default)]
+    pub mode: GameMode,
+    /// Whether power-ups appear in the arena, see `PowerUpConfig`.
+    #[serde(default)]
+    pub power_ups: bool,
+}
+
+impl MatchSettings {
+    pub fn controller(&self, side: Side) -> PaddleController {
+        match side {
+            Side::Left => self.left,
+            Side::Right => self.right,
+            Side::Top => self.top,
+            Side::Bottom => self.bottom,
+        }
+    }
+
+    pub fn controller_mut(&mut self, side: Side) -> &mut PaddleController {
+        match side {
+            Side::Left => &mut self.left,
+            Side::Right => &mut self.right,
+            Side::Top => &mut self.top,
+            Side::Bottom => &mut self.bottom,
+        }
+    }
 }
 
 pub struct Paddle {
     pub velocity: f32,
     pub side: Side,
+    /// Thickness of the paddle, across its axis.
     pub width: f32,
+    /// Length of the paddle, along its axis.
     pub height: f32,
+    /// Velocity the paddle moved with along its axis during the last simulation step, positive
+    /// upwards or to the right.
+    pub movement: f32,
+}
+
+impl Paddle {
+    /// Extent of the paddle along the x and y axis of the arena.
+    pub fn size(&self) -> [f32; 2] {
+        match self.side.axis() {
+            1 => [self.width, self.height],
+            _ => [self.height, self.width],
+        }
+    }
 }
 
 impl Component for Paddle {
     type Storage = DenseVecStorage<Self>;
 }
 
-#[derive(Default)]
+/// Points of every player, indexed by `Side::index`.
+#[derive(Debug, Default, Clone, PartialEq)]
 pub struct ScoreBoard {
-    score_left: i32,
-    score_right: i32,
+    scores: Vec<i32>,
 }
 
 impl ScoreBoard {
-    pub const fn new() -> Self {
+    /// A score board for a match between the given number of players, without any points.
+    pub fn new(players: usize) -> Self {
         Self {
-            score_left: 0,
-            score_right: 0,
+            scores: vec![0; players],
+        }
+    }
+
+    pub fn from_scores(scores: Vec<i32>) -> Self {
+        Self { scores }
+    }
+
+    pub fn scores(&self) -> &[i32] {
+        &self.scores
+    }
+
+    pub fn score(&self, side: Side) -> i32 {
+        self.scores.get(side.index()).copied().unwrap_or(0)
+    }
+
+    /// Awards a point to the player on `side`, and returns the new score. We top the score at 999
+    /// to avoid text overlap.
+    pub fn add_point(&mut self, side: Side) -> i32 {
+        if self.scores.len() <= side.index() {
+            self.scores.resize(side.index() + 1, 0);
+        }
+        let score = &mut self.scores[side.index()];
+        *score = (*score + 1).min(999);
+        *score
+    }
+
+    /// Returns the side that has won the match under the given rules, if the match is decided. The
+    /// leader has to be ahead of every other player by `win_by`.
+    pub fn winner(&self, rules: &MatchRules) -> Option<Side> {
+        let (leader, score) = Side::ALL
+            .iter()
+            .zip(&self.scores)
+            .fold(None, |leader: Option<(Side, i32)>, (side, score)| match leader {
+                Some((_, best)) if best >= *score => leader,
+                _ => Some((*side, *score)),
+            })?;
+        let runner_up = Side::ALL
+            .iter()
+            .zip(&self.scores)
+            .filter(|(side, _)| **side != leader)
+            .map(|(_, score)| *score)
+            .max()
+            .unwrap_or(0);
+        if score >= rules.winning_score && score - runner_up >= rules.win_by.max(1) {
+            Some(leader)
+        } else {
+            None
         }
     }
 }
@@ -199,7 +615,7 @@ pub enum TestEvent {
 #[derive(Debug, Derivative, EventReader)]
 #[derivative(Clone(bound = ""))]
 #[reader(GameStateEventReader)]
-pub enum GameStateEvent<T = StringBindings>
+pub enum GameStateEvent<T = GameBindings>
 where
     T: BindingTypes,
 {
@@ -209,6 +625,10 @@ where
     Ui(UiEvent),
     /// Events sent by the input system.
     Input(InputEvent<T>),
+    /// Events sent by the hot reload system.
+    HotReload(HotReloadEvent),
+    /// Events sent by the winner system when a match is decided.
+    MatchOver(MatchOver),
     Test(TestEvent),
 }
 
@@ -216,24 +636,121 @@ where
 mod tests {
     use super::*;
     use std::{panic, path::PathBuf};
+    use test_case::test_case;
 
     #[test]
     fn score_board_initialisation() {
-        let scoreboard = ScoreBoard::new();
-        assert_eq!(scoreboard.score_left, 0);
-        assert_eq!(scoreboard.score_right, 0);
-        assert_eq!(scoreboard.score_right, scoreboard.score_left);
+        let scoreboard = ScoreBoard::new(2);
+        assert_eq!(scoreboard.score(Side::Left), 0);
+        assert_eq!(scoreboard.score(Side::Right), 0);
+        assert_eq!(scoreboard.scores(), &[0, 0]);
+        assert_eq!(ScoreBoard::new(4).scores(), &[0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn score_board_add_point() {
+        let mut scoreboard = ScoreBoard::new(4);
+        assert_eq!(scoreboard.add_point(Side::Top), 1);
+        assert_eq!(scoreboard.add_point(Side::Top), 2);
+        assert_eq!(scoreboard.scores(), &[0, 0, 2, 0]);
+        assert_eq!(ScoreBoard::from_scores(vec![999, 0]).add_point(Side::Left), 999);
+    }
+
+    #[test_case(0, 0 => None ; "no points")]
+    #[test_case(11, 9 => Some(Side::Left) ; "left reaches winning score")]
+    #[test_case(3, 11 => Some(Side::Right) ; "right reaches winning score")]
+    #[test_case(11, 10 => None ; "lead too small")]
+    #[test_case(12, 10 => Some(Side::Left) ; "win by two after deuce")]
+    #[test_case(10, 8 => None ; "winning score not reached")]
+    fn score_board_winner(score_left: i32, score_right: i32) -> Option<Side> {
+        let scoreboard = ScoreBoard::from_scores(vec![score_left, score_right]);
+        scoreboard.winner(&MatchRules::default())
+    }
+
+    #[test_case(vec![11, 3, 9, 2] => Some(Side::Left) ; "ahead of everyone")]
+    #[test_case(vec![11, 3, 10, 2] => None ; "lead over third player too small")]
+    #[test_case(vec![4, 5, 2, 13] => Some(Side::Bottom) ; "fourth player")]
+    #[test_case(vec![11, 11, 0, 0] => None ; "tied leaders")]
+    fn score_board_winner_of_four(scores: Vec<i32>) -> Option<Side> {
+        ScoreBoard::from_scores(scores).winner(&MatchRules::default())
+    }
+
+    #[test]
+    fn score_board_winner_without_win_by() {
+        let rules = MatchRules {
+            winning_score: 5,
+            win_by: 0,
+        };
+        let scoreboard = ScoreBoard::from_scores(vec![4, 5]);
+        assert_eq!(scoreboard.winner(&rules), Some(Side::Right));
+    }
+
+    #[test]
+    fn paddle_controller_cycles_through_all_choices() {
+        let mut controller = PaddleController::default();
+        assert_eq!(controller, PaddleController::Human);
+        for expected in &[
+            PaddleController::Cpu(AiDifficulty::Easy),
+            PaddleController::Cpu(AiDifficulty::Normal),
+            PaddleController::Cpu(AiDifficulty::Hard),
+            PaddleController::Pointer,
+            PaddleController::Human,
+        ] {
+            controller = controller.next();
+            assert_eq!(controller, *expected);
+        }
+    }
+
+    #[test]
+    fn game_mode_cycles_through_all_modes() {
+        let mut mode = GameMode::default();
+        for expected in &[GameMode::MultiBall, GameMode::FourPlayer, GameMode::Classic] {
+            mode = mode.next();
+            assert_eq!(mode, *expected);
+        }
+        assert_eq!(GameMode::Classic.sides(), &[Side::Left, Side::Right]);
+        assert_eq!(GameMode::FourPlayer.sides().len(), 4);
     }
 
     #[test]
     fn validate_paths_are_not_garbage() -> amethyst::Result<()> {
-        let (mut display_config_path, mut key_bindings_path, mut assets_dir) = initialize_paths()?;
+        let GamePaths {
+            mut display_config,
+            mut key_bindings,
+            controller_bindings,
+            mut pong_config,
+            mut user_settings,
+            mut user_bindings,
+            mut saves_dir,
+            mut replays_dir,
+            mut assets_dir,
+        } = initialize_paths()?;
+
+        assert!(display_config.is_absolute());
+        assert!(display_config.pop());
+
+        assert!(key_bindings.is_absolute());
+        assert!(key_bindings.pop());
+
+        if let Some(mut controller_bindings) = controller_bindings {
+            assert!(controller_bindings.is_absolute());
+            assert!(controller_bindings.pop());
+        }
+
+        assert!(pong_config.is_absolute());
+        assert!(pong_config.pop());
+
+        assert!(user_settings.is_absolute());
+        assert!(user_settings.pop());
 
-        assert!(display_config_path.is_absolute());
-        assert!(display_config_path.pop());
+        assert!(user_bindings.is_absolute());
+        assert!(user_bindings.pop());
 
-        assert!(key_bindings_path.is_absolute());
-        assert!(key_bindings_path.pop());
+        assert!(saves_dir.is_absolute());
+        assert!(saves_dir.pop());
+
+        assert!(replays_dir.is_absolute());
+        assert!(replays_dir.pop());
 
         assert!(assets_dir.is_absolute());
         assert!(assets_dir.pop());
@@ -241,22 +758,80 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn user_files_are_outside_the_assets() -> amethyst::Result<()> {
+        let paths = initialize_paths()?;
+        for path in &[&paths.user_settings, &paths.user_bindings, &paths.saves_dir, &paths.replays_dir] {
+            assert!(!path.starts_with(&paths.assets_dir), "{:?} is below the assets", path);
+        }
+
+        let user_dirs = UserDirs::next_to(&paths.assets_dir);
+        let paths = GamePaths::with_user_dirs(paths.assets_dir, &user_dirs);
+        for path in &[&paths.user_settings, &paths.user_bindings, &paths.saves_dir, &paths.replays_dir] {
+            assert!(!path.starts_with(&paths.assets_dir), "{:?} is below the assets", path);
+        }
+        Ok(())
+    }
+
     #[test]
     fn validate_game_data_builder() -> amethyst::Result<()> {
-        let (display_config_path, key_bindings_path, _) = initialize_paths()?;
-        build_game_data(display_config_path, key_bindings_path)?;
+        build_game_data(initialize_paths()?, &CommandLine::default())?;
+        Ok(())
+    }
+
+    #[test]
+    fn validate_headless_game_data_builder() -> amethyst::Result<()> {
+        let paths = initialize_paths()?;
+        // the display config is not needed without a window
+        build_game_data(
+            GamePaths {
+                display_config: PathBuf::new(),
+                ..paths
+            },
+            &CommandLine {
+                headless: true,
+                ..CommandLine::default()
+            },
+        )?;
         Ok(())
     }
 
     #[test]
     fn validate_game_data_builder_garbage_key_bindings_path() {
-        let (_, key_bindings_path, _) = initialize_paths().expect("valid paths required");
-        assert!(build_game_data(PathBuf::new(), key_bindings_path).is_err());
+        let paths = initialize_paths().expect("valid paths required");
+        assert!(build_game_data(
+            GamePaths {
+                display_config: PathBuf::new(),
+                ..paths
+            },
+            &CommandLine::default()
+        )
+        .is_err());
     }
 
     #[test]
     fn validate_game_data_builder_garbage_display_config_path() {
-        let (display_config_path, ..) = initialize_paths().expect("valid paths required");
-        assert!(build_game_data(display_config_path, PathBuf::new()).is_err());
+        let paths = initialize_paths().expect("valid paths required");
+        assert!(build_game_data(
+            GamePaths {
+                key_bindings: PathBuf::new(),
+                ..paths
+            },
+            &CommandLine::default()
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn validate_game_data_builder_garbage_pong_config_path() {
+        let paths = initialize_paths().expect("valid paths required");
+        assert!(build_game_data(
+            GamePaths {
+                pong_config: PathBuf::new(),
+                ..paths
+            },
+            &CommandLine::default()
+        )
+        .is_err());
     }
 }
@@ -0,0 +1,653 @@
+mod rollback;
+
+pub use self::rollback::{
+    settings_hash, LinkConditions, PeerMessage, Rollback, RollbackSession, SimulatedTransport, Transport,
+    UdpTransport, DEFAULT_MAX_PREDICTION,
+};
+
+use crate::{
+    config::PongConfig,
+    level::Level,
+    replay::PaddleInputs,
+    save::{BallSnapshot, PaddleSnapshot, SaveGame},
+    MatchSettings, PaddleController, Side,
+};
+use amethyst::{ecs::prelude::World, error::Error};
+use serde::{Deserialize, Serialize};
+use std::{
+    io,
+    net::{IpAddr, SocketAddr, UdpSocket},
+    time::{Duration, Instant},
+};
+
+/// Version of the network protocol. Hosts turn away clients speaking another version.
+pub const PROTOCOL_VERSION: u32 = 1;
+/// Port a host listens on unless another one is given.
+pub const DEFAULT_PORT: u16 = 7777;
+/// Time without a message from a client after which the host frees its side.
+pub const PLAYER_TIMEOUT: Duration = Duration::from_secs(5);
+/// Time between two join requests of a client the host did not answer yet.
+pub const JOIN_INTERVAL: Duration = Duration::from_millis(250);
+/// Join requests a client sends before it gives up on the host.
+pub const MAX_JOIN_ATTEMPTS: u32 = 20;
+/// Largest datagram sent or received. Snapshots of a match with a handful of balls stay far below.
+const MAX_PACKET_SIZE: usize = 8192;
+
+/// Everything a client needs to show the match it joined.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MatchInfo {
+    /// Side the client plays on.
+    pub side: Side,
+    pub config: PongConfig,
+    pub match_settings: MatchSettings,
+    pub level: Level,
+}
+
+/// State of a match on the host, which the clients show instead of simulating it themselves.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Snapshot {
+    /// Increases with every snapshot, so clients can drop the ones arriving out of order.
+    pub sequence: u32,
+    pub balls: Vec<BallSnapshot>,
+    pub paddles: Vec<PaddleSnapshot>,
+    /// Score of every player, see `ScoreBoard`.
+    pub scores: Vec<i32>,
+}
+
+impl Snapshot {
+    /// Takes a snapshot of the match currently running in `world`.
+    pub fn capture(world: &World, sequence: u32) -> Self {
+        let SaveGame {
+            balls,
+            paddles,
+            scores,
+            ..
+        } = SaveGame::capture(world, None);
+        Self {
+            sequence,
+            balls,
+            paddles,
+            scores,
+        }
+    }
+}
+
+/// Part the game plays in a network match, chosen on the command line, see `CommandLine`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NetworkRole {
+    /// `--host [ADDRESS]` hosts a match on `address`.
+    Host(SocketAddr),
+    /// `--join ADDRESS` joins the match hosted on `address`.
+    Join(SocketAddr),
+    /// `--peer ADDRESS` plays a rollback match against the peer at `remote`, listening on
+    /// `--listen ADDRESS` and playing `--side left` or `--side right`.
+    Peer {
+        listen: SocketAddr,
+        remote: SocketAddr,
+        side: Side,
+    },
+}
+
+/// Parses `IP:PORT`, or an IP alone for the `DEFAULT_PORT`.
+pub fn parse_address(text: &str) -> Result<SocketAddr, Error> {
+    text.parse::<SocketAddr>()
+        .or_else(|_| text.parse::<IpAddr>().map(|ip| SocketAddr::new(ip, DEFAULT_PORT)))
+        .map_err(|_| Error::from_string(format!("{:?} is not a network address", text)))
+}
+
+/// Datagrams exchanged between a host and its clients.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Message {
+    /// A client asks to join the match. Clients repeat it until the host answers, or give up after
+    /// `MAX_JOIN_ATTEMPTS`.
+    Join { version: u32 },
+    /// The host accepted a client.
+    Welcome(MatchInfo),
+    /// The host turned a client away, e.g. because every side is taken.
+    Reject { reason: String },
+    /// Paddle axis value of a client, numbered so the host can drop inputs arriving out of order.
+    Input { sequence: u32, axis: Option<f32> },
+    Snapshot(Snapshot),
+    /// Either end leaves the match.
+    Leave,
+    /// Between the two peers of a rollback match, see `RollbackSession`.
+    Peer(PeerMessage),
+}
+
+/// Non-blocking UDP socket exchanging `Message`s.
+#[derive(Debug)]
+pub struct Connection {
+    socket: UdpSocket,
+}
+
+impl Connection {
+    pub fn bind(address: SocketAddr) -> Result<Self, Error> {
+        let socket = UdpSocket::bind(address)?;
+        socket.set_nonblocking(true)?;
+        Ok(Self { socket })
+    }
+
+    pub fn local_address(&self) -> Result<SocketAddr, Error> {
+        Ok(self.socket.local_addr()?)
+    }
+
+    /// Sends `message` to `address`. UDP gives no delivery guarantees, so failures are only
+    /// logged.
+    pub fn send(&self, message: &Message, address: SocketAddr) {
+        match ron::ser::to_string(message) {
+            Ok(text) => {
+                if let Err(e) = self.socket.send_to(text.as_bytes(), address) {
+                    log::warn!("Failed to send {:?} to {}: {}", message, address, e);
+                }
+            }
+            Err(e) => log::error!("Failed to encode {:?}: {}", message, e),
+        }
+    }
+
+    /// Returns every message received since the last call. Datagrams that are not a `Message` are
+    /// dropped.
+    pub fn receive(&self) -> Vec<(Message, SocketAddr)> {
+        let mut buffer = [0; MAX_PACKET_SIZE];
+        let mut messages = Vec::new();
+        loop {
+            match self.socket.recv_from(&mut buffer) {
+                Ok((length, address)) => match ron::de::from_bytes(&buffer[..length]) {
+                    Ok(message) => messages.push((message, address)),
+                    Err(e) => log::warn!("Dropped malformed datagram from {}: {}", address, e),
+                },
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                // For example an ICMP "port unreachable" of a peer that went away.
+                Err(e) if e.kind() == io::ErrorKind::ConnectionReset => continue,
+                Err(e) => {
+                    log::warn!("Failed to receive datagram: {}", e);
+                    break;
+                }
+            }
+        }
+        messages
+    }
+}
+
+/// A client that joined the match of a `NetworkHost`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RemotePlayer {
+    pub address: SocketAddr,
+    pub side: Side,
+    /// Latest paddle axis value received from the client.
+    pub axis: Option<f32>,
+    sequence: u32,
+    /// When the latest message of the client arrived.
+    last_seen: Instant,
+}
+
+/// Host of a network match. The host runs the simulation, hands the sides that are neither played
+/// on the host nor by the CPU to the clients joining, feeds their inputs into the `PaddleInputs`,
+/// and sends them snapshots of the match. A client that stays silent for longer than the timeout is
+/// dropped, which opens its side again.
+#[derive(Debug)]
+pub struct NetworkHost {
+    connection: Connection,
+    /// Sides played with the input devices of the host.
+    local_sides: Vec<Side>,
+    /// Sides played by clients, taken or not.
+    remote_sides: Vec<Side>,
+    players: Vec<RemotePlayer>,
+    /// Match the clients join, `None` until `open` is called.
+    match_info: Option<MatchInfo>,
+    snapshot_sequence: u32,
+    timeout: Duration,
+}
+
+impl NetworkHost {
+    /// Listens for clients on `address`, use port 0 to pick any free port.
+    pub fn bind(address: SocketAddr, local_sides: &[Side]) -> Result<Self, Error> {
+        Ok(Self {
+            connection: Connection::bind(address)?,
+            local_sides: local_sides.to_vec(),
+            remote_sides: Vec::new(),
+            players: Vec::new(),
+            match_info: None,
+            snapshot_sequence: 0,
+            timeout: PLAYER_TIMEOUT,
+        })
+    }
+
+    /// Drops clients after `timeout` without a message instead of the `PLAYER_TIMEOUT`.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn local_address(&self) -> Result<SocketAddr, Error> {
+        self.connection.local_address()
+    }
+
+    /// Opens the match for clients to join.
+    pub fn open(&mut self, config: &PongConfig, match_settings: &MatchSettings, level: &Level) {
+        self.remote_sides = match_settings
+            .mode
+            .sides()
+            .iter()
+            .copied()
+            .filter(|side| {
+                !self.local_sides.contains(side) && match_settings.controller(*side) == PaddleController::Human
+            })
+            .collect();
+        self.match_info = Some(MatchInfo {
+            side: Side::Left,
+            config: config.clone(),
+            match_settings: *match_settings,
+            level: level.clone(),
+        });
+    }
+
+    pub fn players(&self) -> &[RemotePlayer] {
+        &self.players
+    }
+
+    /// Whether sides played by clients are still waiting for a client to join.
+    pub fn waiting_for_players(&self) -> bool {
+        self.players.len() < self.remote_sides.len()
+    }
+
+    /// Handles the messages the clients sent since the last call, and drops the clients that timed
+    /// out.
+    pub fn poll(&mut self) {
+        let now = Instant::now();
+        for (message, address) in self.connection.receive() {
+            if let Some(player) = self.players.iter_mut().find(|player| player.address == address) {
+                player.last_seen = now;
+            }
+            match message {
+                Message::Join { version } => self.accept(address, version),
+                Message::Input { sequence, axis } => {
+                    if let Some(player) = self.players.iter_mut().find(|player| player.address == address) {
+                        if sequence > player.sequence {
+                            player.sequence = sequence;
+                            player.axis = axis;
+                        }
+                    }
+                }
+                Message::Leave => {
+                    if let Some(index) = self.players.iter().position(|player| player.address == address) {
+                        let player = self.players.remove(index);
+                        log::info!("{} left, {:?} side is open again", address, player.side);
+                    }
+                }
+                message => log::warn!("Ignored unexpected {:?} from {}", message, address),
+            }
+        }
+
+        let timeout = self.timeout;
+        self.players.retain(|player| {
+            let alive = now.duration_since(player.last_seen) <= timeout;
+            if !alive {
+                log::info!("{} timed out, {:?} side is open again", player.address, player.side);
+            }
+            alive
+        });
+    }
+
+    fn accept(&mut self, address: SocketAddr, version: u32) {
+        let match_info = match &self.match_info {
+            Some(match_info) => match_info,
+            None => return,
+        };
+        if version != PROTOCOL_VERSION {
+            let reason = format!("host speaks protocol version {}", PROTOCOL_VERSION);
+            self.connection.send(&Message::Reject { reason }, address);
+            return;
+        }
+        // Clients repeat their join until the welcome arrives.
+        let side = match self.players.iter().find(|player| player.address == address) {
+            Some(player) => player.side,
+            None => {
+                let taken = self.players.iter().map(|player| player.side).collect::<Vec<_>>();
+                match self.remote_sides.iter().find(|side| !taken.contains(side)) {
+                    Some(side) => {
+                        log::info!("{} joined on the {:?} side", address, side);
+                        self.players.push(RemotePlayer {
+                            address,
+                            side: *side,
+                            axis: None,
+                            sequence: 0,
+                            last_seen: Instant::now(),
+                        });
+                        *side
+                    }
+                    None => {
+                        let reason = "the match is full".to_string();
+                        self.connection.send(&Message::Reject { reason }, address);
+                        return;
+                    }
+                }
+            }
+        };
+        let welcome = MatchInfo {
+            side,
+            ..match_info.clone()
+        };
+        self.connection.send(&Message::Welcome(welcome), address);
+    }
+
+    /// Replaces the inputs of the sides played by clients with the inputs they sent. Open sides
+    /// stand still.
+    pub fn apply_inputs(&self, inputs: &mut PaddleInputs) {
+        for side in &self.remote_sides {
+            let player = self.players.iter().find(|player| player.side == *side);
+            inputs.set_axis_value(*side, player.and_then(|player| player.axis));
+        }
+    }
+
+    /// Sends a snapshot of the match running in `world` to every client.
+    pub fn send_snapshot(&mut self, world: &World) {
+        self.snapshot_sequence += 1;
+        let message = Message::Snapshot(Snapshot::capture(world, self.snapshot_sequence));
+        for player in &self.players {
+            self.connection.send(&message, player.address);
+        }
+    }
+}
+
+impl Drop for NetworkHost {
+    fn drop(&mut self) {
+        for player in &self.players {
+            self.connection.send(&Message::Leave, player.address);
+        }
+    }
+}
+
+/// Client of a network match, which sends the paddle input of its player to a `NetworkHost` and
+/// receives the state of the match in return.
+#[derive(Debug)]
+pub struct NetworkClient {
+    connection: Connection,
+    host: SocketAddr,
+    match_info: Option<MatchInfo>,
+    /// Why joining failed, if it did: the host turned the client away or never answered.
+    rejection: Option<String>,
+    join_attempts: u32,
+    max_join_attempts: u32,
+    last_join: Instant,
+    host_left: bool,
+    input_sequence: u32,
+    /// Newest snapshot received, `None` once it was taken.
+    snapshot: Option<Snapshot>,
+    snapshot_sequence: u32,
+}
+
+impl NetworkClient {
+    /// Asks the host at `host` to join its match.
+    pub fn connect(host: SocketAddr) -> Result<Self, Error> {
+        let any_address: SocketAddr = if host.is_ipv4() {
+            ([0, 0, 0, 0], 0).into()
+        } else {
+            ([0; 8], 0).into()
+        };
+        let mut client = Self {
+            connection: Connection::bind(any_address)?,
+            host,
+            match_info: None,
+            rejection: None,
+            join_attempts: 0,
+            max_join_attempts: MAX_JOIN_ATTEMPTS,
+            last_join: Instant::now(),
+            host_left: false,
+            input_sequence: 0,
+            snapshot: None,
+            snapshot_sequence: 0,
+        };
+        client.send_join();
+        Ok(client)
+    }
+
+    /// Gives up on the host after `attempts` join requests instead of the `MAX_JOIN_ATTEMPTS`.
+    pub fn with_max_join_attempts(mut self, attempts: u32) -> Self {
+        self.max_join_attempts = attempts;
+        self
+    }
+
+    fn send_join(&mut self) {
+        self.join_attempts += 1;
+        self.last_join = Instant::now();
+        self.connection.send(
+            &Message::Join {
+                version: PROTOCOL_VERSION,
+            },
+            self.host,
+        );
+    }
+
+    /// The match joined, once the host welcomed the client.
+    pub fn match_info(&self) -> Option<&MatchInfo> {
+        self.match_info.as_ref()
+    }
+
+    pub fn rejection(&self) -> Option<&str> {
+        self.rejection.as_deref()
+    }
+
+    pub const fn host_left(&self) -> bool {
+        self.host_left
+    }
+
+    /// Handles the messages the host sent since the last call, and asks to join again every
+    /// `JOIN_INTERVAL` while the host did not answer. Once the join attempts are used up, joining
+    /// fails like a rejection.
+    pub fn poll(&mut self) {
+        for (message, address) in self.connection.receive() {
+            if address != self.host {
+                continue;
+            }
+            match message {
+                Message::Welcome(match_info) => {
+                    if self.match_info.is_none() {
+                        log::info!("Joined {} on the {:?} side", address, match_info.side);
+                        self.match_info = Some(match_info);
+                    }
+                }
+                Message::Reject { reason } => {
+                    log::warn!("{} turned us away: {}", address, reason);
+                    self.rejection = Some(reason);
+                }
+                Message::Snapshot(snapshot) => {
+                    if snapshot.sequence > self.snapshot_sequence {
+                        self.snapshot_sequence = snapshot.sequence;
+                        self.snapshot = Some(snapshot);
+                    }
+                }
+                Message::Leave => {
+                    log::info!("{} closed the match", address);
+                    self.host_left = true;
+                }
+                message => log::warn!("Ignored unexpected {:?} from {}", message, address),
+            }
+        }
+        if self.match_info.is_none() && self.rejection.is_none() && self.last_join.elapsed() >= JOIN_INTERVAL {
+            if self.join_attempts < self.max_join_attempts {
+                self.send_join();
+            } else {
+                log::warn!("{} did not answer {} join requests", self.host, self.join_attempts);
+                self.rejection = Some("the host did not answer".to_string());
+            }
+        }
+    }
+
+    /// Sends the paddle axis value of the player to the host.
+    pub fn send_input(&mut self, axis: Option<f32>) {
+        self.input_sequence += 1;
+        let message = Message::Input {
+            sequence: self.input_sequence,
+            axis,
+        };
+        self.connection.send(&message, self.host);
+    }
+
+    /// Takes the newest snapshot received since the last call.
+    pub fn take_snapshot(&mut self) -> Option<Snapshot> {
+        self.snapshot.take()
+    }
+}
+
+impl Drop for NetworkClient {
+    fn drop(&mut self) {
+        if self.match_info.is_some() && !self.host_left {
+            self.connection.send(&Message::Leave, self.host);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::GameMode;
+    use std::{thread, time::Duration};
+
+    fn loopback() -> SocketAddr {
+        ([127, 0, 0, 1], 0).into()
+    }
+
+    /// Polls both ends until `done` holds, giving the datagrams time to cross the loopback device.
+    fn exchange(
+        host: &mut NetworkHost,
+        client: &mut NetworkClient,
+        done: impl Fn(&NetworkHost, &NetworkClient) -> bool,
+    ) {
+        for _ in 0..100 {
+            host.poll();
+            client.poll();
+            if done(host, client) {
+                return;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        panic!("host and client did not get there over the loopback device");
+    }
+
+    fn open_host(match_settings: MatchSettings) -> NetworkHost {
+        let mut host = NetworkHost::bind(loopback(), &[Side::Left]).expect("failed to bind host");
+        host.open(&PongConfig::default(), &match_settings, &Level::default());
+        host
+    }
+
+    #[test]
+    fn message_round_trip() {
+        let message = Message::Input {
+            sequence: 7,
+            axis: Some(-1.0),
+        };
+        let text = ron::ser::to_string(&message).expect("failed to encode message");
+        assert_eq!(ron::de::from_str::<Message>(&text).expect("failed to decode message"), message);
+    }
+
+    #[test]
+    fn client_joins_open_side() {
+        let mut host = open_host(MatchSettings::default());
+        let address = host.local_address().expect("host has no address");
+        let mut client = NetworkClient::connect(address).expect("failed to connect");
+        assert!(host.waiting_for_players());
+
+        exchange(&mut host, &mut client, |_, client| client.match_info().is_some());
+        assert_eq!(client.match_info().map(|info| info.side), Some(Side::Right));
+        assert!(!host.waiting_for_players());
+    }
+
+    #[test]
+    fn host_uses_latest_client_input() {
+        let mut host = open_host(MatchSettings::default());
+        let address = host.local_address().expect("host has no address");
+        let mut client = NetworkClient::connect(address).expect("failed to connect");
+        exchange(&mut host, &mut client, |_, client| client.match_info().is_some());
+
+        client.send_input(Some(1.0));
+        exchange(&mut host, &mut client, |host, _| host.players()[0].axis == Some(1.0));
+
+        let mut inputs = PaddleInputs {
+            left: Some(-1.0),
+            right: Some(-1.0),
+            ..PaddleInputs::default()
+        };
+        host.apply_inputs(&mut inputs);
+        // The host keeps its own side, the client moves the other one.
+        assert_eq!(inputs.left, Some(-1.0));
+        assert_eq!(inputs.right, Some(1.0));
+    }
+
+    #[test]
+    fn reject_client_of_full_match() {
+        let mut host = open_host(MatchSettings {
+            right: PaddleController::Cpu(crate::systems::AiDifficulty::Easy),
+            ..MatchSettings::default()
+        });
+        let address = host.local_address().expect("host has no address");
+        let mut client = NetworkClient::connect(address).expect("failed to connect");
+
+        exchange(&mut host, &mut client, |_, client| client.rejection().is_some());
+        assert!(client.match_info().is_none());
+    }
+
+    #[test]
+    fn silent_client_frees_its_side() {
+        let mut host = open_host(MatchSettings::default()).with_timeout(Duration::from_millis(100));
+        let address = host.local_address().expect("host has no address");
+        let mut client = NetworkClient::connect(address).expect("failed to connect");
+        exchange(&mut host, &mut client, |_, client| client.match_info().is_some());
+        assert!(!host.waiting_for_players());
+
+        // The client went away without saying goodbye.
+        thread::sleep(Duration::from_millis(200));
+        host.poll();
+        assert!(host.waiting_for_players());
+        assert!(host.players().is_empty());
+    }
+
+    #[test]
+    fn give_up_on_silent_host() {
+        // A socket nobody reads from stands in for a host that does not answer.
+        let silent = Connection::bind(loopback()).expect("failed to bind socket");
+        let address = silent.local_address().expect("socket has no address");
+        let mut client = NetworkClient::connect(address)
+            .expect("failed to connect")
+            .with_max_join_attempts(2);
+        for _ in 0..100 {
+            client.poll();
+            if client.rejection().is_some() {
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(client.rejection(), Some("the host did not answer"));
+        assert!(client.match_info().is_none());
+    }
+
+    #[test]
+    fn clients_fill_four_player_match() {
+        let mut host = open_host(MatchSettings {
+            mode: GameMode::FourPlayer,
+            ..MatchSettings::default()
+        });
+        let address = host.local_address().expect("host has no address");
+        let mut sides = Vec::new();
+        let mut clients = Vec::new();
+        for _ in 0..3 {
+            let mut client = NetworkClient::connect(address).expect("failed to connect");
+            exchange(&mut host, &mut client, |_, client| client.match_info().is_some());
+            sides.extend(client.match_info().map(|info| info.side));
+            clients.push(client);
+        }
+        assert_eq!(sides, vec![Side::Right, Side::Top, Side::Bottom]);
+        assert!(!host.waiting_for_players());
+
+        // A client leaving opens its side again.
+        drop(clients.remove(1));
+        for _ in 0..100 {
+            host.poll();
+            if host.waiting_for_players() {
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert!(host.waiting_for_players());
+    }
+}
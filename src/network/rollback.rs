@@ -0,0 +1,639 @@
+use crate::{
+    network::{Connection, Message},
+    replay::PaddleInputs,
+    rng::GameRng,
+    Side,
+};
+use amethyst::error::Error;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::VecDeque,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+};
+
+/// Frames a peer may run ahead of the last input confirmed by the other peer, unless configured
+/// otherwise. Beyond that it waits for the other peer to catch up.
+pub const DEFAULT_MAX_PREDICTION: u32 = 12;
+
+/// Datagrams exchanged between the two peers of a rollback match.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum PeerMessage {
+    /// Sent until the other peer answers. The peer on the left picks the seed of the match, the
+    /// other one adopts it. `settings` identifies what the sender plays with, see
+    /// `settings_hash`, and has to match on both peers.
+    Sync { seed: Option<u64>, settings: u64 },
+    /// Paddle axis values of the sender from `first_frame` on. Every message repeats the inputs the
+    /// receiver did not acknowledge yet, so lost datagrams need no resend.
+    Inputs {
+        first_frame: u32,
+        axes: Vec<Option<f32>>,
+        /// Number of frames the sender received the inputs of.
+        ack: u32,
+    },
+}
+
+/// Identifies `settings` in a `PeerMessage::Sync`, the same on every machine and build. Peers that
+/// differ in anything the simulation depends on would drift apart from the first frame on.
+pub fn settings_hash<T: Serialize>(settings: &T) -> u64 {
+    let text = ron::ser::to_string(settings).expect("failed to encode match settings");
+    // 64-bit FNV-1a, unlike `DefaultHasher` stable across Rust releases
+    text.bytes()
+        .fold(0xcbf2_9ce4_8422_2325, |hash, byte| (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3))
+}
+
+/// Carries `PeerMessage`s between the peers of a rollback match.
+pub trait Transport: Send + Sync {
+    fn send(&mut self, message: PeerMessage);
+    /// Returns every message received since the last call.
+    fn receive(&mut self) -> Vec<PeerMessage>;
+}
+
+/// Exchanges `PeerMessage`s with the other peer over UDP.
+#[derive(Debug)]
+pub struct UdpTransport {
+    connection: Connection,
+    peer: SocketAddr,
+}
+
+impl UdpTransport {
+    /// Listens on `address` for datagrams of the peer at `peer`.
+    pub fn bind(address: SocketAddr, peer: SocketAddr) -> Result<Self, Error> {
+        Ok(Self {
+            connection: Connection::bind(address)?,
+            peer,
+        })
+    }
+}
+
+impl Transport for UdpTransport {
+    fn send(&mut self, message: PeerMessage) {
+        self.connection.send(&Message::Peer(message), self.peer);
+    }
+
+    fn receive(&mut self) -> Vec<PeerMessage> {
+        let peer = self.peer;
+        self.connection
+            .receive()
+            .into_iter()
+            .filter_map(|(message, address)| match message {
+                Message::Peer(message) if address == peer => Some(message),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+/// How the link of a `SimulatedTransport` treats the messages crossing it. Time is counted in
+/// calls to `receive`, which peers make once per frame.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct LinkConditions {
+    /// Frames every message takes to arrive.
+    pub latency: u32,
+    /// Up to this many frames are added to the latency at random, which reorders messages.
+    pub jitter: u32,
+    /// Chance from 0.0 to 1.0 that a message is lost.
+    pub loss: f32,
+}
+
+/// Message on its way through a `SimulatedTransport`, delivered once the clock of the receiver
+/// reaches `arrival`.
+#[derive(Debug)]
+struct InFlight {
+    arrival: u64,
+    message: PeerMessage,
+}
+
+/// One end of an in-process link with simulated latency, jitter and packet loss. Whatever happens
+/// to a message is drawn from a seeded `GameRng`, so a link behaves the same in every run.
+#[derive(Debug)]
+pub struct SimulatedTransport {
+    conditions: LinkConditions,
+    rng: GameRng,
+    /// frames this end has received for
+    clock: u64,
+    inbox: Arc<Mutex<Vec<InFlight>>>,
+    outbox: Arc<Mutex<Vec<InFlight>>>,
+}
+
+impl SimulatedTransport {
+    /// Returns both ends of a link, each of them sending under `conditions`.
+    pub fn pair(conditions: LinkConditions, seed: u64) -> (Self, Self) {
+        let first = Arc::new(Mutex::new(Vec::new()));
+        let second = Arc::new(Mutex::new(Vec::new()));
+        (
+            Self {
+                conditions,
+                rng: GameRng::from_seed(seed),
+                clock: 0,
+                inbox: Arc::clone(&first),
+                outbox: Arc::clone(&second),
+            },
+            Self {
+                conditions,
+                rng: GameRng::from_seed(seed.wrapping_add(1)),
+                clock: 0,
+                inbox: second,
+                outbox: first,
+            },
+        )
+    }
+}
+
+impl Transport for SimulatedTransport {
+    fn send(&mut self, message: PeerMessage) {
+        if self.rng.gen_range(0.0, 1.0) < self.conditions.loss {
+            return;
+        }
+        let jitter = self.rng.gen_index(self.conditions.jitter as usize + 1) as u64;
+        let arrival = self.clock + u64::from(self.conditions.latency) + jitter;
+        self.outbox
+            .lock()
+            .expect("simulated link is poisoned")
+            .push(InFlight { arrival, message });
+    }
+
+    fn receive(&mut self) -> Vec<PeerMessage> {
+        self.clock += 1;
+        let clock = self.clock;
+        let mut inbox = self.inbox.lock().expect("simulated link is poisoned");
+        let (arrived, in_flight) = inbox.drain(..).partition::<Vec<_>, _>(|message| message.arrival <= clock);
+        *inbox = in_flight;
+        arrived.into_iter().map(|in_flight| in_flight.message).collect()
+    }
+}
+
+/// A game a `RollbackSession` can rewind.
+pub trait Rollback {
+    type State: Clone;
+
+    /// Captures everything the simulation of the next frame depends on.
+    fn save_state(&mut self) -> Self::State;
+    fn load_state(&mut self, state: &Self::State);
+    /// Simulates one frame with the given inputs.
+    fn advance_frame(&mut self, inputs: PaddleInputs);
+}
+
+/// Saved before a frame whose remote input was predicted.
+#[derive(Debug)]
+struct PredictedFrame<S> {
+    frame: u32,
+    state: S,
+    remote: Option<f32>,
+}
+
+/// Keeps the two peers of a match in step without waiting for each other. Frames are simulated
+/// right away with the input of the remote peer predicted to stay what it was last. Once the
+/// actual input arrives and differs, the game is rewound to the mispredicted frame and simulated
+/// again up to the present.
+pub struct RollbackSession<S> {
+    local_side: Side,
+    transport: Box<dyn Transport>,
+    /// seed of the match, known from the start on the left and sent over to the right
+    seed: Option<u64>,
+    /// whether the remote peer knows the seed too
+    remote_synced: bool,
+    /// `settings_hash` of what the local peer plays with
+    settings: u64,
+    /// whether the remote peer plays with other settings, which keeps the match from starting
+    settings_mismatch: bool,
+    max_prediction: u32,
+    /// next frame to simulate
+    frame: u32,
+    /// local input of every simulated frame
+    local_inputs: Vec<Option<f32>>,
+    /// remote input of every frame received, without gaps
+    remote_inputs: Vec<Option<f32>>,
+    /// number of local inputs the remote peer received
+    remote_ack: u32,
+    /// frames simulated with predicted remote input, oldest first
+    predicted: VecDeque<PredictedFrame<S>>,
+    /// frames simulated again after mispredictions
+    resimulated: u32,
+}
+
+impl<S: Clone> RollbackSession<S> {
+    /// Starts a session for the peer on `local_side`, which has to be `Side::Left` or
+    /// `Side::Right`. The left peer picks the `seed`.
+    pub fn new(local_side: Side, transport: Box<dyn Transport>, seed: Option<u64>) -> Self {
+        Self {
+            local_side,
+            transport,
+            seed,
+            remote_synced: false,
+            settings: 0,
+            settings_mismatch: false,
+            max_prediction: DEFAULT_MAX_PREDICTION,
+            frame: 0,
+            local_inputs: Vec::new(),
+            remote_inputs: Vec::new(),
+            remote_ack: 0,
+            predicted: VecDeque::new(),
+            resimulated: 0,
+        }
+    }
+
+    /// Sets the `settings_hash` the remote peer has to play with as well.
+    pub fn with_settings(mut self, settings: u64) -> Self {
+        self.settings = settings;
+        self
+    }
+
+    pub fn with_max_prediction(mut self, max_prediction: u32) -> Self {
+        self.max_prediction = max_prediction.max(1);
+        self
+    }
+
+    pub const fn local_side(&self) -> Side {
+        self.local_side
+    }
+
+    pub const fn seed(&self) -> Option<u64> {
+        self.seed
+    }
+
+    /// Next frame to simulate.
+    pub const fn frame(&self) -> u32 {
+        self.frame
+    }
+
+    /// Number of frames the remote input is known for.
+    pub fn confirmed_frames(&self) -> u32 {
+        self.remote_inputs.len() as u32
+    }
+
+    /// Whether every frame simulated so far used the actual remote input, so no late input can
+    /// rewind it anymore.
+    pub fn is_confirmed(&self) -> bool {
+        self.confirmed_frames() >= self.frame
+    }
+
+    /// Whether the remote peer received the local input of every frame simulated so far.
+    pub fn is_acknowledged(&self) -> bool {
+        self.remote_ack >= self.local_inputs.len() as u32
+    }
+
+    /// Frames simulated again after mispredictions so far.
+    pub const fn resimulated_frames(&self) -> u32 {
+        self.resimulated
+    }
+
+    /// Whether both peers agreed on the seed and the settings, so the match can start.
+    pub fn is_synced(&self) -> bool {
+        self.seed.is_some() && self.remote_synced && !self.settings_mismatch
+    }
+
+    /// Whether the remote peer turned out to play with other settings, see `with_settings`.
+    pub const fn settings_mismatch(&self) -> bool {
+        self.settings_mismatch
+    }
+
+    /// Whether the next frame may be simulated without running too far ahead of the remote peer.
+    pub fn can_advance(&self) -> bool {
+        self.is_synced() && self.frame < self.confirmed_frames() + self.max_prediction
+    }
+
+    /// Handles the messages of the remote peer, and rewinds `game` if its inputs show that a
+    /// prediction went wrong.
+    pub fn poll<G: Rollback<State = S>>(&mut self, game: &mut G) {
+        for message in self.transport.receive() {
+            match message {
+                PeerMessage::Sync { settings, .. } if settings != self.settings => {
+                    if !self.settings_mismatch {
+                        log::error!("The remote peer plays with other settings, refusing to start the match");
+                    }
+                    self.settings_mismatch = true;
+                }
+                PeerMessage::Sync { seed, .. } => {
+                    if seed.is_some() {
+                        self.seed = self.seed.or(seed);
+                        self.remote_synced = true;
+                    }
+                }
+                PeerMessage::Inputs { first_frame, axes, ack } => {
+                    self.remote_synced = true;
+                    self.remote_ack = self.remote_ack.max(ack);
+                    for (frame, axis) in (first_frame..).zip(axes) {
+                        // later inputs have to wait until the gap is filled by a resend
+                        if frame == self.confirmed_frames() {
+                            self.remote_inputs.push(axis);
+                        }
+                    }
+                }
+            }
+        }
+
+        let remote_inputs = &self.remote_inputs;
+        let mispredicted = self
+            .predicted
+            .iter()
+            .position(|predicted| match remote_inputs.get(predicted.frame as usize) {
+                Some(actual) => actual.map(f32::to_bits) != predicted.remote.map(f32::to_bits),
+                None => false,
+            });
+        if let Some(index) = mispredicted {
+            self.rewind(game, index);
+        }
+
+        let confirmed = self.confirmed_frames();
+        while self.predicted.front().map_or(false, |predicted| predicted.frame < confirmed) {
+            self.predicted.pop_front();
+        }
+    }
+
+    /// Loads the state saved before the `index`th predicted frame and simulates every frame from
+    /// there again.
+    fn rewind<G: Rollback<State = S>>(&mut self, game: &mut G, index: usize) {
+        let present = self.frame;
+        let mut predicted = self.predicted.split_off(index);
+        if let Some(first) = predicted.pop_front() {
+            game.load_state(&first.state);
+            self.resimulated += present - first.frame;
+            self.frame = first.frame;
+            while self.frame < present {
+                let local = self.local_inputs[self.frame as usize];
+                self.simulate(game, local);
+            }
+        }
+    }
+
+    /// Simulates the next frame with `local` input for the local paddle and the remote input, or
+    /// its prediction, for the other one.
+    pub fn advance<G: Rollback<State = S>>(&mut self, game: &mut G, local: Option<f32>) {
+        self.local_inputs.push(local);
+        self.simulate(game, local);
+    }
+
+    fn simulate<G: Rollback<State = S>>(&mut self, game: &mut G, local: Option<f32>) {
+        let remote = match self.remote_inputs.get(self.frame as usize) {
+            Some(remote) => *remote,
+            None => {
+                // predict the remote paddle keeps doing what it did last
+                let remote = self.remote_inputs.last().copied().flatten();
+                self.predicted.push_back(PredictedFrame {
+                    frame: self.frame,
+                    state: game.save_state(),
+                    remote,
+                });
+                remote
+            }
+        };
+        let mut inputs = PaddleInputs::default();
+        inputs.set_axis_value(self.local_side, local);
+        inputs.set_axis_value(self.local_side.opponent(), remote);
+        game.advance_frame(inputs);
+        self.frame += 1;
+    }
+
+    /// Sends the local inputs the remote peer did not acknowledge yet, or the seed while the
+    /// peers are not in sync.
+    pub fn send_inputs(&mut self) {
+        if !self.remote_synced {
+            self.transport.send(PeerMessage::Sync {
+                seed: self.seed,
+                settings: self.settings,
+            });
+            return;
+        }
+        let first_frame = self.remote_ack.min(self.local_inputs.len() as u32);
+        self.transport.send(PeerMessage::Inputs {
+            first_frame,
+            axes: self.local_inputs[first_frame as usize..].to_vec(),
+            ack: self.confirmed_frames(),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Deterministic stand-in for a match: two paddles moving with their inputs and a checksum over
+    /// every frame, so any input used in the wrong frame shows in the final state.
+    #[derive(Debug, Default, Clone, PartialEq)]
+    struct Toy {
+        positions: [f32; 2],
+        checksum: u64,
+        frame: u32,
+    }
+
+    impl Rollback for Toy {
+        type State = Self;
+
+        fn save_state(&mut self) -> Self {
+            self.clone()
+        }
+
+        fn load_state(&mut self, state: &Self) {
+            *self = state.clone();
+        }
+
+        fn advance_frame(&mut self, inputs: PaddleInputs) {
+            self.frame += 1;
+            for (position, axis) in self.positions.iter_mut().zip(&[inputs.left, inputs.right]) {
+                *position = (*position + axis.unwrap_or(0.0) * self.frame as f32).max(-500.0).min(500.0);
+                self.checksum = self.checksum.wrapping_mul(31).wrapping_add(u64::from(position.to_bits()));
+            }
+        }
+    }
+
+    /// Scripted paddle input of `side`, changing often enough to break most predictions.
+    fn script(side: Side, frame: u32) -> Option<f32> {
+        match (frame / (7 + side.index() as u32 * 4)) % 3 {
+            0 => None,
+            1 => Some(1.0),
+            _ => Some(-0.5),
+        }
+    }
+
+    /// Plays `frames` frames on both peers over a link with `conditions`, then lets them settle
+    /// without new frames until every input is confirmed. Returns the games of both peers and the
+    /// frames they simulated again.
+    fn play(conditions: LinkConditions, frames: u32) -> ([Toy; 2], u32) {
+        let (left_transport, right_transport) = SimulatedTransport::pair(conditions, 99);
+        let mut left = RollbackSession::new(Side::Left, Box::new(left_transport), Some(5));
+        let mut right = RollbackSession::new(Side::Right, Box::new(right_transport), None);
+        let mut games = [Toy::default(), Toy::default()];
+
+        for _ in 0..frames * 4 {
+            for (session, game) in [&mut left, &mut right].iter_mut().zip(games.iter_mut()) {
+                session.poll(game);
+                if session.frame() < frames && session.can_advance() {
+                    let local = script(session.local_side(), session.frame());
+                    session.advance(game, local);
+                }
+                session.send_inputs();
+            }
+            if left.confirmed_frames() == frames && right.confirmed_frames() == frames {
+                break;
+            }
+        }
+        assert_eq!(left.seed(), right.seed());
+        assert_eq!((left.frame(), right.frame()), (frames, frames));
+        assert_eq!((left.confirmed_frames(), right.confirmed_frames()), (frames, frames));
+        (games, left.resimulated_frames() + right.resimulated_frames())
+    }
+
+    /// The game as it plays out with every input known in time.
+    fn expected(frames: u32) -> Toy {
+        let mut game = Toy::default();
+        for frame in 0..frames {
+            game.advance_frame(PaddleInputs {
+                left: script(Side::Left, frame),
+                right: script(Side::Right, frame),
+                ..PaddleInputs::default()
+            });
+        }
+        game
+    }
+
+    #[test]
+    fn perfect_link_needs_no_rollback() {
+        let ([left, right], _) = play(LinkConditions::default(), 100);
+        assert_eq!(left, expected(100));
+        assert_eq!(right, expected(100));
+    }
+
+    #[test]
+    fn rollback_repairs_mispredictions() {
+        let conditions = LinkConditions {
+            latency: 3,
+            jitter: 4,
+            loss: 0.2,
+        };
+        let ([left, right], resimulated) = play(conditions, 300);
+        assert!(resimulated > 0, "the link never caused a misprediction");
+        assert_eq!(left, expected(300));
+        assert_eq!(right, expected(300));
+    }
+
+    /// Position of the left paddle that decides the toy match.
+    const GOAL: f32 = 250.0;
+
+    impl Toy {
+        fn decided(&self) -> bool {
+            self.positions[0] >= GOAL
+        }
+    }
+
+    /// Left paddle input that nearly reaches the `GOAL` after 22 frames, backs off and only reaches
+    /// it later. The right paddle stands still.
+    fn late_goal_script(side: Side, frame: u32) -> Option<f32> {
+        match (side, frame) {
+            (Side::Right, _) => None,
+            (_, 20..=29) => Some(-1.0),
+            _ => Some(1.0),
+        }
+    }
+
+    #[test]
+    fn finish_only_once_the_deciding_frame_is_confirmed() {
+        let conditions = LinkConditions {
+            latency: 3,
+            ..LinkConditions::default()
+        };
+        let (left_transport, right_transport) = SimulatedTransport::pair(conditions, 7);
+        let mut sessions = [
+            RollbackSession::new(Side::Left, Box::new(left_transport), Some(5)),
+            RollbackSession::new(Side::Right, Box::new(right_transport), None),
+        ];
+        let mut games = [Toy::default(), Toy::default()];
+        let mut finished = [None, None];
+        let mut taken_back = [false, false];
+
+        for _ in 0..1000 {
+            for (index, (session, game)) in sessions.iter_mut().zip(games.iter_mut()).enumerate() {
+                let was_decided = game.decided();
+                session.poll(game);
+                if was_decided && !game.decided() {
+                    taken_back[index] = true;
+                }
+                if finished[index].is_some() {
+                    // a finished peer keeps answering until the other one finished too
+                    session.send_inputs();
+                    continue;
+                }
+                if !game.decided() && session.can_advance() {
+                    let local = late_goal_script(session.local_side(), session.frame());
+                    session.advance(game, local);
+                }
+                session.send_inputs();
+                if game.decided() && session.is_confirmed() && session.is_acknowledged() {
+                    finished[index] = Some(session.frame());
+                }
+            }
+            if finished.iter().all(Option::is_some) {
+                break;
+            }
+        }
+
+        let mut reference = Toy::default();
+        while !reference.decided() {
+            let frame = reference.frame;
+            reference.advance_frame(PaddleInputs {
+                left: late_goal_script(Side::Left, frame),
+                right: late_goal_script(Side::Right, frame),
+                ..PaddleInputs::default()
+            });
+        }
+        assert!(taken_back[1], "the right peer never predicted the goal that did not happen");
+        assert!(reference.frame > 22);
+        assert_eq!(finished, [Some(reference.frame), Some(reference.frame)]);
+        assert_eq!(games, [reference.clone(), reference]);
+    }
+
+    #[test]
+    fn peer_waits_when_too_far_ahead() {
+        let (left_transport, _right_transport) = SimulatedTransport::pair(LinkConditions::default(), 1);
+        let mut session = RollbackSession::new(Side::Left, Box::new(left_transport), Some(5)).with_max_prediction(4);
+        // pretend the right peer synced without ever sending inputs
+        session.remote_synced = true;
+        let mut game = Toy::default();
+        while session.can_advance() {
+            session.advance(&mut game, None);
+        }
+        assert_eq!(session.frame(), 4);
+    }
+
+    #[test]
+    fn simulated_link_delivers_after_latency() {
+        let conditions = LinkConditions {
+            latency: 2,
+            ..LinkConditions::default()
+        };
+        let (mut sender, mut receiver) = SimulatedTransport::pair(conditions, 1);
+        let sync = PeerMessage::Sync {
+            seed: Some(1),
+            settings: 2,
+        };
+        sender.send(sync.clone());
+        assert!(receiver.receive().is_empty());
+        assert_eq!(receiver.receive(), vec![sync]);
+    }
+
+    #[test]
+    fn refuse_to_start_with_other_settings() {
+        let (left_transport, right_transport) = SimulatedTransport::pair(LinkConditions::default(), 1);
+        let mut left = RollbackSession::new(Side::Left, Box::new(left_transport), Some(5))
+            .with_settings(settings_hash(&("classic", 10)));
+        let mut right = RollbackSession::new(Side::Right, Box::new(right_transport), None)
+            .with_settings(settings_hash(&("classic", 11)));
+        let mut games = [Toy::default(), Toy::default()];
+        for _ in 0..10 {
+            for (session, game) in [&mut left, &mut right].iter_mut().zip(games.iter_mut()) {
+                session.poll(game);
+                session.send_inputs();
+            }
+        }
+        assert!(left.settings_mismatch() && right.settings_mismatch());
+        assert!(!left.is_synced() && !right.is_synced());
+        assert!(!left.can_advance() && !right.can_advance());
+        assert_eq!(right.seed(), None);
+    }
+
+    #[test]
+    fn test_settings_hash() {
+        assert_eq!(settings_hash(&("classic", 10)), settings_hash(&("classic", 10)));
+        assert_ne!(settings_hash(&("classic", 10)), settings_hash(&("classic", 11)));
+    }
+}
@@ -0,0 +1,284 @@
+use crate::{bindings::GameBindings, config::PongConfig, level::Level, save::SaveGame, MatchSettings, ScoreBoard, Side};
+use amethyst::{
+    config::Config,
+    error::Error,
+    input::InputHandler,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Version written into every replay file. Bump it whenever `Replay` changes, or the simulation
+/// changes in a way that makes older replays play out differently.
+pub const REPLAY_VERSION: u32 = 3;
+
+/// Axis values the `PaddleSystem` moves the human controlled paddles with during one simulation
+/// step. `Pong` fills this resource from the `InputHandler`, or from a `Replay` when playing back.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct PaddleInputs {
+    pub left: Option<f32>,
+    pub right: Option<f32>,
+    #[serde(default)]
+    pub top: Option<f32>,
+    #[serde(default)]
+    pub bottom: Option<f32>,
+}
+
+impl PaddleInputs {
+    /// Reads the paddle axes from the current input state.
+    pub fn from_input(input: &InputHandler<GameBindings>) -> Self {
+        Self {
+            left: input.axis_value(&Side::Left.input_axis()),
+            right: input.axis_value(&Side::Right.input_axis()),
+            top: input.axis_value(&Side::Top.input_axis()),
+            bottom: input.axis_value(&Side::Bottom.input_axis()),
+        }
+    }
+
+    pub const fn axis_value(&self, side: Side) -> Option<f32> {
+        match side {
+            Side::Left => self.left,
+            Side::Right => self.right,
+            Side::Top => self.top,
+            Side::Bottom => self.bottom,
+        }
+    }
+
+    pub fn set_axis_value(&mut self, side: Side, value: Option<f32>) {
+        match side {
+            Side::Left => self.left = value,
+            Side::Right => self.right = value,
+            Side::Top => self.top = value,
+            Side::Bottom => self.bottom = value,
+        }
+    }
+}
+
+/// Paddle inputs in effect from `step` on, until the next change.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct InputChange {
+    pub step: u32,
+    pub inputs: PaddleInputs,
+}
+
+/// Everything needed to simulate a match again: the simulation is deterministic for a given
+/// config, RNG seed and sequence of inputs.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Replay {
+    pub version: u32,
+    pub seed: u64,
+    pub config: PongConfig,
+    pub match_settings: MatchSettings,
+    /// Level the match was played in.
+    #[serde(default)]
+    pub level: Level,
+    /// Saved match the recording continued, if it did not start from zero.
+    pub start: Option<SaveGame>,
+    /// Paddle inputs of every simulation step, stored only when they change.
+    pub inputs: Vec<InputChange>,
+    /// Number of simulation steps the match took.
+    pub steps: u32,
+    /// Final score of every player, see `ScoreBoard`.
+    pub scores: Vec<i32>,
+}
+
+impl Replay {
+    /// Reads a replay file, rejecting files written by an incompatible version of the game.
+    pub fn read<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let replay = Self::load_no_fallback(path.as_ref())?;
+        if replay.version != REPLAY_VERSION {
+            return Err(Error::from_string(format!(
+                "replay file {:?} has version {}, expected {}",
+                path.as_ref(),
+                replay.version,
+                REPLAY_VERSION
+            )));
+        }
+        Ok(replay)
+    }
+
+    /// Writes the replay file, creating the replay directory if necessary.
+    pub fn store<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        let path = path.as_ref();
+        if let Some(directory) = path.parent() {
+            fs::create_dir_all(directory)?;
+        }
+        self.write(path)?;
+        Ok(())
+    }
+}
+
+/// Location of the replay of the last finished match.
+pub fn last_match_path(replays_dir: &Path) -> PathBuf {
+    replays_dir.join("last_match.ron")
+}
+
+/// Collects the inputs of a running match into a `Replay`.
+#[derive(Debug, Clone)]
+pub struct ReplayRecorder {
+    replay: Replay,
+    last_inputs: Option<PaddleInputs>,
+}
+
+impl ReplayRecorder {
+    pub fn new(
+        seed: u64,
+        config: PongConfig,
+        match_settings: MatchSettings,
+        level: Level,
+        start: Option<SaveGame>,
+    ) -> Self {
+        Self {
+            replay: Replay {
+                version: REPLAY_VERSION,
+                seed,
+                config,
+                match_settings,
+                level,
+                start,
+                inputs: Vec::new(),
+                steps: 0,
+                scores: Vec::new(),
+            },
+            last_inputs: None,
+        }
+    }
+
+    /// Records the inputs of the next simulation step.
+    pub fn record(&mut self, inputs: PaddleInputs) {
+        if self.last_inputs != Some(inputs) {
+            self.replay.inputs.push(InputChange {
+                step: self.replay.steps,
+                inputs,
+            });
+            self.last_inputs = Some(inputs);
+        }
+        self.replay.steps += 1;
+    }
+
+    /// Completes the recording with the final score of the match.
+    pub fn finish(mut self, score_board: &ScoreBoard) -> Replay {
+        self.replay.scores = score_board.scores().to_vec();
+        self.replay
+    }
+}
+
+/// Hands out the recorded inputs of a `Replay` step by step.
+#[derive(Debug, Clone)]
+pub struct ReplayPlayback {
+    replay: Replay,
+    step: u32,
+    next_change: usize,
+    inputs: PaddleInputs,
+}
+
+impl ReplayPlayback {
+    pub fn new(replay: Replay) -> Self {
+        Self {
+            replay,
+            step: 0,
+            next_change: 0,
+            inputs: PaddleInputs::default(),
+        }
+    }
+
+    pub const fn replay(&self) -> &Replay {
+        &self.replay
+    }
+
+    /// Returns the inputs of the next simulation step, or `None` once every recorded step was
+    /// played back.
+    pub fn next_inputs(&mut self) -> Option<PaddleInputs> {
+        if self.step >= self.replay.steps {
+            return None;
+        }
+        while let Some(change) = self.replay.inputs.get(self.next_change) {
+            if change.step > self.step {
+                break;
+            }
+            self.inputs = change.inputs;
+            self.next_change += 1;
+        }
+        self.step += 1;
+        Some(self.inputs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::test_harness::{temp_path, IntegrationTestApplication};
+
+    fn inputs(left: Option<f32>, right: Option<f32>) -> PaddleInputs {
+        PaddleInputs {
+            left,
+            right,
+            ..PaddleInputs::default()
+        }
+    }
+
+    fn record(steps: &[PaddleInputs]) -> Replay {
+        let mut recorder =
+            ReplayRecorder::new(42, PongConfig::default(), MatchSettings::default(), Level::default(), None);
+        for step in steps {
+            recorder.record(*step);
+        }
+        recorder.finish(&ScoreBoard::from_scores(vec![11, 4]))
+    }
+
+    #[test]
+    fn record_only_changes() {
+        let replay = record(&[
+            inputs(None, None),
+            inputs(None, None),
+            inputs(Some(1.0), None),
+            inputs(Some(1.0), None),
+            inputs(Some(1.0), Some(-1.0)),
+        ]);
+        assert_eq!(replay.steps, 5);
+        assert_eq!(
+            replay.inputs.iter().map(|change| change.step).collect::<Vec<_>>(),
+            vec![0, 2, 4]
+        );
+        assert_eq!((replay.seed, replay.scores.as_slice()), (42, &[11, 4][..]));
+    }
+
+    #[test]
+    fn playback_returns_recorded_inputs() {
+        let steps = vec![
+            inputs(None, None),
+            inputs(Some(-1.0), None),
+            inputs(Some(-1.0), None),
+            inputs(None, Some(0.5)),
+        ];
+        let mut playback = ReplayPlayback::new(record(&steps));
+        let played = (0..steps.len()).filter_map(|_| playback.next_inputs()).collect::<Vec<_>>();
+        assert_eq!(played, steps);
+        assert_eq!(playback.next_inputs(), None);
+    }
+
+    #[test]
+    fn store_and_read_round_trip() -> amethyst::Result<()> {
+        let replays_dir = temp_path("replays");
+        let path = last_match_path(&replays_dir);
+        let replay = record(&[inputs(Some(1.0), None), inputs(None, Some(-1.0))]);
+
+        replay.store(&path)?;
+        let loaded = Replay::read(&path)?;
+        fs::remove_dir_all(&replays_dir)?;
+
+        assert_eq!(loaded, replay);
+        Ok(())
+    }
+
+    #[test]
+    fn replay_first_point() -> amethyst::Result<()> {
+        amethyst::start_logger(amethyst::LoggerConfig::default());
+        let replay = Replay::read(crate::initialize_paths()?.assets_dir.join("replays/first_point.ron"))?;
+        assert!(IntegrationTestApplication::replay_regression(replay).run().is_ok());
+        Ok(())
+    }
+}
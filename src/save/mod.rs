@@ -0,0 +1,380 @@
+use crate::{
+    level::Level,
+    systems::{Effect, PowerUp, PowerUpKind, Serving, Stuck},
+    Ball, GameMode, MatchSettings, Paddle, ScoreBoard, Side,
+};
+use amethyst::{
+    config::Config,
+    core::transform::Transform,
+    ecs::prelude::{Join, World, WorldExt},
+    error::Error,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Version written into every save file. Bump it whenever `SaveGame` changes in a way older files
+/// can not be read with.
+pub const SAVE_VERSION: u32 = 4;
+/// Number of save slots offered by the slot picker.
+pub const SAVE_SLOTS: usize = 3;
+
+/// Position and movement of a ball at the time the game was saved.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BallSnapshot {
+    pub position: [f32; 2],
+    pub velocity: [f32; 2],
+    pub radius: f32,
+    /// Serve the ball was waiting for, if any.
+    #[serde(default)]
+    pub serving: Option<Serving>,
+    /// Player whose paddle the ball touched last, who collects the power-ups it hits.
+    #[serde(default)]
+    pub last_hit: Option<Side>,
+    /// Sticky paddle holding the ball, if any.
+    #[serde(default)]
+    pub stuck: Option<Stuck>,
+}
+
+/// Position of a paddle at the time the game was saved.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PaddleSnapshot {
+    pub side: Side,
+    pub position: [f32; 2],
+}
+
+/// A power-up lying in the arena at the time the game was saved.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PowerUpSnapshot {
+    pub kind: PowerUpKind,
+    pub position: [f32; 2],
+}
+
+/// A power-up effect that had not worn off at the time the game was saved, see `Effect`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EffectSnapshot {
+    pub kind: PowerUpKind,
+    pub side: Side,
+    /// Index of the affected ball in `SaveGame::balls`.
+    pub ball: Option<usize>,
+    pub remaining: f32,
+}
+
+/// Snapshot of an in-progress `Pong` match.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SaveGame {
+    pub version: u32,
+    /// Score of every player, see `ScoreBoard`.
+    pub scores: Vec<i32>,
+    pub balls: Vec<BallSnapshot>,
+    pub paddles: Vec<PaddleSnapshot>,
+    /// Seconds until the first ball spawns, if it has not spawned yet.
+    pub ball_spawn_timer: Option<f32>,
+    /// Level the match is played in. Moving obstacles start over from their initial positions.
+    #[serde(default)]
+    pub level: Level,
+    /// Mode the match is played in, which decides the sides that have a paddle.
+    #[serde(default)]
+    pub mode: GameMode,
+    #[serde(default)]
+    pub power_ups: Vec<PowerUpSnapshot>,
+    #[serde(default)]
+    pub effects: Vec<EffectSnapshot>,
+}
+
+impl Default for SaveGame {
+    fn default() -> Self {
+        Self {
+            version: SAVE_VERSION,
+            scores: vec![0, 0],
+            balls: Vec::new(),
+            paddles: Vec::new(),
+            ball_spawn_timer: None,
+            level: Level::default(),
+            mode: GameMode::default(),
+            power_ups: Vec::new(),
+            effects: Vec::new(),
+        }
+    }
+}
+
+impl SaveGame {
+    /// Takes a snapshot of the match currently running in `world`.
+    pub fn capture(world: &World, ball_spawn_timer: Option<f32>) -> Self {
+        let score_board = world.read_resource::<ScoreBoard>();
+        let entities = world.entities();
+        let balls = world.read_storage::<Ball>();
+        let servings = world.read_storage::<Serving>();
+        let stuck_balls = world.read_storage::<Stuck>();
+        let paddles = world.read_storage::<Paddle>();
+        let power_ups = world.read_storage::<PowerUp>();
+        let effects = world.read_storage::<Effect>();
+        let transforms = world.read_storage::<Transform>();
+
+        let (ball_entities, balls): (Vec<_>, Vec<_>) =
+            (&*entities, &balls, &transforms, (&servings).maybe(), (&stuck_balls).maybe())
+                .join()
+                .map(|(entity, ball, transform, serving, stuck)| {
+                    let snapshot = BallSnapshot {
+                        position: [transform.translation().x, transform.translation().y],
+                        velocity: ball.velocity,
+                        radius: ball.radius,
+                        serving: serving.copied(),
+                        last_hit: ball.last_hit,
+                        stuck: stuck.copied(),
+                    };
+                    (entity, snapshot)
+                })
+                .unzip();
+
+        Self {
+            version: SAVE_VERSION,
+            scores: score_board.scores().to_vec(),
+            balls,
+            paddles: (&paddles, &transforms)
+                .join()
+                .map(|(paddle, transform)| PaddleSnapshot {
+                    side: paddle.side,
+                    position: [transform.translation().x, transform.translation().y],
+                })
+                .collect(),
+            ball_spawn_timer,
+            level: world.try_fetch::<Level>().map(|level| level.clone()).unwrap_or_default(),
+            mode: world.try_fetch::<MatchSettings>().map(|settings| settings.mode).unwrap_or_default(),
+            power_ups: (&power_ups, &transforms)
+                .join()
+                .map(|(power_up, transform)| PowerUpSnapshot {
+                    kind: power_up.kind,
+                    position: [transform.translation().x, transform.translation().y],
+                })
+                .collect(),
+            effects: (&effects)
+                .join()
+                .filter(|effect| effect.remaining > 0.0)
+                .filter_map(|effect| {
+                    // the effect on a ball that is gone already has nothing left to undo
+                    let ball = match effect.ball {
+                        Some(ball) => Some(ball_entities.iter().position(|entity| *entity == ball)?),
+                        None => None,
+                    };
+                    Some(EffectSnapshot {
+                        kind: effect.kind,
+                        side: effect.side,
+                        ball,
+                        remaining: effect.remaining,
+                    })
+                })
+                .collect(),
+        }
+    }
+
+    /// Reads a save file, rejecting files written by an incompatible version of the game.
+    pub fn read<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let save = Self::load_no_fallback(path.as_ref())?;
+        if save.version != SAVE_VERSION {
+            return Err(Error::from_string(format!(
+                "save file {:?} has version {}, expected {}",
+                path.as_ref(),
+                save.version,
+                SAVE_VERSION
+            )));
+        }
+        Ok(save)
+    }
+
+    /// Writes the save file, creating the save directory if necessary.
+    pub fn store<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        let path = path.as_ref();
+        if let Some(directory) = path.parent() {
+            fs::create_dir_all(directory)?;
+        }
+        self.write(path)?;
+        Ok(())
+    }
+}
+
+/// Location of the save file for the given slot.
+pub fn slot_path(saves_dir: &Path, slot: usize) -> PathBuf {
+    saves_dir.join(format!("slot_{}.ron", slot + 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{
+        states::{initialise_ball, initialise_paddles, initialise_power_up, load_sprite_sheet},
+        test_harness::{temp_path, IntegrationTestApplication},
+    };
+    use amethyst::prelude::Builder;
+
+    fn example_save() -> SaveGame {
+        SaveGame {
+            scores: vec![3, 5],
+            balls: vec![
+                BallSnapshot {
+                    position: [10.0, 20.0],
+                    velocity: [-75.0, 50.0],
+                    radius: 2.0,
+                    serving: None,
+                    last_hit: None,
+                    stuck: None,
+                },
+                BallSnapshot {
+                    position: [80.0, 45.0],
+                    velocity: [0.0, 0.0],
+                    radius: 2.0,
+                    serving: Some(Serving::new(0.5, Some(Side::Right))),
+                    last_hit: None,
+                    stuck: None,
+                },
+            ],
+            paddles: vec![
+                PaddleSnapshot {
+                    side: Side::Left,
+                    position: [2.0, 30.0],
+                },
+                PaddleSnapshot {
+                    side: Side::Right,
+                    position: [158.0, 60.0],
+                },
+            ],
+            power_ups: vec![PowerUpSnapshot {
+                kind: PowerUpKind::Grow,
+                position: [60.0, 30.0],
+            }],
+            effects: vec![EffectSnapshot {
+                kind: PowerUpKind::SpeedUp,
+                side: Side::Left,
+                ball: Some(0),
+                remaining: 4.5,
+            }],
+            ..SaveGame::default()
+        }
+    }
+
+    #[test]
+    fn store_and_read_round_trip() -> amethyst::Result<()> {
+        let saves_dir = temp_path("saves-round-trip");
+        let path = slot_path(&saves_dir, 0);
+        let save = example_save();
+
+        save.store(&path)?;
+        let loaded = SaveGame::read(&path)?;
+        fs::remove_dir_all(&saves_dir)?;
+
+        assert_eq!(loaded, save);
+        Ok(())
+    }
+
+    #[test]
+    fn reject_other_versions() -> amethyst::Result<()> {
+        let saves_dir = temp_path("saves-version");
+        let path = slot_path(&saves_dir, 1);
+        SaveGame {
+            version: SAVE_VERSION + 1,
+            ..example_save()
+        }
+        .store(&path)?;
+
+        let loaded = SaveGame::read(&path);
+        fs::remove_dir_all(&saves_dir)?;
+
+        assert!(loaded.is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn slot_paths_are_distinct() {
+        let saves_dir = PathBuf::from("saves");
+        assert_eq!(slot_path(&saves_dir, 0), saves_dir.join("slot_1.ron"));
+        assert_ne!(slot_path(&saves_dir, 0), slot_path(&saves_dir, 1));
+    }
+
+    #[test]
+    fn capture_running_match() {
+        amethyst::start_logger(amethyst::LoggerConfig::default());
+        let test_result = IntegrationTestApplication::pong_base()
+            .with_setup(|world| {
+                world.insert(ScoreBoard::from_scores(vec![2, 7]));
+                let root_entity = world.create_entity().with(Transform::default()).build();
+                let sprite_sheet_handle = load_sprite_sheet(world);
+                initialise_paddles(world, root_entity, sprite_sheet_handle.clone());
+                let ball = initialise_ball(
+                    world,
+                    root_entity,
+                    sprite_sheet_handle.clone(),
+                    2.0,
+                    [-75.0, 50.0],
+                    Some([40.0, 20.0]),
+                );
+                let stuck = Stuck {
+                    side: Side::Left,
+                    offset: 1.5,
+                    velocity: [75.0, 10.0],
+                    remaining: 0.25,
+                };
+                world.write_storage::<Stuck>().insert(ball, stuck).expect("ball was just created");
+                world
+                    .create_entity()
+                    .with(Effect {
+                        kind: PowerUpKind::Sticky,
+                        side: Side::Left,
+                        ball: None,
+                        remaining: 3.0,
+                    })
+                    .build();
+                world
+                    .create_entity()
+                    .with(Effect {
+                        kind: PowerUpKind::SlowDown,
+                        side: Side::Right,
+                        ball: Some(ball),
+                        remaining: 2.0,
+                    })
+                    .build();
+                initialise_power_up(world, root_entity, sprite_sheet_handle, PowerUpKind::Shrink, [70.0, 50.0]);
+            })
+            .with_assertion(|world| {
+                let save = SaveGame::capture(world, Some(0.5));
+                assert_eq!(save.version, SAVE_VERSION);
+                assert_eq!(save.scores, vec![2, 7]);
+                assert_eq!(
+                    save.balls,
+                    vec![BallSnapshot {
+                        position: [40.0, 20.0],
+                        velocity: [-75.0, 50.0],
+                        radius: 2.0,
+                        serving: None,
+                        last_hit: None,
+                        stuck: Some(Stuck {
+                            side: Side::Left,
+                            offset: 1.5,
+                            velocity: [75.0, 10.0],
+                            remaining: 0.25,
+                        }),
+                    }]
+                );
+                assert_eq!(save.paddles.len(), 2);
+                assert_eq!(save.ball_spawn_timer, Some(0.5));
+                assert_eq!(
+                    save.power_ups,
+                    vec![PowerUpSnapshot {
+                        kind: PowerUpKind::Shrink,
+                        position: [70.0, 50.0],
+                    }]
+                );
+                assert_eq!(save.effects.len(), 2);
+                let slow_down = save
+                    .effects
+                    .iter()
+                    .find(|effect| effect.kind == PowerUpKind::SlowDown)
+                    .expect("effect was saved");
+                assert_eq!((slow_down.side, slow_down.ball), (Side::Right, Some(0)));
+            })
+            .run();
+        assert!(test_result.is_ok());
+    }
+}
@@ -6,7 +6,7 @@ use crate::{
 use amethyst::{
     assets::ProgressCounter,
     ecs::prelude::Entity,
-    input::{is_close_requested, is_key_down, is_mouse_button_down},
+    input::{is_close_requested, is_key_down, is_mouse_button_down, InputEvent},
     prelude::*,
     ui::UiCreator,
     winit::{MouseButton, VirtualKeyCode},
@@ -14,7 +14,7 @@ use amethyst::{
 use derivative::Derivative;
 
 // A simple 'Screen' State, only capable of loading/showing the prefab ui and registering simple
-// UI interactions (pressing escape or clicking anywhere).
+// UI interactions (pressing escape or enter, clicking anywhere or pressing a controller button).
 
 #[derive(Derivative)]
 #[derivative(Debug)]
@@ -54,7 +54,9 @@ impl<'a, 'b> State<CustomGameData<'static, 'static>, GameStateEvent> for Credits
                 if is_close_requested(&event) {
                     log::info!("[Trans::Quit] Quitting Application!");
                     Trans::Quit
-                } else if is_key_down(&event, VirtualKeyCode::Escape) || is_mouse_button_down(&event, MouseButton::Left)
+                } else if is_key_down(&event, VirtualKeyCode::Escape)
+                    || is_key_down(&event, VirtualKeyCode::Return)
+                    || is_mouse_button_down(&event, MouseButton::Left)
                 {
                     log::info!("[Trans::Switch] Switching to MainMenu!");
                     Trans::Switch(Box::new(MainMenu::default()))
@@ -62,6 +64,10 @@ impl<'a, 'b> State<CustomGameData<'static, 'static>, GameStateEvent> for Credits
                     Trans::None
                 }
             }
+            GameStateEvent::Input(InputEvent::ControllerButtonPressed { .. }) => {
+                log::info!("[Trans::Switch] Switching to MainMenu!");
+                Trans::Switch(Box::new(MainMenu::default()))
+            }
             GameStateEvent::Test(test_event) => crate::test_harness::handle_test_event(&test_event),
             _ => Trans::None,
         }
@@ -80,6 +86,7 @@ impl<'a, 'b> State<CustomGameData<'static, 'static>, GameStateEvent> for Credits
 mod tests {
     use super::*;
     use crate::audio::initialise_audio;
+    use crate::bindings::GameBindings;
     use crate::test_harness::SendMockEvents;
     use amethyst::{
         assets::ProgressCounter,
@@ -159,6 +166,30 @@ mod tests {
         assert!(test_result.is_ok());
     }
 
+    #[test]
+    fn controller_button() {
+        amethyst::start_logger(amethyst::LoggerConfig::default());
+        let test_result = crate::test_harness::IntegrationTestApplication::pong_base()
+            .with_setup(|world| {
+                let mut progress = ProgressCounter::default();
+                initialise_audio(world, &mut progress);
+            })
+            .with_state(|| {
+                SendMockEvents::test_state(|_world| Box::new(CreditsScreen::default()))
+                    .with_step(|world| {
+                        let event = InputEvent::ControllerButtonPressed {
+                            which: 0,
+                            button: amethyst::input::ControllerButton::A,
+                        };
+                        let mut events: Write<EventChannel<InputEvent<GameBindings>>> = world.system_data();
+                        events.single_write(event);
+                    })
+                    .end_test()
+            })
+            .run();
+        assert!(test_result.is_ok());
+    }
+
     #[test]
     fn unhandled_window_event() {
         amethyst::start_logger(amethyst::LoggerConfig::default());
@@ -0,0 +1,219 @@
+use crate::{
+    game_data::CustomGameData,
+    states::{delete_hierarchy, format_scores, MainMenu, Pong},
+    systems::MatchOver,
+    GameStateEvent, GAME_OVER_PREFAB,
+};
+use amethyst::{
+    assets::ProgressCounter,
+    ecs::Entity,
+    input::is_close_requested,
+    prelude::*,
+    shrev::EventChannel,
+    ui::{UiCreator, UiEvent, UiEventType, UiFinder, UiText},
+    TransEvent,
+};
+
+/// Pushed on top of `Pong` once a match is decided. Shows the winner and lets the players start a
+/// rematch or go back to the main menu.
+pub struct GameOverState {
+    match_over: MatchOver,
+    /// shows the winner and the final score
+    winner_label: Option<Entity>,
+    /// replaces the finished pong state with a new one
+    rematch_button: Option<Entity>,
+    /// replaces the finished pong state with the main menu
+    main_menu_button: Option<Entity>,
+    /// ui hierarchy root entity
+    root: Option<Entity>,
+    load_progress: Option<ProgressCounter>,
+}
+
+/// winner label prefab ID
+const WINNER_LABEL_ID: &str = "winner";
+/// rematch button prefab ID
+const REMATCH_BUTTON_ID: &str = "rematch";
+/// main menu button prefab ID
+const MAIN_MENU_BUTTON_ID: &str = "main_menu";
+
+impl GameOverState {
+    pub fn new(match_over: MatchOver) -> Self {
+        Self {
+            match_over,
+            winner_label: None,
+            rematch_button: None,
+            main_menu_button: None,
+            root: None,
+            load_progress: None,
+        }
+    }
+
+    fn winner_text(&self) -> String {
+        format!(
+            "{} wins {}",
+            self.match_over.winner.name(),
+            format_scores(&self.match_over.scores)
+        )
+    }
+
+    /// Pops this state and replaces the finished `Pong` state below it with the given state.
+    fn leave_match(
+        world: &World,
+        next: fn() -> Trans<CustomGameData<'static, 'static>, GameStateEvent>,
+    ) -> Trans<CustomGameData<'static, 'static>, GameStateEvent> {
+        let mut state_transition_event_channel =
+            world.write_resource::<EventChannel<TransEvent<CustomGameData<'static, 'static>, GameStateEvent>>>();
+
+        state_transition_event_channel.single_write(Box::new(|| Trans::Pop));
+        if cfg!(not(test)) {
+            state_transition_event_channel.single_write(Box::new(next));
+        }
+        Trans::None
+    }
+}
+
+impl<'a, 'b> State<CustomGameData<'static, 'static>, GameStateEvent> for GameOverState {
+    fn on_start(&mut self, data: StateData<'_, CustomGameData<'_, '_>>) {
+        let world = data.world;
+
+        let mut progress = ProgressCounter::default();
+
+        self.root = Some(world.exec(|mut creator: UiCreator<'_>| creator.create(GAME_OVER_PREFAB, &mut progress)));
+
+        self.load_progress = Some(progress);
+    }
+
+    fn on_stop(&mut self, data: StateData<'_, CustomGameData<'_, '_>>) {
+        if let Some(root) = self.root.take() {
+            delete_hierarchy(root, data.world).expect("Failed to remove GameOverState");
+        }
+        self.winner_label = None;
+        self.rematch_button = None;
+        self.main_menu_button = None;
+        self.load_progress = None;
+    }
+
+    fn handle_event(
+        &mut self,
+        data: StateData<'_, CustomGameData<'_, '_>>,
+        event: GameStateEvent,
+    ) -> Trans<CustomGameData<'static, 'static>, GameStateEvent> {
+        match event {
+            GameStateEvent::Window(event) => {
+                if is_close_requested(&event) {
+                    log::info!("[Trans::Quit] Quitting Application!");
+                    Trans::Quit
+                } else {
+                    Trans::None
+                }
+            }
+            GameStateEvent::Ui(UiEvent {
+                event_type: UiEventType::Click,
+                target,
+            }) => {
+                if Some(target) == self.rematch_button {
+                    log::info!("[Trans::Pop] Closing Game Over Screen!");
+                    log::info!("[Trans::Switch] Switching to Pong!");
+                    Self::leave_match(data.world, || Trans::Switch(Box::new(Pong::default())))
+                } else if Some(target) == self.main_menu_button {
+                    log::info!("[Trans::Pop] Closing Game Over Screen!");
+                    log::info!("[Trans::Switch] Switching to MainMenu!");
+                    Self::leave_match(data.world, || Trans::Switch(Box::new(MainMenu::default())))
+                } else {
+                    Trans::None
+                }
+            }
+            GameStateEvent::Test(test_event) => crate::test_harness::handle_test_event(&test_event),
+            _ => Trans::None,
+        }
+    }
+
+    fn update(
+        &mut self,
+        data: StateData<'_, CustomGameData<'_, '_>>,
+    ) -> Trans<CustomGameData<'static, 'static>, GameStateEvent> {
+        data.data.update(data.world, true);
+        // once deferred creation of the root ui entity finishes, look up the widgets
+        if self.winner_label.is_none() || self.rematch_button.is_none() || self.main_menu_button.is_none() {
+            data.world.exec(|ui_finder: UiFinder<'_>| {
+                self.winner_label = ui_finder.find(WINNER_LABEL_ID);
+                self.rematch_button = ui_finder.find(REMATCH_BUTTON_ID);
+                self.main_menu_button = ui_finder.find(MAIN_MENU_BUTTON_ID);
+            });
+            if let Some(winner_label) = self.winner_label {
+                if let Some(text) = data.world.write_storage::<UiText>().get_mut(winner_label) {
+                    text.text = self.winner_text();
+                }
+            }
+        }
+
+        Trans::None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        test_harness::{click, find_ui, wait_for_ui, SendMockEvents},
+        Side,
+    };
+    use std::time::Duration;
+
+    fn match_over() -> MatchOver {
+        MatchOver {
+            winner: Side::Right,
+            scores: vec![9, 11],
+        }
+    }
+
+    #[test]
+    fn test_game_over_state() {
+        amethyst::start_logger(amethyst::LoggerConfig::default());
+        let test_result = crate::test_harness::IntegrationTestApplication::pong_base()
+            .with_state(|| {
+                SendMockEvents::test_state(|_world| Box::new(GameOverState::new(match_over())))
+                    .with_condition_barrier(wait_for_ui(MAIN_MENU_BUTTON_ID), Duration::from_secs(20))
+                    .with_wait(0.5)
+                    .with_step(|world| {
+                        let winner_label = find_ui(world, WINNER_LABEL_ID).expect("Could not find winner label");
+                        let ui_text = world.read_storage::<UiText>();
+                        let text = ui_text.get(winner_label).expect("winner label has no text");
+                        assert_eq!(text.text, "P2 wins 9 - 11");
+                    })
+                    .end_test()
+            })
+            .run();
+        assert!(test_result.is_ok());
+    }
+
+    #[test]
+    fn click_rematch_button() {
+        amethyst::start_logger(amethyst::LoggerConfig::default());
+        let test_result = crate::test_harness::IntegrationTestApplication::pong_base()
+            .with_state(|| {
+                SendMockEvents::test_state(|_world| Box::new(GameOverState::new(match_over())))
+                    .with_condition_barrier(wait_for_ui(MAIN_MENU_BUTTON_ID), Duration::from_secs(20))
+                    .with_step(click(REMATCH_BUTTON_ID))
+                    .with_wait(1.0)
+                    .end_test()
+            })
+            .run();
+        assert!(test_result.is_ok());
+    }
+
+    #[test]
+    fn click_main_menu_button() {
+        amethyst::start_logger(amethyst::LoggerConfig::default());
+        let test_result = crate::test_harness::IntegrationTestApplication::pong_base()
+            .with_state(|| {
+                SendMockEvents::test_state(|_world| Box::new(GameOverState::new(match_over())))
+                    .with_condition_barrier(wait_for_ui(MAIN_MENU_BUTTON_ID), Duration::from_secs(20))
+                    .with_step(click(MAIN_MENU_BUTTON_ID))
+                    .with_wait(1.0)
+                    .end_test()
+            })
+            .run();
+        assert!(test_result.is_ok());
+    }
+}
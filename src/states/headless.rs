@@ -0,0 +1,114 @@
+use crate::{
+    game_data::CustomGameData,
+    states::{format_scores, Pong},
+    systems::AiDifficulty,
+    GameStateEvent, MatchSettings, PaddleController,
+};
+use amethyst::prelude::*;
+
+/// Plays a match without a window, e.g. for soak tests. The CPU takes over every paddle, the match
+/// advances one simulation step per frame, and the game quits once the match is decided, printing
+/// the result.
+pub struct HeadlessState<'a, 'b> {
+    pong: Pong<'a, 'b>,
+}
+
+impl<'a, 'b> Default for HeadlessState<'a, 'b> {
+    fn default() -> Self {
+        Self {
+            pong: Pong::fixed_tick(),
+        }
+    }
+}
+
+impl<'a, 'b> State<CustomGameData<'static, 'static>, GameStateEvent> for HeadlessState<'a, 'b> {
+    fn on_start(&mut self, data: StateData<'_, CustomGameData<'_, '_>>) {
+        {
+            let mut match_settings = data.world.write_resource::<MatchSettings>();
+            for side in match_settings.mode.sides() {
+                let controller = match_settings.controller_mut(*side);
+                match *controller {
+                    PaddleController::Human | PaddleController::Pointer => {
+                        *controller = PaddleController::Cpu(AiDifficulty::Normal);
+                    }
+                    PaddleController::Cpu(_) => {}
+                }
+            }
+        }
+        self.pong.on_start(data);
+    }
+
+    fn on_stop(&mut self, data: StateData<'_, CustomGameData<'_, '_>>) {
+        self.pong.on_stop(data);
+    }
+
+    fn handle_event(
+        &mut self,
+        data: StateData<'_, CustomGameData<'_, '_>>,
+        event: GameStateEvent,
+    ) -> Trans<CustomGameData<'static, 'static>, GameStateEvent> {
+        match event {
+            GameStateEvent::MatchOver(match_over) => {
+                println!("{} won {}", match_over.winner.name(), format_scores(&match_over.scores));
+                log::info!("[Trans::Quit] Quitting Application!");
+                Trans::Quit
+            }
+            event => self.pong.handle_event(data, event),
+        }
+    }
+
+    fn update(
+        &mut self,
+        data: StateData<'_, CustomGameData<'_, '_>>,
+    ) -> Trans<CustomGameData<'static, 'static>, GameStateEvent> {
+        self.pong.update(data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{systems::MatchOver, test_harness::SendMockEvents, Side};
+    use amethyst::{core::shrev::EventChannel, ecs::prelude::*};
+
+    #[test]
+    fn cpu_plays_every_paddle() {
+        amethyst::start_logger(amethyst::LoggerConfig::default());
+        let test_result = crate::test_harness::IntegrationTestApplication::pong_base()
+            .with_state(|| {
+                SendMockEvents::test_state(|_world| Box::new(HeadlessState::default()))
+                    .with_wait(0.5)
+                    .with_step(|world| {
+                        let match_settings = world.read_resource::<MatchSettings>();
+                        for side in match_settings.mode.sides() {
+                            assert_eq!(
+                                match_settings.controller(*side),
+                                PaddleController::Cpu(AiDifficulty::Normal)
+                            );
+                        }
+                    })
+                    .end_test()
+            })
+            .run();
+        assert!(test_result.is_ok());
+    }
+
+    #[test]
+    fn quits_when_match_is_over() {
+        amethyst::start_logger(amethyst::LoggerConfig::default());
+        let test_result = crate::test_harness::IntegrationTestApplication::pong_base()
+            .with_state(|| {
+                SendMockEvents::test_state(|_world| Box::new(HeadlessState::default()))
+                    .with_step(|world| {
+                        let mut events: Write<EventChannel<MatchOver>> = world.system_data();
+                        events.single_write(MatchOver {
+                            winner: Side::Right,
+                            scores: vec![7, 11],
+                        });
+                    })
+                    .with_wait(0.5)
+            })
+            .run();
+        assert!(test_result.is_ok());
+    }
+}
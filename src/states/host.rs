@@ -0,0 +1,180 @@
+use crate::{
+    game_data::CustomGameData,
+    network::NetworkHost,
+    states::{format_scores, Pong},
+    GameStateEvent,
+};
+use amethyst::prelude::*;
+
+/// Hosts a network match, see `Pong::host`. A headless host has no player of its own and quits
+/// once the match is decided, instead of showing the game over screen.
+pub struct HostState<'a, 'b> {
+    pong: Pong<'a, 'b>,
+    headless: bool,
+}
+
+impl<'a, 'b> HostState<'a, 'b> {
+    pub fn new(host: NetworkHost, headless: bool) -> Self {
+        Self {
+            pong: Pong::host(host),
+            headless,
+        }
+    }
+}
+
+impl<'a, 'b> State<CustomGameData<'static, 'static>, GameStateEvent> for HostState<'a, 'b> {
+    fn on_start(&mut self, data: StateData<'_, CustomGameData<'_, '_>>) {
+        self.pong.on_start(data);
+    }
+
+    fn on_stop(&mut self, data: StateData<'_, CustomGameData<'_, '_>>) {
+        self.pong.on_stop(data);
+    }
+
+    fn on_pause(&mut self, data: StateData<'_, CustomGameData<'_, '_>>) {
+        self.pong.on_pause(data);
+    }
+
+    fn on_resume(&mut self, data: StateData<'_, CustomGameData<'_, '_>>) {
+        self.pong.on_resume(data);
+    }
+
+    fn handle_event(
+        &mut self,
+        data: StateData<'_, CustomGameData<'_, '_>>,
+        event: GameStateEvent,
+    ) -> Trans<CustomGameData<'static, 'static>, GameStateEvent> {
+        match event {
+            GameStateEvent::MatchOver(match_over) if self.headless => {
+                log::info!(
+                    "{} won the hosted match {}",
+                    match_over.winner.name(),
+                    format_scores(&match_over.scores)
+                );
+                log::info!("[Trans::Quit] Quitting Application!");
+                Trans::Quit
+            }
+            event => self.pong.handle_event(data, event),
+        }
+    }
+
+    fn update(
+        &mut self,
+        data: StateData<'_, CustomGameData<'_, '_>>,
+    ) -> Trans<CustomGameData<'static, 'static>, GameStateEvent> {
+        self.pong.update(data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        network::NetworkClient,
+        states::HostAddress,
+        systems::MatchOver,
+        test_harness::{ConditionBarrierResult, SendMockEvents},
+        Ball, Paddle, ScoreBoard, Side,
+    };
+    use amethyst::{
+        core::{shrev::EventChannel, transform::Transform},
+        ecs::prelude::*,
+    };
+    use std::time::Duration;
+
+    fn loopback_host(headless: bool) -> HostState<'static, 'static> {
+        let local_sides: &[Side] = if headless { &[] } else { &[Side::Left] };
+        let host = NetworkHost::bind(([127, 0, 0, 1], 0).into(), local_sides).expect("failed to bind host");
+        HostState::new(host, headless)
+    }
+
+    /// Joins the hosted match with a client stored in the world.
+    fn join(world: &mut World) {
+        let address = world.read_resource::<HostAddress>().0;
+        world.insert(NetworkClient::connect(address).expect("failed to connect"));
+    }
+
+    fn wait_for_snapshot(world: &mut World) -> ConditionBarrierResult {
+        let mut client = world.write_resource::<NetworkClient>();
+        client.poll();
+        if client.match_info().is_some() {
+            if let Some(snapshot) = client.take_snapshot() {
+                if !snapshot.balls.is_empty() {
+                    return ConditionBarrierResult::ResumeImmediately;
+                }
+            }
+        }
+        ConditionBarrierResult::ContinueEvaluating
+    }
+
+    #[test]
+    fn host_waits_for_client() {
+        amethyst::start_logger(amethyst::LoggerConfig::default());
+        let test_result = crate::test_harness::IntegrationTestApplication::pong_base()
+            .with_state(|| {
+                SendMockEvents::test_state(|_world| Box::new(loopback_host(false)))
+                    .with_wait(1.5)
+                    .with_step(|world| {
+                        // The ball spawns a second into the match, which has not started yet.
+                        assert_eq!(0, world.read_storage::<Ball>().count());
+                    })
+                    .with_step(join)
+                    .with_condition_barrier(wait_for_snapshot, Duration::from_secs(20))
+                    .with_step(|world| {
+                        let client = world.read_resource::<NetworkClient>();
+                        assert_eq!(client.match_info().map(|info| info.side), Some(Side::Right));
+                    })
+                    .end_test()
+            })
+            .run();
+        assert!(test_result.is_ok());
+    }
+
+    #[test]
+    fn client_moves_its_paddle() {
+        amethyst::start_logger(amethyst::LoggerConfig::default());
+        let test_result = crate::test_harness::IntegrationTestApplication::pong_base()
+            .with_state(|| {
+                SendMockEvents::test_state(|_world| Box::new(loopback_host(false)))
+                    .with_step(join)
+                    .with_condition_barrier(wait_for_snapshot, Duration::from_secs(20))
+                    .with_step(|world| world.write_resource::<NetworkClient>().send_input(Some(1.0)))
+                    .with_wait(0.5)
+                    .with_step(|world| {
+                        let paddles = world.read_storage::<Paddle>();
+                        let transforms = world.read_storage::<Transform>();
+                        for (paddle, transform) in (&paddles, &transforms).join() {
+                            match paddle.side {
+                                Side::Right => assert!(transform.translation().y > 45.0),
+                                _ => assert!((transform.translation().y - 45.0).abs() < 1e-3),
+                            }
+                        }
+                    })
+                    .end_test()
+            })
+            .run();
+        assert!(test_result.is_ok());
+    }
+
+    #[test]
+    fn headless_host_quits_when_match_is_over() {
+        amethyst::start_logger(amethyst::LoggerConfig::default());
+        let test_result = crate::test_harness::IntegrationTestApplication::pong_base()
+            .with_state(|| {
+                SendMockEvents::test_state(|_world| Box::new(loopback_host(true)))
+                    .with_step(join)
+                    .with_condition_barrier(wait_for_snapshot, Duration::from_secs(20))
+                    .with_step(|world| {
+                        assert_eq!(world.read_resource::<ScoreBoard>().scores(), &[0, 0]);
+                        let mut events: Write<EventChannel<MatchOver>> = world.system_data();
+                        events.single_write(MatchOver {
+                            winner: Side::Left,
+                            scores: vec![11, 3],
+                        });
+                    })
+                    .with_wait(0.5)
+            })
+            .run();
+        assert!(test_result.is_ok());
+    }
+}
@@ -0,0 +1,276 @@
+use crate::{
+    game_data::CustomGameData,
+    level::{level_names, Level, LEVELS},
+    states::{set_button_text, util::delete_hierarchy, MainMenu, Pong},
+    GamePaths, GameStateEvent,
+};
+use amethyst::{
+    assets::ProgressCounter,
+    ecs::prelude::Entity,
+    input::{is_close_requested, is_key_down},
+    prelude::*,
+    ui::{UiCreator, UiEvent, UiEventType, UiFinder},
+    winit::VirtualKeyCode,
+};
+use std::path::PathBuf;
+
+const LEVEL_BUTTON_IDS: [&str; 4] = ["level_1", "level_2", "level_3", "level_4"];
+const PAGE_BUTTON_ID: &str = "level_select_page";
+const BACK_BUTTON_ID: &str = "level_select_back";
+
+/// Level picker between the main menu and a match, listing every level file a page of buttons at a
+/// time. The picked level becomes the `Level` resource the match is played in.
+#[derive(Default)]
+pub struct LevelSelectState {
+    /// contents of every level file, `None` for unreadable files
+    levels: Vec<Option<Level>>,
+    level_buttons: [Option<Entity>; 4],
+    page_button: Option<Entity>,
+    back_button: Option<Entity>,
+    page: usize,
+    /// ui hierarchy root entity
+    root: Option<Entity>,
+    load_progress: Option<ProgressCounter>,
+}
+
+impl LevelSelectState {
+    fn assets_dir(world: &World) -> Option<PathBuf> {
+        match world.try_fetch::<GamePaths>() {
+            Some(paths) => Some(paths.assets_dir.clone()),
+            // tests run without `GamePaths`, the levels are read from the repository then
+            None => crate::initialize_paths().ok().map(|paths| paths.assets_dir),
+        }
+    }
+
+    fn read_levels(&mut self, world: &World) {
+        let assets_dir = Self::assets_dir(world);
+        let names = match assets_dir.as_ref().map(|assets_dir| level_names(assets_dir)) {
+            Some(Ok(names)) => names,
+            Some(Err(e)) => {
+                log::error!("Failed to list the levels: {}", e);
+                LEVELS.iter().map(|name| name.to_string()).collect()
+            }
+            None => LEVELS.iter().map(|name| name.to_string()).collect(),
+        };
+        self.levels = names
+            .iter()
+            .map(|name| {
+                Level::read(assets_dir.as_ref()?, name)
+                    .map_err(|e| log::error!("Failed to read level {:?}: {}", name, e))
+                    .ok()
+            })
+            .collect();
+    }
+
+    fn page_count(&self) -> usize {
+        ((self.levels.len() + LEVEL_BUTTON_IDS.len() - 1) / LEVEL_BUTTON_IDS.len()).max(1)
+    }
+
+    /// Writes the names of the levels on the current page into the buttons.
+    fn update_labels(&self, world: &World) {
+        let first_level = self.page * LEVEL_BUTTON_IDS.len();
+        for (index, button) in self.level_buttons.iter().enumerate() {
+            if let Some(button) = *button {
+                let label = match self.levels.get(first_level + index) {
+                    Some(Some(level)) => level.name.clone(),
+                    Some(None) => "UNAVAILABLE".to_string(),
+                    None => String::new(),
+                };
+                set_button_text(world, button, &label);
+            }
+        }
+        if let Some(button) = self.page_button {
+            set_button_text(world, button, &format!("PAGE {}/{}", self.page + 1, self.page_count()));
+        }
+    }
+
+    fn pick_level(&self, world: &mut World, index: usize) -> Trans<CustomGameData<'static, 'static>, GameStateEvent> {
+        match self.levels.get(self.page * LEVEL_BUTTON_IDS.len() + index) {
+            Some(Some(level)) => {
+                log::info!("Next match is played in level {}", level.name);
+                world.insert(level.clone());
+                log::info!("[Trans::Switch] Switching to Pong!");
+                Trans::Switch(Box::new(Pong::default()))
+            }
+            _ => Trans::None,
+        }
+    }
+}
+
+impl<'a, 'b> State<CustomGameData<'static, 'static>, GameStateEvent> for LevelSelectState {
+    fn on_start(&mut self, data: StateData<'_, CustomGameData<'_, '_>>) {
+        let world = data.world;
+
+        self.read_levels(world);
+
+        let mut progress = ProgressCounter::default();
+
+        self.root = Some(world.exec(|mut creator: UiCreator<'_>| creator.create("ui/level_select.ron", &mut progress)));
+
+        self.load_progress = Some(progress);
+    }
+
+    fn on_stop(&mut self, data: StateData<'_, CustomGameData<'_, '_>>) {
+        if let Some(root) = self.root.take() {
+            delete_hierarchy(root, data.world).expect("Failed to remove LevelSelectState");
+        }
+        self.level_buttons = [None; 4];
+        self.page_button = None;
+        self.back_button = None;
+        self.page = 0;
+        self.load_progress = None;
+    }
+
+    fn handle_event(
+        &mut self,
+        data: StateData<'_, CustomGameData<'_, '_>>,
+        event: GameStateEvent,
+    ) -> Trans<CustomGameData<'static, 'static>, GameStateEvent> {
+        match event {
+            GameStateEvent::Window(event) => {
+                if is_close_requested(&event) {
+                    log::info!("[Trans::Quit] Quitting Application!");
+                    Trans::Quit
+                } else if is_key_down(&event, VirtualKeyCode::Escape) {
+                    log::info!("[Trans::Switch] Switching to MainMenu!");
+                    Trans::Switch(Box::new(MainMenu::default()))
+                } else {
+                    Trans::None
+                }
+            }
+            GameStateEvent::Ui(UiEvent {
+                event_type: UiEventType::Click,
+                target,
+            }) => {
+                if Some(target) == self.back_button {
+                    log::info!("[Trans::Switch] Switching to MainMenu!");
+                    return Trans::Switch(Box::new(MainMenu::default()));
+                }
+                if Some(target) == self.page_button {
+                    self.page = (self.page + 1) % self.page_count();
+                    self.update_labels(data.world);
+                    return Trans::None;
+                }
+                match self.level_buttons.iter().position(|button| *button == Some(target)) {
+                    Some(index) => self.pick_level(data.world, index),
+                    None => Trans::None,
+                }
+            }
+            GameStateEvent::Test(test_event) => crate::test_harness::handle_test_event(&test_event),
+            _ => Trans::None,
+        }
+    }
+
+    fn update(
+        &mut self,
+        data: StateData<'_, CustomGameData<'_, '_>>,
+    ) -> Trans<CustomGameData<'static, 'static>, GameStateEvent> {
+        data.data.update(data.world, true);
+        // once deferred creation of the root ui entity finishes, look up the widgets
+        if self.back_button.is_none() || self.page_button.is_none() || self.level_buttons.iter().any(Option::is_none) {
+            let level_buttons = &mut self.level_buttons;
+            let page_button = &mut self.page_button;
+            let back_button = &mut self.back_button;
+            data.world.exec(|ui_finder: UiFinder<'_>| {
+                *back_button = ui_finder.find(BACK_BUTTON_ID);
+                *page_button = ui_finder.find(PAGE_BUTTON_ID);
+                for (button, id) in level_buttons.iter_mut().zip(LEVEL_BUTTON_IDS.iter()) {
+                    *button = ui_finder.find(id);
+                }
+            });
+            self.update_labels(data.world);
+        }
+
+        Trans::None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        systems::Obstacle,
+        test_harness::{click, temp_path, wait_for_ui, SendMockEvents},
+    };
+    use amethyst::ecs::prelude::*;
+    use std::time::Duration;
+
+    #[test]
+    fn play_picked_level() {
+        amethyst::start_logger(amethyst::LoggerConfig::default());
+        let test_result = crate::test_harness::IntegrationTestApplication::pong_base()
+            .with_setup(|world| {
+                let mut progress = ProgressCounter::default();
+                crate::audio::initialise_audio(world, &mut progress);
+            })
+            .with_state(|| {
+                SendMockEvents::test_state(|_world| Box::new(LevelSelectState::default()))
+                    .with_condition_barrier(wait_for_ui(BACK_BUTTON_ID), Duration::from_secs(20))
+                    .with_step(click(LEVEL_BUTTON_IDS[1]))
+                    .with_wait(0.5)
+                    .with_step(|world| {
+                        assert_eq!(world.read_resource::<Level>().name, "PILLARS");
+                        assert_eq!(4, world.read_storage::<Obstacle>().count());
+                    })
+                    .end_test()
+            })
+            .run();
+        assert!(test_result.is_ok());
+    }
+
+    #[test]
+    fn page_to_added_level() -> amethyst::Result<()> {
+        use crate::level::{level_path, LEVELS_DIR};
+        use amethyst::config::Config;
+
+        // the shipped levels fill the first page, the added one is alone on the second
+        let assets_dir = temp_path("level-pages");
+        let shipped_dir = crate::initialize_paths()?.assets_dir;
+        std::fs::create_dir_all(assets_dir.join(LEVELS_DIR))?;
+        for name in &LEVELS {
+            Level::read(&shipped_dir, name)?.write(level_path(&assets_dir, name))?;
+        }
+        Level {
+            name: "ADDED".to_string(),
+            ..Level::default()
+        }
+        .write(level_path(&assets_dir, "added"))?;
+
+        amethyst::start_logger(amethyst::LoggerConfig::default());
+        let test_assets_dir = assets_dir.clone();
+        let test_result = crate::test_harness::IntegrationTestApplication::pong_base()
+            .with_resource(GamePaths::new(test_assets_dir))
+            .with_setup(|world| {
+                let mut progress = ProgressCounter::default();
+                crate::audio::initialise_audio(world, &mut progress);
+            })
+            .with_state(|| {
+                SendMockEvents::test_state(|_world| Box::new(LevelSelectState::default()))
+                    .with_condition_barrier(wait_for_ui(PAGE_BUTTON_ID), Duration::from_secs(20))
+                    .with_step(click(PAGE_BUTTON_ID))
+                    .with_step(click(LEVEL_BUTTON_IDS[0]))
+                    .with_wait(0.5)
+                    .with_step(|world| assert_eq!(world.read_resource::<Level>().name, "ADDED"))
+                    .end_test()
+            })
+            .run();
+        std::fs::remove_dir_all(&assets_dir)?;
+        assert!(test_result.is_ok());
+        Ok(())
+    }
+
+    #[test]
+    fn click_back_button() {
+        amethyst::start_logger(amethyst::LoggerConfig::default());
+        let test_result = crate::test_harness::IntegrationTestApplication::pong_base()
+            .with_state(|| {
+                SendMockEvents::test_state(|_world| Box::new(LevelSelectState::default()))
+                    .with_condition_barrier(wait_for_ui(BACK_BUTTON_ID), Duration::from_secs(20))
+                    .with_step(click(BACK_BUTTON_ID))
+                    .with_wait(0.5)
+                    .end_test()
+            })
+            .run();
+        assert!(test_result.is_ok());
+    }
+}
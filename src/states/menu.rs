@@ -2,8 +2,13 @@
 
 use crate::{
     game_data::CustomGameData,
-    states::{util::delete_hierarchy, CreditsScreen, Pong, WelcomeScreen},
-    GameStateEvent,
+    replay::{last_match_path, Replay},
+    states::{
+        set_button_text, util::delete_hierarchy, CreditsScreen, LevelSelectState, OptionsState, ReplayState,
+        SaveSlotState, WelcomeScreen,
+    },
+    systems::HotReloadEvent,
+    GamePaths, GameStateEvent, MatchSettings, Side, MENU_PREFAB,
 };
 use amethyst::{
     assets::{Completion, ProgressCounter},
@@ -19,6 +24,11 @@ const BUTTON_START: &str = "start";
 const BUTTON_LOAD: &str = "load";
 const BUTTON_OPTIONS: &str = "options";
 const BUTTON_CREDITS: &str = "credits";
+const BUTTON_LEFT_PLAYER: &str = "left_player";
+const BUTTON_RIGHT_PLAYER: &str = "right_player";
+const BUTTON_REPLAY: &str = "replay";
+const BUTTON_MODE: &str = "mode";
+const BUTTON_POWER_UPS: &str = "power_ups";
 
 #[derive(Derivative)]
 #[derivative(Debug)]
@@ -29,6 +39,11 @@ pub struct MainMenu {
     button_load: Option<Entity>,
     button_options: Option<Entity>,
     button_credits: Option<Entity>,
+    button_left_player: Option<Entity>,
+    button_right_player: Option<Entity>,
+    button_replay: Option<Entity>,
+    button_mode: Option<Entity>,
+    button_power_ups: Option<Entity>,
     #[derivative(Debug = "ignore")]
     load_progress: Option<ProgressCounter>,
 }
@@ -38,7 +53,7 @@ impl<'a, 'b> State<CustomGameData<'static, 'static>, GameStateEvent> for MainMen
         // create UI from prefab and save the reference.
         let world = data.world;
         let mut progress = ProgressCounter::default();
-        self.ui_root = Some(world.exec(|mut creator: UiCreator<'_>| creator.create("ui/menu.ron", &mut progress)));
+        self.ui_root = Some(world.exec(|mut creator: UiCreator<'_>| creator.create(MENU_PREFAB, &mut progress)));
         self.load_progress = Some(progress);
     }
 
@@ -61,12 +76,17 @@ impl<'a, 'b> State<CustomGameData<'static, 'static>, GameStateEvent> for MainMen
         self.button_load = None;
         self.button_options = None;
         self.button_credits = None;
+        self.button_left_player = None;
+        self.button_right_player = None;
+        self.button_replay = None;
+        self.button_mode = None;
+        self.button_power_ups = None;
         self.load_progress = None;
     }
 
     fn handle_event(
         &mut self,
-        _: StateData<'_, CustomGameData<'_, '_>>,
+        data: StateData<'_, CustomGameData<'_, '_>>,
         event: GameStateEvent,
     ) -> Trans<CustomGameData<'static, 'static>, GameStateEvent> {
         match event {
@@ -90,13 +110,75 @@ impl<'a, 'b> State<CustomGameData<'static, 'static>, GameStateEvent> for MainMen
                     return Trans::Switch(Box::new(CreditsScreen::default()));
                 }
                 if Some(target) == self.button_start {
-                    log::info!("[Trans::Switch] Switching to Pong!");
-                    return Trans::Switch(Box::new(Pong::default()));
+                    log::info!("[Trans::Switch] Switching to LevelSelectState!");
+                    return Trans::Switch(Box::new(LevelSelectState::default()));
+                }
+                if Some(target) == self.button_options {
+                    log::info!("[Trans::Switch] Switching to OptionsState!");
+                    return Trans::Switch(Box::new(OptionsState::default()));
+                }
+                if Some(target) == self.button_load {
+                    log::info!("[Trans::Switch] Switching to SaveSlotState!");
+                    return Trans::Switch(Box::new(SaveSlotState::load()));
+                }
+                if Some(target) == self.button_replay {
+                    // the replay of the last match is only written when running the real game
+                    if let Some(paths) = data.world.try_fetch::<GamePaths>() {
+                        let path = last_match_path(&paths.replays_dir);
+                        match Replay::read(&path) {
+                            Ok(replay) => {
+                                log::info!("[Trans::Switch] Switching to ReplayState!");
+                                return Trans::Switch(Box::new(ReplayState::new(replay)));
+                            }
+                            Err(e) => log::error!("Failed to read replay {:?}: {}", path, e),
+                        }
+                    }
+                }
+                if Some(target) == self.button_left_player || Some(target) == self.button_right_player {
+                    let side = if Some(target) == self.button_left_player {
+                        Side::Left
+                    } else {
+                        Side::Right
+                    };
+                    let mut match_settings = data.world.write_resource::<MatchSettings>();
+                    let controller = match_settings.controller_mut(side);
+                    *controller = controller.next();
+                    log::info!("{:?} paddle is now controlled by {:?}", side, controller);
                 }
-                if Some(target) == self.button_load || Some(target) == self.button_options {
-                    log::info!("This Buttons functionality is not yet implemented!");
+                if Some(target) == self.button_mode {
+                    let mut match_settings = data.world.write_resource::<MatchSettings>();
+                    match_settings.mode = match_settings.mode.next();
+                    log::info!("Next match is played in {:?} mode", match_settings.mode);
                 }
+                if Some(target) == self.button_power_ups {
+                    let mut match_settings = data.world.write_resource::<MatchSettings>();
+                    match_settings.power_ups = !match_settings.power_ups;
+                    log::info!("Power-ups enabled: {}", match_settings.power_ups);
+                }
+
+                Trans::None
+            }
+            GameStateEvent::HotReload(HotReloadEvent::UiPrefab(prefab)) if prefab == MENU_PREFAB => {
+                // Recreate the menu and let `update` look up the new buttons.
+                if let Some(entity) = self.ui_root.take() {
+                    delete_hierarchy(entity, data.world).expect("Failed to remove MainMenu");
+                }
+                self.button_start = None;
+                self.button_load = None;
+                self.button_options = None;
+                self.button_credits = None;
+                self.button_left_player = None;
+                self.button_right_player = None;
+                self.button_replay = None;
+                self.button_mode = None;
+                self.button_power_ups = None;
 
+                let mut progress = ProgressCounter::default();
+                self.ui_root = Some(
+                    data.world
+                        .exec(|mut creator: UiCreator<'_>| creator.create(MENU_PREFAB, &mut progress)),
+                );
+                self.load_progress = Some(progress);
                 Trans::None
             }
             GameStateEvent::Test(test_event) => crate::test_harness::handle_test_event(&test_event),
@@ -116,15 +198,41 @@ impl<'a, 'b> State<CustomGameData<'static, 'static>, GameStateEvent> for MainMen
             || self.button_load.is_none()
             || self.button_options.is_none()
             || self.button_credits.is_none()
+            || self.button_left_player.is_none()
+            || self.button_right_player.is_none()
+            || self.button_replay.is_none()
+            || self.button_mode.is_none()
+            || self.button_power_ups.is_none()
         {
             world.exec(|ui_finder: UiFinder<'_>| {
                 self.button_start = ui_finder.find(BUTTON_START);
                 self.button_load = ui_finder.find(BUTTON_LOAD);
                 self.button_options = ui_finder.find(BUTTON_OPTIONS);
                 self.button_credits = ui_finder.find(BUTTON_CREDITS);
+                self.button_left_player = ui_finder.find(BUTTON_LEFT_PLAYER);
+                self.button_right_player = ui_finder.find(BUTTON_RIGHT_PLAYER);
+                self.button_replay = ui_finder.find(BUTTON_REPLAY);
+                self.button_mode = ui_finder.find(BUTTON_MODE);
+                self.button_power_ups = ui_finder.find(BUTTON_POWER_UPS);
             });
         }
 
+        // keep the player buttons in sync with the current match settings
+        let match_settings = *world.read_resource::<MatchSettings>();
+        if let Some(button) = self.button_left_player {
+            set_button_text(world, button, &format!("P1: {}", match_settings.left.name()));
+        }
+        if let Some(button) = self.button_right_player {
+            set_button_text(world, button, &format!("P2: {}", match_settings.right.name()));
+        }
+        if let Some(button) = self.button_mode {
+            set_button_text(world, button, match_settings.mode.name());
+        }
+        if let Some(button) = self.button_power_ups {
+            let state = if match_settings.power_ups { "ON" } else { "OFF" };
+            set_button_text(world, button, &format!("POWER-UPS: {}", state));
+        }
+
         Trans::None
     }
 }
@@ -133,7 +241,8 @@ impl<'a, 'b> State<CustomGameData<'static, 'static>, GameStateEvent> for MainMen
 mod tests {
     use super::*;
     use crate::audio::initialise_audio;
-    use crate::test_harness::{ConditionBarrierResult, SendMockEvents};
+    use crate::states::Pong;
+    use crate::test_harness::{click, wait_for_ui, SendMockEvents};
     use amethyst::{
         assets::ProgressCounter,
         core::shrev::EventChannel,
@@ -167,29 +276,8 @@ mod tests {
             })
             .with_state(|| {
                 SendMockEvents::test_state(|_world| Box::new(MainMenu::default()))
-                    .with_condition_barrier(
-                        |world| {
-                            let mut button: Option<Entity> = None;
-                            world.exec(|ui_finder: UiFinder<'_>| {
-                                button = ui_finder.find(BUTTON_START);
-                            });
-                            if button.is_some() {
-                                ConditionBarrierResult::ResumeImmediately
-                            } else {
-                                ConditionBarrierResult::ContinueEvaluating
-                            }
-                        },
-                        Duration::from_secs(20),
-                    )
-                    .with_step(|world| {
-                        let mut button: Option<Entity> = None;
-                        world.exec(|ui_finder: UiFinder<'_>| {
-                            button = ui_finder.find(BUTTON_START);
-                        });
-                        let event = UiEvent::new(UiEventType::Click, button.expect("Could not find start button"));
-                        let mut events: Write<EventChannel<UiEvent>> = world.system_data();
-                        events.single_write(event);
-                    })
+                    .with_condition_barrier(wait_for_ui(BUTTON_START), Duration::from_secs(20))
+                    .with_step(click(BUTTON_START))
                     .with_wait(1.0)
                     .end_test()
             })
@@ -203,29 +291,8 @@ mod tests {
         let test_result = crate::test_harness::IntegrationTestApplication::pong_base()
             .with_state(|| {
                 SendMockEvents::test_state(|_world| Box::new(MainMenu::default()))
-                    .with_condition_barrier(
-                        |world| {
-                            let mut button: Option<Entity> = None;
-                            world.exec(|ui_finder: UiFinder<'_>| {
-                                button = ui_finder.find(BUTTON_LOAD);
-                            });
-                            if button.is_some() {
-                                ConditionBarrierResult::ResumeImmediately
-                            } else {
-                                ConditionBarrierResult::ContinueEvaluating
-                            }
-                        },
-                        Duration::from_secs(20),
-                    )
-                    .with_step(|world| {
-                        let mut button: Option<Entity> = None;
-                        world.exec(|ui_finder: UiFinder<'_>| {
-                            button = ui_finder.find(BUTTON_LOAD);
-                        });
-                        let event = UiEvent::new(UiEventType::Click, button.expect("Could not find load button"));
-                        let mut events: Write<EventChannel<UiEvent>> = world.system_data();
-                        events.single_write(event);
-                    })
+                    .with_condition_barrier(wait_for_ui(BUTTON_LOAD), Duration::from_secs(20))
+                    .with_step(click(BUTTON_LOAD))
                     .with_wait(1.0)
                     .end_test()
             })
@@ -239,30 +306,66 @@ mod tests {
         let test_result = crate::test_harness::IntegrationTestApplication::pong_base()
             .with_state(|| {
                 SendMockEvents::test_state(|_world| Box::new(MainMenu::default()))
-                    .with_condition_barrier(
-                        |world| {
-                            let mut button: Option<Entity> = None;
-                            world.exec(|ui_finder: UiFinder<'_>| {
-                                button = ui_finder.find(BUTTON_OPTIONS);
-                            });
-                            if button.is_some() {
-                                ConditionBarrierResult::ResumeImmediately
-                            } else {
-                                ConditionBarrierResult::ContinueEvaluating
-                            }
-                        },
-                        Duration::from_secs(20),
-                    )
+                    .with_condition_barrier(wait_for_ui(BUTTON_OPTIONS), Duration::from_secs(20))
+                    .with_step(click(BUTTON_OPTIONS))
+                    .with_wait(1.0)
+                    .end_test()
+            })
+            .run();
+        assert!(test_result.is_ok());
+    }
+
+    #[test]
+    fn click_credits_button() {
+        amethyst::start_logger(amethyst::LoggerConfig::default());
+        let test_result = crate::test_harness::IntegrationTestApplication::pong_base()
+            .with_state(|| {
+                SendMockEvents::test_state(|_world| Box::new(MainMenu::default()))
+                    .with_condition_barrier(wait_for_ui(BUTTON_CREDITS), Duration::from_secs(20))
+                    .with_step(click(BUTTON_CREDITS))
+                    .with_wait(1.0)
+                    .end_test()
+            })
+            .run();
+        assert!(test_result.is_ok());
+    }
+
+    #[test]
+    fn click_left_player_button() {
+        use crate::{systems::AiDifficulty, PaddleController};
+
+        amethyst::start_logger(amethyst::LoggerConfig::default());
+        let test_result = crate::test_harness::IntegrationTestApplication::pong_base()
+            .with_state(|| {
+                SendMockEvents::test_state(|_world| Box::new(MainMenu::default()))
+                    .with_condition_barrier(wait_for_ui(BUTTON_LEFT_PLAYER), Duration::from_secs(20))
+                    .with_step(click(BUTTON_LEFT_PLAYER))
+                    .with_wait(1.0)
                     .with_step(|world| {
-                        let mut button: Option<Entity> = None;
-                        world.exec(|ui_finder: UiFinder<'_>| {
-                            button = ui_finder.find(BUTTON_OPTIONS);
-                        });
-                        let event = UiEvent::new(UiEventType::Click, button.expect("Could not find options button"));
-                        let mut events: Write<EventChannel<UiEvent>> = world.system_data();
-                        events.single_write(event);
+                        let match_settings = world.read_resource::<MatchSettings>();
+                        assert_eq!(match_settings.left, PaddleController::Cpu(AiDifficulty::Easy));
+                        assert_eq!(match_settings.right, PaddleController::Human);
                     })
+                    .end_test()
+            })
+            .run();
+        assert!(test_result.is_ok());
+    }
+
+    #[test]
+    fn click_mode_button() {
+        use crate::GameMode;
+
+        amethyst::start_logger(amethyst::LoggerConfig::default());
+        let test_result = crate::test_harness::IntegrationTestApplication::pong_base()
+            .with_state(|| {
+                SendMockEvents::test_state(|_world| Box::new(MainMenu::default()))
+                    .with_condition_barrier(wait_for_ui(BUTTON_MODE), Duration::from_secs(20))
+                    .with_step(click(BUTTON_MODE))
                     .with_wait(1.0)
+                    .with_step(|world| {
+                        assert_eq!(world.read_resource::<MatchSettings>().mode, GameMode::MultiBall);
+                    })
                     .end_test()
             })
             .run();
@@ -270,35 +373,35 @@ mod tests {
     }
 
     #[test]
-    fn click_credits_button() {
+    fn click_power_ups_button() {
         amethyst::start_logger(amethyst::LoggerConfig::default());
         let test_result = crate::test_harness::IntegrationTestApplication::pong_base()
             .with_state(|| {
                 SendMockEvents::test_state(|_world| Box::new(MainMenu::default()))
-                    .with_condition_barrier(
-                        |world| {
-                            let mut button: Option<Entity> = None;
-                            world.exec(|ui_finder: UiFinder<'_>| {
-                                button = ui_finder.find(BUTTON_CREDITS);
-                            });
-                            if button.is_some() {
-                                ConditionBarrierResult::ResumeImmediately
-                            } else {
-                                ConditionBarrierResult::ContinueEvaluating
-                            }
-                        },
-                        Duration::from_secs(20),
-                    )
+                    .with_condition_barrier(wait_for_ui(BUTTON_POWER_UPS), Duration::from_secs(20))
+                    .with_step(click(BUTTON_POWER_UPS))
+                    .with_wait(1.0)
                     .with_step(|world| {
-                        let mut button: Option<Entity> = None;
-                        world.exec(|ui_finder: UiFinder<'_>| {
-                            button = ui_finder.find(BUTTON_CREDITS);
-                        });
-                        let event = UiEvent::new(UiEventType::Click, button.expect("Could not find credits button"));
-                        let mut events: Write<EventChannel<UiEvent>> = world.system_data();
-                        events.single_write(event);
+                        assert!(world.read_resource::<MatchSettings>().power_ups);
                     })
+                    .end_test()
+            })
+            .run();
+        assert!(test_result.is_ok());
+    }
+
+    #[test]
+    fn hot_reload_menu_prefab() {
+        amethyst::start_logger(amethyst::LoggerConfig::default());
+        let test_result = crate::test_harness::IntegrationTestApplication::pong_base()
+            .with_state(|| {
+                SendMockEvents::test_state(|_world| Box::new(MainMenu::default()))
                     .with_wait(1.0)
+                    .with_step(|world| {
+                        let mut events: Write<EventChannel<HotReloadEvent>> = world.system_data();
+                        events.single_write(HotReloadEvent::UiPrefab(MENU_PREFAB));
+                    })
+                    .with_condition_barrier(wait_for_ui(BUTTON_START), Duration::from_secs(20))
                     .end_test()
             })
             .run();
@@ -1,16 +1,37 @@
 mod credits;
+mod game_over;
+mod headless;
+mod host;
+mod level_select;
 mod menu;
+mod network_client;
+mod options;
 mod pause;
 mod pong;
+mod rebind;
+mod replay;
+mod save_slots;
 mod util;
 mod welcome;
 
 pub use self::{
     credits::CreditsScreen,
+    game_over::GameOverState,
+    headless::HeadlessState,
+    host::HostState,
+    level_select::LevelSelectState,
     menu::MainMenu,
+    network_client::NetworkClientState,
+    options::OptionsState,
     pause::PauseMenuState,
-    pong::{initialise_ball, initialise_camera, initialise_paddles, initialise_score, load_sprite_sheet, Pong},
-    util::delete_hierarchy,
+    pong::{
+        initialise_ball, initialise_camera, initialise_obstacles, initialise_paddles, initialise_power_up,
+        initialise_score, load_sprite_sheet, show_scores, BallSpawnTimer, HostAddress, Pong, RollbackState,
+    },
+    rebind::RebindState,
+    replay::ReplayState,
+    save_slots::SaveSlotState,
+    util::{apply_user_settings, delete_hierarchy, format_scores, set_button_text},
     welcome::WelcomeScreen,
 };
 
@@ -0,0 +1,374 @@
+use crate::{
+    bindings::GameBindings,
+    config::PongConfig,
+    game_data::CustomGameData,
+    level::Level,
+    network::{MatchInfo, NetworkClient, Snapshot},
+    replay::PaddleInputs,
+    states::{
+        delete_hierarchy, initialise_ball, initialise_camera, initialise_obstacles, initialise_paddles,
+        initialise_score, load_sprite_sheet, show_scores, GameOverState, MainMenu,
+    },
+    systems::{steer_pointer_paddles, AiPaddle, FixedTimestep, Interpolated, MatchOver, Serving},
+    Ball, GameStateEvent, MatchSettings, Paddle, PaddleController, ScoreBoard, HUD_PREFAB,
+};
+use amethyst::{
+    assets::{Handle, ProgressCounter},
+    core::transform::Transform,
+    ecs::prelude::{Entity, Join, World, WorldExt},
+    input::{is_close_requested, is_key_down, InputHandler},
+    prelude::*,
+    renderer::SpriteSheet,
+    ui::UiCreator,
+    winit::VirtualKeyCode,
+};
+use std::net::SocketAddr;
+
+/// Plays a match hosted on another machine, see `NetworkHost`. The client does not simulate
+/// anything itself: it sends the paddle input of its player to the host and shows the snapshots it
+/// receives. The player moves the paddle with the keys of the side the host assigned, or with the
+/// pointer if they chose it for that side.
+pub struct NetworkClientState {
+    host: SocketAddr,
+    client: Option<NetworkClient>,
+    /// config, match settings and level chosen by the user, restored when leaving the match
+    previous: Option<(PongConfig, MatchSettings, Level)>,
+    sprite_sheet_handle: Option<Handle<SpriteSheet>>,
+    root_entity: Option<Entity>,
+    ui_root: Option<Entity>,
+    /// one entity per ball of the latest snapshot
+    balls: Vec<Entity>,
+    /// set once the game over screen was pushed
+    match_over: bool,
+}
+
+impl NetworkClientState {
+    pub fn new(host: SocketAddr) -> Self {
+        Self {
+            host,
+            client: None,
+            previous: None,
+            sprite_sheet_handle: None,
+            root_entity: None,
+            ui_root: None,
+            balls: Vec::new(),
+            match_over: false,
+        }
+    }
+
+    /// Shows the arena of the match the host welcomed us to.
+    fn enter_match(&mut self, world: &mut World, match_info: MatchInfo) {
+        let own_settings = *world.read_resource::<MatchSettings>();
+        self.previous = Some((
+            world.read_resource::<PongConfig>().clone(),
+            own_settings,
+            world.entry::<Level>().or_insert_with(Level::default).clone(),
+        ));
+        // The host only knows a human plays the side, the client knows whether they use the pointer.
+        let mut match_settings = match_info.match_settings;
+        if own_settings.controller(match_info.side) == PaddleController::Pointer {
+            *match_settings.controller_mut(match_info.side) = PaddleController::Pointer;
+        }
+        // The pointer steers the paddle as far as it moves in one simulation step of the host.
+        world.insert(FixedTimestep::new(&match_info.config.simulation));
+        world.insert(match_info.config);
+        world.insert(match_settings);
+        world.insert(match_info.level.clone());
+        world.insert(ScoreBoard::new(match_settings.mode.sides().len()));
+
+        let root_entity = world.create_entity().with(Transform::default()).build();
+        let sprite_sheet_handle = load_sprite_sheet(world);
+        initialise_paddles(world, root_entity, sprite_sheet_handle.clone());
+        initialise_obstacles(world, root_entity, sprite_sheet_handle.clone(), &match_info.level);
+        initialise_camera(world, root_entity);
+        self.root_entity = Some(root_entity);
+        self.sprite_sheet_handle = Some(sprite_sheet_handle);
+
+        let mut progress = ProgressCounter::default();
+        let ui_root = world.exec(|mut creator: UiCreator<'_>| creator.create(HUD_PREFAB, &mut progress));
+        initialise_score(world, ui_root);
+        self.ui_root = Some(ui_root);
+    }
+
+    /// Moves the paddles and balls to where the host has them and takes over the scores.
+    fn apply_snapshot(&mut self, world: &mut World, snapshot: Snapshot) {
+        {
+            let paddles = world.read_storage::<Paddle>();
+            let mut transforms = world.write_storage::<Transform>();
+            for (paddle, transform) in (&paddles, &mut transforms).join() {
+                if let Some(paddle) = snapshot.paddles.iter().find(|snapshot| snapshot.side == paddle.side) {
+                    transform.set_translation_x(paddle.position[0]);
+                    transform.set_translation_y(paddle.position[1]);
+                }
+            }
+        }
+
+        // Balls come and go with the points scored, spawn and remove entities to match.
+        while self.balls.len() > snapshot.balls.len() {
+            if let Some(ball) = self.balls.pop() {
+                world.delete_entity(ball).expect("Failed to remove ball");
+            }
+        }
+        if let (Some(root_entity), Some(sprite_sheet)) = (self.root_entity, self.sprite_sheet_handle.clone()) {
+            for ball in &snapshot.balls[self.balls.len()..] {
+                let entity = initialise_ball(
+                    world,
+                    root_entity,
+                    sprite_sheet.clone(),
+                    ball.radius,
+                    ball.velocity,
+                    Some(ball.position),
+                );
+                self.balls.push(entity);
+            }
+        }
+        {
+            let mut balls = world.write_storage::<Ball>();
+            let mut transforms = world.write_storage::<Transform>();
+            for (entity, snapshot) in self.balls.iter().zip(&snapshot.balls) {
+                if let (Some(ball), Some(transform)) = (balls.get_mut(*entity), transforms.get_mut(*entity)) {
+                    ball.velocity = snapshot.velocity;
+                    transform.set_translation_x(snapshot.position[0]);
+                    transform.set_translation_y(snapshot.position[1]);
+                }
+            }
+        }
+
+        world.insert(ScoreBoard::from_scores(snapshot.scores));
+        show_scores(world);
+    }
+}
+
+impl<'a, 'b> State<CustomGameData<'static, 'static>, GameStateEvent> for NetworkClientState {
+    fn on_start(&mut self, data: StateData<'_, CustomGameData<'_, '_>>) {
+        data.world.register::<Interpolated>();
+        data.world.register::<Serving>();
+        data.world.register::<AiPaddle>();
+        match NetworkClient::connect(self.host) {
+            Ok(client) => {
+                log::info!("Joining the match hosted on {}", self.host);
+                self.client = Some(client);
+            }
+            Err(e) => log::error!("Failed to join the match hosted on {}: {}", self.host, e),
+        }
+    }
+
+    fn on_stop(&mut self, data: StateData<'_, CustomGameData<'_, '_>>) {
+        if let Some(entity) = self.root_entity.take() {
+            delete_hierarchy(entity, data.world).expect("Failed to remove NetworkClientState entities");
+        }
+        if let Some(entity) = self.ui_root.take() {
+            delete_hierarchy(entity, data.world).expect("Failed to remove NetworkClientState HUD entities");
+        }
+        // Tell the host we left.
+        self.client = None;
+        self.sprite_sheet_handle = None;
+        self.balls.clear();
+        self.match_over = false;
+        if let Some((config, match_settings, level)) = self.previous.take() {
+            data.world.insert(config);
+            data.world.insert(match_settings);
+            data.world.insert(level);
+        }
+    }
+
+    fn handle_event(
+        &mut self,
+        _data: StateData<'_, CustomGameData<'_, '_>>,
+        event: GameStateEvent,
+    ) -> Trans<CustomGameData<'static, 'static>, GameStateEvent> {
+        match event {
+            GameStateEvent::Window(event) => {
+                if is_close_requested(&event) {
+                    log::info!("[Trans::Quit] Quitting Application!");
+                    Trans::Quit
+                } else if is_key_down(&event, VirtualKeyCode::Escape) {
+                    log::info!("[Trans::Switch] Leaving network match, switching to MainMenu!");
+                    Trans::Switch(Box::new(MainMenu::default()))
+                } else {
+                    Trans::None
+                }
+            }
+            GameStateEvent::Test(test_event) => crate::test_harness::handle_test_event(&test_event),
+            _ => Trans::None,
+        }
+    }
+
+    fn update(
+        &mut self,
+        data: StateData<'_, CustomGameData<'_, '_>>,
+    ) -> Trans<CustomGameData<'static, 'static>, GameStateEvent> {
+        data.data.update(data.world, true);
+
+        let client = match self.client.as_mut() {
+            Some(client) => client,
+            None => {
+                log::info!("[Trans::Switch] Switching to MainMenu!");
+                return Trans::Switch(Box::new(MainMenu::default()));
+            }
+        };
+        client.poll();
+        if client.rejection().is_some() || client.host_left() {
+            log::info!("[Trans::Switch] Network match ended, switching to MainMenu!");
+            return Trans::Switch(Box::new(MainMenu::default()));
+        }
+        let side = match client.match_info() {
+            Some(match_info) => match_info.side,
+            None => return Trans::None,
+        };
+
+        if self.root_entity.is_none() {
+            let match_info = self.client.as_ref().and_then(NetworkClient::match_info).cloned();
+            if let Some(match_info) = match_info {
+                self.enter_match(data.world, match_info);
+            }
+        }
+
+        let mut inputs = PaddleInputs::from_input(&data.world.read_resource::<InputHandler<GameBindings>>());
+        steer_pointer_paddles(data.world, &mut inputs);
+        let snapshot = self.client.as_mut().and_then(|client| {
+            client.send_input(inputs.axis_value(side));
+            client.take_snapshot()
+        });
+        if let Some(snapshot) = snapshot {
+            self.apply_snapshot(data.world, snapshot);
+        }
+
+        if !self.match_over {
+            let winner = {
+                let config = data.world.read_resource::<PongConfig>();
+                data.world.read_resource::<ScoreBoard>().winner(&config.rules)
+            };
+            if let Some(winner) = winner {
+                self.match_over = true;
+                let scores = data.world.read_resource::<ScoreBoard>().scores().to_vec();
+                log::info!("[Trans::Push] Match is over!");
+                return Trans::Push(Box::new(GameOverState::new(MatchOver { winner, scores })));
+            }
+        }
+
+        Trans::None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        network::NetworkHost,
+        systems::ScoreText,
+        test_harness::{ConditionBarrierResult, SendMockEvents},
+        Side,
+    };
+    use amethyst::ui::UiText;
+    use std::time::Duration;
+
+    /// Opens a match on a host stored in the world and joins it.
+    fn join_host(world: &mut World) -> Box<dyn State<CustomGameData<'static, 'static>, GameStateEvent>> {
+        let mut host = NetworkHost::bind(([127, 0, 0, 1], 0).into(), &[Side::Left]).expect("failed to bind host");
+        host.open(&PongConfig::default(), &MatchSettings::default(), &Level::default());
+        let address = host.local_address().expect("host has no address");
+        world.insert(host);
+        Box::new(NetworkClientState::new(address))
+    }
+
+    fn wait_for_paddles(world: &mut World) -> ConditionBarrierResult {
+        world.write_resource::<NetworkHost>().poll();
+        if world.read_storage::<Paddle>().count() == 2 {
+            ConditionBarrierResult::ResumeImmediately
+        } else {
+            ConditionBarrierResult::ContinueEvaluating
+        }
+    }
+
+    #[test]
+    fn show_snapshot_of_host() {
+        amethyst::start_logger(amethyst::LoggerConfig::default());
+        let test_result = crate::test_harness::IntegrationTestApplication::pong_base()
+            .with_state(|| {
+                SendMockEvents::test_state(join_host)
+                    .with_condition_barrier(wait_for_paddles, Duration::from_secs(20))
+                    .with_step(|world| {
+                        // The host sends what it finds in the world, which the client shares here.
+                        let mut transform = Transform::default();
+                        transform.set_translation_xyz(30.0, 40.0, 0.0);
+                        world
+                            .create_entity()
+                            .with(Ball {
+                                radius: 2.0,
+                                velocity: [0.0, 0.0],
+                                last_hit: None,
+                            })
+                            .with(transform)
+                            .build();
+                        world.insert(ScoreBoard::from_scores(vec![3, 5]));
+                        world.write_resource::<NetworkHost>().send_snapshot(world);
+                    })
+                    .with_wait(0.5)
+                    .with_step(|world| {
+                        assert_eq!(2, world.read_storage::<Ball>().count());
+                        let score_text = world.read_resource::<ScoreText>();
+                        let ui_text = world.read_storage::<UiText>();
+                        let texts = score_text
+                            .scores
+                            .iter()
+                            .filter_map(|score| ui_text.get(*score))
+                            .map(|text| text.text.as_str())
+                            .collect::<Vec<_>>();
+                        assert_eq!(texts, vec!["3", "5"]);
+                    })
+                    .end_test()
+            })
+            .run();
+        assert!(test_result.is_ok());
+    }
+
+    #[test]
+    fn steer_with_the_pointer() {
+        use crate::systems::Pointers;
+
+        amethyst::start_logger(amethyst::LoggerConfig::default());
+        let test_result = crate::test_harness::IntegrationTestApplication::pong_base()
+            .with_setup(|world| world.write_resource::<MatchSettings>().right = PaddleController::Pointer)
+            .with_state(|| {
+                SendMockEvents::test_state(join_host)
+                    .with_condition_barrier(wait_for_paddles, Duration::from_secs(20))
+                    .with_step(|world| {
+                        world.insert(Pointers {
+                            mouse: Some([10.0, 0.0]),
+                            touches: Default::default(),
+                        })
+                    })
+                    .with_wait(0.5)
+                    .with_step(|world| {
+                        let mut host = world.write_resource::<NetworkHost>();
+                        host.poll();
+                        let mut inputs = PaddleInputs::default();
+                        host.apply_inputs(&mut inputs);
+                        assert_eq!(inputs.axis_value(Side::Right), Some(-1.0));
+                    })
+                    .end_test()
+            })
+            .run();
+        assert!(test_result.is_ok());
+    }
+
+    #[test]
+    fn leave_match_when_host_closes_it() {
+        amethyst::start_logger(amethyst::LoggerConfig::default());
+        let test_result = crate::test_harness::IntegrationTestApplication::pong_base()
+            .with_state(|| {
+                SendMockEvents::test_state(join_host)
+                    .with_condition_barrier(wait_for_paddles, Duration::from_secs(20))
+                    .with_step(|world| drop(world.remove::<NetworkHost>()))
+                    .with_wait(0.5)
+                    .with_step(|world| {
+                        // The client switched to the main menu, which removed the arena.
+                        assert_eq!(0, world.read_storage::<Paddle>().count());
+                    })
+                    .end_test()
+            })
+            .run();
+        assert!(test_result.is_ok());
+    }
+}
@@ -0,0 +1,300 @@
+use crate::{
+    config::UserSettings,
+    game_data::CustomGameData,
+    states::{apply_user_settings, set_button_text, util::delete_hierarchy, MainMenu, RebindState},
+    GamePaths, GameStateEvent,
+};
+use amethyst::{
+    assets::ProgressCounter,
+    config::Config,
+    ecs::prelude::Entity,
+    input::{is_close_requested, is_key_down},
+    prelude::*,
+    ui::{UiCreator, UiEvent, UiEventType, UiFinder},
+    winit::VirtualKeyCode,
+};
+use derivative::Derivative;
+
+const BUTTON_MUSIC_VOLUME: &str = "music_volume";
+const BUTTON_SFX_VOLUME: &str = "sfx_volume";
+const BUTTON_FULLSCREEN: &str = "fullscreen";
+const BUTTON_RESOLUTION: &str = "resolution";
+const BUTTON_VSYNC: &str = "vsync";
+const BUTTON_FRAME_LIMIT: &str = "frame_limit";
+const BUTTON_CONTROLS: &str = "controls";
+const BUTTON_BACK: &str = "back";
+
+/// Lets the user change the `UserSettings`. Every click cycles the value of a button, changes are
+/// applied right away and written to the user settings file when leaving the menu.
+#[derive(Derivative)]
+#[derivative(Debug)]
+#[derivative(Default)]
+pub struct OptionsState {
+    ui_root: Option<Entity>,
+    button_music_volume: Option<Entity>,
+    button_sfx_volume: Option<Entity>,
+    button_fullscreen: Option<Entity>,
+    button_resolution: Option<Entity>,
+    button_vsync: Option<Entity>,
+    button_frame_limit: Option<Entity>,
+    button_controls: Option<Entity>,
+    button_back: Option<Entity>,
+    #[derivative(Debug = "ignore")]
+    load_progress: Option<ProgressCounter>,
+}
+
+impl OptionsState {
+    /// Changes the setting behind the clicked button. Returns `false` if `target` is not one of the
+    /// setting buttons.
+    fn change_setting(&self, settings: &mut UserSettings, target: Entity) -> bool {
+        let target = Some(target);
+        if target == self.button_music_volume {
+            settings.music_volume = UserSettings::next_volume(settings.music_volume);
+        } else if target == self.button_sfx_volume {
+            settings.sfx_volume = UserSettings::next_volume(settings.sfx_volume);
+        } else if target == self.button_fullscreen {
+            settings.fullscreen = !settings.fullscreen;
+        } else if target == self.button_resolution {
+            settings.resolution = Some(UserSettings::next_resolution(settings.resolution));
+        } else if target == self.button_vsync {
+            settings.vsync = !settings.vsync;
+        } else if target == self.button_frame_limit {
+            settings.frame_limit = UserSettings::next_frame_limit(settings.frame_limit);
+        } else {
+            return false;
+        }
+        true
+    }
+
+    /// Writes the current values into the button labels.
+    fn update_labels(&self, world: &World) {
+        let settings = world.read_resource::<UserSettings>().clone();
+        let on_off = |value: bool| if value { "ON" } else { "OFF" };
+        let labels = [
+            (
+                self.button_music_volume,
+                format!("MUSIC: {:.0}%", settings.music_volume * 100.0),
+            ),
+            (
+                self.button_sfx_volume,
+                format!("SFX: {:.0}%", settings.sfx_volume * 100.0),
+            ),
+            (
+                self.button_fullscreen,
+                format!("FULLSCREEN: {}", on_off(settings.fullscreen)),
+            ),
+            (
+                self.button_resolution,
+                match settings.resolution {
+                    Some((width, height)) => format!("RESOLUTION: {}x{}", width, height),
+                    None => "RESOLUTION: DEFAULT".to_string(),
+                },
+            ),
+            (self.button_vsync, format!("VSYNC: {}", on_off(settings.vsync))),
+            (
+                self.button_frame_limit,
+                match settings.frame_limit {
+                    0 => "FRAME LIMIT: NONE".to_string(),
+                    limit => format!("FRAME LIMIT: {}", limit),
+                },
+            ),
+        ];
+        for (button, label) in &labels {
+            if let Some(button) = *button {
+                set_button_text(world, button, label);
+            }
+        }
+    }
+}
+
+impl<'a, 'b> State<CustomGameData<'static, 'static>, GameStateEvent> for OptionsState {
+    fn on_start(&mut self, data: StateData<'_, CustomGameData<'_, '_>>) {
+        let world = data.world;
+
+        let mut progress = ProgressCounter::default();
+
+        self.ui_root = Some(world.exec(|mut creator: UiCreator<'_>| creator.create("ui/options.ron", &mut progress)));
+
+        self.load_progress = Some(progress);
+    }
+
+    fn on_stop(&mut self, data: StateData<'_, CustomGameData<'_, '_>>) {
+        // persist the settings, the paths are only known when running the real game
+        if let Some(paths) = data.world.try_fetch::<GamePaths>() {
+            let settings = data.world.read_resource::<UserSettings>();
+            match settings.store(&paths.user_settings) {
+                Ok(()) => log::info!("Saved user settings to {:?}", paths.user_settings),
+                Err(e) => log::error!("Failed to save user settings to {:?}: {}", paths.user_settings, e),
+            }
+        }
+
+        if let Some(root_entity) = self.ui_root.take() {
+            delete_hierarchy(root_entity, data.world).expect("Failed to remove OptionsState");
+        }
+
+        self.button_music_volume = None;
+        self.button_sfx_volume = None;
+        self.button_fullscreen = None;
+        self.button_resolution = None;
+        self.button_vsync = None;
+        self.button_frame_limit = None;
+        self.button_controls = None;
+        self.button_back = None;
+        self.load_progress = None;
+    }
+
+    fn handle_event(
+        &mut self,
+        data: StateData<'_, CustomGameData<'_, '_>>,
+        event: GameStateEvent,
+    ) -> Trans<CustomGameData<'static, 'static>, GameStateEvent> {
+        match event {
+            GameStateEvent::Window(event) => {
+                if is_close_requested(&event) {
+                    log::info!("[Trans::Quit] Quitting Application!");
+                    Trans::Quit
+                } else if is_key_down(&event, VirtualKeyCode::Escape) {
+                    log::info!("[Trans::Switch] Switching to MainMenu!");
+                    Trans::Switch(Box::new(MainMenu::default()))
+                } else {
+                    Trans::None
+                }
+            }
+            GameStateEvent::Ui(UiEvent {
+                event_type: UiEventType::Click,
+                target,
+            }) => {
+                if Some(target) == self.button_back {
+                    log::info!("[Trans::Switch] Switching to MainMenu!");
+                    return Trans::Switch(Box::new(MainMenu::default()));
+                }
+                if Some(target) == self.button_controls {
+                    log::info!("[Trans::Switch] Switching to RebindState!");
+                    return Trans::Switch(Box::new(RebindState::default()));
+                }
+                let changed = {
+                    let mut settings = data.world.write_resource::<UserSettings>();
+                    self.change_setting(&mut settings, target)
+                };
+                if changed {
+                    log::info!("Changed user settings: {:?}", *data.world.read_resource::<UserSettings>());
+                    apply_user_settings(data.world);
+                }
+                Trans::None
+            }
+            GameStateEvent::Test(test_event) => crate::test_harness::handle_test_event(&test_event),
+            _ => Trans::None,
+        }
+    }
+
+    fn update(
+        &mut self,
+        data: StateData<'_, CustomGameData<'_, '_>>,
+    ) -> Trans<CustomGameData<'static, 'static>, GameStateEvent> {
+        data.data.update(data.world, true);
+        // only search for buttons if they have not been found yet
+        let StateData { world, .. } = data;
+
+        if self.button_music_volume.is_none()
+            || self.button_sfx_volume.is_none()
+            || self.button_fullscreen.is_none()
+            || self.button_resolution.is_none()
+            || self.button_vsync.is_none()
+            || self.button_frame_limit.is_none()
+            || self.button_controls.is_none()
+            || self.button_back.is_none()
+        {
+            world.exec(|ui_finder: UiFinder<'_>| {
+                self.button_music_volume = ui_finder.find(BUTTON_MUSIC_VOLUME);
+                self.button_sfx_volume = ui_finder.find(BUTTON_SFX_VOLUME);
+                self.button_fullscreen = ui_finder.find(BUTTON_FULLSCREEN);
+                self.button_resolution = ui_finder.find(BUTTON_RESOLUTION);
+                self.button_vsync = ui_finder.find(BUTTON_VSYNC);
+                self.button_frame_limit = ui_finder.find(BUTTON_FRAME_LIMIT);
+                self.button_controls = ui_finder.find(BUTTON_CONTROLS);
+                self.button_back = ui_finder.find(BUTTON_BACK);
+            });
+        }
+        self.update_labels(world);
+
+        Trans::None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_harness::{click, temp_path, wait_for_ui, SendMockEvents};
+    use assert_approx_eq::assert_approx_eq;
+    use std::time::Duration;
+
+    #[test]
+    fn test_options_state() {
+        amethyst::start_logger(amethyst::LoggerConfig::default());
+        let test_result = crate::test_harness::IntegrationTestApplication::pong_base()
+            .with_state(|| {
+                SendMockEvents::test_state(|_world| Box::new(OptionsState::default()))
+                    .with_wait(1.0)
+                    .end_test()
+            })
+            .run();
+        assert!(test_result.is_ok());
+    }
+
+    #[test]
+    fn click_setting_buttons() {
+        amethyst::start_logger(amethyst::LoggerConfig::default());
+        let test_result = crate::test_harness::IntegrationTestApplication::pong_base()
+            .with_state(|| {
+                SendMockEvents::test_state(|_world| Box::new(OptionsState::default()))
+                    .with_condition_barrier(wait_for_ui(BUTTON_BACK), Duration::from_secs(20))
+                    .with_step(click(BUTTON_MUSIC_VOLUME))
+                    .with_step(click(BUTTON_SFX_VOLUME))
+                    .with_step(click(BUTTON_FULLSCREEN))
+                    .with_step(click(BUTTON_RESOLUTION))
+                    .with_step(click(BUTTON_VSYNC))
+                    .with_step(click(BUTTON_FRAME_LIMIT))
+                    .with_wait(0.5)
+                    .with_step(|world| {
+                        let settings = world.read_resource::<UserSettings>();
+                        assert_approx_eq!(settings.music_volume, 0.3);
+                        assert_approx_eq!(settings.sfx_volume, 0.0);
+                        assert!(settings.fullscreen);
+                        assert_eq!(settings.resolution, Some((1280, 720)));
+                        assert!(settings.vsync);
+                        assert_eq!(settings.frame_limit, 240);
+                    })
+                    .end_test()
+            })
+            .run();
+        assert!(test_result.is_ok());
+    }
+
+    #[test]
+    fn persist_settings_on_back() {
+        amethyst::start_logger(amethyst::LoggerConfig::default());
+        let settings_path = temp_path("options.ron");
+        let _ = std::fs::remove_file(&settings_path);
+        let paths = GamePaths {
+            user_settings: settings_path.clone(),
+            ..crate::initialize_paths().expect("valid paths required")
+        };
+
+        let test_result = crate::test_harness::IntegrationTestApplication::pong_base()
+            .with_resource(paths)
+            .with_state(|| {
+                SendMockEvents::test_state(|_world| Box::new(OptionsState::default()))
+                    .with_condition_barrier(wait_for_ui(BUTTON_BACK), Duration::from_secs(20))
+                    .with_step(click(BUTTON_VSYNC))
+                    .with_step(click(BUTTON_BACK))
+                    .with_wait(0.5)
+                    .end_test()
+            })
+            .run();
+        assert!(test_result.is_ok());
+
+        let saved = UserSettings::load_or_default(&settings_path);
+        std::fs::remove_file(&settings_path).expect("settings were not saved");
+        assert!(saved.vsync);
+    }
+}
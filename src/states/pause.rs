@@ -1,8 +1,14 @@
-use crate::{game_data::CustomGameData, states::MainMenu, GameStateEvent};
+use crate::{
+    bindings::ActionBinding,
+    game_data::CustomGameData,
+    save::SaveGame,
+    states::{util::delete_hierarchy, BallSpawnTimer, MainMenu, SaveSlotState},
+    GameStateEvent,
+};
 use amethyst::{
     assets::ProgressCounter,
     ecs::Entity,
-    input::{is_close_requested, is_key_down},
+    input::{is_close_requested, is_key_down, InputEvent},
     prelude::*,
     shrev::EventChannel,
     ui::{UiCreator, UiEvent, UiEventType, UiFinder},
@@ -16,6 +22,8 @@ use amethyst::{
 pub struct PauseMenuState {
     /// goes back to pong state
     resume_button: Option<Entity>,
+    /// opens the save slot picker
+    save_button: Option<Entity>,
     /// goes back to mainmenu state
     exit_to_main_menu_button: Option<Entity>,
     /// closes the game
@@ -27,6 +35,8 @@ pub struct PauseMenuState {
 
 /// resume button prefab ID
 const RESUME_BUTTON_ID: &str = "resume";
+/// save button prefab ID
+const SAVE_BUTTON_ID: &str = "save";
 /// resume button prefab ID
 const EXIT_TO_MAIN_MENU_BUTTON_ID: &str = "exit_to_main_menu";
 /// resume button prefab ID
@@ -34,6 +44,7 @@ const EXIT_BUTTON_ID: &str = "exit";
 
 // load the pause_menu.ron prefab then instantiate it
 // if the "resume" button is clicked, goto MainGameState
+// if the "save" button is clicked, snapshot the match and push the save slot picker
 // if the "exit_to_main_menu" button is clicked, remove the pause and main game states and go to
 // MenuState. if the "exit" button is clicked, quit the program.
 impl<'a, 'b> State<CustomGameData<'static, 'static>, GameStateEvent> for PauseMenuState {
@@ -48,16 +59,26 @@ impl<'a, 'b> State<CustomGameData<'static, 'static>, GameStateEvent> for PauseMe
     }
 
     fn on_stop(&mut self, data: StateData<'_, CustomGameData<'_, '_>>) {
-        if let Some(root) = self.root {
-            if data.world.delete_entity(root).is_ok() {
-                self.root = None;
-            }
+        if let Some(root) = self.root.take() {
+            delete_hierarchy(root, data.world).expect("Failed to remove PauseMenuState");
         }
         self.resume_button = None;
+        self.save_button = None;
         self.exit_to_main_menu_button = None;
+        self.exit_button = None;
         self.load_progress = None;
     }
 
+    // the save slot picker covers the pause menu, so its buttons go away until it is closed, and
+    // keyboard navigation and the mouse only reach the picker's
+    fn on_pause(&mut self, data: StateData<'_, CustomGameData<'_, '_>>) {
+        self.on_stop(data);
+    }
+
+    fn on_resume(&mut self, data: StateData<'_, CustomGameData<'_, '_>>) {
+        self.on_start(data);
+    }
+
     fn handle_event(
         &mut self,
         data: StateData<'_, CustomGameData<'_, '_>>,
@@ -75,6 +96,10 @@ impl<'a, 'b> State<CustomGameData<'static, 'static>, GameStateEvent> for PauseMe
                     Trans::None
                 }
             }
+            GameStateEvent::Input(InputEvent::ActionPressed(ActionBinding::Pause)) => {
+                log::info!("[Trans::Pop] Closing Pause Menu!");
+                Trans::Pop
+            }
             GameStateEvent::Ui(UiEvent {
                 event_type: UiEventType::Click,
                 target,
@@ -82,6 +107,11 @@ impl<'a, 'b> State<CustomGameData<'static, 'static>, GameStateEvent> for PauseMe
                 if Some(target) == self.resume_button {
                     log::info!("Resuming Pong!");
                     Trans::Pop
+                } else if Some(target) == self.save_button {
+                    let ball_spawn_timer = data.world.try_fetch::<BallSpawnTimer>().and_then(|timer| timer.0);
+                    let save_game = SaveGame::capture(data.world, ball_spawn_timer);
+                    log::info!("[Trans::Push] Opening Save Slots!");
+                    Trans::Push(Box::new(SaveSlotState::save(save_game)))
                 } else if Some(target) == self.exit_to_main_menu_button {
                     let mut state_transition_event_channel = data
                         .world
@@ -118,9 +148,14 @@ impl<'a, 'b> State<CustomGameData<'static, 'static>, GameStateEvent> for PauseMe
     ) -> Trans<CustomGameData<'static, 'static>, GameStateEvent> {
         data.data.update(data.world, true);
         // once deferred creation of the root ui entity finishes, look up buttons
-        if self.resume_button.is_none() || self.exit_to_main_menu_button.is_none() || self.exit_button.is_none() {
+        if self.resume_button.is_none()
+            || self.save_button.is_none()
+            || self.exit_to_main_menu_button.is_none()
+            || self.exit_button.is_none()
+        {
             data.world.exec(|ui_finder: UiFinder<'_>| {
                 self.resume_button = ui_finder.find(RESUME_BUTTON_ID);
+                self.save_button = ui_finder.find(SAVE_BUTTON_ID);
                 self.exit_to_main_menu_button = ui_finder.find(EXIT_TO_MAIN_MENU_BUTTON_ID);
                 self.exit_button = ui_finder.find(EXIT_BUTTON_ID);
             });
@@ -134,7 +169,11 @@ impl<'a, 'b> State<CustomGameData<'static, 'static>, GameStateEvent> for PauseMe
 mod tests {
     use super::*;
     use crate::audio::initialise_audio;
-    use crate::test_harness::{ConditionBarrierResult, SendMockEvents};
+    use crate::{
+        bindings::GameBindings,
+        systems::{MenuFocus, MenuFocusSystemDesc},
+        test_harness::{click, find_ui, wait_for_ui, SendMockEvents},
+    };
     use amethyst::{
         assets::ProgressCounter,
         core::shrev::EventChannel,
@@ -159,35 +198,18 @@ mod tests {
     }
 
     #[test]
-    fn click_exit_button() {
+    fn click_save_button() {
         amethyst::start_logger(amethyst::LoggerConfig::default());
         let test_result = crate::test_harness::IntegrationTestApplication::pong_base()
+            .with_resource(crate::ScoreBoard::new(2))
             .with_state(|| {
                 SendMockEvents::test_state(|_world| Box::new(PauseMenuState::default()))
-                    .with_condition_barrier(
-                        |world| {
-                            let mut exit_button: Option<Entity> = None;
-                            world.exec(|ui_finder: UiFinder<'_>| {
-                                exit_button = ui_finder.find(EXIT_BUTTON_ID);
-                            });
-                            if exit_button.is_some() {
-                                ConditionBarrierResult::ResumeImmediately
-                            } else {
-                                ConditionBarrierResult::ContinueEvaluating
-                            }
-                        },
-                        Duration::from_secs(20),
-                    )
+                    .with_condition_barrier(wait_for_ui(SAVE_BUTTON_ID), Duration::from_secs(20))
+                    .with_step(click(SAVE_BUTTON_ID))
+                    .with_wait(1.0)
                     .with_step(|world| {
-                        let mut exit_button: Option<Entity> = None;
-                        world.exec(|ui_finder: UiFinder<'_>| {
-                            exit_button = ui_finder.find(EXIT_BUTTON_ID);
-                        });
-                        let event = UiEvent::new(UiEventType::Click, exit_button.expect("Could not find exit button"));
-                        let mut events: Write<EventChannel<UiEvent>> = world.system_data();
-                        events.single_write(event);
+                        assert!(find_ui(world, "slot_1").is_some());
                     })
-                    .with_wait(1.0)
                     .end_test()
             })
             .run();
@@ -195,37 +217,70 @@ mod tests {
     }
 
     #[test]
-    fn click_exit_to_main_menu_button() {
+    fn navigate_save_slots_over_pause_menu() {
+        fn press(key_code: VirtualKeyCode) -> impl Fn(&mut World) + Send + Sync + 'static {
+            move |world| {
+                let mut events: Write<EventChannel<InputEvent<GameBindings>>> = world.system_data();
+                events.single_write(InputEvent::KeyPressed { key_code, scancode: 0 });
+            }
+        }
+        fn assert_focused(id: &'static str) -> impl Fn(&mut World) + Send + Sync + 'static {
+            move |world| {
+                let focused = world.read_resource::<MenuFocus>().focused;
+                assert_eq!(focused, find_ui(world, id), "{} is not focused", id);
+            }
+        }
+
         amethyst::start_logger(amethyst::LoggerConfig::default());
         let test_result = crate::test_harness::IntegrationTestApplication::pong_base()
+            .with_resource(crate::ScoreBoard::new(2))
+            .with_system_desc(MenuFocusSystemDesc::default(), "menu_focus_system", &["input_system"])
             .with_state(|| {
                 SendMockEvents::test_state(|_world| Box::new(PauseMenuState::default()))
-                    .with_condition_barrier(
-                        |world| {
-                            let mut exit_to_main_menu_button: Option<Entity> = None;
-                            world.exec(|ui_finder: UiFinder<'_>| {
-                                exit_to_main_menu_button = ui_finder.find(EXIT_TO_MAIN_MENU_BUTTON_ID);
-                            });
-                            if exit_to_main_menu_button.is_some() {
-                                ConditionBarrierResult::ResumeImmediately
-                            } else {
-                                ConditionBarrierResult::ContinueEvaluating
-                            }
-                        },
-                        Duration::from_secs(20),
-                    )
+                    .with_condition_barrier(wait_for_ui(SAVE_BUTTON_ID), Duration::from_secs(20))
+                    .with_step(click(SAVE_BUTTON_ID))
+                    .with_condition_barrier(wait_for_ui("slot_1"), Duration::from_secs(20))
+                    .with_step(|world| assert!(find_ui(world, RESUME_BUTTON_ID).is_none()))
+                    .with_step(press(VirtualKeyCode::Up))
+                    .with_wait(0.1)
+                    .with_step(assert_focused("save_slots_back"))
+                    // the four buttons of the picker and nothing else make up the cycle
                     .with_step(|world| {
-                        let mut exit_to_main_menu_button: Option<Entity> = None;
-                        world.exec(|ui_finder: UiFinder<'_>| {
-                            exit_to_main_menu_button = ui_finder.find(EXIT_TO_MAIN_MENU_BUTTON_ID);
-                        });
-                        let event = UiEvent::new(
-                            UiEventType::Click,
-                            exit_to_main_menu_button.expect("Could not find exit to main menu button"),
-                        );
-                        let mut events: Write<EventChannel<UiEvent>> = world.system_data();
-                        events.single_write(event);
+                        for _ in 0..5 {
+                            press(VirtualKeyCode::Tab)(world);
+                        }
                     })
+                    .with_wait(0.1)
+                    .with_step(assert_focused("slot_1"))
+                    .end_test()
+            })
+            .run();
+        assert!(test_result.is_ok());
+    }
+
+    #[test]
+    fn click_exit_button() {
+        amethyst::start_logger(amethyst::LoggerConfig::default());
+        let test_result = crate::test_harness::IntegrationTestApplication::pong_base()
+            .with_state(|| {
+                SendMockEvents::test_state(|_world| Box::new(PauseMenuState::default()))
+                    .with_condition_barrier(wait_for_ui(EXIT_BUTTON_ID), Duration::from_secs(20))
+                    .with_step(click(EXIT_BUTTON_ID))
+                    .with_wait(1.0)
+                    .end_test()
+            })
+            .run();
+        assert!(test_result.is_ok());
+    }
+
+    #[test]
+    fn click_exit_to_main_menu_button() {
+        amethyst::start_logger(amethyst::LoggerConfig::default());
+        let test_result = crate::test_harness::IntegrationTestApplication::pong_base()
+            .with_state(|| {
+                SendMockEvents::test_state(|_world| Box::new(PauseMenuState::default()))
+                    .with_condition_barrier(wait_for_ui(EXIT_TO_MAIN_MENU_BUTTON_ID), Duration::from_secs(20))
+                    .with_step(click(EXIT_TO_MAIN_MENU_BUTTON_ID))
                     .with_wait(1.0)
                     .end_test()
             })
@@ -239,29 +294,26 @@ mod tests {
         let test_result = crate::test_harness::IntegrationTestApplication::pong_base()
             .with_state(|| {
                 SendMockEvents::test_state(|_world| Box::new(PauseMenuState::default()))
-                    .with_condition_barrier(
-                        |world| {
-                            let mut resume_button: Option<Entity> = None;
-                            world.exec(|ui_finder: UiFinder<'_>| {
-                                resume_button = ui_finder.find(RESUME_BUTTON_ID);
-                            });
-                            if resume_button.is_some() {
-                                ConditionBarrierResult::ResumeImmediately
-                            } else {
-                                ConditionBarrierResult::ContinueEvaluating
-                            }
-                        },
-                        Duration::from_secs(20),
-                    )
+                    .with_condition_barrier(wait_for_ui(RESUME_BUTTON_ID), Duration::from_secs(20))
+                    .with_step(click(RESUME_BUTTON_ID))
+                    .with_wait(1.0)
+                    .end_test()
+            })
+            .run();
+        assert!(test_result.is_ok());
+    }
+
+    #[test]
+    fn pause_action_resumes() {
+        use crate::bindings::GameBindings;
+
+        amethyst::start_logger(amethyst::LoggerConfig::default());
+        let test_result = crate::test_harness::IntegrationTestApplication::pong_base()
+            .with_state(|| {
+                SendMockEvents::test_state(|_world| Box::new(PauseMenuState::default()))
                     .with_step(|world| {
-                        let mut resume_button: Option<Entity> = None;
-                        world.exec(|ui_finder: UiFinder<'_>| {
-                            resume_button = ui_finder.find(RESUME_BUTTON_ID);
-                        });
-                        let event =
-                            UiEvent::new(UiEventType::Click, resume_button.expect("Could not find resume button"));
-                        let mut events: Write<EventChannel<UiEvent>> = world.system_data();
-                        events.single_write(event);
+                        let mut events: Write<EventChannel<InputEvent<GameBindings>>> = world.system_data();
+                        events.single_write(InputEvent::ActionPressed(ActionBinding::Pause));
                     })
                     .with_wait(1.0)
                     .end_test()
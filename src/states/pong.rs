@@ -1,51 +1,218 @@
 use crate::game_data::CustomGameData;
 
 use crate::{
-    states::{delete_hierarchy, GameplayState, PauseMenuState},
-    systems::ScoreText,
-    Ball, GameStateEvent, Paddle, Side, ARENA_HEIGHT, ARENA_WIDTH,
+    audio::{initialise_audio, Sounds},
+    bindings::{ActionBinding, GameBindings},
+    config::{ArenaConfig, PongConfig},
+    level::Level,
+    network::{settings_hash, NetworkHost, Rollback, RollbackSession},
+    replay::{last_match_path, PaddleInputs, Replay, ReplayPlayback, ReplayRecorder},
+    rng::GameRng,
+    save::SaveGame,
+    states::{delete_hierarchy, GameOverState, GameplayState, MainMenu, PauseMenuState},
+    systems::{
+        interpolate_transforms, restore_simulated_transforms, steer_pointer_paddles, store_previous_transforms,
+        AiPaddle, Controllers, Effect, FixedTimestep, HotReloadEvent, Interpolated, Obstacle, PowerUp, PowerUpKind,
+        MatchOver, ScoreText, Serving, Stuck,
+    },
+    Ball, GameMode, GamePaths, GameStateEvent, MatchSettings, Paddle, PaddleController, ScoreBoard, Side, HUD_PREFAB,
 };
 use amethyst::{
     assets::{AssetStorage, Handle, Loader, ProgressCounter},
-    core::{timing::Time, transform::Transform, Parent},
+    core::{math::Vector3, timing::Time, transform::Transform, Parent},
     ecs::{
-        prelude::{Entity, World, WorldExt},
+        prelude::{Entity, Join, World, WorldExt},
         Dispatcher, DispatcherBuilder,
     },
-    input::{is_close_requested, is_key_down},
+    input::{is_close_requested, is_key_down, InputEvent, InputHandler},
     prelude::*,
-    renderer::{Camera, ImageFormat, SpriteRender, SpriteSheet, SpriteSheetFormat, Texture},
+    renderer::{
+        palette::Srgba, resources::Tint, Camera, ImageFormat, SpriteRender, SpriteSheet, SpriteSheetFormat, Texture,
+    },
     ui::{Anchor, TtfFormat, UiCreator, UiText, UiTransform},
     winit::VirtualKeyCode,
 };
+use std::{f32::consts::FRAC_PI_2, net::SocketAddr};
+
+/// Seconds until `Pong` spawns the ball, published while the match is paused so the pause menu can
+/// include it in a `SaveGame`.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct BallSpawnTimer(pub Option<f32>);
+
+/// Where the paddle inputs of a match come from.
+enum MatchInput {
+    /// Read from the `InputHandler` and recorded, to write a replay when the match is over.
+    Live(ReplayRecorder),
+    Playback(ReplayPlayback),
+    /// Read from the `InputHandler` for the local paddle and exchanged with the remote peer.
+    Rollback(RollbackSession<RollbackState>),
+}
+
+/// Everything a simulation step of a rollback match depends on, see `Pong::peer`. Power-ups are
+/// not part of it, so rollback matches are played without them.
+#[derive(Debug, Clone)]
+pub struct RollbackState {
+    /// component, position and serve of every ball
+    balls: Vec<(Ball, [f32; 2], Option<Serving>)>,
+    /// side, position, movement and CPU control of every paddle
+    paddles: Vec<(Side, [f32; 2], f32, Option<AiPaddle>)>,
+    /// position and velocity of every obstacle
+    obstacles: Vec<([f32; 2], [f32; 2])>,
+    score_board: ScoreBoard,
+    rng: GameRng,
+    ball_spawn_timer: Option<f32>,
+    extra_ball_timer: Option<f32>,
+}
+
+/// A `Pong` match running in `world`, rewound by its `RollbackSession`.
+struct PongSimulation<'s, 'a, 'b> {
+    pong: &'s mut Pong<'a, 'b>,
+    world: &'s mut World,
+}
 
 #[derive(Default)]
 pub struct Pong<'a, 'b> {
     ball_spawn_timer: Option<f32>,
+    /// Seconds until the next extra ball joins a multi-ball match.
+    extra_ball_timer: Option<f32>,
+    /// Seconds until the next power-up appears, if they are enabled.
+    power_up_timer: Option<f32>,
     sprite_sheet_handle: Option<Handle<SpriteSheet>>,
     dispatcher: Option<Dispatcher<'a, 'b>>,
     root_entity: Option<Entity>,
     ui_root: Option<Entity>,
     load_progress: Option<ProgressCounter>,
+    /// Match to continue instead of starting a new one.
+    save_game: Option<SaveGame>,
+    /// Recorded match to play back instead of a live one.
+    replay: Option<Replay>,
+    /// `None` once the match is decided, which stops the simulation.
+    match_input: Option<MatchInput>,
+    /// Configured arena dimensions, while the level replaces them.
+    previous_arena: Option<ArenaConfig>,
+    /// Clients playing along over the network, if the match is hosted.
+    network_host: Option<NetworkHost>,
+    /// Connection to the other peer of a rollback match, until the match starts.
+    rollback_session: Option<RollbackSession<RollbackState>>,
+    /// Connection to the other peer of a decided rollback match, which may still wait for the final
+    /// inputs.
+    finished_session: Option<RollbackSession<RollbackState>>,
+    /// Simulates one step per update instead of following the clock, see `HeadlessState`.
+    fixed_tick: bool,
 }
 
+/// Address a hosted match listens on for clients, see `Pong::host`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HostAddress(pub SocketAddr);
+
 impl<'a, 'b> State<CustomGameData<'static, 'static>, GameStateEvent> for Pong<'a, 'b> {
     fn on_start(&mut self, data: StateData<'_, CustomGameData<'_, '_>>) {
         *data.world.write_resource::<GameplayState>() = GameplayState::Running;
 
+        // Matches started from the command line skip the welcome screen, which loads the sounds.
+        if !data.world.has_value::<Sounds>() {
+            let mut progress = ProgressCounter::default();
+            initialise_audio(data.world, &mut progress);
+        }
+
         self.initialize_gameplay_dispatcher(data.world);
+        data.world.register::<Interpolated>();
+        data.world.register::<Serving>();
+        data.world.register::<Tint>();
+        // Registered by the renderer, unless the game runs headless.
+        data.world.register::<SpriteRender>();
+        data.world.register::<Camera>();
+        let timestep = FixedTimestep::new(&data.world.read_resource::<PongConfig>().simulation);
+        data.world.insert(timestep);
+
+        let replay = self.replay.take();
+        let save_game = match &replay {
+            Some(replay) => replay.start.clone(),
+            None => self.save_game.take(),
+        };
+
+        // A saved match continues in the level and mode it was played in. The level may resize the
+        // arena and the mode decides the paddles, which has to happen before the config and the
+        // settings are recorded.
+        if let Some(save_game) = &save_game {
+            data.world.insert(save_game.level.clone());
+            data.world.write_resource::<MatchSettings>().mode = save_game.mode;
+        }
+        let level = data.world.entry::<Level>().or_insert_with(Level::default).clone();
+        if let Some(arena) = level.arena {
+            let mut config = data.world.write_resource::<PongConfig>();
+            self.previous_arena = Some(std::mem::replace(&mut config.arena, arena));
+        }
+
+        // Seed the RNG, so the match can be replayed from its inputs. Both peers of a rollback
+        // match draw from the same seed, once they agreed on it.
+        let rollback_session = self.rollback_session.take();
+        let rng = match (&replay, rollback_session.as_ref().and_then(RollbackSession::seed)) {
+            (Some(replay), _) => GameRng::from_seed(replay.seed),
+            (None, Some(seed)) => GameRng::from_seed(seed),
+            (None, None) => GameRng::default(),
+        };
+        let rollback = rollback_session.is_some();
+        self.match_input = Some(match (replay, rollback_session) {
+            (Some(replay), _) => MatchInput::Playback(ReplayPlayback::new(replay)),
+            (None, Some(session)) => MatchInput::Rollback(session.with_settings(rollback_settings(data.world))),
+            (None, None) => MatchInput::Live(ReplayRecorder::new(
+                rng.seed(),
+                data.world.read_resource::<PongConfig>().clone(),
+                *data.world.read_resource::<MatchSettings>(),
+                level.clone(),
+                save_game.clone(),
+            )),
+        });
+        data.world.insert(rng);
+        data.world.insert(PaddleInputs::default());
+        // controllers lost before the match started are no reason to pause it
+        if let Some(mut controllers) = data.world.try_fetch_mut::<Controllers>() {
+            controllers.take_disconnected();
+        }
+
+        if let Some(host) = self.network_host.as_mut() {
+            host.open(
+                &data.world.read_resource::<PongConfig>(),
+                &data.world.read_resource::<MatchSettings>(),
+                &level,
+            );
+            match host.local_address() {
+                Ok(address) => {
+                    log::info!("Hosting the match on {}", address);
+                    data.world.insert(HostAddress(address));
+                }
+                Err(e) => log::error!("Failed to look up the address of the hosted match: {}", e),
+            }
+        }
+
+        // Every match starts from zero, unless it continues a saved one.
+        let players = data.world.read_resource::<MatchSettings>().mode.sides().len();
+        data.world.insert(match &save_game {
+            Some(save_game) => ScoreBoard::from_scores(save_game.scores.clone()),
+            None => ScoreBoard::new(players),
+        });
 
         // Wait one second before spawning the ball.
-        self.ball_spawn_timer.replace(1.0);
+        self.ball_spawn_timer = match &save_game {
+            Some(save_game) => save_game.ball_spawn_timer,
+            None => Some(1.0),
+        };
+        self.extra_ball_timer = match data.world.read_resource::<MatchSettings>().mode {
+            GameMode::Classic | GameMode::FourPlayer => None,
+            GameMode::MultiBall => Some(data.world.read_resource::<PongConfig>().multi_ball.spawn_interval),
+        };
+        self.power_up_timer = if data.world.read_resource::<MatchSettings>().power_ups && !rollback {
+            Some(data.world.read_resource::<PongConfig>().power_ups.spawn_interval)
+        } else {
+            None
+        };
 
         let mut progress = ProgressCounter::default();
 
         // Initialize roots
         self.root_entity = Some(data.world.create_entity().with(Transform::default()).build());
-        self.ui_root = Some(
-            data.world
-                .exec(|mut creator: UiCreator<'_>| creator.create("ui/hud.ron", &mut progress)),
-        );
+        self.create_hud(data.world, &mut progress);
 
         // Load the spritesheet necessary to render the graphics.
         // `spritesheet` is the layout of the sprites on the image;
@@ -53,8 +220,12 @@ impl<'a, 'b> State<CustomGameData<'static, 'static>, GameStateEvent> for Pong<'a
         self.sprite_sheet_handle.replace(load_sprite_sheet(data.world));
         if let Some(root_entity) = self.root_entity {
             if let Some(sprite_sheet_handle) = self.sprite_sheet_handle.clone() {
-                initialise_paddles(data.world, root_entity, sprite_sheet_handle);
+                initialise_paddles(data.world, root_entity, sprite_sheet_handle.clone());
+                initialise_obstacles(data.world, root_entity, sprite_sheet_handle.clone(), &level);
                 initialise_camera(data.world, root_entity);
+                if let Some(save_game) = &save_game {
+                    restore_save_game(data.world, root_entity, sprite_sheet_handle, save_game);
+                }
             }
         }
         if let Some(ui_root) = self.ui_root {
@@ -79,11 +250,21 @@ impl<'a, 'b> State<CustomGameData<'static, 'static>, GameStateEvent> for Pong<'a
 
         self.sprite_sheet_handle = None;
         self.ball_spawn_timer = None;
+        self.extra_ball_timer = None;
+        self.power_up_timer = None;
         self.load_progress = None;
+        self.match_input = None;
+        self.finished_session = None;
+        // Tell the clients the match is over.
+        self.network_host = None;
+        if let Some(arena) = self.previous_arena.take() {
+            data.world.write_resource::<PongConfig>().arena = arena;
+        }
     }
 
     fn on_pause(&mut self, data: StateData<'_, CustomGameData<'_, '_>>) {
         *data.world.write_resource::<GameplayState>() = GameplayState::Paused;
+        data.world.insert(BallSpawnTimer(self.ball_spawn_timer));
     }
 
     fn on_resume(&mut self, data: StateData<'_, CustomGameData<'_, '_>>) {
@@ -92,7 +273,7 @@ impl<'a, 'b> State<CustomGameData<'static, 'static>, GameStateEvent> for Pong<'a
 
     fn handle_event(
         &mut self,
-        _: StateData<'_, CustomGameData<'_, '_>>,
+        data: StateData<'_, CustomGameData<'_, '_>>,
         event: GameStateEvent,
     ) -> Trans<CustomGameData<'static, 'static>, GameStateEvent> {
         match event {
@@ -107,6 +288,25 @@ impl<'a, 'b> State<CustomGameData<'static, 'static>, GameStateEvent> for Pong<'a
                     Trans::None
                 }
             }
+            GameStateEvent::Input(InputEvent::ActionPressed(ActionBinding::Pause)) => {
+                log::info!("[Trans::Push] Pausing Game!");
+                Trans::Push(Box::new(PauseMenuState::default()))
+            }
+            // Rollback matches announce the winner themselves, since a predicted step may decide a
+            // match a late input takes back.
+            GameStateEvent::MatchOver(_) if self.plays_peer() => Trans::None,
+            GameStateEvent::MatchOver(match_over) => {
+                log::info!("[Trans::Push] Match is over!");
+                Trans::Push(Box::new(GameOverState::new(match_over)))
+            }
+            GameStateEvent::HotReload(HotReloadEvent::UiPrefab(prefab)) if prefab == HUD_PREFAB => {
+                if let Some(entity) = self.ui_root.take() {
+                    delete_hierarchy(entity, data.world).expect("Failed to remove Pong HUD entities");
+                }
+                let mut progress = ProgressCounter::default();
+                self.create_hud(data.world, &mut progress);
+                Trans::None
+            }
             GameStateEvent::Test(test_event) => crate::test_harness::handle_test_event(&test_event),
             _ => Trans::None,
         }
@@ -117,49 +317,335 @@ impl<'a, 'b> State<CustomGameData<'static, 'static>, GameStateEvent> for Pong<'a
         data: StateData<'_, CustomGameData<'_, '_>>,
     ) -> Trans<CustomGameData<'static, 'static>, GameStateEvent> {
         data.data.update(data.world, true);
-        if let Some(dispatcher) = self.dispatcher.as_mut() {
-            dispatcher.dispatch(data.world);
-        }
 
         if *data.world.read_resource::<GameplayState>() == GameplayState::Paused {
             return Trans::None;
         }
 
-        if let Some(mut timer) = self.ball_spawn_timer.take() {
-            // If the timer isn't expired yet, substract the time that passed since last update.
-            {
-                let time = data.world.fetch::<Time>();
-                timer -= time.delta_seconds();
+        if self.lost_controller(data.world) {
+            log::info!("[Trans::Push] Pausing Game, a controller disconnected!");
+            return Trans::Push(Box::new(PauseMenuState::default()));
+        }
+
+        if let Some(host) = self.network_host.as_mut() {
+            host.poll();
+            if host.waiting_for_players() {
+                // Hold the match until a client joined on every open side.
+                host.send_snapshot(data.world);
+                return Trans::None;
             }
-            if timer <= 0.0 {
-                if let Some(root_entity) = self.root_entity {
-                    // When timer expire, spawn the ball
-                    let sprite_sheet_clone = self.sprite_sheet_handle.clone();
-                    if let Some(sprite_sheet) = sprite_sheet_clone {
-                        use crate::{BALL_RADIUS, BALL_VELOCITY_X, BALL_VELOCITY_Y};
-                        initialise_ball(
-                            data.world,
-                            root_entity,
-                            sprite_sheet,
-                            BALL_RADIUS,
-                            [BALL_VELOCITY_X, BALL_VELOCITY_Y],
-                            None,
-                        );
-                        #[cfg(test)]
-                        return Trans::Quit;
+        }
+
+        // Simulate the time that passed since the last update in fixed steps.
+        let steps = if self.fixed_tick {
+            1
+        } else {
+            let delta_seconds = data.world.fetch::<Time>().delta_seconds();
+            data.world.write_resource::<FixedTimestep>().advance(delta_seconds)
+        };
+        restore_simulated_transforms(data.world);
+        let mut trans = Trans::None;
+        match self.match_input.take() {
+            Some(MatchInput::Rollback(session)) if session.settings_mismatch() => {
+                log::info!("[Trans::Switch] The peers play with other settings, switching to MainMenu!");
+                return Trans::Switch(Box::new(MainMenu::default()));
+            }
+            Some(MatchInput::Rollback(session)) => {
+                if let Some(match_over) = self.update_rollback(data.world, session, steps) {
+                    log::info!("[Trans::Push] Match is over!");
+                    trans = Trans::Push(Box::new(GameOverState::new(match_over)));
+                }
+            }
+            match_input => {
+                self.match_input = match_input;
+                for _ in 0..steps {
+                    if !self.feed_paddle_inputs(data.world) {
+                        break;
+                    }
+                    self.simulate_step(data.world);
+                    if match_decided(data.world) {
+                        self.finish_match(data.world);
+                        break;
                     }
                 }
+            }
+        }
+        if let Some(host) = self.network_host.as_mut() {
+            host.send_snapshot(data.world);
+        }
+        let alpha = data.world.read_resource::<FixedTimestep>().alpha();
+        interpolate_transforms(data.world, alpha);
+
+        trans
+    }
+
+    fn shadow_update(&mut self, data: StateData<'_, CustomGameData<'static, 'static>>) {
+        // Keep answering the remote peer of a decided rollback match until it decided the match too.
+        if let Some(mut session) = self.finished_session.take() {
+            let mut simulation = PongSimulation { pong: self, world: data.world };
+            session.poll(&mut simulation);
+            session.send_inputs();
+            self.finished_session = Some(session);
+        }
+    }
+}
+
+impl<'a, 'b> Pong<'a, 'b> {
+    /// Plays back a recorded match. The world has to hold the recorded `PongConfig` and
+    /// `MatchSettings`, see `ReplayState`.
+    pub fn from_replay(replay: Replay) -> Self {
+        Self {
+            replay: Some(replay),
+            ..Self::default()
+        }
+    }
+
+    /// Hosts the match for the clients of `host`, which play the sides the host leaves to them.
+    pub fn host(host: NetworkHost) -> Self {
+        Self {
+            network_host: Some(host),
+            ..Self::default()
+        }
+    }
+
+    /// Plays a two-player match against the remote peer of `session`, see `RollbackSession`.
+    pub fn peer(session: RollbackSession<RollbackState>) -> Self {
+        Self {
+            rollback_session: Some(session),
+            ..Self::default()
+        }
+    }
+
+    /// Plays a match one simulation step per update, however long the updates take.
+    pub fn fixed_tick() -> Self {
+        Self {
+            fixed_tick: true,
+            ..Self::default()
+        }
+    }
+
+    /// Continues the match stored in `save_game`.
+    pub fn from_save(save_game: SaveGame) -> Self {
+        Self {
+            save_game: Some(save_game),
+            ..Self::default()
+        }
+    }
+
+    /// Whether the match is played against the remote peer of a `RollbackSession`.
+    fn plays_peer(&self) -> bool {
+        match self.match_input {
+            Some(MatchInput::Rollback(_)) => true,
+            _ => self.rollback_session.is_some() || self.finished_session.is_some(),
+        }
+    }
+
+    /// Whether a player of a human paddle lost their controller since the last update. Only local
+    /// matches wait for the player: network matches go on, and replays need no controllers.
+    fn lost_controller(&self, world: &World) -> bool {
+        let disconnected = match world.try_fetch_mut::<Controllers>() {
+            Some(mut controllers) => controllers.take_disconnected(),
+            None => return false,
+        };
+        let local = match self.match_input {
+            Some(MatchInput::Live(_)) => self.network_host.is_none(),
+            _ => false,
+        };
+        let match_settings = world.read_resource::<MatchSettings>();
+        let human = |side: &Side| match_settings.controller(*side) == PaddleController::Human;
+        local
+            && disconnected
+                .iter()
+                .any(|side| match_settings.mode.sides().contains(side) && human(side))
+    }
+
+    /// Puts the paddle inputs of the next simulation step into the world, recording them during a
+    /// live match. Returns `false` if there is nothing left to simulate.
+    fn feed_paddle_inputs(&mut self, world: &mut World) -> bool {
+        let inputs = match self.match_input.as_mut() {
+            Some(MatchInput::Live(recorder)) => {
+                let mut inputs = PaddleInputs::from_input(&world.read_resource::<InputHandler<GameBindings>>());
+                steer_pointer_paddles(world, &mut inputs);
+                if let Some(host) = &self.network_host {
+                    host.apply_inputs(&mut inputs);
+                }
+                recorder.record(inputs);
+                inputs
+            }
+            Some(MatchInput::Playback(playback)) => match playback.next_inputs() {
+                Some(inputs) => inputs,
+                None => return false,
+            },
+            Some(MatchInput::Rollback(_)) | None => return false,
+        };
+        world.insert(inputs);
+        true
+    }
+
+    /// Runs the gameplay systems for one simulation step with the `PaddleInputs` in the world.
+    fn simulate_step(&mut self, world: &mut World) {
+        world.write_resource::<FixedTimestep>().count_step();
+        store_previous_transforms(world);
+        if let Some(dispatcher) = self.dispatcher.as_mut() {
+            dispatcher.dispatch(world);
+        }
+        // Remove balls deleted during this step, before the next step joins over them.
+        world.maintain();
+        self.tick_ball_spawn_timer(world);
+        self.tick_extra_ball_timer(world);
+        self.tick_power_up_timer(world);
+    }
+
+    /// Simulates the steps of this frame in a rollback match, after rewinding the steps the remote
+    /// peer sent other inputs for than predicted. Steps are skipped while the local peer is too far
+    /// ahead of the remote one. Returns the outcome once the match is decided.
+    fn update_rollback(
+        &mut self,
+        world: &mut World,
+        mut session: RollbackSession<RollbackState>,
+        steps: u32,
+    ) -> Option<MatchOver> {
+        if session.frame() == 0 {
+            // The right peer learns the seed from the left one.
+            if let Some(seed) = session.seed() {
+                world.insert(GameRng::from_seed(seed));
+            }
+        }
+        let mut inputs = PaddleInputs::from_input(&world.read_resource::<InputHandler<GameBindings>>());
+        steer_pointer_paddles(world, &mut inputs);
+        let local = inputs.axis_value(session.local_side());
+
+        let mut simulation = PongSimulation { pong: self, world };
+        session.poll(&mut simulation);
+        for _ in 0..steps {
+            if !session.can_advance() || match_decided(simulation.world) {
+                break;
+            }
+            session.advance(&mut simulation, local);
+        }
+        session.send_inputs();
+
+        // A predicted step may decide the match before the remote inputs arrive, so the match is only
+        // over once they confirmed the deciding step, and the remote peer received every local input
+        // to decide it as well.
+        if match_decided(world) && session.is_confirmed() && session.is_acknowledged() {
+            self.finished_session = Some(session);
+            let config = world.read_resource::<PongConfig>();
+            let score_board = world.read_resource::<ScoreBoard>();
+            return score_board.winner(&config.rules).map(|winner| MatchOver {
+                winner,
+                scores: score_board.scores().to_vec(),
+            });
+        }
+        self.match_input = Some(MatchInput::Rollback(session));
+        None
+    }
+
+    /// Stops the simulation of a decided match and writes the replay of a live one.
+    fn finish_match(&mut self, world: &World) {
+        if let Some(MatchInput::Live(recorder)) = self.match_input.take() {
+            let replay = recorder.finish(&world.read_resource::<ScoreBoard>());
+            // the paths are only known when running the real game
+            if let Some(paths) = world.try_fetch::<GamePaths>() {
+                let path = last_match_path(&paths.replays_dir);
+                match replay.store(&path) {
+                    Ok(()) => log::info!("Saved replay to {:?}", path),
+                    Err(e) => log::error!("Failed to save replay to {:?}: {}", path, e),
+                }
+            }
+        }
+    }
+
+    /// Counts the ball spawn timer down by one simulation step and spawns the ball once it expires.
+    fn tick_ball_spawn_timer(&mut self, world: &mut World) {
+        if let Some(mut timer) = self.ball_spawn_timer.take() {
+            timer -= world.read_resource::<FixedTimestep>().step();
+            if timer <= 0.0 {
+                // When timer expire, spawn the ball and serve it to a random side right away.
+                self.spawn_serving_ball(world, 0.0);
             } else {
                 // If timer is not expired yet, put it back onto the state.
                 self.ball_spawn_timer.replace(timer);
             }
         }
+    }
+
+    /// Counts the extra ball timer of a multi-ball match down by one simulation step, starting
+    /// once the first ball spawned. Once it expires, another ball joins the match unless
+    /// `max_balls` are in play already.
+    fn tick_extra_ball_timer(&mut self, world: &mut World) {
+        if self.ball_spawn_timer.is_some() {
+            return;
+        }
+        if let Some(mut timer) = self.extra_ball_timer.take() {
+            timer -= world.read_resource::<FixedTimestep>().step();
+            if timer <= 0.0 {
+                let (multi_ball, countdown) = {
+                    let config = world.read_resource::<PongConfig>();
+                    (config.multi_ball, config.serve.countdown)
+                };
+                timer += multi_ball.spawn_interval;
+                if (&world.read_storage::<Ball>()).join().count() < multi_ball.max_balls {
+                    self.spawn_serving_ball(world, countdown);
+                }
+            }
+            self.extra_ball_timer.replace(timer);
+        }
+    }
 
-        Trans::None
+    /// Counts the power-up timer down by one simulation step. Once it expires, a random power-up
+    /// appears at a random place in the middle half of the arena, unless `max_pickups` lie there
+    /// already.
+    fn tick_power_up_timer(&mut self, world: &mut World) {
+        if let Some(mut timer) = self.power_up_timer.take() {
+            timer -= world.read_resource::<FixedTimestep>().step();
+            if timer <= 0.0 {
+                let (arena, power_ups) = {
+                    let config = world.read_resource::<PongConfig>();
+                    (config.arena, config.power_ups)
+                };
+                timer += power_ups.spawn_interval;
+                let pickups = (&world.read_storage::<PowerUp>()).join().count();
+                if let (Some(root_entity), Some(sprite_sheet)) = (self.root_entity, self.sprite_sheet_handle.clone()) {
+                    if pickups < power_ups.max_pickups {
+                        let (kind, position) = {
+                            let mut rng = world.write_resource::<GameRng>();
+                            let kind = PowerUpKind::ALL[rng.gen_index(PowerUpKind::ALL.len())];
+                            let position = [
+                                rng.gen_range(arena.width * 0.25, arena.width * 0.75),
+                                rng.gen_range(power_ups.radius, arena.height - power_ups.radius),
+                            ];
+                            (kind, position)
+                        };
+                        initialise_power_up(world, root_entity, sprite_sheet, kind, position);
+                    }
+                }
+            }
+            self.power_up_timer.replace(timer);
+        }
+    }
+
+    /// Spawns a ball in the centre of the arena that is served to a random side after `countdown`
+    /// seconds.
+    fn spawn_serving_ball(&self, world: &mut World, countdown: f32) {
+        if let (Some(root_entity), Some(sprite_sheet)) = (self.root_entity, self.sprite_sheet_handle.clone()) {
+            let radius = world.read_resource::<PongConfig>().ball.radius;
+            let ball = initialise_ball(world, root_entity, sprite_sheet, radius, [0.0, 0.0], None);
+            world
+                .write_storage::<Serving>()
+                .insert(ball, Serving::new(countdown, None))
+                .expect("ball was just created");
+        }
+    }
+
+    /// Creates the HUD from its prefab and fills in the current scores.
+    fn create_hud(&mut self, world: &mut World, progress: &mut ProgressCounter) {
+        let ui_root = world.exec(|mut creator: UiCreator<'_>| creator.create(HUD_PREFAB, progress));
+        initialise_score(world, ui_root);
+        self.ui_root = Some(ui_root);
+        show_scores(world);
     }
-}
 
-impl<'a, 'b> Pong<'a, 'b> {
     fn initialize_gameplay_dispatcher(&mut self, world: &mut World) {
         if self.dispatcher.is_none() {
             use crate::systems::PongBundle;
@@ -178,6 +664,136 @@ impl<'a, 'b> Pong<'a, 'b> {
     }
 }
 
+/// Whether the `ScoreBoard` shows a winner under the rules of the `PongConfig`.
+fn match_decided(world: &World) -> bool {
+    let config = world.read_resource::<PongConfig>();
+    world.read_resource::<ScoreBoard>().winner(&config.rules).is_some()
+}
+
+/// Identifies the `PongConfig`, `MatchSettings` and `Level` of a rollback match, which both peers
+/// have to agree on.
+fn rollback_settings(world: &World) -> u64 {
+    settings_hash(&(
+        &*world.read_resource::<PongConfig>(),
+        *world.read_resource::<MatchSettings>(),
+        &*world.read_resource::<Level>(),
+    ))
+}
+
+impl<'s, 'a, 'b> Rollback for PongSimulation<'s, 'a, 'b> {
+    type State = RollbackState;
+
+    fn save_state(&mut self) -> RollbackState {
+        let balls = self.world.read_storage::<Ball>();
+        let paddles = self.world.read_storage::<Paddle>();
+        let obstacles = self.world.read_storage::<Obstacle>();
+        let servings = self.world.read_storage::<Serving>();
+        let ai_paddles = self.world.read_storage::<AiPaddle>();
+        let transforms = self.world.read_storage::<Transform>();
+        let position = |transform: &Transform| [transform.translation().x, transform.translation().y];
+
+        RollbackState {
+            balls: (&balls, &transforms, (&servings).maybe())
+                .join()
+                .map(|(ball, transform, serving)| (ball.clone(), position(transform), serving.copied()))
+                .collect(),
+            paddles: (&paddles, &transforms, (&ai_paddles).maybe())
+                .join()
+                .map(|(paddle, transform, ai)| (paddle.side, position(transform), paddle.movement, ai.cloned()))
+                .collect(),
+            obstacles: (&obstacles, &transforms)
+                .join()
+                .map(|(obstacle, transform)| (position(transform), obstacle.velocity))
+                .collect(),
+            score_board: self.world.read_resource::<ScoreBoard>().clone(),
+            rng: self.world.read_resource::<GameRng>().clone(),
+            ball_spawn_timer: self.pong.ball_spawn_timer,
+            extra_ball_timer: self.pong.extra_ball_timer,
+        }
+    }
+
+    fn load_state(&mut self, state: &RollbackState) {
+        let world = &mut *self.world;
+        // Balls keep their entities, unless some scored or spawned since. Recreating them all
+        // could change the order the systems handle them in.
+        let ball_entities = (&world.entities(), &world.read_storage::<Ball>())
+            .join()
+            .map(|(entity, _)| entity)
+            .collect::<Vec<_>>();
+        let ball_entities = if ball_entities.len() == state.balls.len() {
+            ball_entities
+        } else {
+            world.delete_entities(&ball_entities).expect("Failed to remove balls");
+            match (self.pong.root_entity, self.pong.sprite_sheet_handle.clone()) {
+                (Some(root_entity), Some(sprite_sheet)) => state
+                    .balls
+                    .iter()
+                    .map(|(ball, position, _)| {
+                        let sprite_sheet = sprite_sheet.clone();
+                        initialise_ball(world, root_entity, sprite_sheet, ball.radius, ball.velocity, Some(*position))
+                    })
+                    .collect(),
+                _ => Vec::new(),
+            }
+        };
+        {
+            let mut balls = world.write_storage::<Ball>();
+            let mut servings = world.write_storage::<Serving>();
+            let mut transforms = world.write_storage::<Transform>();
+            let mut interpolated = world.write_storage::<Interpolated>();
+            for (entity, (ball, position, serving)) in ball_entities.iter().zip(&state.balls) {
+                balls.insert(*entity, ball.clone()).expect("ball entity is alive");
+                if let Some(serving) = serving {
+                    servings.insert(*entity, *serving).expect("ball entity is alive");
+                } else {
+                    servings.remove(*entity);
+                }
+                if let Some(transform) = transforms.get_mut(*entity) {
+                    transform.set_translation_x(position[0]);
+                    transform.set_translation_y(position[1]);
+                }
+                if let Some(interpolated) = interpolated.get_mut(*entity) {
+                    interpolated.teleport(*position);
+                }
+            }
+        }
+        {
+            let entities = world.entities();
+            let mut paddles = world.write_storage::<Paddle>();
+            let mut ai_paddles = world.write_storage::<AiPaddle>();
+            let mut transforms = world.write_storage::<Transform>();
+            for (entity, paddle, transform) in (&entities, &mut paddles, &mut transforms).join() {
+                if let Some((_, position, movement, ai)) = state.paddles.iter().find(|saved| saved.0 == paddle.side) {
+                    transform.set_translation_x(position[0]);
+                    transform.set_translation_y(position[1]);
+                    paddle.movement = *movement;
+                    if let Some(ai) = ai {
+                        ai_paddles.insert(entity, ai.clone()).expect("paddle entity is alive");
+                    }
+                }
+            }
+            let mut obstacles = world.write_storage::<Obstacle>();
+            for ((obstacle, transform), (position, velocity)) in
+                (&mut obstacles, &mut transforms).join().zip(&state.obstacles)
+            {
+                transform.set_translation_x(position[0]);
+                transform.set_translation_y(position[1]);
+                obstacle.velocity = *velocity;
+            }
+        }
+        world.insert(state.score_board.clone());
+        world.insert(state.rng.clone());
+        show_scores(world);
+        self.pong.ball_spawn_timer = state.ball_spawn_timer;
+        self.pong.extra_ball_timer = state.extra_ball_timer;
+    }
+
+    fn advance_frame(&mut self, inputs: PaddleInputs) {
+        self.world.insert(inputs);
+        self.pong.simulate_step(self.world);
+    }
+}
+
 pub fn load_sprite_sheet(world: &mut World) -> Handle<SpriteSheet> {
     // Load the sprite sheet necessary to render the graphics.
     // The texture is the pixel data
@@ -205,31 +821,95 @@ pub fn load_sprite_sheet(world: &mut World) -> Handle<SpriteSheet> {
     )
 }
 
+/// Moves the paddles to their saved positions and spawns the saved balls, power-ups and effects.
+fn restore_save_game(
+    world: &mut World,
+    parent: Entity,
+    sprite_sheet_handle: Handle<SpriteSheet>,
+    save_game: &SaveGame,
+) {
+    {
+        let paddles = world.read_storage::<Paddle>();
+        let mut transforms = world.write_storage::<Transform>();
+        let mut interpolated = world.write_storage::<Interpolated>();
+        for (paddle, transform, interpolated) in (&paddles, &mut transforms, &mut interpolated).join() {
+            if let Some(snapshot) = save_game.paddles.iter().find(|snapshot| snapshot.side == paddle.side) {
+                transform.set_translation_x(snapshot.position[0]);
+                transform.set_translation_y(snapshot.position[1]);
+                interpolated.teleport(snapshot.position);
+            }
+        }
+    }
+
+    let mut balls = Vec::new();
+    for ball in &save_game.balls {
+        let entity = initialise_ball(
+            world,
+            parent,
+            sprite_sheet_handle.clone(),
+            ball.radius,
+            ball.velocity,
+            Some(ball.position),
+        );
+        if let Some(serving) = ball.serving {
+            world
+                .write_storage::<Serving>()
+                .insert(entity, serving)
+                .expect("ball was just created");
+        }
+        if let Some(stuck) = ball.stuck {
+            world
+                .write_storage::<Stuck>()
+                .insert(entity, stuck)
+                .expect("ball was just created");
+        }
+        if let Some(ball_component) = world.write_storage::<Ball>().get_mut(entity) {
+            ball_component.last_hit = ball.last_hit;
+        }
+        balls.push(entity);
+    }
+
+    for power_up in &save_game.power_ups {
+        initialise_power_up(world, parent, sprite_sheet_handle.clone(), power_up.kind, power_up.position);
+    }
+    // The paddles are sized by the `PowerUpSystem` according to the restored effects.
+    for effect in &save_game.effects {
+        world
+            .create_entity()
+            .with(Effect {
+                kind: effect.kind,
+                side: effect.side,
+                ball: effect.ball.and_then(|index| balls.get(index).copied()),
+                remaining: effect.remaining,
+            })
+            .with(Parent { entity: parent })
+            .build();
+    }
+}
+
 /// Initialise the camera.
 pub fn initialise_camera(world: &mut World, parent: Entity) {
     // Setup camera in a way that our screen covers whole arena and (0, 0) is in the bottom left.
+    let arena = world.read_resource::<PongConfig>().arena;
     let mut transform = Transform::default();
-    transform.set_translation_xyz(ARENA_WIDTH * 0.5, ARENA_HEIGHT * 0.5, 1.0);
+    transform.set_translation_xyz(arena.width * 0.5, arena.height * 0.5, 1.0);
 
     world
         .create_entity()
-        .with(Camera::standard_2d(ARENA_WIDTH, ARENA_HEIGHT))
+        .with(Camera::standard_2d(arena.width, arena.height))
         .with(Parent { entity: parent })
         .with(transform)
         .build();
 }
 
-/// Initialises one paddle on the left, and one paddle on the right.
+/// Initialises one paddle on every side guarded in the `GameMode` of the `MatchSettings`. Paddles
+/// assigned to the CPU get an `AiPaddle` component.
 pub fn initialise_paddles(world: &mut World, parent: Entity, sprite_sheet_handle: Handle<SpriteSheet>) {
-    use crate::{PADDLE_HEIGHT, PADDLE_VELOCITY, PADDLE_WIDTH};
-
-    let mut left_transform = Transform::default();
-    let mut right_transform = Transform::default();
-
-    // Correctly position the paddles.
-    let y = ARENA_HEIGHT / 2.0;
-    left_transform.set_translation_xyz(PADDLE_WIDTH * 0.5, y, 0.0);
-    right_transform.set_translation_xyz(ARENA_WIDTH - PADDLE_WIDTH * 0.5, y, 0.0);
+    let (arena, paddle) = {
+        let config = world.read_resource::<PongConfig>();
+        (config.arena, config.paddle)
+    };
+    let match_settings = *world.read_resource::<MatchSettings>();
 
     // Assign the sprites for the paddles
     let sprite_render = SpriteRender {
@@ -237,36 +917,77 @@ pub fn initialise_paddles(world: &mut World, parent: Entity, sprite_sheet_handle
         sprite_number: 0, // paddle is the first sprite in the sprite_sheet
     };
 
-    // Create a left plank entity.
-    world
-        .create_entity()
-        .with(sprite_render.clone())
-        .with(Paddle {
-            velocity: PADDLE_VELOCITY,
-            side: Side::Left,
-            width: PADDLE_WIDTH,
-            height: PADDLE_HEIGHT,
-        })
-        .with(left_transform)
-        .with(Parent { entity: parent })
-        .build();
+    for side in match_settings.mode.sides() {
+        // Correctly position the paddles, centred on their wall.
+        let (x, y) = match side {
+            Side::Left => (paddle.width * 0.5, arena.height / 2.0),
+            Side::Right => (arena.width - paddle.width * 0.5, arena.height / 2.0),
+            Side::Top => (arena.width / 2.0, arena.height - paddle.width * 0.5),
+            Side::Bottom => (arena.width / 2.0, paddle.width * 0.5),
+        };
+        let mut transform = Transform::default();
+        transform.set_translation_xyz(x, y, 0.0);
+        if side.axis() == 0 {
+            // the paddle sprite is upright, the top and bottom paddles lie on their side
+            transform.set_rotation_2d(FRAC_PI_2);
+        }
 
-    // Create right plank entity.
-    world
-        .create_entity()
-        .with(sprite_render)
-        .with(Paddle {
-            velocity: PADDLE_VELOCITY,
-            side: Side::Right,
-            width: PADDLE_WIDTH,
-            height: PADDLE_HEIGHT,
-        })
-        .with(right_transform)
-        .with(Parent { entity: parent })
-        .build();
+        let mut builder = world
+            .create_entity()
+            .with(sprite_render.clone())
+            .with(Paddle {
+                velocity: paddle.velocity,
+                side: *side,
+                width: paddle.width,
+                height: paddle.height,
+                movement: 0.0,
+            })
+            .with(Interpolated::new([x, y]))
+            .with(transform)
+            .with(Parent { entity: parent });
+        if let PaddleController::Cpu(difficulty) = match_settings.controller(*side) {
+            builder = builder.with(AiPaddle::new(difficulty));
+        }
+        builder.build();
+    }
+}
+
+/// Initialises the obstacles of `level`, including its goal posts, drawn as stretched paddles.
+pub fn initialise_obstacles(
+    world: &mut World,
+    parent: Entity,
+    sprite_sheet_handle: Handle<SpriteSheet>,
+    level: &Level,
+) {
+    let arena = world.read_resource::<PongConfig>().arena;
+    for layout in level.obstacles_with_goal_posts(&arena) {
+        let [x, y] = layout.position;
+        let mut transform = Transform::default();
+        transform.set_translation_xyz(x, y, 0.0);
+        // the paddle sprite is 4 units wide and 16 units high
+        transform.set_scale(Vector3::new(layout.size[0] / 4.0, layout.size[1] / 16.0, 1.0));
+
+        world
+            .create_entity()
+            .with(SpriteRender {
+                sprite_sheet: sprite_sheet_handle.clone(),
+                sprite_number: 0, // obstacles use the paddle sprite
+            })
+            .with(Obstacle {
+                width: layout.size[0],
+                height: layout.size[1],
+                velocity: layout.velocity,
+                min: [x - layout.travel[0], y - layout.travel[1]],
+                max: [x + layout.travel[0], y + layout.travel[1]],
+            })
+            .with(Interpolated::new(layout.position))
+            .with(transform)
+            .with(Parent { entity: parent })
+            .build();
+    }
 }
 
-/// Initialises one ball in the middle-ish of the arena.
+/// Initialises one ball in the middle-ish of the arena and returns its entity.
 pub fn initialise_ball(
     world: &mut World,
     parent: Entity,
@@ -274,10 +995,11 @@ pub fn initialise_ball(
     radius: f32,
     velocity: [f32; 2],
     position: Option<[f32; 2]>,
-) {
+) -> Entity {
     // Create the translation.
+    let arena = world.read_resource::<PongConfig>().arena;
     let mut local_transform = Transform::default();
-    let initial_position = position.unwrap_or_else(|| [ARENA_WIDTH / 2.0, ARENA_HEIGHT / 2.0]);
+    let initial_position = position.unwrap_or_else(|| [arena.width / 2.0, arena.height / 2.0]);
     local_transform.set_translation_xyz(initial_position[0], initial_position[1], 0.0);
 
     // Assign the sprite for the ball
@@ -289,37 +1011,79 @@ pub fn initialise_ball(
     world
         .create_entity()
         .with(sprite_render)
-        .with(Ball { radius, velocity })
+        .with(Ball {
+            radius,
+            velocity,
+            last_hit: None,
+        })
+        .with(Interpolated::new(initial_position))
         .with(local_transform)
         .with(Parent { entity: parent })
-        .build();
+        .build()
 }
 
+/// Initialises a power-up lying at `position`, drawn as a ball tinted in the colour of its kind.
+pub fn initialise_power_up(
+    world: &mut World,
+    parent: Entity,
+    sprite_sheet_handle: Handle<SpriteSheet>,
+    kind: PowerUpKind,
+    position: [f32; 2],
+) -> Entity {
+    let radius = world.read_resource::<PongConfig>().power_ups.radius;
+    let mut transform = Transform::default();
+    transform.set_translation_xyz(position[0], position[1], 0.0);
+    // the ball sprite is 4 units wide
+    transform.set_scale(Vector3::new(radius * 0.5, radius * 0.5, 1.0));
+
+    let [red, green, blue] = kind.color();
+    world
+        .create_entity()
+        .with(SpriteRender {
+            sprite_sheet: sprite_sheet_handle,
+            sprite_number: 1, // power-ups use the ball sprite
+        })
+        .with(Tint(Srgba::new(red, green, blue, 1.0)))
+        .with(PowerUp { kind, radius })
+        .with(transform)
+        .with(Parent { entity: parent })
+        .build()
+}
+
+/// Initialises one score text per player of the `ScoreBoard`, side by side at the top of the HUD, and the FPS
+/// display.
 pub fn initialise_score(world: &mut World, parent: Entity) {
     let font = world
         .read_resource::<Loader>()
         .load("font/square.ttf", TtfFormat, (), &world.read_resource());
-    let p1_transform = UiTransform::new(
-        "P1".to_string(),
-        Anchor::TopMiddle,
-        Anchor::Middle,
-        -50.,
-        -50.,
-        1.,
-        200.,
-        50.,
-    );
+    let players = world
+        .try_fetch::<ScoreBoard>()
+        .map_or(2, |score_board| score_board.scores().len());
 
-    let p2_transform = UiTransform::new(
-        "P2".to_string(),
-        Anchor::TopMiddle,
-        Anchor::Middle,
-        50.,
-        -50.,
-        1.,
-        200.,
-        50.,
-    );
+    let scores = Side::ALL
+        .iter()
+        .take(players)
+        .map(|side| {
+            // centre the row of scores, 100 units apart
+            let x = (side.index() as f32 - (players as f32 - 1.0) * 0.5) * 100.;
+            let transform = UiTransform::new(
+                side.name().to_string(),
+                Anchor::TopMiddle,
+                Anchor::Middle,
+                x,
+                -50.,
+                1.,
+                200.,
+                50.,
+            );
+            world
+                .create_entity()
+                .with(transform)
+                .with(UiText::new(font.clone(), "0".to_string(), [1.0, 1.0, 1.0, 1.0], 50.))
+                .with(Parent { entity: parent })
+                .build()
+        })
+        .collect();
 
     let fps_text_transform = UiTransform::new(
         "FPS".to_string(),
@@ -332,36 +1096,35 @@ pub fn initialise_score(world: &mut World, parent: Entity) {
         50.,
     );
 
-    let p1_score = world
-        .create_entity()
-        .with(p1_transform)
-        .with(UiText::new(font.clone(), "0".to_string(), [1.0, 1.0, 1.0, 1.0], 50.))
-        .with(Parent { entity: parent })
-        .build();
-
-    let p2_score = world
-        .create_entity()
-        .with(p2_transform)
-        .with(UiText::new(font.clone(), "0".to_string(), [1.0, 1.0, 1.0, 1.0], 50.))
-        .with(Parent { entity: parent })
-        .build();
-
     let fps_display = world
         .create_entity()
         .with(fps_text_transform)
         .with(UiText::new(font, "0".to_string(), [1.0, 1.0, 1.0, 1.0], 24.))
         .with(Parent { entity: parent })
         .build();
-    world.insert(ScoreText {
-        p1_score,
-        p2_score,
-        fps_display,
-    });
+    world.insert(ScoreText { scores, fps_display });
 }
+
+/// Fills the scores of the `ScoreBoard` into the score texts of the HUD.
+pub fn show_scores(world: &World) {
+    let score_text = world.read_resource::<ScoreText>();
+    let score_board = world.read_resource::<ScoreBoard>();
+    let mut ui_text = world.write_storage::<UiText>();
+    for (entity, score) in score_text.scores.iter().zip(score_board.scores()) {
+        if let Some(text) = ui_text.get_mut(*entity) {
+            text.text = score.to_string();
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{audio::initialise_audio, test_harness::SendMockEvents};
+    use crate::{
+        audio::initialise_audio,
+        systems::AiDifficulty,
+        test_harness::{temp_path, ConditionBarrierResult, SendMockEvents},
+    };
     use amethyst::{
         assets::ProgressCounter,
         core::shrev::EventChannel,
@@ -370,6 +1133,20 @@ mod tests {
         winit,
         winit::*,
     };
+    use std::time::Duration;
+
+    /// Returns a condition barrier that waits until the match simulated at least `steps` steps. A
+    /// frame simulates several steps at once, so the barrier may resume a few steps later.
+    fn wait_for_steps(steps: u32) -> impl Fn(&mut World) -> ConditionBarrierResult + Send + Sync + 'static {
+        move |world| {
+            let simulated = world.try_fetch::<FixedTimestep>().map_or(0, |timestep| timestep.simulated_steps());
+            if simulated >= steps {
+                ConditionBarrierResult::ResumeImmediately
+            } else {
+                ConditionBarrierResult::ContinueEvaluating
+            }
+        }
+    }
 
     #[test]
     fn pong_state() {
@@ -388,6 +1165,467 @@ mod tests {
         assert!(test_result.is_ok());
     }
 
+    #[test]
+    fn paddles_use_injected_config() {
+        use crate::config::ArenaConfig;
+        use assert_approx_eq::assert_approx_eq;
+
+        amethyst::start_logger(amethyst::LoggerConfig::default());
+        let config = PongConfig {
+            arena: ArenaConfig {
+                width: 320.0,
+                height: 180.0,
+            },
+            ..PongConfig::default()
+        };
+        let test_result = crate::test_harness::IntegrationTestApplication::pong_base()
+            .with_resource(config)
+            .with_setup(|world| {
+                let root_entity = world.create_entity().with(Transform::default()).build();
+                let sprite_sheet_handle = load_sprite_sheet(world);
+                initialise_paddles(world, root_entity, sprite_sheet_handle);
+            })
+            .with_assertion(|world| {
+                let paddles = world.read_storage::<Paddle>();
+                let transforms = world.read_storage::<Transform>();
+                assert_eq!(2, paddles.count());
+
+                for (paddle, transform) in (&paddles, &transforms).join() {
+                    assert_approx_eq!(transform.translation().y, 90.0);
+                    if paddle.side == Side::Right {
+                        assert_approx_eq!(transform.translation().x, 320.0 - paddle.width * 0.5);
+                    }
+                }
+            })
+            .run();
+        assert!(test_result.is_ok());
+    }
+
+    #[test]
+    fn hot_reload_hud_prefab() {
+        amethyst::start_logger(amethyst::LoggerConfig::default());
+        let test_result = crate::test_harness::IntegrationTestApplication::pong_base()
+            .with_setup(|world| {
+                let mut progress = ProgressCounter::default();
+                initialise_audio(world, &mut progress);
+            })
+            .with_state(|| {
+                SendMockEvents::test_state(|_world| Box::new(Pong::default()))
+                    .with_step(|world| {
+                        let mut events: Write<EventChannel<HotReloadEvent>> = world.system_data();
+                        events.single_write(HotReloadEvent::UiPrefab(HUD_PREFAB));
+                    })
+                    .with_wait(0.5)
+                    .with_step(|world| {
+                        let score_text = world.read_resource::<ScoreText>();
+                        let ui_text = world.read_storage::<UiText>();
+                        assert_eq!(score_text.scores.len(), 2);
+                        for score in &score_text.scores {
+                            assert!(ui_text.get(*score).is_some());
+                        }
+                    })
+                    .end_test()
+            })
+            .run();
+        assert!(test_result.is_ok());
+    }
+
+    #[test]
+    fn match_over_pushes_game_over_state() {
+        use crate::systems::MatchOver;
+
+        amethyst::start_logger(amethyst::LoggerConfig::default());
+        let test_result = crate::test_harness::IntegrationTestApplication::pong_base()
+            .with_setup(|world| {
+                let mut progress = ProgressCounter::default();
+                initialise_audio(world, &mut progress);
+            })
+            .with_state(|| {
+                SendMockEvents::test_state(|_world| Box::new(Pong::default()))
+                    .with_step(|world| {
+                        let mut events: Write<EventChannel<MatchOver>> = world.system_data();
+                        events.single_write(MatchOver {
+                            winner: Side::Left,
+                            scores: vec![11, 3],
+                        });
+                    })
+                    .with_wait(0.5)
+                    .with_step(|world| {
+                        assert_eq!(*world.read_resource::<GameplayState>(), GameplayState::Paused);
+                    })
+                    .end_test()
+            })
+            .run();
+        assert!(test_result.is_ok());
+    }
+
+    #[test]
+    fn continue_saved_match() {
+        use crate::save::{BallSnapshot, PaddleSnapshot};
+        use assert_approx_eq::assert_approx_eq;
+
+        amethyst::start_logger(amethyst::LoggerConfig::default());
+        let test_result = crate::test_harness::IntegrationTestApplication::pong_base()
+            .with_setup(|world| {
+                let mut progress = ProgressCounter::default();
+                initialise_audio(world, &mut progress);
+            })
+            .with_state(|| {
+                SendMockEvents::test_state(|_world| {
+                    Box::new(Pong::from_save(SaveGame {
+                        scores: vec![4, 6],
+                        balls: vec![BallSnapshot {
+                            position: [30.0, 40.0],
+                            velocity: [0.0, 0.0],
+                            radius: 2.0,
+                            serving: None,
+                            last_hit: None,
+                            stuck: None,
+                        }],
+                        paddles: vec![PaddleSnapshot {
+                            side: Side::Right,
+                            position: [158.0, 20.0],
+                        }],
+                        ball_spawn_timer: None,
+                        ..SaveGame::default()
+                    }))
+                })
+                .with_wait(0.5)
+                .with_step(|world| {
+                    let score_board = world.read_resource::<ScoreBoard>();
+                    assert_eq!(score_board.scores(), &[4, 6]);
+
+                    let balls = world.read_storage::<Ball>();
+                    let paddles = world.read_storage::<Paddle>();
+                    let transforms = world.read_storage::<Transform>();
+                    assert_eq!(1, balls.count());
+                    for (paddle, transform) in (&paddles, &transforms).join() {
+                        if paddle.side == Side::Right {
+                            assert_approx_eq!(transform.translation().y, 20.0);
+                        }
+                    }
+                })
+                .end_test()
+            })
+            .run();
+        assert!(test_result.is_ok());
+    }
+
+    #[test]
+    fn save_and_continue_four_player_match() {
+        use crate::save::slot_path;
+        use std::sync::{Arc, Mutex};
+
+        amethyst::start_logger(amethyst::LoggerConfig::default());
+        let saved = Arc::new(Mutex::new(None));
+        let capture = saved.clone();
+        let test_result = crate::test_harness::IntegrationTestApplication::pong_base()
+            .with_resource(MatchSettings {
+                mode: GameMode::FourPlayer,
+                ..MatchSettings::default()
+            })
+            .with_setup(|world| {
+                let mut progress = ProgressCounter::default();
+                initialise_audio(world, &mut progress);
+            })
+            .with_state(move || {
+                let capture = capture.clone();
+                SendMockEvents::test_state(|_world| Box::new(Pong::default()))
+                    .with_wait(0.5)
+                    .with_step(move |world| {
+                        *capture.lock().unwrap() = Some(SaveGame::capture(world, None));
+                    })
+                    .end_test()
+            })
+            .run();
+        assert!(test_result.is_ok());
+
+        let saves_dir = temp_path("four-player-save");
+        let path = slot_path(&saves_dir, 0);
+        let save_game = saved.lock().unwrap().take().expect("match was saved");
+        assert_eq!(save_game.mode, GameMode::FourPlayer);
+        assert_eq!(save_game.paddles.len(), 4);
+        save_game.store(&path).expect("Failed to store the save");
+        let loaded = SaveGame::read(&path).expect("Failed to read the save");
+        std::fs::remove_dir_all(&saves_dir).expect("Failed to remove the saves");
+        assert_eq!(loaded, save_game);
+
+        // the menu is back at its default two-player mode when the save is loaded
+        let test_result = crate::test_harness::IntegrationTestApplication::pong_base()
+            .with_setup(|world| {
+                let mut progress = ProgressCounter::default();
+                initialise_audio(world, &mut progress);
+            })
+            .with_state(move || {
+                let loaded = loaded.clone();
+                SendMockEvents::test_state(move |_world| Box::new(Pong::from_save(loaded.clone())))
+                    .with_wait(0.5)
+                    .with_step(|world| {
+                        assert_eq!(world.read_resource::<MatchSettings>().mode, GameMode::FourPlayer);
+                        assert_eq!(world.read_resource::<ScoreBoard>().scores().len(), 4);
+                        let paddles = world.read_storage::<Paddle>();
+                        let mut sides = paddles.join().map(|paddle| paddle.side).collect::<Vec<_>>();
+                        sides.sort_by_key(|side| side.index());
+                        assert_eq!(sides, Side::ALL.to_vec());
+                    })
+                    .end_test()
+            })
+            .run();
+        assert!(test_result.is_ok());
+    }
+
+    #[test]
+    fn multi_ball_spawns_extra_balls() {
+        use crate::{config::MultiBallConfig, GameMode};
+
+        amethyst::start_logger(amethyst::LoggerConfig::default());
+        let config = PongConfig {
+            multi_ball: MultiBallConfig {
+                spawn_interval: 0.25,
+                max_balls: 3,
+            },
+            ..PongConfig::default()
+        };
+        let test_result = crate::test_harness::IntegrationTestApplication::pong_base()
+            .with_resource(config)
+            .with_resource(MatchSettings {
+                mode: GameMode::MultiBall,
+                ..MatchSettings::default()
+            })
+            .with_setup(|world| {
+                let mut progress = ProgressCounter::default();
+                initialise_audio(world, &mut progress);
+            })
+            .with_state(|| {
+                // The first ball appears after 120 steps, the extra ones every 30 steps after it, and
+                // no ball can reach a goal before step 220.
+                SendMockEvents::test_state(|_world| Box::new(Pong::default()))
+                    .with_condition_barrier(wait_for_steps(190), Duration::from_secs(20))
+                    .with_step(|world| assert_eq!(world.read_storage::<Ball>().count(), 3))
+                    .end_test()
+            })
+            .run();
+        assert!(test_result.is_ok());
+    }
+
+    #[test]
+    fn four_player_match() {
+        amethyst::start_logger(amethyst::LoggerConfig::default());
+        let test_result = crate::test_harness::IntegrationTestApplication::pong_base()
+            .with_resource(MatchSettings {
+                mode: GameMode::FourPlayer,
+                ..MatchSettings::default()
+            })
+            .with_setup(|world| {
+                let mut progress = ProgressCounter::default();
+                initialise_audio(world, &mut progress);
+            })
+            .with_state(|| {
+                SendMockEvents::test_state(|_world| Box::new(Pong::default()))
+                    .with_wait(0.5)
+                    .with_step(|world| {
+                        let paddles = world.read_storage::<Paddle>();
+                        let mut sides = paddles.join().map(|paddle| paddle.side.index()).collect::<Vec<_>>();
+                        sides.sort();
+                        assert_eq!(sides, vec![0, 1, 2, 3]);
+                        assert_eq!(world.read_resource::<ScoreBoard>().scores(), &[0, 0, 0, 0]);
+                        assert_eq!(world.read_resource::<ScoreText>().scores.len(), 4);
+                    })
+                    .end_test()
+            })
+            .run();
+        assert!(test_result.is_ok());
+    }
+
+    /// Remote peer of a rollback match, which holds its paddle up without simulating anything.
+    struct RemotePeer(RollbackSession<()>);
+
+    struct Idle;
+
+    impl Rollback for Idle {
+        type State = ();
+
+        fn save_state(&mut self) {}
+
+        fn load_state(&mut self, _state: &()) {}
+
+        fn advance_frame(&mut self, _inputs: PaddleInputs) {}
+    }
+
+    fn drive_remote_peer(world: &mut World) -> ConditionBarrierResult {
+        let frame = {
+            let mut remote = world.write_resource::<RemotePeer>();
+            remote.0.poll(&mut Idle);
+            if remote.0.can_advance() {
+                remote.0.advance(&mut Idle, Some(1.0));
+            }
+            remote.0.send_inputs();
+            remote.0.frame()
+        };
+        let paddles = world.read_storage::<Paddle>();
+        let transforms = world.read_storage::<Transform>();
+        let right_moved = (&paddles, &transforms)
+            .join()
+            .any(|(paddle, transform)| paddle.side == Side::Right && transform.translation().y > 46.0);
+        if frame >= 60 && right_moved {
+            ConditionBarrierResult::ResumeImmediately
+        } else {
+            ConditionBarrierResult::ContinueEvaluating
+        }
+    }
+
+    #[test]
+    fn rollback_match_follows_remote_peer() {
+        use crate::network::{LinkConditions, SimulatedTransport};
+
+        amethyst::start_logger(amethyst::LoggerConfig::default());
+        let test_result = crate::test_harness::IntegrationTestApplication::pong_base()
+            .with_state(|| {
+                SendMockEvents::test_state(|world| {
+                    let conditions = LinkConditions {
+                        latency: 2,
+                        jitter: 2,
+                        loss: 0.1,
+                    };
+                    let (local, remote) = SimulatedTransport::pair(conditions, 3);
+                    world.insert(Level::default());
+                    let remote = RollbackSession::new(Side::Right, Box::new(remote), None)
+                        .with_settings(rollback_settings(world));
+                    world.insert(RemotePeer(remote));
+                    Box::new(Pong::peer(RollbackSession::new(Side::Left, Box::new(local), Some(11))))
+                })
+                .with_condition_barrier(drive_remote_peer, Duration::from_secs(20))
+                .with_step(|world| {
+                    assert_eq!(world.read_resource::<GameRng>().seed(), 11);
+                    assert_eq!(world.read_resource::<RemotePeer>().0.seed(), Some(11));
+                })
+                .end_test()
+            })
+            .run();
+        assert!(test_result.is_ok());
+    }
+
+    #[test]
+    fn spawn_power_ups_when_enabled() {
+        use crate::{config::PowerUpConfig, systems::PowerUp};
+
+        amethyst::start_logger(amethyst::LoggerConfig::default());
+        let config = PongConfig {
+            power_ups: PowerUpConfig {
+                spawn_interval: 0.25,
+                max_pickups: 2,
+                ..PowerUpConfig::default()
+            },
+            ..PongConfig::default()
+        };
+        let test_result = crate::test_harness::IntegrationTestApplication::pong_base()
+            .with_resource(config)
+            .with_resource(MatchSettings {
+                power_ups: true,
+                ..MatchSettings::default()
+            })
+            .with_setup(|world| {
+                let mut progress = ProgressCounter::default();
+                initialise_audio(world, &mut progress);
+            })
+            .with_state(|| {
+                // A power-up appears every 30 steps, and the ball that could collect them only after
+                // 120 steps.
+                SendMockEvents::test_state(|_world| Box::new(Pong::default()))
+                    .with_condition_barrier(wait_for_steps(90), Duration::from_secs(20))
+                    .with_step(|world| assert_eq!(world.read_storage::<PowerUp>().count(), 2))
+                    .end_test()
+            })
+            .run();
+        assert!(test_result.is_ok());
+    }
+
+    #[test]
+    fn stopping_removes_power_up_effects() {
+        amethyst::start_logger(amethyst::LoggerConfig::default());
+        let test_result = crate::test_harness::IntegrationTestApplication::pong_base()
+            .with_setup(|world| {
+                let mut progress = ProgressCounter::default();
+                initialise_audio(world, &mut progress);
+            })
+            .with_state(|| {
+                SendMockEvents::test_state(|_world| Box::new(Pong::default()))
+                    .with_wait(0.2)
+                    .with_step(|world| {
+                        // effects live below the root entity of the match, like the ones collected
+                        let root_entity = (&world.read_storage::<Paddle>(), &world.read_storage::<Parent>())
+                            .join()
+                            .map(|(_, parent)| parent.entity)
+                            .next()
+                            .expect("paddles were created");
+                        world
+                            .create_entity()
+                            .with(Effect {
+                                kind: PowerUpKind::Grow,
+                                side: Side::Left,
+                                ball: None,
+                                remaining: 60.0,
+                            })
+                            .with(Parent { entity: root_entity })
+                            .build();
+                    })
+                    .with_wait(0.2)
+                    .with_step(|world| {
+                        assert_eq!(world.read_storage::<Effect>().count(), 1);
+                        world
+                            .write_resource::<EventChannel<crate::TestEvent>>()
+                            .single_write(crate::TestEvent::Pop);
+                    })
+                    .with_wait(0.2)
+                    .with_step(|world| assert_eq!(world.read_storage::<Effect>().count(), 0))
+                    .end_test()
+            })
+            .run();
+        assert!(test_result.is_ok());
+    }
+
+    #[test]
+    fn play_in_level() {
+        use crate::{
+            config::ArenaConfig,
+            level::{Level, ObstacleLayout},
+        };
+        use assert_approx_eq::assert_approx_eq;
+
+        amethyst::start_logger(amethyst::LoggerConfig::default());
+        let test_result = crate::test_harness::IntegrationTestApplication::pong_base()
+            .with_resource(Level {
+                name: "TEST".to_string(),
+                arena: Some(ArenaConfig {
+                    width: 200.0,
+                    height: 90.0,
+                }),
+                goal_height: Some(40.0),
+                obstacles: vec![ObstacleLayout {
+                    position: [100.0, 20.0],
+                    size: [4.0, 8.0],
+                    velocity: [0.0, 0.0],
+                    travel: [0.0, 0.0],
+                }],
+            })
+            .with_setup(|world| {
+                let mut progress = ProgressCounter::default();
+                initialise_audio(world, &mut progress);
+            })
+            .with_state(|| {
+                SendMockEvents::test_state(|_world| Box::new(Pong::default()))
+                    .with_wait(0.5)
+                    .with_step(|world| {
+                        assert_approx_eq!(world.read_resource::<PongConfig>().arena.width, 200.0);
+                        // the obstacle and two goal posts on either side
+                        assert_eq!(5, world.read_storage::<Obstacle>().count());
+                    })
+                    .end_test()
+            })
+            .run();
+        assert!(test_result.is_ok());
+    }
+
     #[test]
     fn is_close_requested() {
         amethyst::start_logger(amethyst::LoggerConfig::default());
@@ -444,6 +1682,70 @@ mod tests {
         assert!(test_result.is_ok());
     }
 
+    #[test]
+    fn pause_action() {
+        amethyst::start_logger(amethyst::LoggerConfig::default());
+        let test_result = crate::test_harness::IntegrationTestApplication::pong_base()
+            .with_setup(|world| {
+                let mut progress = ProgressCounter::default();
+                initialise_audio(world, &mut progress);
+            })
+            .with_state(|| {
+                SendMockEvents::test_state(|_world| Box::new(Pong::default()))
+                    .with_step(|world| {
+                        let mut events: Write<EventChannel<InputEvent<GameBindings>>> = world.system_data();
+                        events.single_write(InputEvent::ActionPressed(ActionBinding::Pause));
+                    })
+                    .with_wait(0.5)
+                    .with_step(|world| {
+                        assert_eq!(*world.read_resource::<GameplayState>(), GameplayState::Paused);
+                    })
+                    .end_test()
+            })
+            .run();
+        assert!(test_result.is_ok());
+    }
+
+    #[test]
+    fn pause_when_controller_disconnects() {
+        amethyst::start_logger(amethyst::LoggerConfig::default());
+        let test_result = crate::test_harness::IntegrationTestApplication::pong_base()
+            .with_setup(|world| {
+                let mut progress = ProgressCounter::default();
+                initialise_audio(world, &mut progress);
+                world.insert(MatchSettings {
+                    left: PaddleController::Human,
+                    right: PaddleController::Cpu(AiDifficulty::Normal),
+                    ..MatchSettings::default()
+                });
+                let mut controllers = Controllers::default();
+                controllers.connect(0);
+                controllers.connect(1);
+                world.insert(controllers);
+            })
+            .with_state(|| {
+                SendMockEvents::test_state(|_world| Box::new(Pong::default()))
+                    .with_wait(0.1)
+                    .with_step(|world| {
+                        assert_eq!(*world.read_resource::<GameplayState>(), GameplayState::Running);
+                        // the controller of the CPU's side goes unnoticed
+                        world.write_resource::<Controllers>().disconnect(1);
+                    })
+                    .with_wait(0.1)
+                    .with_step(|world| {
+                        assert_eq!(*world.read_resource::<GameplayState>(), GameplayState::Running);
+                        world.write_resource::<Controllers>().disconnect(0);
+                    })
+                    .with_wait(0.1)
+                    .with_step(|world| {
+                        assert_eq!(*world.read_resource::<GameplayState>(), GameplayState::Paused);
+                    })
+                    .end_test()
+            })
+            .run();
+        assert!(test_result.is_ok());
+    }
+
     #[test]
     fn unhandled_window_event() {
         amethyst::start_logger(amethyst::LoggerConfig::default());
@@ -0,0 +1,449 @@
+use crate::{
+    bindings::GameBindings,
+    config::{button_label, rebind, BindingSlot, UserBindings},
+    game_data::CustomGameData,
+    states::{set_button_text, util::delete_hierarchy, OptionsState},
+    systems::{Controllers, MenuFocus},
+    GamePaths, GameStateEvent,
+};
+use amethyst::{
+    assets::ProgressCounter,
+    config::Config,
+    ecs::prelude::Entity,
+    input::{is_close_requested, is_key_down, Bindings, Button, InputEvent, InputHandler},
+    prelude::*,
+    ui::{UiCreator, UiEvent, UiEventType, UiFinder},
+    winit::VirtualKeyCode,
+};
+use derivative::Derivative;
+
+const ROW_BUTTON_IDS: [&str; 8] = [
+    "binding_1",
+    "binding_2",
+    "binding_3",
+    "binding_4",
+    "binding_5",
+    "binding_6",
+    "binding_7",
+    "binding_8",
+];
+const BUTTON_PAGE: &str = "bindings_page";
+const BUTTON_RESET: &str = "bindings_reset";
+const BUTTON_BACK: &str = "bindings_back";
+const LABEL_STATUS: &str = "bindings_status";
+
+/// Lists every button of the loaded key bindings, a page of rows at a time. Clicking a row waits
+/// for the next key or controller button press, which takes over the row; a button already used
+/// by another row is swapped with it. Changes apply right away and are written to the user
+/// bindings file when leaving the screen.
+#[derive(Derivative)]
+#[derivative(Debug)]
+#[derivative(Default)]
+pub struct RebindState {
+    ui_root: Option<Entity>,
+    row_buttons: [Option<Entity>; 8],
+    button_page: Option<Entity>,
+    button_reset: Option<Entity>,
+    button_back: Option<Entity>,
+    label_status: Option<Entity>,
+    /// shipped bindings, which the user's changes are compared against
+    #[derivative(Debug = "ignore")]
+    defaults: Option<Bindings<GameBindings>>,
+    slots: Vec<BindingSlot>,
+    page: usize,
+    /// row waiting for the next button press
+    capturing: Option<BindingSlot>,
+    status: String,
+    #[derivative(Debug = "ignore")]
+    load_progress: Option<ProgressCounter>,
+}
+
+impl RebindState {
+    fn page_count(&self) -> usize {
+        ((self.slots.len() + ROW_BUTTON_IDS.len() - 1) / ROW_BUTTON_IDS.len()).max(1)
+    }
+
+    /// Reads the shipped bindings, falling back to the loaded ones in tests, which run without
+    /// `GamePaths`.
+    fn load_defaults(world: &World) -> Bindings<GameBindings> {
+        let loaded = world.read_resource::<InputHandler<GameBindings>>().bindings.clone();
+        match world.try_fetch::<GamePaths>() {
+            Some(paths) => paths.load_bindings().unwrap_or_else(|e| {
+                log::error!("Failed to load key bindings {:?}: {}", paths.key_bindings, e);
+                loaded
+            }),
+            None => loaded,
+        }
+    }
+
+    /// Gives the row waiting for a button press the `button`.
+    fn assign(&mut self, world: &mut World, button: Button) {
+        let slot = match self.capturing.take() {
+            Some(slot) => slot,
+            None => return,
+        };
+        let mut input = world.write_resource::<InputHandler<GameBindings>>();
+        self.status = match rebind(&mut input.bindings, &slot, button) {
+            Ok(Some(swapped)) => format!("SWAPPED WITH {}", swapped.label()),
+            Ok(None) => String::new(),
+            Err(e) => {
+                log::error!("Failed to bind {:?} to {:?}: {}", button, slot, e);
+                format!("COULD NOT BIND {}", button_label(Some(button)))
+            }
+        };
+        log::info!("Bound {:?} to {:?}", button, slot);
+    }
+
+    /// Writes the current bindings into the row labels.
+    fn update_labels(&self, world: &World) {
+        let input = world.read_resource::<InputHandler<GameBindings>>();
+        let first_row = self.page * ROW_BUTTON_IDS.len();
+        for (index, button) in self.row_buttons.iter().enumerate() {
+            let label = match self.slots.get(first_row + index) {
+                Some(slot) if Some(slot) == self.capturing.as_ref() => format!("{}: ?", slot.label()),
+                Some(slot) => format!("{}: {}", slot.label(), button_label(slot.button(&input.bindings))),
+                None => String::new(),
+            };
+            if let Some(button) = *button {
+                set_button_text(world, button, &label);
+            }
+        }
+        if let Some(button) = self.button_page {
+            set_button_text(world, button, &format!("PAGE {}/{}", self.page + 1, self.page_count()));
+        }
+        if let Some(label) = self.label_status {
+            set_button_text(world, label, &self.status);
+        }
+    }
+}
+
+impl<'a, 'b> State<CustomGameData<'static, 'static>, GameStateEvent> for RebindState {
+    fn on_start(&mut self, data: StateData<'_, CustomGameData<'_, '_>>) {
+        let world = data.world;
+
+        self.defaults = Some(Self::load_defaults(world));
+        self.slots = BindingSlot::all(&world.read_resource::<InputHandler<GameBindings>>().bindings);
+
+        let mut progress = ProgressCounter::default();
+        self.ui_root = Some(world.exec(|mut creator: UiCreator<'_>| creator.create("ui/rebind.ron", &mut progress)));
+        self.load_progress = Some(progress);
+    }
+
+    fn on_stop(&mut self, data: StateData<'_, CustomGameData<'_, '_>>) {
+        if let Some(mut defaults) = self.defaults.take() {
+            // the controllers assigned right now are no change made by the user
+            if let Some(controllers) = data.world.try_fetch::<Controllers>() {
+                controllers.assign(&mut defaults);
+            }
+            let user_bindings = {
+                let input = data.world.read_resource::<InputHandler<GameBindings>>();
+                UserBindings::changes(&defaults, &input.bindings)
+            };
+            // persist the bindings, the paths are only known when running the real game
+            if let Some(paths) = data.world.try_fetch::<GamePaths>() {
+                match user_bindings.store(&paths.user_bindings) {
+                    Ok(()) => log::info!("Saved user bindings to {:?}", paths.user_bindings),
+                    Err(e) => log::error!("Failed to save user bindings to {:?}: {}", paths.user_bindings, e),
+                }
+            }
+            data.world.insert(user_bindings);
+        }
+
+        if let Some(root_entity) = self.ui_root.take() {
+            delete_hierarchy(root_entity, data.world).expect("Failed to remove RebindState");
+        }
+
+        if let Some(mut focus) = data.world.try_fetch_mut::<MenuFocus>() {
+            focus.suspended = false;
+        }
+        self.row_buttons = [None; 8];
+        self.button_page = None;
+        self.button_reset = None;
+        self.button_back = None;
+        self.label_status = None;
+        self.slots.clear();
+        self.page = 0;
+        self.capturing = None;
+        self.status.clear();
+        self.load_progress = None;
+    }
+
+    fn handle_event(
+        &mut self,
+        data: StateData<'_, CustomGameData<'_, '_>>,
+        event: GameStateEvent,
+    ) -> Trans<CustomGameData<'static, 'static>, GameStateEvent> {
+        match event {
+            GameStateEvent::Window(event) => {
+                if is_close_requested(&event) {
+                    log::info!("[Trans::Quit] Quitting Application!");
+                    Trans::Quit
+                } else if is_key_down(&event, VirtualKeyCode::Escape) {
+                    if self.capturing.take().is_some() {
+                        self.status.clear();
+                        return Trans::None;
+                    }
+                    log::info!("[Trans::Switch] Switching to OptionsState!");
+                    Trans::Switch(Box::new(OptionsState::default()))
+                } else {
+                    Trans::None
+                }
+            }
+            // Escape cancels instead of being bound, see above
+            GameStateEvent::Input(InputEvent::KeyPressed { key_code, .. }) if key_code != VirtualKeyCode::Escape => {
+                self.assign(data.world, Button::Key(key_code));
+                Trans::None
+            }
+            GameStateEvent::Input(InputEvent::ControllerButtonPressed { which, button }) => {
+                self.assign(data.world, Button::Controller(which, button));
+                Trans::None
+            }
+            GameStateEvent::Ui(UiEvent {
+                event_type: UiEventType::Click,
+                target,
+            }) => {
+                let target = Some(target);
+                if target == self.button_back {
+                    log::info!("[Trans::Switch] Switching to OptionsState!");
+                    return Trans::Switch(Box::new(OptionsState::default()));
+                }
+                self.capturing = None;
+                self.status.clear();
+                if target == self.button_page {
+                    self.page = (self.page + 1) % self.page_count();
+                } else if target == self.button_reset {
+                    if let Some(defaults) = &self.defaults {
+                        data.world.write_resource::<InputHandler<GameBindings>>().bindings = defaults.clone();
+                        self.slots = BindingSlot::all(defaults);
+                        self.page = self.page.min(self.page_count() - 1);
+                        self.status = "RESTORED THE DEFAULTS".to_string();
+                    }
+                } else if let Some(row) = self.row_buttons.iter().position(|button| *button == target) {
+                    if let Some(slot) = self.slots.get(self.page * ROW_BUTTON_IDS.len() + row) {
+                        self.status = format!("PRESS A KEY FOR {}, ESCAPE CANCELS", slot.label());
+                        self.capturing = Some(slot.clone());
+                    }
+                }
+                Trans::None
+            }
+            GameStateEvent::Test(test_event) => crate::test_harness::handle_test_event(&test_event),
+            _ => Trans::None,
+        }
+    }
+
+    fn update(
+        &mut self,
+        data: StateData<'_, CustomGameData<'_, '_>>,
+    ) -> Trans<CustomGameData<'static, 'static>, GameStateEvent> {
+        // the key the row waits for must not move the focus or click the focused button
+        if let Some(mut focus) = data.world.try_fetch_mut::<MenuFocus>() {
+            focus.suspended = self.capturing.is_some();
+        }
+        data.data.update(data.world, true);
+        // only search for buttons if they have not been found yet
+        let StateData { world, .. } = data;
+
+        if self.row_buttons.iter().any(Option::is_none)
+            || self.button_page.is_none()
+            || self.button_reset.is_none()
+            || self.button_back.is_none()
+            || self.label_status.is_none()
+        {
+            world.exec(|ui_finder: UiFinder<'_>| {
+                for (button, id) in self.row_buttons.iter_mut().zip(&ROW_BUTTON_IDS) {
+                    *button = ui_finder.find(id);
+                }
+                self.button_page = ui_finder.find(BUTTON_PAGE);
+                self.button_reset = ui_finder.find(BUTTON_RESET);
+                self.button_back = ui_finder.find(BUTTON_BACK);
+                self.label_status = ui_finder.find(LABEL_STATUS);
+            });
+        }
+        self.update_labels(world);
+
+        Trans::None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        bindings::AxisBinding,
+        test_harness::{click, temp_path, wait_for_ui, SendMockEvents},
+    };
+    use amethyst::{core::shrev::EventChannel, ecs::prelude::*, input::Axis};
+    use std::time::Duration;
+
+    fn press(key_code: VirtualKeyCode) -> impl Fn(&mut World) + Send + Sync + 'static {
+        move |world| {
+            let mut events: Write<EventChannel<InputEvent<GameBindings>>> = world.system_data();
+            events.single_write(InputEvent::KeyPressed { key_code, scancode: 0 });
+        }
+    }
+
+    /// Opens the screen with the shipped key bindings loaded.
+    fn rebind_state(world: &mut World) -> Box<dyn State<CustomGameData<'static, 'static>, GameStateEvent>> {
+        let path = crate::initialize_paths().expect("valid paths required").key_bindings;
+        let bindings = Bindings::load_no_fallback(path).expect("failed to load key bindings");
+        world.write_resource::<InputHandler<GameBindings>>().bindings = bindings;
+        Box::new(RebindState::default())
+    }
+
+    fn left_paddle(world: &World) -> Option<Axis> {
+        let input = world.read_resource::<InputHandler<GameBindings>>();
+        input.bindings.axis(&AxisBinding::LeftPaddle).cloned()
+    }
+
+    #[test]
+    fn test_rebind_state() {
+        amethyst::start_logger(amethyst::LoggerConfig::default());
+        let test_result = crate::test_harness::IntegrationTestApplication::pong_base()
+            .with_state(|| SendMockEvents::test_state(rebind_state).with_wait(1.0).end_test())
+            .run();
+        assert!(test_result.is_ok());
+    }
+
+    #[test]
+    fn bind_pressed_key() {
+        amethyst::start_logger(amethyst::LoggerConfig::default());
+        let test_result = crate::test_harness::IntegrationTestApplication::pong_base()
+            .with_state(|| {
+                SendMockEvents::test_state(rebind_state)
+                    .with_condition_barrier(wait_for_ui(BUTTON_BACK), Duration::from_secs(20))
+                    // the rows are sorted, the third one is "LEFT PADDLE +"
+                    .with_step(click("binding_3"))
+                    .with_step(press(VirtualKeyCode::Q))
+                    .with_step(click("binding_4"))
+                    .with_step(press(VirtualKeyCode::Up))
+                    .with_wait(0.5)
+                    .with_step(|world| {
+                        assert_eq!(
+                            left_paddle(world),
+                            Some(Axis::Emulated {
+                                pos: Button::Key(VirtualKeyCode::Q),
+                                neg: Button::Key(VirtualKeyCode::Up),
+                            })
+                        );
+                        // the right paddle got the key the left paddle gave up
+                        let input = world.read_resource::<InputHandler<GameBindings>>();
+                        let right = BindingSlot::AxisPositive(AxisBinding::RightPaddle);
+                        assert_eq!(right.button(&input.bindings), Some(Button::Key(VirtualKeyCode::S)));
+                    })
+                    .end_test()
+            })
+            .run();
+        assert!(test_result.is_ok());
+    }
+
+    #[test]
+    fn keys_are_ignored_until_row_is_clicked() {
+        amethyst::start_logger(amethyst::LoggerConfig::default());
+        let test_result = crate::test_harness::IntegrationTestApplication::pong_base()
+            .with_state(|| {
+                SendMockEvents::test_state(rebind_state)
+                    .with_condition_barrier(wait_for_ui(BUTTON_BACK), Duration::from_secs(20))
+                    .with_step(press(VirtualKeyCode::Q))
+                    .with_step(click("binding_3"))
+                    .with_step(click(BUTTON_PAGE))
+                    .with_step(press(VirtualKeyCode::Q))
+                    .with_wait(0.5)
+                    .with_step(|world| {
+                        assert_eq!(
+                            left_paddle(world),
+                            Some(Axis::Emulated {
+                                pos: Button::Key(VirtualKeyCode::W),
+                                neg: Button::Key(VirtualKeyCode::S),
+                            })
+                        );
+                    })
+                    .end_test()
+            })
+            .run();
+        assert!(test_result.is_ok());
+    }
+
+    #[test]
+    fn capturing_suspends_menu_focus() {
+        amethyst::start_logger(amethyst::LoggerConfig::default());
+        let test_result = crate::test_harness::IntegrationTestApplication::pong_base()
+            .with_resource(MenuFocus::default())
+            .with_state(|| {
+                SendMockEvents::test_state(rebind_state)
+                    .with_condition_barrier(wait_for_ui(BUTTON_BACK), Duration::from_secs(20))
+                    .with_step(click("binding_3"))
+                    .with_wait(0.1)
+                    .with_step(|world| assert!(world.read_resource::<MenuFocus>().suspended))
+                    .with_step(press(VirtualKeyCode::Return))
+                    .with_wait(0.1)
+                    .with_step(|world| assert!(!world.read_resource::<MenuFocus>().suspended))
+                    .end_test()
+            })
+            .run();
+        assert!(test_result.is_ok());
+    }
+
+    #[test]
+    fn return_activating_a_row_is_not_bound() {
+        use crate::systems::MenuFocusSystemDesc;
+
+        amethyst::start_logger(amethyst::LoggerConfig::default());
+        let test_result = crate::test_harness::IntegrationTestApplication::pong_base()
+            .with_system_desc(MenuFocusSystemDesc::default(), "menu_focus_system", &["input_system"])
+            .with_state(|| {
+                SendMockEvents::test_state(rebind_state)
+                    .with_condition_barrier(wait_for_ui(BUTTON_BACK), Duration::from_secs(20))
+                    .with_step(press(VirtualKeyCode::Down))
+                    .with_step(press(VirtualKeyCode::Return))
+                    .with_wait(0.1)
+                    .with_step(|world| {
+                        // the first row still waits for its key
+                        assert!(world.read_resource::<MenuFocus>().suspended);
+                        let input = world.read_resource::<InputHandler<GameBindings>>();
+                        let bound_return = BindingSlot::all(&input.bindings)
+                            .iter()
+                            .any(|slot| slot.button(&input.bindings) == Some(Button::Key(VirtualKeyCode::Return)));
+                        assert!(!bound_return);
+                    })
+                    .end_test()
+            })
+            .run();
+        assert!(test_result.is_ok());
+    }
+
+    #[test]
+    fn persist_bindings_on_back() {
+        amethyst::start_logger(amethyst::LoggerConfig::default());
+        let bindings_path = temp_path("rebind.ron");
+        let _ = std::fs::remove_file(&bindings_path);
+        let paths = GamePaths {
+            user_bindings: bindings_path.clone(),
+            ..crate::initialize_paths().expect("valid paths required")
+        };
+
+        let test_result = crate::test_harness::IntegrationTestApplication::pong_base()
+            .with_resource(paths)
+            .with_state(|| {
+                SendMockEvents::test_state(rebind_state)
+                    .with_condition_barrier(wait_for_ui(BUTTON_BACK), Duration::from_secs(20))
+                    .with_step(click("binding_3"))
+                    .with_step(press(VirtualKeyCode::Q))
+                    .with_step(click(BUTTON_BACK))
+                    .with_wait(0.5)
+                    .end_test()
+            })
+            .run();
+        assert!(test_result.is_ok());
+
+        let saved = UserBindings::load_or_default(&bindings_path);
+        std::fs::remove_file(&bindings_path).expect("bindings were not saved");
+        assert_eq!(
+            saved.axes.get(&AxisBinding::LeftPaddle),
+            Some(&Axis::Emulated {
+                pos: Button::Key(VirtualKeyCode::Q),
+                neg: Button::Key(VirtualKeyCode::S),
+            })
+        );
+        assert_eq!(saved.axes.len(), 1);
+    }
+}
@@ -0,0 +1,135 @@
+use crate::{
+    config::PongConfig,
+    game_data::CustomGameData,
+    level::Level,
+    replay::Replay,
+    states::{MainMenu, Pong},
+    GameStateEvent, MatchSettings,
+};
+use amethyst::{
+    input::{is_close_requested, is_key_down},
+    prelude::*,
+    winit::VirtualKeyCode,
+};
+
+/// Plays a recorded match back. The match runs with the recorded config, level, RNG seed and
+/// inputs, so it ends exactly like the recorded one. Escape leaves the replay instead of pausing it.
+pub struct ReplayState<'a, 'b> {
+    pong: Pong<'a, 'b>,
+    config: PongConfig,
+    match_settings: MatchSettings,
+    level: Level,
+    /// config, match settings and level chosen by the user, restored when the replay ends
+    previous: Option<(PongConfig, MatchSettings, Level)>,
+}
+
+impl<'a, 'b> ReplayState<'a, 'b> {
+    pub fn new(replay: Replay) -> Self {
+        Self {
+            config: replay.config.clone(),
+            match_settings: replay.match_settings,
+            level: replay.level.clone(),
+            pong: Pong::from_replay(replay),
+            previous: None,
+        }
+    }
+}
+
+impl<'a, 'b> State<CustomGameData<'static, 'static>, GameStateEvent> for ReplayState<'a, 'b> {
+    fn on_start(&mut self, data: StateData<'_, CustomGameData<'_, '_>>) {
+        self.previous = Some((
+            data.world.read_resource::<PongConfig>().clone(),
+            *data.world.read_resource::<MatchSettings>(),
+            data.world.entry::<Level>().or_insert_with(Level::default).clone(),
+        ));
+        data.world.insert(self.config.clone());
+        data.world.insert(self.match_settings);
+        data.world.insert(self.level.clone());
+        self.pong.on_start(data);
+    }
+
+    fn on_stop(&mut self, data: StateData<'_, CustomGameData<'_, '_>>) {
+        // `Pong` restores the arena of the recorded config first, the config chosen by the user
+        // replaces it afterwards.
+        let StateData { world, data } = data;
+        self.pong.on_stop(StateData::new(world, data));
+        if let Some((config, match_settings, level)) = self.previous.take() {
+            world.insert(config);
+            world.insert(match_settings);
+            world.insert(level);
+        }
+    }
+
+    fn on_pause(&mut self, data: StateData<'_, CustomGameData<'_, '_>>) {
+        self.pong.on_pause(data);
+    }
+
+    fn on_resume(&mut self, data: StateData<'_, CustomGameData<'_, '_>>) {
+        self.pong.on_resume(data);
+    }
+
+    fn handle_event(
+        &mut self,
+        data: StateData<'_, CustomGameData<'_, '_>>,
+        event: GameStateEvent,
+    ) -> Trans<CustomGameData<'static, 'static>, GameStateEvent> {
+        match event {
+            GameStateEvent::Window(event) => {
+                if is_close_requested(&event) {
+                    log::info!("[Trans::Quit] Quitting Application!");
+                    Trans::Quit
+                } else if is_key_down(&event, VirtualKeyCode::Escape) {
+                    log::info!("[Trans::Switch] Leaving replay, switching to MainMenu!");
+                    Trans::Switch(Box::new(MainMenu::default()))
+                } else {
+                    Trans::None
+                }
+            }
+            event => self.pong.handle_event(data, event),
+        }
+    }
+
+    fn update(
+        &mut self,
+        data: StateData<'_, CustomGameData<'_, '_>>,
+    ) -> Trans<CustomGameData<'static, 'static>, GameStateEvent> {
+        self.pong.update(data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{audio::initialise_audio, test_harness::SendMockEvents};
+    use amethyst::assets::ProgressCounter;
+
+    fn example_replay() -> Replay {
+        let mut config = PongConfig::default();
+        config.arena.width = 320.0;
+        crate::replay::ReplayRecorder::new(7, config, MatchSettings::default(), Level::default(), None)
+            .finish(&crate::ScoreBoard::new(2))
+    }
+
+    #[test]
+    fn use_recorded_config_while_playing() {
+        use assert_approx_eq::assert_approx_eq;
+
+        amethyst::start_logger(amethyst::LoggerConfig::default());
+        let test_result = crate::test_harness::IntegrationTestApplication::pong_base()
+            .with_setup(|world| {
+                let mut progress = ProgressCounter::default();
+                initialise_audio(world, &mut progress);
+            })
+            .with_state(|| {
+                SendMockEvents::test_state(|_world| Box::new(ReplayState::new(example_replay())))
+                    .with_wait(0.5)
+                    .with_step(|world| {
+                        assert_approx_eq!(world.read_resource::<PongConfig>().arena.width, 320.0);
+                        assert_eq!(world.read_resource::<crate::rng::GameRng>().seed(), 7);
+                    })
+                    .end_test()
+            })
+            .run();
+        assert!(test_result.is_ok());
+    }
+}
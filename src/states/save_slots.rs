@@ -0,0 +1,349 @@
+use crate::{
+    game_data::CustomGameData,
+    save::{slot_path, SaveGame, SAVE_SLOTS},
+    states::{format_scores, set_button_text, util::delete_hierarchy, MainMenu, Pong},
+    GamePaths, GameStateEvent,
+};
+use amethyst::{
+    assets::ProgressCounter,
+    ecs::prelude::Entity,
+    input::{is_close_requested, is_key_down},
+    prelude::*,
+    ui::{UiCreator, UiEvent, UiEventType, UiFinder, UiText},
+    winit::VirtualKeyCode,
+};
+use std::path::PathBuf;
+
+const TITLE_LABEL_ID: &str = "save_slots_title";
+const SLOT_BUTTON_IDS: [&str; SAVE_SLOTS] = ["slot_1", "slot_2", "slot_3"];
+const BACK_BUTTON_ID: &str = "save_slots_back";
+
+/// What happens when a slot is picked.
+#[derive(Debug)]
+enum SlotMode {
+    /// Writes the snapshot into the slot and returns to the pause menu.
+    Save(SaveGame),
+    /// Continues the match stored in the slot.
+    Load,
+}
+
+/// Save slot picker. Pushed on top of the pause menu to save the running match, or switched to
+/// from the main menu to continue a saved one.
+pub struct SaveSlotState {
+    mode: SlotMode,
+    /// contents of every slot, `None` for empty or unreadable slots
+    slots: Vec<Option<SaveGame>>,
+    title_label: Option<Entity>,
+    slot_buttons: [Option<Entity>; SAVE_SLOTS],
+    back_button: Option<Entity>,
+    /// ui hierarchy root entity
+    root: Option<Entity>,
+    load_progress: Option<ProgressCounter>,
+}
+
+impl SaveSlotState {
+    /// Picks the slot `save_game` is written to.
+    pub fn save(save_game: SaveGame) -> Self {
+        Self::new(SlotMode::Save(save_game))
+    }
+
+    /// Picks the slot whose match is continued.
+    pub fn load() -> Self {
+        Self::new(SlotMode::Load)
+    }
+
+    fn new(mode: SlotMode) -> Self {
+        Self {
+            mode,
+            slots: vec![None; SAVE_SLOTS],
+            title_label: None,
+            slot_buttons: [None; SAVE_SLOTS],
+            back_button: None,
+            root: None,
+            load_progress: None,
+        }
+    }
+
+    fn saves_dir(world: &World) -> Option<PathBuf> {
+        world.try_fetch::<GamePaths>().map(|paths| paths.saves_dir.clone())
+    }
+
+    fn read_slots(&mut self, world: &World) {
+        let saves_dir = Self::saves_dir(world);
+        self.slots = (0..SAVE_SLOTS)
+            .map(|slot| {
+                let path = slot_path(saves_dir.as_ref()?, slot);
+                if !path.is_file() {
+                    return None;
+                }
+                SaveGame::read(&path)
+                    .map_err(|e| log::error!("Failed to read save slot {:?}: {}", path, e))
+                    .ok()
+            })
+            .collect();
+    }
+
+    /// Leaves the picker, either back to the pause menu or back to the main menu.
+    fn back(&self) -> Trans<CustomGameData<'static, 'static>, GameStateEvent> {
+        match self.mode {
+            SlotMode::Save(_) => {
+                log::info!("[Trans::Pop] Closing Save Slots!");
+                Trans::Pop
+            }
+            SlotMode::Load => {
+                log::info!("[Trans::Switch] Switching to MainMenu!");
+                Trans::Switch(Box::new(MainMenu::default()))
+            }
+        }
+    }
+
+    fn pick_slot(&self, world: &World, slot: usize) -> Trans<CustomGameData<'static, 'static>, GameStateEvent> {
+        match &self.mode {
+            SlotMode::Save(save_game) => {
+                let saves_dir = match Self::saves_dir(world) {
+                    Some(saves_dir) => saves_dir,
+                    None => {
+                        log::error!("No save directory configured!");
+                        return Trans::None;
+                    }
+                };
+                let path = slot_path(&saves_dir, slot);
+                match save_game.store(&path) {
+                    Ok(()) => {
+                        log::info!("Saved game to {:?}", path);
+                        self.back()
+                    }
+                    Err(e) => {
+                        log::error!("Failed to save game to {:?}: {}", path, e);
+                        Trans::None
+                    }
+                }
+            }
+            SlotMode::Load => match &self.slots[slot] {
+                Some(save_game) => {
+                    log::info!("[Trans::Switch] Switching to Pong!");
+                    Trans::Switch(Box::new(Pong::from_save(save_game.clone())))
+                }
+                None => Trans::None,
+            },
+        }
+    }
+}
+
+impl<'a, 'b> State<CustomGameData<'static, 'static>, GameStateEvent> for SaveSlotState {
+    fn on_start(&mut self, data: StateData<'_, CustomGameData<'_, '_>>) {
+        let world = data.world;
+
+        self.read_slots(world);
+
+        let mut progress = ProgressCounter::default();
+
+        self.root = Some(world.exec(|mut creator: UiCreator<'_>| creator.create("ui/save_slots.ron", &mut progress)));
+
+        self.load_progress = Some(progress);
+    }
+
+    fn on_stop(&mut self, data: StateData<'_, CustomGameData<'_, '_>>) {
+        if let Some(root) = self.root.take() {
+            delete_hierarchy(root, data.world).expect("Failed to remove SaveSlotState");
+        }
+        self.title_label = None;
+        self.slot_buttons = [None; SAVE_SLOTS];
+        self.back_button = None;
+        self.load_progress = None;
+    }
+
+    fn handle_event(
+        &mut self,
+        data: StateData<'_, CustomGameData<'_, '_>>,
+        event: GameStateEvent,
+    ) -> Trans<CustomGameData<'static, 'static>, GameStateEvent> {
+        match event {
+            GameStateEvent::Window(event) => {
+                if is_close_requested(&event) {
+                    log::info!("[Trans::Quit] Quitting Application!");
+                    Trans::Quit
+                } else if is_key_down(&event, VirtualKeyCode::Escape) {
+                    self.back()
+                } else {
+                    Trans::None
+                }
+            }
+            GameStateEvent::Ui(UiEvent {
+                event_type: UiEventType::Click,
+                target,
+            }) => {
+                if Some(target) == self.back_button {
+                    return self.back();
+                }
+                match self.slot_buttons.iter().position(|button| *button == Some(target)) {
+                    Some(slot) => self.pick_slot(data.world, slot),
+                    None => Trans::None,
+                }
+            }
+            GameStateEvent::Test(test_event) => crate::test_harness::handle_test_event(&test_event),
+            _ => Trans::None,
+        }
+    }
+
+    fn update(
+        &mut self,
+        data: StateData<'_, CustomGameData<'_, '_>>,
+    ) -> Trans<CustomGameData<'static, 'static>, GameStateEvent> {
+        data.data.update(data.world, true);
+        // once deferred creation of the root ui entity finishes, look up the widgets
+        if self.title_label.is_none() || self.back_button.is_none() || self.slot_buttons.iter().any(Option::is_none) {
+            let slot_buttons = &mut self.slot_buttons;
+            let title_label = &mut self.title_label;
+            let back_button = &mut self.back_button;
+            data.world.exec(|ui_finder: UiFinder<'_>| {
+                *title_label = ui_finder.find(TITLE_LABEL_ID);
+                *back_button = ui_finder.find(BACK_BUTTON_ID);
+                for (button, id) in slot_buttons.iter_mut().zip(SLOT_BUTTON_IDS.iter()) {
+                    *button = ui_finder.find(id);
+                }
+            });
+
+            if let Some(title_label) = self.title_label {
+                if let Some(text) = data.world.write_storage::<UiText>().get_mut(title_label) {
+                    text.text = match self.mode {
+                        SlotMode::Save(_) => "SAVE GAME".to_string(),
+                        SlotMode::Load => "LOAD GAME".to_string(),
+                    };
+                }
+            }
+            for (slot, button) in self.slot_buttons.iter().enumerate() {
+                if let Some(button) = *button {
+                    let label = match &self.slots[slot] {
+                        Some(save_game) => format!("SLOT {}: {}", slot + 1, format_scores(&save_game.scores)),
+                        None => format!("SLOT {}: EMPTY", slot + 1),
+                    };
+                    set_button_text(data.world, button, &label);
+                }
+            }
+        }
+
+        Trans::None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_harness::{click, find_ui, temp_path, wait_for_ui, SendMockEvents};
+    use std::{fs, time::Duration};
+
+    fn temp_paths(name: &str) -> GamePaths {
+        let saves_dir = temp_path(name);
+        let _ = fs::remove_dir_all(&saves_dir);
+        GamePaths {
+            saves_dir,
+            ..crate::initialize_paths().expect("valid paths required")
+        }
+    }
+
+    #[test]
+    fn save_into_slot() {
+        amethyst::start_logger(amethyst::LoggerConfig::default());
+        let paths = temp_paths("save-slot");
+        let saved_slot = slot_path(&paths.saves_dir, 1);
+        let saves_dir = paths.saves_dir.clone();
+
+        let test_result = crate::test_harness::IntegrationTestApplication::pong_base()
+            .with_resource(paths)
+            .with_state(|| {
+                SendMockEvents::test_state(|_world| {
+                    Box::new(SaveSlotState::save(SaveGame {
+                        scores: vec![1, 2],
+                        ..SaveGame::default()
+                    }))
+                })
+                .with_condition_barrier(wait_for_ui(BACK_BUTTON_ID), Duration::from_secs(20))
+                .with_step(click(SLOT_BUTTON_IDS[1]))
+                .with_wait(0.5)
+                .end_test()
+            })
+            .run();
+        assert!(test_result.is_ok());
+
+        let saved = SaveGame::read(&saved_slot).expect("game was not saved");
+        fs::remove_dir_all(&saves_dir).expect("could not remove save directory");
+        assert_eq!(saved.scores, vec![1, 2]);
+    }
+
+    #[test]
+    fn load_from_slot() {
+        amethyst::start_logger(amethyst::LoggerConfig::default());
+        let paths = temp_paths("load-slot");
+        let saves_dir = paths.saves_dir.clone();
+        SaveGame {
+            scores: vec![8, 9],
+            ..SaveGame::default()
+        }
+        .store(slot_path(&saves_dir, 0))
+        .expect("could not write save game");
+
+        let test_result = crate::test_harness::IntegrationTestApplication::pong_base()
+            .with_resource(paths)
+            .with_setup(|world| {
+                let mut progress = amethyst::assets::ProgressCounter::default();
+                crate::audio::initialise_audio(world, &mut progress);
+            })
+            .with_state(|| {
+                SendMockEvents::test_state(|_world| Box::new(SaveSlotState::load()))
+                    .with_condition_barrier(wait_for_ui(BACK_BUTTON_ID), Duration::from_secs(20))
+                    .with_wait(0.5)
+                    .with_step(|world| {
+                        let button = find_ui(world, SLOT_BUTTON_IDS[0]).expect("Could not find slot button");
+                        let hierarchy = world.read_resource::<amethyst::core::ParentHierarchy>();
+                        let ui_text = world.read_storage::<UiText>();
+                        let label = std::iter::once(button)
+                            .chain(hierarchy.children(button).iter().cloned())
+                            .find_map(|entity| ui_text.get(entity))
+                            .expect("slot button has no label");
+                        assert_eq!(label.text, "SLOT 1: 8 - 9");
+                    })
+                    .with_step(click(SLOT_BUTTON_IDS[0]))
+                    .with_wait(0.5)
+                    .with_step(|world| {
+                        assert_eq!(world.read_resource::<crate::ScoreBoard>().scores(), &[8, 9]);
+                    })
+                    .end_test()
+            })
+            .run();
+        fs::remove_dir_all(&saves_dir).expect("could not remove save directory");
+        assert!(test_result.is_ok());
+    }
+
+    #[test]
+    fn empty_slot_is_ignored() {
+        amethyst::start_logger(amethyst::LoggerConfig::default());
+        let test_result = crate::test_harness::IntegrationTestApplication::pong_base()
+            .with_resource(temp_paths("empty-slot"))
+            .with_state(|| {
+                SendMockEvents::test_state(|_world| Box::new(SaveSlotState::load()))
+                    .with_condition_barrier(wait_for_ui(BACK_BUTTON_ID), Duration::from_secs(20))
+                    .with_step(click(SLOT_BUTTON_IDS[2]))
+                    .with_wait(0.5)
+                    .with_step(|world| assert!(find_ui(world, BACK_BUTTON_ID).is_some()))
+                    .end_test()
+            })
+            .run();
+        assert!(test_result.is_ok());
+    }
+
+    #[test]
+    fn click_back_button() {
+        amethyst::start_logger(amethyst::LoggerConfig::default());
+        let test_result = crate::test_harness::IntegrationTestApplication::pong_base()
+            .with_state(|| {
+                SendMockEvents::test_state(|_world| Box::new(SaveSlotState::load()))
+                    .with_condition_barrier(wait_for_ui(BACK_BUTTON_ID), Duration::from_secs(20))
+                    .with_step(click(BACK_BUTTON_ID))
+                    .with_wait(0.5)
+                    .end_test()
+            })
+            .run();
+        assert!(test_result.is_ok());
+    }
+}
@@ -1,9 +1,13 @@
+use crate::config::UserSettings;
 use amethyst::{
-    core::transform::ParentHierarchy,
+    core::{frame_limiter::FrameLimiter, transform::ParentHierarchy},
     ecs::{
         error::WrongGeneration,
         prelude::{Entity, World, WorldExt},
     },
+    ui::UiText,
+    window::Window,
+    winit::dpi::LogicalSize,
 };
 use std::iter;
 
@@ -19,11 +23,69 @@ pub fn delete_hierarchy(root: Entity, world: &mut World) -> Result<(), WrongGene
     world.delete_entities(&entities)
 }
 
+/// Replaces the label of a button created from a UI prefab. Depending on how the button was built
+/// the `UiText` lives on the button entity itself or on one of its children.
+pub fn set_button_text(world: &World, button: Entity, text: &str) {
+    let hierarchy = world.read_resource::<ParentHierarchy>();
+    let mut ui_texts = world.write_storage::<UiText>();
+    let label = iter::once(button)
+        .chain(hierarchy.children(button).iter().cloned())
+        .find(|entity| ui_texts.contains(*entity));
+    if let Some(ui_text) = label.and_then(|entity| ui_texts.get_mut(entity)) {
+        if ui_text.text != text {
+            ui_text.text = text.to_string();
+        }
+    }
+}
+
+/// Formats the score of every player for a label, e.g. "11 - 4".
+pub fn format_scores(scores: &[i32]) -> String {
+    scores.iter().map(i32::to_string).collect::<Vec<_>>().join(" - ")
+}
+
+/// Applies the `UserSettings` that can change while the game is running: music volume, window
+/// mode and size, and the frame limit. Vsync only takes effect through the frame limit.
+pub fn apply_user_settings(world: &mut World) {
+    let settings = world.read_resource::<UserSettings>().clone();
+
+    #[cfg(not(test))]
+    crate::audio::set_sink_volume(world, settings.music_volume);
+
+    if let Some(window) = world.try_fetch::<Window>() {
+        if settings.fullscreen {
+            window.set_fullscreen(Some(window.get_current_monitor()));
+        } else {
+            window.set_fullscreen(None);
+            if let Some((width, height)) = settings.resolution {
+                window.set_inner_size(LogicalSize::new(f64::from(width), f64::from(height)));
+            }
+        }
+    }
+
+    if cfg!(not(test)) {
+        if let Some(mut frame_limiter) = world.try_fetch_mut::<FrameLimiter>() {
+            let (strategy, fps) = settings.frame_rate_limit();
+            frame_limiter.set_rate(strategy, fps);
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
-    use amethyst::{core::Parent, prelude::Builder};
+    use amethyst::{
+        assets::Loader,
+        core::Parent,
+        prelude::Builder,
+        ui::{Anchor, TtfFormat, UiTransform},
+    };
+
+    #[test]
+    fn test_format_scores() {
+        assert_eq!(format_scores(&[11, 4]), "11 - 4");
+        assert_eq!(format_scores(&[3, 0, 11, 7]), "3 - 0 - 11 - 7");
+    }
 
     #[test]
     fn test_delete_single_entity() {
@@ -83,4 +145,48 @@ mod tests {
             .run();
         assert!(test_result.is_ok());
     }
+
+    #[test]
+    fn test_set_button_text_on_child() {
+        amethyst::start_logger(amethyst::LoggerConfig::default());
+        let test_result = crate::test_harness::IntegrationTestApplication::pong_base()
+            .with_setup(|world| {
+                let font = world
+                    .read_resource::<Loader>()
+                    .load("font/square.ttf", TtfFormat, (), &world.read_resource());
+                let button = world
+                    .create_entity()
+                    .with(UiTransform::new(
+                        "button".to_string(),
+                        Anchor::Middle,
+                        Anchor::Middle,
+                        0.,
+                        0.,
+                        1.,
+                        200.,
+                        50.,
+                    ))
+                    .build();
+                world
+                    .create_entity()
+                    .with(UiText::new(font, "old".to_string(), [1.0, 1.0, 1.0, 1.0], 24.))
+                    .with(Parent { entity: button })
+                    .build();
+            })
+            .with_assertion(|world| {
+                let mut button = None;
+                world.exec(|ui_finder: amethyst::ui::UiFinder<'_>| {
+                    button = ui_finder.find("button");
+                });
+                set_button_text(world, button.expect("Could not find button"), "new");
+
+                let hierarchy = world.read_resource::<ParentHierarchy>();
+                let ui_texts = world.read_storage::<UiText>();
+                let children = hierarchy.children(button.expect("Could not find button"));
+                assert_eq!(1, children.len());
+                assert_eq!(ui_texts.get(children[0]).map(|text| text.text.as_str()), Some("new"));
+            })
+            .run();
+        assert!(test_result.is_ok());
+    }
 }
@@ -1,9 +1,7 @@
-#[cfg(not(test))]
-use crate::audio::set_sink_volume;
 use crate::{audio::initialise_audio, game_data::CustomGameData};
 
 use crate::{
-    states::{util::delete_hierarchy, GameplayState},
+    states::{apply_user_settings, util::delete_hierarchy, GameplayState},
     GameStateEvent,
 };
 use amethyst::{
@@ -33,8 +31,7 @@ impl<'a, 'b> State<CustomGameData<'static, 'static>, GameStateEvent> for Welcome
         initialise_audio(data.world, &mut progress);
         self.load_progress = Some(progress);
 
-        #[cfg(not(test))]
-        set_sink_volume(data.world, 0.2);
+        apply_user_settings(data.world);
     }
 
     fn on_stop(&mut self, data: StateData<'_, CustomGameData<'_, '_>>) {
@@ -90,10 +87,10 @@ impl<'a, 'b> State<CustomGameData<'static, 'static>, GameStateEvent> for Welcome
 mod tests {
     use super::*;
 
-    use crate::test_harness::SendMockEvents;
+    use crate::{bindings::GameBindings, test_harness::SendMockEvents};
     use amethyst::{
         core::shrev::EventChannel,
-        input::{InputEvent, StringBindings},
+        input::InputEvent,
         ui::{UiEvent, UiEventType},
         winit,
         winit::*,
@@ -221,12 +218,12 @@ mod tests {
             .with_state(|| {
                 SendMockEvents::test_state(|_world| Box::new(WelcomeScreen::default()))
                     .with_step(|world| {
-                        let event = InputEvent::<StringBindings>::CursorMoved {
+                        let event = InputEvent::<GameBindings>::CursorMoved {
                             delta_x: 0.0,
                             delta_y: 0.0,
                         };
 
-                        let mut events: Write<EventChannel<InputEvent<StringBindings>>> = world.system_data();
+                        let mut events: Write<EventChannel<InputEvent<GameBindings>>> = world.system_data();
                         events.single_write(event);
                     })
                     .end_test()
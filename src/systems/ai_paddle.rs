@@ -0,0 +1,335 @@
+use crate::{config::PongConfig, rng::GameRng, systems::FixedTimestep, Ball, Paddle};
+use amethyst::{
+    core::{transform::Transform, SystemDesc},
+    derive::SystemDesc,
+    ecs::prelude::{
+        Component, DenseVecStorage, Join, Read, ReadStorage, System, SystemData, World, Write, WriteStorage,
+    },
+};
+use serde::{Deserialize, Serialize};
+
+/// How well a CPU controlled paddle plays.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AiDifficulty {
+    Easy,
+    Normal,
+    Hard,
+}
+
+/// Tuning values behind an `AiDifficulty`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AiProfile {
+    /// Seconds between two re-evaluations of where the ball is going to be.
+    pub reaction_delay: f32,
+    /// Fraction of the paddle velocity the AI is allowed to use.
+    pub max_speed: f32,
+    /// Maximum distance in world units the AI aims away from the predicted intercept.
+    pub aim_error: f32,
+}
+
+impl AiDifficulty {
+    pub const fn profile(self) -> AiProfile {
+        match self {
+            Self::Easy => AiProfile {
+                reaction_delay: 0.4,
+                max_speed: 0.6,
+                aim_error: 8.0,
+            },
+            Self::Normal => AiProfile {
+                reaction_delay: 0.2,
+                max_speed: 0.85,
+                aim_error: 4.0,
+            },
+            Self::Hard => AiProfile {
+                reaction_delay: 0.05,
+                max_speed: 1.0,
+                aim_error: 0.0,
+            },
+        }
+    }
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Easy => "EASY",
+            Self::Normal => "NORMAL",
+            Self::Hard => "HARD",
+        }
+    }
+}
+
+/// Marks a paddle as controlled by the computer instead of the `PaddleSystem`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AiPaddle {
+    pub difficulty: AiDifficulty,
+    reaction_timer: f32,
+    /// Position along its axis the paddle moves to.
+    target: Option<f32>,
+}
+
+impl AiPaddle {
+    pub const fn new(difficulty: AiDifficulty) -> Self {
+        Self {
+            difficulty,
+            reaction_timer: 0.0,
+            target: None,
+        }
+    }
+}
+
+impl Component for AiPaddle {
+    type Storage = DenseVecStorage<Self>;
+}
+
+/// Predicts the height at which a ball will reach `target_x`, folding the flight path at the top
+/// and bottom walls the same way the `BounceSystem` reflects it. Returns `None` if the ball is not
+/// moving towards `target_x`.
+pub fn predict_intercept(
+    position: [f32; 2],
+    velocity: [f32; 2],
+    radius: f32,
+    target_x: f32,
+    arena_height: f32,
+) -> Option<f32> {
+    let distance = target_x - position[0];
+    if velocity[0] == 0.0 || distance.signum() != velocity[0].signum() {
+        return None;
+    }
+    let time = distance / velocity[0];
+    let unfolded_y = position[1] + velocity[1] * time;
+
+    // The ball centre moves between `radius` and `arena_height - radius`; every time it crosses
+    // one of these bounds it is mirrored back.
+    let span = arena_height - 2.0 * radius;
+    if span <= 0.0 {
+        return Some(arena_height * 0.5);
+    }
+    let mut folded = (unfolded_y - radius).rem_euclid(2.0 * span);
+    if folded > span {
+        folded = 2.0 * span - folded;
+    }
+    Some(folded + radius)
+}
+
+/// This system moves all paddles with an `AiPaddle` component along their axis towards the point
+/// where the next incoming ball is predicted to arrive.
+#[derive(SystemDesc)]
+pub struct AiPaddleSystem;
+
+impl<'s> System<'s> for AiPaddleSystem {
+    type SystemData = (
+        ReadStorage<'s, Ball>,
+        WriteStorage<'s, Paddle>,
+        WriteStorage<'s, AiPaddle>,
+        WriteStorage<'s, Transform>,
+        Read<'s, FixedTimestep>,
+        Read<'s, PongConfig>,
+        Write<'s, GameRng>,
+    );
+
+    fn run(
+        &mut self,
+        (balls, mut paddles, mut ai_paddles, mut transforms, timestep, config, mut rng): Self::SystemData,
+    ) {
+        let ball_states = (&balls, &transforms)
+            .join()
+            .map(|(ball, transform)| {
+                (
+                    [transform.translation().x, transform.translation().y],
+                    ball.velocity,
+                    ball.radius,
+                )
+            })
+            .collect::<Vec<_>>();
+
+        for (paddle, ai_paddle, transform) in (&mut paddles, &mut ai_paddles, &mut transforms).join() {
+            let profile = ai_paddle.difficulty.profile();
+            // `predict_intercept` works across the arena from left to right, so the coordinates are
+            // swapped for the top and bottom paddles.
+            let axis = paddle.side.axis();
+            let depth = 1 - axis;
+            let arena_length = config.arena.length(axis);
+
+            ai_paddle.reaction_timer -= timestep.step();
+            if ai_paddle.reaction_timer <= 0.0 || ai_paddle.target.is_none() {
+                ai_paddle.reaction_timer = profile.reaction_delay;
+
+                // Track the ball that reaches this paddle first, return to the centre otherwise.
+                let face_offset = paddle.side.normal()[depth] * paddle.width * 0.5;
+                let paddle_front = transform.translation()[depth] + face_offset;
+                let incoming = ball_states
+                    .iter()
+                    .filter_map(|(position, velocity, radius)| {
+                        let position = [position[depth], position[axis]];
+                        let velocity = [velocity[depth], velocity[axis]];
+                        let target = paddle_front + radius * face_offset.signum();
+                        predict_intercept(position, velocity, *radius, target, arena_length)
+                            .map(|intercept| ((target - position[0]) / velocity[0], intercept))
+                    })
+                    .min_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(std::cmp::Ordering::Equal));
+
+                ai_paddle.target = Some(match incoming {
+                    Some((_, intercept)) => intercept + rng.gen_range(-profile.aim_error, profile.aim_error),
+                    None => arena_length * 0.5,
+                });
+            }
+
+            let previous = transform.translation()[axis];
+            if let Some(target) = ai_paddle.target {
+                let max_step = paddle.velocity * profile.max_speed * timestep.step();
+                let step = (target - previous).max(-max_step).min(max_step);
+                transform.translation_mut()[axis] = (previous + step)
+                    .max(paddle.height * 0.5)
+                    .min(arena_length - paddle.height * 0.5);
+            }
+            paddle.movement = (transform.translation()[axis] - previous) / timestep.step();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{
+        states::{initialise_ball, initialise_paddles, load_sprite_sheet},
+        GameMode, MatchSettings, PaddleController, Side,
+    };
+    use amethyst::{ecs::prelude::WorldExt, prelude::Builder};
+    use assert_approx_eq::assert_approx_eq;
+    use test_case::test_case;
+
+    #[test_case([80.0, 45.0], [-10.0, 0.0], 2.0, 4.0, 90.0 => Some(45.0) ; "straight")]
+    #[test_case([80.0, 45.0], [10.0, 0.0], 2.0, 4.0, 90.0 => None ; "moving away")]
+    #[test_case([80.0, 45.0], [0.0, 10.0], 2.0, 4.0, 90.0 => None ; "not moving horizontally")]
+    #[test_case([80.0, 45.0], [-10.0, 2.0], 2.0, 0.0, 90.0 => Some(61.0) ; "no bounce")]
+    #[test_case([80.0, 80.0], [-10.0, 2.0], 2.0, 0.0, 90.0 => Some(80.0) ; "one bounce at the top")]
+    #[test_case([80.0, 10.0], [-10.0, -2.0], 2.0, 0.0, 90.0 => Some(10.0) ; "one bounce at the bottom")]
+    #[test_case([0.0, 2.0], [10.0, 10.0], 2.0, 172.0, 90.0 => Some(2.0) ; "two bounces")]
+    fn test_predict_intercept(
+        position: [f32; 2],
+        velocity: [f32; 2],
+        radius: f32,
+        target_x: f32,
+        arena_height: f32,
+    ) -> Option<f32> {
+        predict_intercept(position, velocity, radius, target_x, arena_height).map(f32::round)
+    }
+
+    #[test]
+    fn cpu_paddle_tracks_incoming_ball() {
+        amethyst::start_logger(amethyst::LoggerConfig::default());
+        let test_result = crate::test_harness::IntegrationTestApplication::pong_base()
+            .with_resource(MatchSettings {
+                left: PaddleController::Cpu(AiDifficulty::Hard),
+                right: PaddleController::Human,
+                ..MatchSettings::default()
+            })
+            .with_resource(GameRng::from_seed(1))
+            .with_setup(|world| {
+                let root_entity = world.create_entity().with(Transform::default()).build();
+                let sprite_sheet_handle = load_sprite_sheet(world);
+                initialise_paddles(world, root_entity, sprite_sheet_handle.clone());
+                initialise_ball(
+                    world,
+                    root_entity,
+                    sprite_sheet_handle,
+                    2.0,
+                    [-75.0, 0.0],
+                    Some([80.0, 80.0]),
+                );
+            })
+            .with_system_single(AiPaddleSystem, "ai_paddle_system", &[])
+            .with_assertion(|world| {
+                let paddles = world.read_storage::<Paddle>();
+                let ai_paddles = world.read_storage::<AiPaddle>();
+                let transforms = world.read_storage::<Transform>();
+                assert_eq!(1, ai_paddles.count());
+
+                for (paddle, ai_paddle, transform) in (&paddles, &ai_paddles, &transforms).join() {
+                    assert!(paddle.side == Side::Left);
+                    assert_approx_eq!(ai_paddle.target.expect("target was not set"), 80.0);
+                    // The paddle starts in the middle and may only move towards the target.
+                    assert!(transform.translation().y >= 45.0);
+                }
+            })
+            .run();
+        assert!(test_result.is_ok());
+    }
+
+    #[test]
+    fn cpu_top_paddle_tracks_incoming_ball() {
+        amethyst::start_logger(amethyst::LoggerConfig::default());
+        let test_result = crate::test_harness::IntegrationTestApplication::pong_base()
+            .with_resource(MatchSettings {
+                top: PaddleController::Cpu(AiDifficulty::Hard),
+                mode: GameMode::FourPlayer,
+                ..MatchSettings::default()
+            })
+            .with_resource(GameRng::from_seed(1))
+            .with_setup(|world| {
+                let root_entity = world.create_entity().with(Transform::default()).build();
+                let sprite_sheet_handle = load_sprite_sheet(world);
+                initialise_paddles(world, root_entity, sprite_sheet_handle.clone());
+                initialise_ball(
+                    world,
+                    root_entity,
+                    sprite_sheet_handle,
+                    2.0,
+                    [0.0, 75.0],
+                    Some([130.0, 45.0]),
+                );
+            })
+            .with_system_single(AiPaddleSystem, "ai_paddle_system", &[])
+            .with_assertion(|world| {
+                let paddles = world.read_storage::<Paddle>();
+                let ai_paddles = world.read_storage::<AiPaddle>();
+                let transforms = world.read_storage::<Transform>();
+                assert_eq!(1, ai_paddles.count());
+
+                for (paddle, ai_paddle, transform) in (&paddles, &ai_paddles, &transforms).join() {
+                    assert!(paddle.side == Side::Top);
+                    assert_approx_eq!(ai_paddle.target.expect("target was not set"), 130.0);
+                    // The paddle moves along the top wall only.
+                    assert!(transform.translation().x > 80.0);
+                    assert_approx_eq!(transform.translation().y, 88.0);
+                }
+            })
+            .run();
+        assert!(test_result.is_ok());
+    }
+
+    #[test]
+    fn cpu_paddle_returns_to_centre_without_incoming_ball() {
+        amethyst::start_logger(amethyst::LoggerConfig::default());
+        let test_result = crate::test_harness::IntegrationTestApplication::pong_base()
+            .with_resource(MatchSettings {
+                left: PaddleController::Human,
+                right: PaddleController::Cpu(AiDifficulty::Easy),
+                ..MatchSettings::default()
+            })
+            .with_setup(|world| {
+                let root_entity = world.create_entity().with(Transform::default()).build();
+                let sprite_sheet_handle = load_sprite_sheet(world);
+                initialise_paddles(world, root_entity, sprite_sheet_handle.clone());
+                initialise_ball(
+                    world,
+                    root_entity,
+                    sprite_sheet_handle,
+                    2.0,
+                    [-75.0, 10.0],
+                    None,
+                );
+            })
+            .with_system_single(AiPaddleSystem, "ai_paddle_system", &[])
+            .with_assertion(|world| {
+                let ai_paddles = world.read_storage::<AiPaddle>();
+                let transforms = world.read_storage::<Transform>();
+                for (ai_paddle, transform) in (&ai_paddles, &transforms).join() {
+                    assert_approx_eq!(ai_paddle.target.expect("target was not set"), 45.0);
+                    assert_approx_eq!(transform.translation().y, 45.0);
+                }
+            })
+            .run();
+        assert!(test_result.is_ok());
+    }
+}
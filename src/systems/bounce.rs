@@ -1,70 +1,304 @@
 use crate::{
     audio::{play_bounce, Sounds},
-    Ball, Paddle, Side,
+    config::{BounceConfig, PongConfig, UserSettings},
+    systems::{Effect, FixedTimestep, Obstacle, PowerUpKind, Serving, Stuck},
+    Ball, MatchSettings, Paddle, Side,
 };
 use amethyst::{
     assets::AssetStorage,
     audio::{output::Output, Source},
     core::{transform::Transform, SystemDesc},
     derive::SystemDesc,
-    ecs::prelude::{Join, Read, ReadExpect, ReadStorage, System, SystemData, World, WriteStorage},
+    ecs::prelude::{Entities, Join, Read, ReadExpect, ReadStorage, System, SystemData, World, WriteStorage},
 };
+
+/// Upper bound of contacts resolved for a ball in one simulation step. A ball needs more only when
+/// it crosses the arena several times per step.
+const MAX_CONTACTS_PER_STEP: usize = 4;
+
+/// Where a moving circle first touches an obstacle.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Contact {
+    /// Fraction of the movement after which the circle touches the obstacle, from 0.0 to 1.0.
+    pub time: f32,
+    /// Surface normal at the contact point, pointing away from the obstacle.
+    pub normal: [f32; 2],
+}
+
+/// A paddle or an obstacle of the level as seen by the balls during one simulation step.
+struct Collider {
+    /// side of the paddle, `None` for an `Obstacle`
+    paddle: Option<Side>,
+    min: [f32; 2],
+    max: [f32; 2],
+    /// velocity of the paddle along its axis, passed on to the ball as spin
+    movement: f32,
+    /// whether the paddle holds the balls hitting its front, see `PowerUpKind::Sticky`
+    sticky: bool,
+}
+
+impl Collider {
+    /// Direction the front of a paddle is facing.
+    fn front(&self) -> Option<[f32; 2]> {
+        self.paddle.map(Side::normal)
+    }
+
+    /// Normal of the face a ball inside the collider at `position` leaves it through. Balls leave
+    /// paddles through their front, and obstacles through the nearest face.
+    fn escape_normal(&self, position: [f32; 2]) -> [f32; 2] {
+        if let Some(front) = self.front() {
+            return front;
+        }
+        let distances = [
+            (position[0] - self.min[0], [-1.0, 0.0]),
+            (self.max[0] - position[0], [1.0, 0.0]),
+            (position[1] - self.min[1], [0.0, -1.0]),
+            (self.max[1] - position[1], [0.0, 1.0]),
+        ];
+        distances
+            .iter()
+            .fold((std::f32::INFINITY, [0.0, 0.0]), |nearest, &(distance, normal)| {
+                if distance < nearest.0 {
+                    (distance, normal)
+                } else {
+                    nearest
+                }
+            })
+            .1
+    }
+}
+
 /// This system is responsible for detecting collisions between balls and
-/// paddles, as well as balls and the top and bottom edges of the arena.
+/// paddles, balls and the obstacles of the level, as well as balls and the
+/// top and bottom edges of the arena, unless players guard these edges in
+/// the `GameMode` of the match.
+///
+/// Instead of only looking at where a ball ended up, it sweeps the ball along the path it travelled
+/// during the simulation step. The `MoveBallsSystem` moved the ball by `velocity * step`, so the
+/// system rewinds to the start of that path, moves the ball up to the first contact, bounces it off
+/// and continues with the remaining time. Fast balls can therefore not tunnel through a paddle or
+/// out of the arena.
+///
+/// Balls hitting the front of a paddle leave it at an angle depending on where they hit, see
+/// `deflect`. A sticky paddle holds them instead, and the `PowerUpSystem` releases them later. Other
+/// hits, including every hit on an obstacle, reflect the ball. Balls that touch each other at the
+/// end of the step bounce off each other, see `collide_balls`.
 #[derive(SystemDesc)]
 pub struct BounceSystem;
 
 impl<'s> System<'s> for BounceSystem {
     type SystemData = (
+        Entities<'s>,
         WriteStorage<'s, Ball>,
+        ReadStorage<'s, Serving>,
+        WriteStorage<'s, Stuck>,
+        ReadStorage<'s, Effect>,
         ReadStorage<'s, Paddle>,
-        ReadStorage<'s, Transform>,
+        ReadStorage<'s, Obstacle>,
+        WriteStorage<'s, Transform>,
         Read<'s, AssetStorage<Source>>,
         ReadExpect<'s, Sounds>,
         Option<Read<'s, Output>>,
+        Read<'s, PongConfig>,
+        Read<'s, MatchSettings>,
+        Read<'s, UserSettings>,
+        Read<'s, FixedTimestep>,
     );
 
-    fn run(&mut self, (mut balls, paddles, transforms, storage, sounds, audio_output): Self::SystemData) {
-        // Check whether a ball collided, and bounce off accordingly.
-        //
-        // We also check for the velocity of the ball every time, to prevent multiple collisions
-        // from occurring.
-        for (ball, transform) in (&mut balls, &transforms).join() {
-            use crate::ARENA_HEIGHT;
-
-            let ball_x = transform.translation().x;
-            let ball_y = transform.translation().y;
-
-            // Bounce at the top or the bottom of the arena.
-            if (ball_y <= ball.radius && ball.velocity[1] < 0.0)
-                || (ball_y >= ARENA_HEIGHT - ball.radius && ball.velocity[1] > 0.0)
-            {
-                ball.velocity[1] = -ball.velocity[1];
-                play_bounce(&*sounds, &storage, audio_output.as_ref().map(std::ops::Deref::deref));
+    fn run(
+        &mut self,
+        (
+            entities,
+            mut balls,
+            servings,
+            mut stuck_balls,
+            effects,
+            paddles,
+            level_obstacles,
+            mut transforms,
+            storage,
+            sounds,
+            audio_output,
+            config,
+            match_settings,
+            user_settings,
+            timestep,
+        ): Self::SystemData,
+    ) {
+        // Paddles and obstacles collide at the position they moved to during this step.
+        let mut colliders = (&paddles, &transforms)
+            .join()
+            .map(|(paddle, transform)| {
+                let centre = [transform.translation().x, transform.translation().y];
+                let size = paddle.size();
+                let half_size = [size[0] * 0.5, size[1] * 0.5];
+                Collider {
+                    paddle: Some(paddle.side),
+                    min: [centre[0] - half_size[0], centre[1] - half_size[1]],
+                    max: [centre[0] + half_size[0], centre[1] + half_size[1]],
+                    movement: paddle.movement,
+                    sticky: (&effects).join().any(|effect| {
+                        effect.kind == PowerUpKind::Sticky && effect.side == paddle.side && effect.remaining > 0.0
+                    }),
+                }
+            })
+            .collect::<Vec<_>>();
+        let walls = !match_settings.mode.sides().contains(&Side::Top);
+        colliders.extend((&level_obstacles, &transforms).join().map(|(obstacle, transform)| {
+            let centre = [transform.translation().x, transform.translation().y];
+            let half_size = [obstacle.width * 0.5, obstacle.height * 0.5];
+            Collider {
+                paddle: None,
+                min: [centre[0] - half_size[0], centre[1] - half_size[1]],
+                max: [centre[0] + half_size[0], centre[1] + half_size[1]],
+                movement: 0.0,
+                sticky: false,
+            }
+        }));
+
+        let mut caught = Vec::new();
+        for (entity, ball, transform, _) in (&entities, &mut balls, &mut transforms, !&stuck_balls).join() {
+            let mut time_left = timestep.step();
+            let mut position = [
+                transform.translation().x - ball.velocity[0] * time_left,
+                transform.translation().y - ball.velocity[1] * time_left,
+            ];
+
+            for contact_index in 0..MAX_CONTACTS_PER_STEP {
+                let movement = [ball.velocity[0] * time_left, ball.velocity[1] * time_left];
+
+                // Bounce at the top or the bottom of the arena.
+                let mut first_contact = if walls {
+                    sweep_walls(position, movement, ball.radius, config.arena.height).map(|contact| (contact, None))
+                } else {
+                    None
+                };
+
+                // Bounce at the paddles and obstacles.
+                for collider in &colliders {
+                    let (min, max) = (collider.min, collider.max);
+                    // A ball can start the step inside a paddle, e.g. when the paddle moved into
+                    // it. It then bounces off the front of the paddle if it is moving towards the
+                    // back, or off the nearest face of an obstacle if it is moving inwards. To
+                    // determine whether the ball is inside, we create a larger rectangle around
+                    // it, by subtracting the ball radius from the lowest coordinates, and adding
+                    // the ball radius to the highest ones.
+                    let contact = if contact_index == 0
+                        && point_in_rect(
+                            position[0],
+                            position[1],
+                            min[0] - ball.radius,
+                            min[1] - ball.radius,
+                            max[0] + ball.radius,
+                            max[1] + ball.radius,
+                        ) {
+                        let normal = collider.escape_normal(position);
+                        if ball.velocity[0] * normal[0] + ball.velocity[1] * normal[1] < 0.0 {
+                            Some(Contact { time: 0.0, normal })
+                        } else {
+                            None
+                        }
+                    } else {
+                        sweep_circle_aabb(position, movement, ball.radius, min, max)
+                    };
+                    if let Some(contact) = contact {
+                        if first_contact.map_or(true, |(first, _)| contact.time < first.time) {
+                            first_contact = Some((contact, Some(collider)));
+                        }
+                    }
+                }
+
+                match first_contact {
+                    Some((contact, collider)) => {
+                        position[0] += movement[0] * contact.time;
+                        position[1] += movement[1] * contact.time;
+                        time_left *= 1.0 - contact.time;
+                        let paddle =
+                            collider.and_then(|collider| Some((collider, collider.paddle?, collider.front()?)));
+                        if let Some((_, side, _)) = paddle {
+                            ball.last_hit = Some(side);
+                        }
+                        ball.velocity = match paddle {
+                            // Hits on the front of a paddle, including its front corners.
+                            Some((paddle, side, front))
+                                if contact.normal[0] * front[0] + contact.normal[1] * front[1] > 0.0 =>
+                            {
+                                let axis = side.axis();
+                                let centre = (paddle.min[axis] + paddle.max[axis]) * 0.5;
+                                let reach = (paddle.max[axis] - paddle.min[axis]) * 0.5 + ball.radius;
+                                let velocity = deflect(
+                                    ball.velocity,
+                                    front,
+                                    (position[axis] - centre) / reach,
+                                    paddle.movement,
+                                    &config.bounce,
+                                );
+                                if paddle.sticky {
+                                    caught.push((
+                                        entity,
+                                        Stuck {
+                                            side,
+                                            offset: position[axis] - centre,
+                                            velocity,
+                                            remaining: config.power_ups.stick_time,
+                                        },
+                                    ));
+                                    [0.0, 0.0]
+                                } else {
+                                    velocity
+                                }
+                            }
+                            _ => reflect(ball.velocity, contact.normal),
+                        };
+                        play_bounce(
+                            &*sounds,
+                            &storage,
+                            audio_output.as_ref().map(std::ops::Deref::deref),
+                            user_settings.sfx_volume,
+                        );
+                    }
+                    None => break,
+                }
             }
 
-            // Bounce at the paddles.
-            for (paddle, paddle_transform) in (&paddles, &transforms).join() {
-                let paddle_x = paddle_transform.translation().x - (paddle.width * 0.5);
-                let paddle_y = paddle_transform.translation().y - (paddle.height * 0.5);
-
-                // To determine whether the ball has collided with a paddle, we create a larger
-                // rectangle around the current one, by subtracting the ball radius from the
-                // lowest coordinates, and adding the ball radius to the highest ones. The ball
-                // is then within the paddle if its centre is within the larger wrapper
-                // rectangle.
-                if point_in_rect(
-                    ball_x,
-                    ball_y,
-                    paddle_x - ball.radius,
-                    paddle_y - ball.radius,
-                    paddle_x + (paddle.width + ball.radius),
-                    paddle_y + (paddle.height + ball.radius),
-                ) && ((paddle.side == Side::Left && ball.velocity[0] < 0.0)
-                    || (paddle.side == Side::Right && ball.velocity[0] > 0.0))
-                {
-                    ball.velocity[0] = -ball.velocity[0];
-                    play_bounce(&*sounds, &storage, audio_output.as_ref().map(std::ops::Deref::deref));
+            transform.set_translation_x(position[0] + ball.velocity[0] * time_left);
+            transform.set_translation_y(position[1] + ball.velocity[1] * time_left);
+        }
+        for (entity, stuck) in caught {
+            stuck_balls.insert(entity, stuck).expect("ball entity is alive");
+        }
+
+        // Bounce balls off each other. Balls waiting for their serve sit in the centre of the
+        // arena, the other balls pass through them, as well as through balls held by a paddle.
+        let in_play = (&entities, &balls, &transforms, !&servings, !&stuck_balls)
+            .join()
+            .map(|(entity, ball, transform, _, _)| {
+                (entity, [transform.translation().x, transform.translation().y], ball.radius)
+            })
+            .collect::<Vec<_>>();
+        for (index, &(first, first_position, first_radius)) in in_play.iter().enumerate() {
+            for &(second, second_position, second_radius) in &in_play[index + 1..] {
+                let velocities = match (balls.get(first), balls.get(second)) {
+                    (Some(first_ball), Some(second_ball)) => [first_ball.velocity, second_ball.velocity],
+                    _ => continue,
+                };
+                if let Some([first_velocity, second_velocity]) = collide_balls(
+                    [first_position, second_position],
+                    velocities,
+                    [first_radius, second_radius],
+                ) {
+                    if let Some(ball) = balls.get_mut(first) {
+                        ball.velocity = first_velocity;
+                    }
+                    if let Some(ball) = balls.get_mut(second) {
+                        ball.velocity = second_velocity;
+                    }
+                    play_bounce(
+                        &*sounds,
+                        &storage,
+                        audio_output.as_ref().map(std::ops::Deref::deref),
+                        user_settings.sfx_volume,
+                    );
                 }
             }
         }
@@ -77,6 +311,168 @@ fn point_in_rect(x: f32, y: f32, left: f32, bottom: f32, right: f32, top: f32) -
     x >= left && x <= right && y >= bottom && y <= top
 }
 
+/// Mirrors `velocity` at a surface with the given unit `normal`.
+pub fn reflect(velocity: [f32; 2], normal: [f32; 2]) -> [f32; 2] {
+    let dot = velocity[0] * normal[0] + velocity[1] * normal[1];
+    [velocity[0] - 2.0 * dot * normal[0], velocity[1] - 2.0 * dot * normal[1]]
+}
+
+/// Velocity of a ball leaving the front of a paddle, which faces towards the unit vector `front`.
+///
+/// `offset` is where the ball hit the paddle along its axis, from -1.0 at the bottom or left edge
+/// to 1.0 at the top or right edge.
+/// Hits at the centre leave straight, hits at the edges leave at `max_angle`, tilted further by the
+/// spin of a moving paddle. Every hit multiplies the speed by `speed_up`, up to `max_speed`.
+pub fn deflect(
+    velocity: [f32; 2],
+    front: [f32; 2],
+    offset: f32,
+    paddle_movement: f32,
+    config: &BounceConfig,
+) -> [f32; 2] {
+    let speed = (velocity[0] * velocity[0] + velocity[1] * velocity[1]).sqrt();
+    // A ball that is already faster than the cap keeps its speed.
+    let speed = if speed < config.max_speed {
+        (speed * config.speed_up).min(config.max_speed)
+    } else {
+        speed
+    };
+
+    let max_angle = config.max_angle.to_radians();
+    let angle = offset.max(-1.0).min(1.0) * max_angle;
+    let along = speed * angle.sin() + paddle_movement * config.spin;
+    let angle = along.atan2(speed * angle.cos()).max(-max_angle).min(max_angle);
+    // The axis of the paddle points up or to the right.
+    let tangent = [front[1].abs(), front[0].abs()];
+    let (across, along) = (speed * angle.cos(), speed * angle.sin());
+    [
+        front[0] * across + tangent[0] * along,
+        front[1] * across + tangent[1] * along,
+    ]
+}
+
+/// Velocities of two balls after an elastic collision, with masses proportional to the area of
+/// the balls. Returns `None` if the balls do not touch, or already move apart.
+pub fn collide_balls(position: [[f32; 2]; 2], velocity: [[f32; 2]; 2], radius: [f32; 2]) -> Option<[[f32; 2]; 2]> {
+    let delta = [position[1][0] - position[0][0], position[1][1] - position[0][1]];
+    let distance = (delta[0] * delta[0] + delta[1] * delta[1]).sqrt();
+    if distance >= radius[0] + radius[1] {
+        return None;
+    }
+    // Balls right on top of each other are pushed apart horizontally.
+    let normal = if distance > 0.0 {
+        [delta[0] / distance, delta[1] / distance]
+    } else {
+        [1.0, 0.0]
+    };
+    let approach = (velocity[0][0] - velocity[1][0]) * normal[0] + (velocity[0][1] - velocity[1][1]) * normal[1];
+    if approach <= 0.0 {
+        return None;
+    }
+
+    let mass = [radius[0] * radius[0], radius[1] * radius[1]];
+    let impulse = [
+        2.0 * mass[1] / (mass[0] + mass[1]) * approach,
+        2.0 * mass[0] / (mass[0] + mass[1]) * approach,
+    ];
+    Some([
+        [velocity[0][0] - impulse[0] * normal[0], velocity[0][1] - impulse[0] * normal[1]],
+        [velocity[1][0] + impulse[1] * normal[0], velocity[1][1] + impulse[1] * normal[1]],
+    ])
+}
+
+/// Sweeps a circle by `movement` against the bottom and top edges of an arena of the given
+/// `height`. A circle already beyond an edge and moving further out touches it right away.
+pub fn sweep_walls(start: [f32; 2], movement: [f32; 2], radius: f32, height: f32) -> Option<Contact> {
+    let (limit, normal) = if movement[1] < 0.0 {
+        (radius, [0.0, 1.0])
+    } else if movement[1] > 0.0 {
+        (height - radius, [0.0, -1.0])
+    } else {
+        return None;
+    };
+    let time = (limit - start[1]) / movement[1];
+    if time <= 1.0 {
+        Some(Contact {
+            time: time.max(0.0),
+            normal,
+        })
+    } else {
+        None
+    }
+}
+
+/// Sweeps a circle by `movement` against the axis aligned box from `min` to `max`, and returns the
+/// first contact if the circle moves into the box. A circle that already overlaps the box at
+/// `start` is not reported.
+///
+/// This is a ray cast against the box grown by `radius` on every side, with rounded corners.
+pub fn sweep_circle_aabb(
+    start: [f32; 2],
+    movement: [f32; 2],
+    radius: f32,
+    min: [f32; 2],
+    max: [f32; 2],
+) -> Option<Contact> {
+    // Ray cast against the grown box, one axis (slab) at a time.
+    let mut enter = std::f32::NEG_INFINITY;
+    let mut exit = std::f32::INFINITY;
+    let mut normal = [0.0, 0.0];
+    for axis in 0..2 {
+        let (low, high) = (min[axis] - radius, max[axis] + radius);
+        if movement[axis] == 0.0 {
+            if start[axis] < low || start[axis] > high {
+                return None;
+            }
+            continue;
+        }
+        let (near, far, sign) = if movement[axis] > 0.0 {
+            (low, high, -1.0)
+        } else {
+            (high, low, 1.0)
+        };
+        let axis_enter = (near - start[axis]) / movement[axis];
+        let axis_exit = (far - start[axis]) / movement[axis];
+        if axis_enter > enter {
+            enter = axis_enter;
+            normal = [0.0, 0.0];
+            normal[axis] = sign;
+        }
+        exit = exit.min(axis_exit);
+    }
+    if enter < 0.0 || enter > 1.0 || enter > exit {
+        return None;
+    }
+
+    // Within the corners of the grown box the circle touches the corner of the box instead, which
+    // is where the circle centre is at `radius` distance to the corner.
+    let hit = [start[0] + movement[0] * enter, start[1] + movement[1] * enter];
+    let outside = |axis: usize| hit[axis] < min[axis] || hit[axis] > max[axis];
+    if outside(0) && outside(1) {
+        let corner = [
+            if hit[0] < min[0] { min[0] } else { max[0] },
+            if hit[1] < min[1] { min[1] } else { max[1] },
+        ];
+        let offset = [start[0] - corner[0], start[1] - corner[1]];
+        let a = movement[0] * movement[0] + movement[1] * movement[1];
+        let b = 2.0 * (offset[0] * movement[0] + offset[1] * movement[1]);
+        let c = offset[0] * offset[0] + offset[1] * offset[1] - radius * radius;
+        let discriminant = b * b - 4.0 * a * c;
+        if discriminant < 0.0 {
+            return None;
+        }
+        let time = (-b - discriminant.sqrt()) / (2.0 * a);
+        if time < 0.0 || time > 1.0 {
+            return None;
+        }
+        let touch = [start[0] + movement[0] * time, start[1] + movement[1] * time];
+        normal = [(touch[0] - corner[0]) / radius, (touch[1] - corner[1]) / radius];
+        return Some(Contact { time, normal });
+    }
+
+    Some(Contact { time: enter, normal })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -84,6 +480,7 @@ mod tests {
     use crate::{
         audio::initialise_audio,
         states::{initialise_ball, initialise_paddles, load_sprite_sheet},
+        systems::MoveBallsSystem,
     };
     use amethyst::{
         assets::AssetStorage,
@@ -110,6 +507,73 @@ mod tests {
         point_in_rect(x, y, left, bottom, right, top)
     }
 
+    // The box is a left paddle of the default config, the circle is a ball with radius 2.
+    #[test_case([20.0, 45.0], [-30.0, 0.0] => Some((0.466_667, [1.0, 0.0])) ; "front face")]
+    #[test_case([2.0, 70.0], [0.0, -20.0] => Some((0.75, [0.0, 1.0])) ; "top face")]
+    #[test_case([10.0, 54.5], [-20.0, 0.0] => Some((0.233_856, [0.661_438, 0.75])) ; "rounded corner")]
+    #[test_case([10.0, 50.6], [-10.0, 10.0] => None ; "passes the rounded corner")]
+    #[test_case([20.0, 70.0], [-30.0, 0.0] => None ; "passes above")]
+    #[test_case([20.0, 45.0], [30.0, 0.0] => None ; "moves away")]
+    #[test_case([20.0, 45.0], [-10.0, 0.0] => None ; "stops short")]
+    #[test_case([2.0, 45.0], [-10.0, 0.0] => None ; "starts inside")]
+    fn test_sweep_circle_aabb(start: [f32; 2], movement: [f32; 2]) -> Option<(f32, [f32; 2])> {
+        let round = |value: f32| (value * 1_000_000.0).round() / 1_000_000.0;
+        sweep_circle_aabb(start, movement, 2.0, [0.0, 37.0], [4.0, 53.0])
+            .map(|contact| (round(contact.time), [round(contact.normal[0]), round(contact.normal[1])]))
+    }
+
+    #[test_case([10.0, 5.0], [0.0, -10.0] => Some((0.3, [0.0, 1.0])) ; "bottom")]
+    #[test_case([10.0, 80.0], [0.0, 20.0] => Some((0.4, [0.0, -1.0])) ; "top")]
+    #[test_case([10.0, 1.0], [5.0, -1.0] => Some((0.0, [0.0, 1.0])) ; "already beyond the bottom")]
+    #[test_case([10.0, 45.0], [0.0, 10.0] => None ; "stops short")]
+    #[test_case([10.0, 1.0], [5.0, 0.0] => None ; "moves along")]
+    fn test_sweep_walls(start: [f32; 2], movement: [f32; 2]) -> Option<(f32, [f32; 2])> {
+        let round = |value: f32| (value * 1_000_000.0).round() / 1_000_000.0;
+        sweep_walls(start, movement, 2.0, 90.0).map(|contact| (round(contact.time), contact.normal))
+    }
+
+    #[test_case([-3.0, 4.0], [1.0, 0.0] => [3.0, 4.0] ; "off a left paddle")]
+    #[test_case([3.0, 4.0], [0.0, -1.0] => [3.0, -4.0] ; "off the top wall")]
+    #[test_case([-1.0, -1.0], [0.6, 0.8] => [0.68, 1.24] ; "off a corner")]
+    fn test_reflect(velocity: [f32; 2], normal: [f32; 2]) -> [f32; 2] {
+        let [x, y] = reflect(velocity, normal);
+        [(x * 100.0).round() / 100.0, (y * 100.0).round() / 100.0]
+    }
+
+    // Uses the default `BounceConfig`: 60 degrees at the edges, 0.25 spin, 5% speed-up up to 180.
+    #[test_case([-75.0, 50.0], [1.0, 0.0], 0.0, 0.0 => [94.65, 0.0] ; "centre leaves straight and faster")]
+    #[test_case([-75.0, 50.0], [1.0, 0.0], 1.0, 0.0 => [47.32, 81.97] ; "edge leaves at max angle")]
+    #[test_case([-75.0, 50.0], [1.0, 0.0], 2.0, 0.0 => [47.32, 81.97] ; "offset is clamped")]
+    #[test_case([75.0, 50.0], [-1.0, 0.0], -0.5, 0.0 => [-81.97, -47.32] ; "right paddle lower half")]
+    #[test_case([-75.0, 50.0], [1.0, 0.0], 0.0, 75.0 => [92.84, 18.39] ; "spin of a moving paddle")]
+    #[test_case([-75.0, 50.0], [1.0, 0.0], 1.0, 75.0 => [47.32, 81.97] ; "spin does not exceed max angle")]
+    #[test_case([-179.0, 0.0], [1.0, 0.0], 0.0, 0.0 => [180.0, 0.0] ; "speed up is capped")]
+    #[test_case([-300.0, 0.0], [1.0, 0.0], 0.0, 0.0 => [300.0, 0.0] ; "fast ball keeps its speed")]
+    #[test_case([50.0, 75.0], [0.0, -1.0], 1.0, 0.0 => [81.97, -47.32] ; "top paddle right edge")]
+    fn test_deflect(velocity: [f32; 2], front: [f32; 2], offset: f32, paddle_movement: f32) -> [f32; 2] {
+        let [x, y] = deflect(velocity, front, offset, paddle_movement, &BounceConfig::default());
+        [(x * 100.0).round() / 100.0, (y * 100.0).round() / 100.0]
+    }
+
+    fn round_velocities(velocities: Option<[[f32; 2]; 2]>) -> Option<[[f32; 2]; 2]> {
+        velocities.map(|velocities| {
+            let round = |value: f32| (value * 100.0).round() / 100.0;
+            [
+                [round(velocities[0][0]), round(velocities[0][1])],
+                [round(velocities[1][0]), round(velocities[1][1])],
+            ]
+        })
+    }
+
+    #[test_case([3.0, 0.0], [[1.0, 0.0], [-1.0, 0.0]], [2.0, 2.0] => Some([[-1.0, 0.0], [1.0, 0.0]]) ; "head on")]
+    #[test_case([3.0, 0.0], [[1.0, 0.0], [0.0, 0.0]], [2.0, 1.0] => Some([[0.6, 0.0], [1.6, 0.0]]) ; "heavier ball")]
+    #[test_case([0.0, 3.0], [[1.0, 1.0], [1.0, -1.0]], [2.0, 2.0] => Some([[1.0, -1.0], [1.0, 1.0]]) ; "sideways")]
+    #[test_case([3.0, 0.0], [[-1.0, 0.0], [1.0, 0.0]], [2.0, 2.0] => None ; "moving apart")]
+    #[test_case([5.0, 0.0], [[1.0, 0.0], [-1.0, 0.0]], [2.0, 2.0] => None ; "not touching")]
+    fn test_collide_balls(offset: [f32; 2], velocity: [[f32; 2]; 2], radius: [f32; 2]) -> Option<[[f32; 2]; 2]> {
+        round_velocities(collide_balls([[0.0, 0.0], offset], velocity, radius))
+    }
+
     #[test]
     fn basic_bounce_system_setup() {
         amethyst::start_logger(amethyst::LoggerConfig::default());
@@ -137,15 +601,8 @@ mod tests {
                         initialise_paddles(world, root_entity, sprite_sheet);
                     }
                     if let Some(sprite_sheet) = sprite_sheet_handle {
-                        use crate::{BALL_RADIUS, BALL_VELOCITY_X, BALL_VELOCITY_Y};
-                        initialise_ball(
-                            world,
-                            root_entity,
-                            sprite_sheet,
-                            BALL_RADIUS,
-                            [BALL_VELOCITY_X, BALL_VELOCITY_Y],
-                            None,
-                        );
+                        let ball = PongConfig::default().ball;
+                        initialise_ball(world, root_entity, sprite_sheet, ball.radius, ball.velocity, None);
                     }
                 }
             })
@@ -181,14 +638,14 @@ mod tests {
                         initialise_paddles(world, root_entity, sprite_sheet);
                     }
                     if let Some(sprite_sheet) = sprite_sheet_handle {
-                        use crate::{ARENA_HEIGHT, BALL_RADIUS, BALL_VELOCITY_Y};
+                        let config = PongConfig::default();
                         initialise_ball(
                             world,
                             root_entity,
                             sprite_sheet,
-                            BALL_RADIUS,
-                            [-1.0, BALL_VELOCITY_Y],
-                            Some([0.0, ARENA_HEIGHT / 2.0]),
+                            config.ball.radius,
+                            [-1.0, config.ball.velocity[1]],
+                            Some([0.0, config.arena.height / 2.0]),
                         );
                     }
                 }
@@ -200,7 +657,11 @@ mod tests {
                 assert_eq!(1, balls.count());
 
                 for (ball, _transform) in (&balls, &transforms).join() {
-                    assert_approx_eq!(ball.velocity[0], 1.0);
+                    // The ball hits slightly below the centre of the paddle and leaves faster.
+                    assert!(ball.velocity[0] > 0.0);
+                    assert!(ball.velocity[1] < 0.0);
+                    let speed = (ball.velocity[0].powi(2) + ball.velocity[1].powi(2)).sqrt();
+                    assert_approx_eq!(speed, (1.0_f32 + 50.0 * 50.0).sqrt() * 1.05, 1e-3);
                 }
             })
             .run();
@@ -223,14 +684,14 @@ mod tests {
                         initialise_paddles(world, root_entity, sprite_sheet);
                     }
                     if let Some(sprite_sheet) = sprite_sheet_handle {
-                        use crate::{ARENA_HEIGHT, BALL_RADIUS, BALL_VELOCITY_Y};
+                        let config = PongConfig::default();
                         initialise_ball(
                             world,
                             root_entity,
                             sprite_sheet,
-                            BALL_RADIUS,
-                            [1.0, BALL_VELOCITY_Y],
-                            Some([0.0, ARENA_HEIGHT / 2.0]),
+                            config.ball.radius,
+                            [1.0, config.ball.velocity[1]],
+                            Some([0.0, config.arena.height / 2.0]),
                         );
                     }
                 }
@@ -265,14 +726,14 @@ mod tests {
                         initialise_paddles(world, root_entity, sprite_sheet);
                     }
                     if let Some(sprite_sheet) = sprite_sheet_handle {
-                        use crate::{ARENA_WIDTH, BALL_RADIUS, BALL_VELOCITY_X};
+                        let config = PongConfig::default();
                         initialise_ball(
                             world,
                             root_entity,
                             sprite_sheet,
-                            BALL_RADIUS,
-                            [BALL_VELOCITY_X, -10.0],
-                            Some([ARENA_WIDTH / 2.0, 0.0]),
+                            config.ball.radius,
+                            [config.ball.velocity[0], -10.0],
+                            Some([config.arena.width / 2.0, 0.0]),
                         );
                     }
                 }
@@ -290,4 +751,192 @@ mod tests {
             .run();
         assert!(test_result.is_ok());
     }
+
+    #[test]
+    fn balls_bounce_off_each_other() {
+        amethyst::start_logger(amethyst::LoggerConfig::default());
+        let test_result = crate::test_harness::IntegrationTestApplication::pong_base()
+            .with_setup(|world| {
+                let mut progress = ProgressCounter::default();
+                initialise_audio(world, &mut progress);
+
+                let root_entity = world.create_entity().with(Transform::default()).build();
+                let sprite_sheet_handle = load_sprite_sheet(world);
+                initialise_ball(world, root_entity, sprite_sheet_handle.clone(), 2.0, [10.0, 0.0], Some([70.0, 45.0]));
+                initialise_ball(world, root_entity, sprite_sheet_handle, 2.0, [-10.0, 0.0], Some([73.0, 45.0]));
+            })
+            .with_system_single(BounceSystem, "collision_system", &[])
+            .with_assertion(|world| {
+                for (ball, transform) in (&world.read_storage::<Ball>(), &world.read_storage::<Transform>()).join() {
+                    // The ball on the left bounces back to the left and vice versa.
+                    assert_approx_eq!(ball.velocity[0], if transform.translation().x < 71.5 { -10.0 } else { 10.0 });
+                    assert_approx_eq!(ball.velocity[1], 0.0);
+                }
+            })
+            .run();
+        assert!(test_result.is_ok());
+    }
+
+    #[test]
+    fn serving_ball_does_not_bounce() {
+        amethyst::start_logger(amethyst::LoggerConfig::default());
+        let test_result = crate::test_harness::IntegrationTestApplication::pong_base()
+            .with_setup(|world| {
+                let mut progress = ProgressCounter::default();
+                initialise_audio(world, &mut progress);
+
+                let root_entity = world.create_entity().with(Transform::default()).build();
+                let sprite_sheet_handle = load_sprite_sheet(world);
+                initialise_ball(world, root_entity, sprite_sheet_handle.clone(), 2.0, [10.0, 0.0], Some([78.0, 45.0]));
+                let serving = initialise_ball(world, root_entity, sprite_sheet_handle, 2.0, [0.0, 0.0], None);
+                world
+                    .write_storage::<Serving>()
+                    .insert(serving, Serving::new(1.0, None))
+                    .expect("ball was just created");
+            })
+            .with_system_single(BounceSystem, "collision_system", &[])
+            .with_assertion(|world| {
+                let servings = world.read_storage::<Serving>();
+                for (ball, serving) in (&world.read_storage::<Ball>(), (&servings).maybe()).join() {
+                    let expected = if serving.is_some() { [0.0, 0.0] } else { [10.0, 0.0] };
+                    assert_eq!(ball.velocity, expected);
+                }
+            })
+            .run();
+        assert!(test_result.is_ok());
+    }
+
+    /// Places a ball with the given velocity and moves it for one simulation step.
+    fn move_fast_ball(velocity: [f32; 2], position: [f32; 2]) -> crate::test_harness::IntegrationTestApplication {
+        crate::test_harness::IntegrationTestApplication::pong_base()
+            .with_setup(move |world| {
+                let mut progress = ProgressCounter::default();
+                initialise_audio(world, &mut progress);
+
+                let root_entity = world.create_entity().with(Transform::default()).build();
+                let sprite_sheet_handle = load_sprite_sheet(world);
+                initialise_paddles(world, root_entity, sprite_sheet_handle.clone());
+                initialise_ball(world, root_entity, sprite_sheet_handle, 2.0, velocity, Some(position));
+            })
+            .with_system_single(MoveBallsSystem, "ball_system", &[])
+            .with_system_single(BounceSystem, "collision_system", &[])
+    }
+
+    fn four_players() -> MatchSettings {
+        MatchSettings {
+            mode: crate::GameMode::FourPlayer,
+            ..MatchSettings::default()
+        }
+    }
+
+    #[test]
+    fn fast_ball_does_not_tunnel_through_paddle() {
+        amethyst::start_logger(amethyst::LoggerConfig::default());
+        // The ball travels ~41.7 units per step, from in front of the left paddle to behind the
+        // left edge of the arena.
+        let test_result = move_fast_ball([-5000.0, 0.0], [20.0, 45.0])
+            .with_assertion(|world| {
+                let balls = world.read_storage::<Ball>();
+                let transforms = world.read_storage::<Transform>();
+                assert_eq!(1, balls.count());
+
+                for (ball, transform) in (&balls, &transforms).join() {
+                    assert_approx_eq!(ball.velocity[0], 5000.0);
+                    // It touches the paddle at x = 6 and travels on for the rest of the step.
+                    let travelled = 5000.0 * FixedTimestep::default().step();
+                    assert_approx_eq!(transform.translation().x, 6.0 + (travelled - 14.0), 1e-3);
+                }
+            })
+            .run();
+        assert!(test_result.is_ok());
+    }
+
+    #[test]
+    fn fast_ball_does_not_leave_the_arena() {
+        amethyst::start_logger(amethyst::LoggerConfig::default());
+        // The ball travels 100 units per step, further than the arena is high.
+        let test_result = move_fast_ball([0.0, 12000.0], [80.0, 45.0])
+            .with_assertion(|world| {
+                let balls = world.read_storage::<Ball>();
+                let transforms = world.read_storage::<Transform>();
+                assert_eq!(1, balls.count());
+
+                for (ball, transform) in (&balls, &transforms).join() {
+                    assert_approx_eq!(ball.velocity[1], -12000.0);
+                    // It touches the top at y = 88 and travels on downwards for the rest.
+                    let travelled = 12000.0 * FixedTimestep::default().step();
+                    assert_approx_eq!(transform.translation().y, 88.0 - (travelled - 43.0), 1e-3);
+                }
+            })
+            .run();
+        assert!(test_result.is_ok());
+    }
+
+    #[test]
+    fn top_paddle_deflects_ball() {
+        amethyst::start_logger(amethyst::LoggerConfig::default());
+        // The top paddle spans x = 72 to 88, the ball hits its front right of the centre.
+        let test_result = move_fast_ball([0.0, 1200.0], [84.0, 80.0])
+            .with_resource(four_players())
+            .with_assertion(|world| {
+                let balls = world.read_storage::<Ball>();
+                for ball in balls.join() {
+                    assert!(ball.velocity[0] > 0.0);
+                    assert!(ball.velocity[1] < 0.0);
+                    assert_eq!(ball.last_hit, Some(Side::Top));
+                }
+            })
+            .run();
+        assert!(test_result.is_ok());
+    }
+
+    #[test]
+    fn guarded_top_edge_does_not_bounce() {
+        amethyst::start_logger(amethyst::LoggerConfig::default());
+        let test_result = move_fast_ball([0.0, 1200.0], [20.0, 85.0])
+            .with_resource(four_players())
+            .with_assertion(|world| {
+                let balls = world.read_storage::<Ball>();
+                let transforms = world.read_storage::<Transform>();
+                for (ball, transform) in (&balls, &transforms).join() {
+                    assert_eq!(ball.velocity, [0.0, 1200.0]);
+                    assert_approx_eq!(transform.translation().y, 95.0, 1e-3);
+                }
+            })
+            .run();
+        assert!(test_result.is_ok());
+    }
+
+    #[test]
+    fn ball_reflects_off_obstacle() {
+        amethyst::start_logger(amethyst::LoggerConfig::default());
+        // The ball travels 10 units per step and touches the obstacle at x = 44 after 6 of them.
+        let test_result = move_fast_ball([-1200.0, 0.0], [50.0, 45.0])
+            .with_setup(|world| {
+                let mut transform = Transform::default();
+                transform.set_translation_xyz(40.0, 45.0, 0.0);
+                world
+                    .create_entity()
+                    .with(Obstacle {
+                        width: 4.0,
+                        height: 12.0,
+                        velocity: [0.0, 0.0],
+                        min: [40.0, 45.0],
+                        max: [40.0, 45.0],
+                    })
+                    .with(transform)
+                    .build();
+            })
+            .with_assertion(|world| {
+                let balls = world.read_storage::<Ball>();
+                let transforms = world.read_storage::<Transform>();
+                for (ball, transform) in (&balls, &transforms).join() {
+                    assert_eq!(ball.velocity, [1200.0, 0.0]);
+                    assert_eq!(ball.last_hit, None);
+                    assert_approx_eq!(transform.translation().x, 48.0, 1e-3);
+                }
+            })
+            .run();
+        assert!(test_result.is_ok());
+    }
 }
@@ -1,6 +1,9 @@
 use crate::{
     states,
-    systems::{BounceSystem, MoveBallsSystem, PaddleSystem, WinnerSystem},
+    systems::{
+        AiPaddleSystem, BounceSystem, MoveBallsSystem, ObstacleSystem, PaddleSystem, PowerUpSystem, ServeSystem,
+        WinnerSystem,
+    },
 };
 use amethyst::{
     core::bundle::SystemBundle,
@@ -21,20 +24,40 @@ impl<'a, 'b> SystemBundle<'a, 'b> for PongBundle {
             "paddle_system",
             &[],
         );
+        builder.add(
+            AiPaddleSystem.pausable(states::GameplayState::Running),
+            "ai_paddle_system",
+            &[],
+        );
+        builder.add(
+            ServeSystem.pausable(states::GameplayState::Running),
+            "serve_system",
+            &[],
+        );
         builder.add(
             MoveBallsSystem.pausable(states::GameplayState::Running),
             "ball_system",
+            &["serve_system"],
+        );
+        builder.add(
+            ObstacleSystem.pausable(states::GameplayState::Running),
+            "obstacle_system",
             &[],
         );
         builder.add(
             BounceSystem.pausable(states::GameplayState::Running),
             "collision_system",
-            &["paddle_system", "ball_system"],
+            &["paddle_system", "ai_paddle_system", "ball_system", "obstacle_system"],
         );
         builder.add(
             WinnerSystem.pausable(states::GameplayState::Running),
             "winner_system",
-            &["paddle_system", "ball_system"],
+            &["paddle_system", "ai_paddle_system", "ball_system"],
+        );
+        builder.add(
+            PowerUpSystem.pausable(states::GameplayState::Running),
+            "power_up_system",
+            &["collision_system", "winner_system"],
         );
         Ok(())
     }
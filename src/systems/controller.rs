@@ -0,0 +1,262 @@
+use crate::{bindings::GameBindings, Side};
+use amethyst::{
+    core::SystemDesc,
+    derive::SystemDesc,
+    ecs::prelude::{Read, System, SystemData, World, Write},
+    input::{Axis, Bindings, InputEvent, InputHandler},
+    shrev::{EventChannel, ReaderId},
+};
+
+/// The controllers connected to the game and the players they are assigned to. A controller
+/// connecting goes to the first player without one. When a player's controller disconnects, a
+/// spare controller takes over, otherwise the player is left without one until another connects.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Controllers {
+    /// Instance ids of the connected controllers, in the order they connected. The controller axes
+    /// of the bindings refer to a controller by its position in this list.
+    connected: Vec<u32>,
+    /// The controller of every player, in the order of `Side::ALL`.
+    players: [Option<u32>; 4],
+    /// Players who lost their controller since the last `take_disconnected`.
+    disconnected: Vec<Side>,
+}
+
+impl Controllers {
+    pub fn connect(&mut self, which: u32) {
+        if self.connected.contains(&which) {
+            return;
+        }
+        self.connected.push(which);
+        if let Some(player) = self.players.iter_mut().find(|player| player.is_none()) {
+            *player = Some(which);
+        }
+    }
+
+    pub fn disconnect(&mut self, which: u32) {
+        self.connected.retain(|connected| *connected != which);
+        for side in Side::ALL.iter() {
+            if self.players[side.index()] != Some(which) {
+                continue;
+            }
+            let spare = self
+                .connected
+                .iter()
+                .copied()
+                .find(|connected| !self.players.contains(&Some(*connected)));
+            self.players[side.index()] = spare;
+            if spare.is_none() {
+                self.disconnected.push(*side);
+            }
+        }
+    }
+
+    /// Instance id of the controller of the player on `side`.
+    pub fn controller(&self, side: Side) -> Option<u32> {
+        self.players[side.index()]
+    }
+
+    /// The players who lost their controller since the last call and did not get another one yet.
+    pub fn take_disconnected(&mut self) -> Vec<Side> {
+        let players = self.players;
+        let mut sides = self
+            .disconnected
+            .drain(..)
+            .filter(|side| players[side.index()].is_none())
+            .collect::<Vec<_>>();
+        sides.dedup();
+        sides
+    }
+
+    /// Points the controller axes of every paddle at the controller of its player. The axes of a
+    /// player without a controller point at an id no controller ever gets, one per player, so the
+    /// bindings stay free of conflicts.
+    pub fn assign(&self, bindings: &mut Bindings<GameBindings>) {
+        let mut changed = Vec::new();
+        for side in Side::ALL.iter() {
+            let controller_id = self
+                .controller(*side)
+                .and_then(|which| self.connected.iter().position(|connected| *connected == which))
+                .map_or(u32::max_value() - side.index() as u32, |position| position as u32);
+            if let Some(axis) = bindings.axis(&side.input_axis()) {
+                let mut axis = axis.clone();
+                if set_controller_id(&mut axis, controller_id) {
+                    changed.push((side.input_axis(), axis));
+                }
+            }
+        }
+        // every changed axis is removed first, as two players may swap controllers
+        for (id, _) in &changed {
+            bindings.remove_axis(id);
+        }
+        for (id, axis) in changed {
+            if let Err(e) = bindings.insert_axis(id, axis) {
+                log::error!("Failed to assign a controller to {}: {}", id, e);
+            }
+        }
+    }
+}
+
+/// Points the controller parts of `axis` at the controller `id`. Returns whether anything changed.
+fn set_controller_id(axis: &mut Axis, id: u32) -> bool {
+    match axis {
+        Axis::Controller { controller_id, .. } if *controller_id != id => {
+            *controller_id = id;
+            true
+        }
+        Axis::Multiple(axes) => axes
+            .iter_mut()
+            .fold(false, |changed, axis| set_controller_id(axis, id) || changed),
+        _ => false,
+    }
+}
+
+/// Keeps track of controllers connecting and disconnecting, and keeps the paddle axes pointed at
+/// the controllers of their players. Reloading or rebinding replaces the bindings, so they are
+/// checked every frame.
+#[derive(SystemDesc)]
+#[system_desc(name(ControllerSystemDesc))]
+pub struct ControllerSystem {
+    #[system_desc(event_channel_reader)]
+    reader_id: ReaderId<InputEvent<GameBindings>>,
+}
+
+impl ControllerSystem {
+    pub fn new(reader_id: ReaderId<InputEvent<GameBindings>>) -> Self {
+        Self { reader_id }
+    }
+}
+
+impl<'s> System<'s> for ControllerSystem {
+    type SystemData = (
+        Read<'s, EventChannel<InputEvent<GameBindings>>>,
+        Write<'s, Controllers>,
+        Write<'s, InputHandler<GameBindings>>,
+    );
+
+    fn run(&mut self, (events, mut controllers, mut input): Self::SystemData) {
+        for event in events.read(&mut self.reader_id) {
+            match *event {
+                InputEvent::ControllerConnected { which } => {
+                    controllers.connect(which);
+                    log::info!("Controller {} connected", which);
+                }
+                InputEvent::ControllerDisconnected { which } => {
+                    controllers.disconnect(which);
+                    log::info!("Controller {} disconnected", which);
+                }
+                _ => {}
+            }
+        }
+        controllers.assign(&mut input.bindings);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::load_bindings;
+    use amethyst::ecs::prelude::{RunNow, WorldExt};
+
+    fn merged_bindings() -> Bindings<GameBindings> {
+        let assets_dir = crate::initialize_paths().expect("valid paths required").assets_dir;
+        load_bindings(
+            &assets_dir.join("config/input.ron"),
+            Some(&assets_dir.join("config/input_controller.ron")),
+        )
+        .expect("failed to load bindings")
+    }
+
+    fn controller_id(bindings: &Bindings<GameBindings>, side: Side) -> Option<u32> {
+        match bindings.axis(&side.input_axis()) {
+            Some(Axis::Multiple(axes)) => axes.iter().find_map(|axis| match axis {
+                Axis::Controller { controller_id, .. } => Some(*controller_id),
+                _ => None,
+            }),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn controllers_go_to_players_in_order() {
+        let mut controllers = Controllers::default();
+        controllers.connect(7);
+        controllers.connect(3);
+        controllers.connect(7);
+        assert_eq!(controllers.controller(Side::Left), Some(7));
+        assert_eq!(controllers.controller(Side::Right), Some(3));
+        assert_eq!(controllers.controller(Side::Top), None);
+
+        controllers.disconnect(7);
+        assert_eq!(controllers.controller(Side::Left), None);
+        assert_eq!(controllers.take_disconnected(), vec![Side::Left]);
+        assert_eq!(controllers.take_disconnected(), vec![]);
+
+        controllers.connect(9);
+        assert_eq!(controllers.controller(Side::Left), Some(9));
+        assert_eq!(controllers.controller(Side::Right), Some(3));
+    }
+
+    #[test]
+    fn reconnected_player_is_not_reported() {
+        let mut controllers = Controllers::default();
+        controllers.connect(1);
+        controllers.disconnect(1);
+        controllers.connect(2);
+        assert_eq!(controllers.take_disconnected(), vec![]);
+    }
+
+    #[test]
+    fn spare_controller_takes_over() {
+        let mut controllers = Controllers::default();
+        for which in 0..5 {
+            controllers.connect(which);
+        }
+        controllers.disconnect(1);
+        assert_eq!(controllers.controller(Side::Right), Some(4));
+        assert_eq!(controllers.take_disconnected(), vec![]);
+    }
+
+    #[test]
+    fn paddle_axes_follow_their_player() {
+        let mut bindings = merged_bindings();
+        let mut controllers = Controllers::default();
+        controllers.connect(7);
+        controllers.connect(3);
+        controllers.assign(&mut bindings);
+        assert_eq!(controller_id(&bindings, Side::Left), Some(0));
+        assert_eq!(controller_id(&bindings, Side::Right), Some(1));
+        assert_eq!(controller_id(&bindings, Side::Top), Some(u32::max_value() - 2));
+
+        // the remaining controller moves up in the list of connected controllers
+        controllers.disconnect(7);
+        controllers.assign(&mut bindings);
+        assert_eq!(controller_id(&bindings, Side::Left), Some(u32::max_value()));
+        assert_eq!(controller_id(&bindings, Side::Right), Some(0));
+        assert!(bindings.axis(&Side::Left.input_axis()).is_some());
+    }
+
+    #[test]
+    fn handle_controller_events() {
+        amethyst::start_logger(amethyst::LoggerConfig::default());
+        let test_result = crate::test_harness::IntegrationTestApplication::pong_base()
+            .with_assertion(|world| {
+                world.write_resource::<InputHandler<GameBindings>>().bindings = merged_bindings();
+                let mut system = ControllerSystemDesc::default().build(world);
+                {
+                    let mut events = world.write_resource::<EventChannel<InputEvent<GameBindings>>>();
+                    events.single_write(InputEvent::ControllerConnected { which: 4 });
+                    events.single_write(InputEvent::ControllerConnected { which: 5 });
+                    events.single_write(InputEvent::ControllerDisconnected { which: 4 });
+                }
+                system.run_now(world);
+
+                let mut controllers = world.write_resource::<Controllers>();
+                assert_eq!(controllers.controller(Side::Right), Some(5));
+                assert_eq!(controllers.take_disconnected(), vec![Side::Left]);
+                let input = world.read_resource::<InputHandler<GameBindings>>();
+                assert_eq!(controller_id(&input.bindings, Side::Right), Some(0));
+            })
+            .run();
+        assert!(test_result.is_ok());
+    }
+}
@@ -0,0 +1,225 @@
+use crate::config::SimulationConfig;
+use amethyst::{
+    core::transform::Transform,
+    ecs::prelude::{Component, DenseVecStorage, Join, World, WorldExt},
+};
+
+/// Accumulates rendered frame time and hands it out as fixed simulation steps. Gameplay systems
+/// integrate with `step()` instead of `Time::delta_seconds()`, so a match plays out the same on
+/// every frame rate.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FixedTimestep {
+    step: f32,
+    max_steps: u32,
+    accumulator: f32,
+    /// Steps simulated since the match started, including the ones a rollback simulated again.
+    simulated_steps: u32,
+}
+
+impl Default for FixedTimestep {
+    fn default() -> Self {
+        Self::new(&SimulationConfig::default())
+    }
+}
+
+impl FixedTimestep {
+    pub fn new(config: &SimulationConfig) -> Self {
+        Self {
+            step: 1.0 / config.tick_rate.max(1.0),
+            max_steps: config.max_ticks_per_frame.max(1),
+            accumulator: 0.0,
+            simulated_steps: 0,
+        }
+    }
+
+    /// Seconds simulated by one step.
+    pub const fn step(&self) -> f32 {
+        self.step
+    }
+
+    /// Adds the duration of a rendered frame and returns how many steps to simulate for it. Time
+    /// beyond `max_ticks_per_frame` steps is dropped instead of being caught up later.
+    pub fn advance(&mut self, delta_seconds: f32) -> u32 {
+        self.accumulator += delta_seconds.max(0.0);
+        let mut steps = 0;
+        while self.accumulator >= self.step && steps < self.max_steps {
+            self.accumulator -= self.step;
+            steps += 1;
+        }
+        if steps == self.max_steps {
+            self.accumulator %= self.step;
+        }
+        steps
+    }
+
+    /// Counts a simulated step, see `simulated_steps`.
+    pub fn count_step(&mut self) {
+        self.simulated_steps += 1;
+    }
+
+    /// Steps simulated since the match started. Unlike the frame time, this does not depend on
+    /// how fast the frames were rendered.
+    pub const fn simulated_steps(&self) -> u32 {
+        self.simulated_steps
+    }
+
+    /// How far the rendered frame is between the last two simulation steps, from 0.0 to 1.0.
+    pub fn alpha(&self) -> f32 {
+        (self.accumulator / self.step).min(1.0)
+    }
+}
+
+/// Keeps the simulated position of an entity apart from its rendered `Transform`, which is
+/// interpolated between the last two simulation steps.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Interpolated {
+    previous: [f32; 2],
+    current: [f32; 2],
+}
+
+impl Interpolated {
+    pub const fn new(position: [f32; 2]) -> Self {
+        Self {
+            previous: position,
+            current: position,
+        }
+    }
+
+    /// Moves the entity without interpolating, e.g. when it is placed at a new position.
+    pub fn teleport(&mut self, position: [f32; 2]) {
+        self.previous = position;
+        self.current = position;
+    }
+}
+
+impl Component for Interpolated {
+    type Storage = DenseVecStorage<Self>;
+}
+
+/// Puts the simulated positions back into the `Transform`s before simulation steps run.
+pub fn restore_simulated_transforms(world: &World) {
+    let interpolated = world.read_storage::<Interpolated>();
+    let mut transforms = world.write_storage::<Transform>();
+    for (interpolated, transform) in (&interpolated, &mut transforms).join() {
+        transform.set_translation_x(interpolated.current[0]);
+        transform.set_translation_y(interpolated.current[1]);
+    }
+}
+
+/// Remembers the simulated positions before a simulation step runs.
+pub fn store_previous_transforms(world: &World) {
+    let mut interpolated = world.write_storage::<Interpolated>();
+    let transforms = world.read_storage::<Transform>();
+    for (interpolated, transform) in (&mut interpolated, &transforms).join() {
+        interpolated.previous = [transform.translation().x, transform.translation().y];
+    }
+}
+
+/// Stores the simulated positions after the simulation steps of a frame ran, and moves the
+/// `Transform`s to the interpolated positions for rendering.
+pub fn interpolate_transforms(world: &World, alpha: f32) {
+    let mut interpolated = world.write_storage::<Interpolated>();
+    let mut transforms = world.write_storage::<Transform>();
+    for (interpolated, transform) in (&mut interpolated, &mut transforms).join() {
+        interpolated.current = [transform.translation().x, transform.translation().y];
+        let (previous, current) = (interpolated.previous, interpolated.current);
+        transform.set_translation_x(previous[0] + (current[0] - previous[0]) * alpha);
+        transform.set_translation_y(previous[1] + (current[1] - previous[1]) * alpha);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::test_harness::IntegrationTestApplication;
+    use amethyst::prelude::Builder;
+    use assert_approx_eq::assert_approx_eq;
+    use test_case::test_case;
+
+    // 64 steps per second, so steps and frame times below are exact in binary.
+    fn timestep() -> FixedTimestep {
+        FixedTimestep::new(&SimulationConfig {
+            tick_rate: 64.0,
+            max_ticks_per_frame: 4,
+        })
+    }
+
+    #[test_case(&[0.007_812_5] => vec![0] ; "shorter than a step")]
+    #[test_case(&[0.015_625] => vec![1] ; "exactly one step")]
+    #[test_case(&[0.007_812_5, 0.007_812_5, 0.007_812_5] => vec![0, 1, 0] ; "accumulates short frames")]
+    #[test_case(&[0.039_062_5, 0.007_812_5] => vec![2, 1] ; "carries the remainder")]
+    #[test_case(&[1.0, 0.0] => vec![4, 0] ; "limits steps per frame")]
+    #[test_case(&[-1.0] => vec![0] ; "ignores negative frames")]
+    fn test_advance(frames: &[f32]) -> Vec<u32> {
+        let mut timestep = timestep();
+        frames.iter().map(|delta| timestep.advance(*delta)).collect()
+    }
+
+    #[test]
+    fn same_steps_for_different_frame_rates() {
+        let mut slow = timestep();
+        let mut fast = timestep();
+        let slow_steps: u32 = (0..30).map(|_| slow.advance(1.0 / 30.0)).sum();
+        let fast_steps: u32 = (0..144).map(|_| fast.advance(1.0 / 144.0)).sum();
+        // Both simulate one second, up to the step that is still accumulating.
+        assert!((slow_steps as i32 - 64).abs() <= 1);
+        assert!((fast_steps as i32 - 64).abs() <= 1);
+    }
+
+    #[test]
+    fn count_simulated_steps() {
+        let mut timestep = timestep();
+        for _ in 0..timestep.advance(1.0) {
+            timestep.count_step();
+        }
+        assert_eq!(timestep.simulated_steps(), 4);
+    }
+
+    #[test]
+    fn alpha_is_fraction_of_a_step() {
+        let mut timestep = timestep();
+        timestep.advance(0.019_531_25);
+        assert_approx_eq!(timestep.alpha(), 0.25);
+    }
+
+    #[test]
+    fn interpolate_between_steps() {
+        amethyst::start_logger(amethyst::LoggerConfig::default());
+        let test_result = IntegrationTestApplication::pong_base()
+            .with_setup(|world| {
+                let mut transform = Transform::default();
+                transform.set_translation_xyz(10.0, 10.0, 0.0);
+                world
+                    .create_entity()
+                    .with(transform)
+                    .with(Interpolated::new([10.0, 10.0]))
+                    .build();
+            })
+            .with_assertion(|world| {
+                restore_simulated_transforms(world);
+                store_previous_transforms(world);
+                for transform in (&mut world.write_storage::<Transform>()).join() {
+                    transform.set_translation_xyz(20.0, 30.0, 0.0);
+                }
+                interpolate_transforms(world, 0.5);
+
+                for (interpolated, transform) in
+                    (&world.read_storage::<Interpolated>(), &world.read_storage::<Transform>()).join()
+                {
+                    assert_eq!(interpolated.current, [20.0, 30.0]);
+                    assert_approx_eq!(transform.translation().x, 15.0);
+                    assert_approx_eq!(transform.translation().y, 20.0);
+                }
+
+                // The next frame continues from the simulated, not the rendered position.
+                restore_simulated_transforms(world);
+                for transform in (&world.read_storage::<Transform>()).join() {
+                    assert_approx_eq!(transform.translation().x, 20.0);
+                    assert_approx_eq!(transform.translation().y, 30.0);
+                }
+            })
+            .run();
+        assert!(test_result.is_ok());
+    }
+}
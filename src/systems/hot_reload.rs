@@ -0,0 +1,362 @@
+use crate::{
+    bindings::GameBindings,
+    config::{load_bindings, PongConfig, UserBindings, UserSettings},
+    GamePaths,
+};
+use amethyst::{
+    config::Config,
+    core::{timing::Time, SystemDesc},
+    ecs::prelude::{Read, System, SystemData, World, Write},
+    error::Error,
+    input::{Bindings, InputHandler},
+    shrev::EventChannel,
+    window::{DisplayConfig, Window},
+    winit::dpi::LogicalSize,
+};
+use log::{error, info};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+/// Seconds between two checks of the watched files' modification times.
+const POLL_INTERVAL: f32 = 0.5;
+
+/// Sent when a watched file changed on disk and the running states need to react to it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HotReloadEvent {
+    /// A UI prefab changed. Contains the prefab path relative to the assets directory.
+    UiPrefab(&'static str),
+}
+
+/// What a watched file contains, and therefore how it is reloaded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WatchedKind {
+    DisplayConfig,
+    /// Key or controller bindings.
+    KeyBindings,
+    PongConfig,
+    /// UI prefab, identified by its path relative to the assets directory.
+    UiPrefab(&'static str),
+}
+
+/// A file on disk together with the modification time it had when it was last loaded.
+#[derive(Debug, Clone)]
+pub struct WatchedFile {
+    path: PathBuf,
+    kind: WatchedKind,
+    last_modified: Option<SystemTime>,
+}
+
+impl WatchedFile {
+    pub fn new(path: PathBuf, kind: WatchedKind) -> Self {
+        let last_modified = modified_time(&path);
+        Self {
+            path,
+            kind,
+            last_modified,
+        }
+    }
+
+    /// Returns `true` once for every change of the file's modification time.
+    fn poll_changed(&mut self) -> bool {
+        let modified = modified_time(&self.path);
+        if modified.is_some() && modified != self.last_modified {
+            self.last_modified = modified;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
+
+/// Builds a `HotReloadSystem` watching the given files.
+#[derive(Debug)]
+pub struct HotReloadSystemDesc {
+    watched_files: Vec<WatchedFile>,
+}
+
+impl HotReloadSystemDesc {
+    pub fn new(watched_files: Vec<WatchedFile>) -> Self {
+        Self { watched_files }
+    }
+}
+
+impl<'a, 'b> SystemDesc<'a, 'b, HotReloadSystem> for HotReloadSystemDesc {
+    fn build(self, world: &mut World) -> HotReloadSystem {
+        <HotReloadSystem as System<'_>>::SystemData::setup(world);
+        HotReloadSystem::new(self.watched_files)
+    }
+}
+
+/// This system polls the config files and UI prefabs for changes while the game is running.
+/// Config files are re-parsed and swapped into the `World`, UI prefab changes are forwarded as
+/// `HotReloadEvent`s so the owning state can recreate its UI. A file that fails to parse is
+/// reported to the log and the previously loaded values stay in use.
+#[derive(Debug)]
+pub struct HotReloadSystem {
+    watched_files: Vec<WatchedFile>,
+    poll_timer: f32,
+}
+
+impl HotReloadSystem {
+    pub fn new(watched_files: Vec<WatchedFile>) -> Self {
+        Self {
+            watched_files,
+            poll_timer: 0.0,
+        }
+    }
+}
+
+/// Loads the bindings after `path` changed. With `GamePaths`, the key and controller bindings are
+/// loaded together, whichever of them changed.
+fn reload_bindings(path: &Path, paths: Option<&GamePaths>) -> Result<Bindings<GameBindings>, Error> {
+    match paths {
+        Some(paths) => paths.load_bindings(),
+        None => load_bindings(path, None),
+    }
+}
+
+impl<'s> System<'s> for HotReloadSystem {
+    type SystemData = (
+        Read<'s, Time>,
+        Write<'s, PongConfig>,
+        Write<'s, DisplayConfig>,
+        Write<'s, InputHandler<GameBindings>>,
+        Write<'s, EventChannel<HotReloadEvent>>,
+        Option<Read<'s, Window>>,
+        Read<'s, UserSettings>,
+        Read<'s, UserBindings>,
+        Option<Read<'s, GamePaths>>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            time,
+            mut pong_config,
+            mut display_config,
+            mut input,
+            mut events,
+            window,
+            user_settings,
+            user_bindings,
+            paths,
+        ): Self::SystemData,
+    ) {
+        self.poll_timer -= time.delta_seconds();
+        if self.poll_timer > 0.0 {
+            return;
+        }
+        self.poll_timer = POLL_INTERVAL;
+
+        for file in &mut self.watched_files {
+            if !file.poll_changed() {
+                continue;
+            }
+            match file.kind {
+                WatchedKind::PongConfig => match PongConfig::load_no_fallback(&file.path) {
+                    Ok(config) => {
+                        *pong_config = config;
+                        info!("Reloaded {:?}", file.path);
+                    }
+                    Err(e) => error!("Failed to reload {:?}: {}", file.path, e),
+                },
+                WatchedKind::KeyBindings => match reload_bindings(&file.path, paths.as_deref()) {
+                    Ok(mut bindings) => {
+                        // the rebinding screen has the last word on the bindings
+                        user_bindings.apply(&mut bindings);
+                        input.bindings = bindings;
+                        info!("Reloaded {:?}", file.path);
+                    }
+                    Err(e) => error!("Failed to reload {:?}: {}", file.path, e),
+                },
+                WatchedKind::DisplayConfig => match DisplayConfig::load_no_fallback(&file.path) {
+                    Ok(mut config) => {
+                        // the options menu has the last word on the window size
+                        user_settings.apply_to_display(&mut config);
+                        if let Some(window) = &window {
+                            window.set_title(&config.title);
+                            if let Some((width, height)) = config.dimensions {
+                                window.set_inner_size(LogicalSize::new(f64::from(width), f64::from(height)));
+                            }
+                        }
+                        *display_config = config;
+                        info!("Reloaded {:?}", file.path);
+                    }
+                    Err(e) => error!("Failed to reload {:?}: {}", file.path, e),
+                },
+                WatchedKind::UiPrefab(prefab) => {
+                    events.single_write(HotReloadEvent::UiPrefab(prefab));
+                    info!("Reloading {:?}", file.path);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::test_harness::{temp_path, IntegrationTestApplication};
+    use amethyst::{ecs::prelude::WorldExt, shrev::ReaderId};
+    use assert_approx_eq::assert_approx_eq;
+
+    fn temp_file(name: &str) -> PathBuf {
+        let path = temp_path(name);
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    #[test]
+    fn detects_newly_created_file() {
+        let path = temp_file("created.ron");
+        let mut file = WatchedFile::new(path.clone(), WatchedKind::PongConfig);
+        assert_eq!(file.poll_changed(), false);
+
+        fs::write(&path, "()").expect("could not write temp file");
+        assert_eq!(file.poll_changed(), true);
+        assert_eq!(file.poll_changed(), false);
+
+        fs::remove_file(&path).expect("could not remove temp file");
+    }
+
+    #[test]
+    fn reloads_pong_config() {
+        amethyst::start_logger(amethyst::LoggerConfig::default());
+        let path = temp_file("reload.ron");
+        let watched_files = vec![WatchedFile::new(path.clone(), WatchedKind::PongConfig)];
+        fs::write(&path, "(arena: (width: 320.0, height: 180.0))").expect("could not write temp file");
+
+        let test_result = IntegrationTestApplication::pong_base()
+            .with_system_desc_single(HotReloadSystemDesc::new(watched_files), "hot_reload_system", &[])
+            .with_assertion(|world| {
+                let config = world.read_resource::<PongConfig>();
+                assert_approx_eq!(config.arena.width, 320.0);
+                assert_approx_eq!(config.arena.height, 180.0);
+            })
+            .run();
+        fs::remove_file(&path).expect("could not remove temp file");
+        assert!(test_result.is_ok());
+    }
+
+    #[test]
+    fn keeps_pong_config_on_parse_error() {
+        amethyst::start_logger(amethyst::LoggerConfig::default());
+        let path = temp_file("broken.ron");
+        let watched_files = vec![WatchedFile::new(path.clone(), WatchedKind::PongConfig)];
+        fs::write(&path, "(arena: (width: ").expect("could not write temp file");
+
+        let test_result = IntegrationTestApplication::pong_base()
+            .with_system_desc_single(HotReloadSystemDesc::new(watched_files), "hot_reload_system", &[])
+            .with_assertion(|world| {
+                assert_eq!(*world.read_resource::<PongConfig>(), PongConfig::default());
+            })
+            .run();
+        fs::remove_file(&path).expect("could not remove temp file");
+        assert!(test_result.is_ok());
+    }
+
+    #[test]
+    fn reloads_key_bindings_under_user_bindings() {
+        use crate::bindings::AxisBinding;
+        use amethyst::{
+            input::{Axis, Button},
+            winit::VirtualKeyCode,
+        };
+
+        amethyst::start_logger(amethyst::LoggerConfig::default());
+        let path = temp_file("input.ron");
+        let watched_files = vec![WatchedFile::new(path.clone(), WatchedKind::KeyBindings)];
+        let bindings = "(axes: {\
+            LeftPaddle: Emulated(pos: Key(W), neg: Key(S)), \
+            RightPaddle: Emulated(pos: Key(Up), neg: Key(Down)), \
+            TopPaddle: Emulated(pos: Key(L), neg: Key(J)), \
+            BottomPaddle: Emulated(pos: Key(Right), neg: Key(Left))})";
+        fs::write(&path, bindings).expect("could not write temp file");
+        let mut user_bindings = UserBindings::default();
+        user_bindings.axes.insert(
+            AxisBinding::LeftPaddle,
+            Axis::Emulated {
+                pos: Button::Key(VirtualKeyCode::Q),
+                neg: Button::Key(VirtualKeyCode::A),
+            },
+        );
+
+        let test_result = IntegrationTestApplication::pong_base()
+            .with_resource(user_bindings)
+            .with_system_desc_single(HotReloadSystemDesc::new(watched_files), "hot_reload_system", &[])
+            .with_assertion(|world| {
+                let input = world.read_resource::<InputHandler<GameBindings>>();
+                let axis = |id: AxisBinding| input.bindings.axis(&id).cloned();
+                assert_eq!(
+                    axis(AxisBinding::LeftPaddle),
+                    Some(Axis::Emulated {
+                        pos: Button::Key(VirtualKeyCode::Q),
+                        neg: Button::Key(VirtualKeyCode::A),
+                    })
+                );
+                assert_eq!(
+                    axis(AxisBinding::RightPaddle),
+                    Some(Axis::Emulated {
+                        pos: Button::Key(VirtualKeyCode::Up),
+                        neg: Button::Key(VirtualKeyCode::Down),
+                    })
+                );
+            })
+            .run();
+        fs::remove_file(&path).expect("could not remove temp file");
+        assert!(test_result.is_ok());
+    }
+
+    #[test]
+    fn keeps_key_bindings_with_unbound_axis() {
+        amethyst::start_logger(amethyst::LoggerConfig::default());
+        let path = temp_file("incomplete-input.ron");
+        let watched_files = vec![WatchedFile::new(path.clone(), WatchedKind::KeyBindings)];
+        let bindings = "(axes: {LeftPaddle: Emulated(pos: Key(W), neg: Key(S))})";
+        fs::write(&path, bindings).expect("could not write temp file");
+
+        let test_result = IntegrationTestApplication::pong_base()
+            .with_system_desc_single(HotReloadSystemDesc::new(watched_files), "hot_reload_system", &[])
+            .with_assertion(|world| {
+                let input = world.read_resource::<InputHandler<GameBindings>>();
+                assert_eq!(input.bindings.axes().count(), 0);
+            })
+            .run();
+        fs::remove_file(&path).expect("could not remove temp file");
+        assert!(test_result.is_ok());
+    }
+
+    #[test]
+    fn forwards_ui_prefab_changes() {
+        amethyst::start_logger(amethyst::LoggerConfig::default());
+        let path = temp_file("hud.ron");
+        let watched_files = vec![WatchedFile::new(path.clone(), WatchedKind::UiPrefab("ui/hud.ron"))];
+        fs::write(&path, "()").expect("could not write temp file");
+
+        let test_result = IntegrationTestApplication::pong_base()
+            .with_setup(|world| {
+                let mut events = EventChannel::<HotReloadEvent>::new();
+                let reader_id = events.register_reader();
+                world.insert(events);
+                world.insert(reader_id);
+            })
+            .with_system_desc_single(HotReloadSystemDesc::new(watched_files), "hot_reload_system", &[])
+            .with_assertion(|world| {
+                let mut reader_id = world.write_resource::<ReaderId<HotReloadEvent>>();
+                let events = world.read_resource::<EventChannel<HotReloadEvent>>();
+                let events = events.read(&mut reader_id).cloned().collect::<Vec<_>>();
+                assert_eq!(events, vec![HotReloadEvent::UiPrefab("ui/hud.ron")]);
+            })
+            .run();
+        fs::remove_file(&path).expect("could not remove temp file");
+        assert!(test_result.is_ok());
+    }
+}
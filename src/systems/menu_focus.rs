@@ -0,0 +1,286 @@
+use crate::bindings::GameBindings;
+use amethyst::{
+    core::SystemDesc,
+    ecs::prelude::{Entities, Entity, Join, Read, ReadStorage, System, SystemData, World, Write},
+    input::{ControllerButton, InputEvent, InputHandler},
+    shrev::{EventChannel, ReaderId},
+    ui::{Interactable, UiEvent, UiEventType, UiTransform},
+    winit::VirtualKeyCode,
+};
+
+/// The button of the current menu that keyboard and controller navigation acts on.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct MenuFocus {
+    pub focused: Option<Entity>,
+    /// Set by a state that wants the navigation keys for itself, like the key rebinding screen
+    /// while it waits for a key.
+    pub suspended: bool,
+}
+
+/// What a key or controller button press does to the focus.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Navigation {
+    Next,
+    Previous,
+    Activate,
+}
+
+impl Navigation {
+    /// The navigation triggered by `event`, if any. Tab moves backwards while shift is held.
+    pub fn from_event(event: &InputEvent<GameBindings>, shift: bool) -> Option<Self> {
+        match event {
+            InputEvent::KeyPressed { key_code, .. } => match key_code {
+                VirtualKeyCode::Tab if shift => Some(Self::Previous),
+                VirtualKeyCode::Down | VirtualKeyCode::Right | VirtualKeyCode::Tab => Some(Self::Next),
+                VirtualKeyCode::Up | VirtualKeyCode::Left => Some(Self::Previous),
+                VirtualKeyCode::Return | VirtualKeyCode::NumpadEnter => Some(Self::Activate),
+                _ => None,
+            },
+            InputEvent::ControllerButtonPressed { button, .. } => match button {
+                ControllerButton::DPadDown | ControllerButton::DPadRight => Some(Self::Next),
+                ControllerButton::DPadUp | ControllerButton::DPadLeft => Some(Self::Previous),
+                ControllerButton::A => Some(Self::Activate),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+}
+
+/// The button after `focused` in `buttons`, wrapping around. Without a focus, moving forwards
+/// starts at the first button and moving backwards at the last one.
+fn neighbour(buttons: &[Entity], focused: Option<Entity>, forwards: bool) -> Option<Entity> {
+    if buttons.is_empty() {
+        return None;
+    }
+    let position = focused.and_then(|focused| buttons.iter().position(|button| *button == focused));
+    let next = match (position, forwards) {
+        (Some(position), true) => (position + 1) % buttons.len(),
+        (Some(position), false) => (position + buttons.len() - 1) % buttons.len(),
+        (None, true) => 0,
+        (None, false) => buttons.len() - 1,
+    };
+    Some(buttons[next])
+}
+
+/// Builds a `MenuFocusSystem` reading the input and UI events.
+#[derive(Debug, Default)]
+pub struct MenuFocusSystemDesc;
+
+impl<'a, 'b> SystemDesc<'a, 'b, MenuFocusSystem> for MenuFocusSystemDesc {
+    fn build(self, world: &mut World) -> MenuFocusSystem {
+        <MenuFocusSystem as System<'_>>::SystemData::setup(world);
+        let input_reader_id = world
+            .fetch_mut::<EventChannel<InputEvent<GameBindings>>>()
+            .register_reader();
+        let ui_reader_id = world.fetch_mut::<EventChannel<UiEvent>>().register_reader();
+        MenuFocusSystem::new(input_reader_id, ui_reader_id)
+    }
+}
+
+/// This system lets the arrow keys, Tab and the d-pad move the focus through the buttons of the
+/// current menu in their `tab_order`, and Enter or the A button click the focused one. The focused
+/// button is highlighted the way the mouse hovering it would, and hovering a button with the mouse
+/// moves the focus there, so states only ever have to handle clicks.
+///
+/// The click follows one frame after the key or button activating it. States get the UI events of
+/// a frame before its input events, so a screen waiting for a key after the click, like the key
+/// rebinding screen, would otherwise take the activating key for the one it waits for.
+#[derive(Debug)]
+pub struct MenuFocusSystem {
+    input_reader_id: ReaderId<InputEvent<GameBindings>>,
+    ui_reader_id: ReaderId<UiEvent>,
+    /// button activated in the last frame, clicked in this one
+    pending_click: Option<Entity>,
+}
+
+impl MenuFocusSystem {
+    pub fn new(input_reader_id: ReaderId<InputEvent<GameBindings>>, ui_reader_id: ReaderId<UiEvent>) -> Self {
+        Self {
+            input_reader_id,
+            ui_reader_id,
+            pending_click: None,
+        }
+    }
+}
+
+impl<'s> System<'s> for MenuFocusSystem {
+    type SystemData = (
+        Entities<'s>,
+        Read<'s, EventChannel<InputEvent<GameBindings>>>,
+        Read<'s, InputHandler<GameBindings>>,
+        ReadStorage<'s, UiTransform>,
+        ReadStorage<'s, Interactable>,
+        Write<'s, EventChannel<UiEvent>>,
+        Write<'s, MenuFocus>,
+    );
+
+    fn run(
+        &mut self,
+        (entities, input_events, input, ui_transforms, interactables, mut ui_events, mut focus): Self::SystemData,
+    ) {
+        let mut buttons = (&entities, &ui_transforms, &interactables)
+            .join()
+            .map(|(entity, ui_transform, _)| (ui_transform.tab_order, entity))
+            .collect::<Vec<_>>();
+        buttons.sort_by_key(|(tab_order, entity)| (*tab_order, entity.id()));
+        let buttons = buttons.into_iter().map(|(_, entity)| entity).collect::<Vec<_>>();
+
+        // the focus goes away with the menu of the focused button
+        if focus.focused.map_or(false, |focused| !buttons.contains(&focused)) {
+            focus.focused = None;
+        }
+        let mut focused = focus.focused;
+        for event in ui_events.read(&mut self.ui_reader_id) {
+            if event.event_type == UiEventType::HoverStart && buttons.contains(&event.target) {
+                focused = Some(event.target);
+            }
+        }
+
+        let shift = input.key_is_down(VirtualKeyCode::LShift) || input.key_is_down(VirtualKeyCode::RShift);
+        let mut clicked = None;
+        for event in input_events.read(&mut self.input_reader_id) {
+            if focus.suspended {
+                continue;
+            }
+            match Navigation::from_event(event, shift) {
+                Some(Navigation::Next) => focused = neighbour(&buttons, focused, true),
+                Some(Navigation::Previous) => focused = neighbour(&buttons, focused, false),
+                Some(Navigation::Activate) if focused.is_some() => clicked = focused,
+                Some(Navigation::Activate) => focused = neighbour(&buttons, None, true),
+                None => {}
+            }
+        }
+
+        if focused != focus.focused {
+            if let Some(previous) = focus.focused {
+                ui_events.single_write(UiEvent::new(UiEventType::HoverStop, previous));
+            }
+            if let Some(next) = focused {
+                ui_events.single_write(UiEvent::new(UiEventType::HoverStart, next));
+            }
+            focus.focused = focused;
+        }
+        // a button that went away with its menu is not clicked anymore
+        if let Some(target) = self.pending_click.take().filter(|target| buttons.contains(target)) {
+            ui_events.single_write(UiEvent::new(UiEventType::Click, target));
+        }
+        self.pending_click = clicked;
+        // skip the events written above, so they are not taken for the mouse's
+        ui_events.read(&mut self.ui_reader_id).for_each(drop);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_harness::find_ui;
+    use amethyst::{
+        ecs::prelude::{Builder, RunNow, WorldExt},
+        ui::Anchor,
+    };
+    use test_case::test_case;
+
+    #[test_case(VirtualKeyCode::Down, false => Some(Navigation::Next))]
+    #[test_case(VirtualKeyCode::Tab, false => Some(Navigation::Next))]
+    #[test_case(VirtualKeyCode::Tab, true => Some(Navigation::Previous))]
+    #[test_case(VirtualKeyCode::Left, false => Some(Navigation::Previous))]
+    #[test_case(VirtualKeyCode::Return, false => Some(Navigation::Activate))]
+    #[test_case(VirtualKeyCode::W, false => None)]
+    fn key_navigation(key_code: VirtualKeyCode, shift: bool) -> Option<Navigation> {
+        Navigation::from_event(&InputEvent::KeyPressed { key_code, scancode: 0 }, shift)
+    }
+
+    #[test_case(ControllerButton::DPadRight => Some(Navigation::Next))]
+    #[test_case(ControllerButton::DPadUp => Some(Navigation::Previous))]
+    #[test_case(ControllerButton::A => Some(Navigation::Activate))]
+    #[test_case(ControllerButton::B => None)]
+    fn controller_navigation(button: ControllerButton) -> Option<Navigation> {
+        Navigation::from_event(&InputEvent::ControllerButtonPressed { which: 0, button }, false)
+    }
+
+    #[test]
+    fn neighbours_wrap_around() {
+        let mut world = World::new();
+        let buttons = (0..3).map(|_| world.create_entity().build()).collect::<Vec<_>>();
+        assert_eq!(neighbour(&buttons, None, true), Some(buttons[0]));
+        assert_eq!(neighbour(&buttons, None, false), Some(buttons[2]));
+        assert_eq!(neighbour(&buttons, Some(buttons[2]), true), Some(buttons[0]));
+        assert_eq!(neighbour(&buttons, Some(buttons[0]), false), Some(buttons[2]));
+        assert_eq!(neighbour(&[], None, true), None);
+    }
+
+    #[test]
+    fn navigate_and_click_in_tab_order() {
+        amethyst::start_logger(amethyst::LoggerConfig::default());
+        let test_result = crate::test_harness::IntegrationTestApplication::pong_base()
+            .with_setup(|world| {
+                world.register::<Interactable>();
+                for (id, tab_order) in [("second", 2), ("first", 1), ("third", 3)].iter() {
+                    let mut ui_transform =
+                        UiTransform::new(id.to_string(), Anchor::Middle, Anchor::Middle, 0., 0., 1., 200., 50.);
+                    ui_transform.tab_order = *tab_order;
+                    world.create_entity().with(ui_transform).with(Interactable).build();
+                }
+                // a label can not be focused
+                world
+                    .create_entity()
+                    .with(UiTransform::new(
+                        "label".to_string(),
+                        Anchor::Middle,
+                        Anchor::Middle,
+                        0.,
+                        0.,
+                        1.,
+                        200.,
+                        50.,
+                    ))
+                    .build();
+            })
+            .with_assertion(|world| {
+                let mut system = MenuFocusSystemDesc::default().build(world);
+                let mut reader_id = world.write_resource::<EventChannel<UiEvent>>().register_reader();
+                let press = |world: &mut World, key_code| {
+                    world
+                        .write_resource::<EventChannel<InputEvent<GameBindings>>>()
+                        .single_write(InputEvent::KeyPressed { key_code, scancode: 0 });
+                };
+
+                press(world, VirtualKeyCode::Up);
+                press(world, VirtualKeyCode::Down);
+                press(world, VirtualKeyCode::Down);
+                system.run_now(world);
+                let focused = world.read_resource::<MenuFocus>().focused;
+                assert_eq!(focused, find_ui(world, "second"));
+
+                world.write_resource::<MenuFocus>().suspended = true;
+                press(world, VirtualKeyCode::Down);
+                system.run_now(world);
+                let focused = world.read_resource::<MenuFocus>().focused;
+                assert_eq!(focused, find_ui(world, "second"));
+
+                world.write_resource::<MenuFocus>().suspended = false;
+                press(world, VirtualKeyCode::Return);
+                system.run_now(world);
+                let events = world
+                    .read_resource::<EventChannel<UiEvent>>()
+                    .read(&mut reader_id)
+                    .cloned()
+                    .collect::<Vec<_>>();
+                let second = find_ui(world, "second").expect("Could not find button");
+                assert!(events.contains(&UiEvent::new(UiEventType::HoverStart, second)));
+                assert!(!events.iter().any(|event| event.event_type == UiEventType::Click));
+
+                // the click follows in the next frame
+                system.run_now(world);
+                let events = world
+                    .read_resource::<EventChannel<UiEvent>>()
+                    .read(&mut reader_id)
+                    .cloned()
+                    .collect::<Vec<_>>();
+                assert_eq!(events, vec![UiEvent::new(UiEventType::Click, second)]);
+            })
+            .run();
+        assert!(test_result.is_ok());
+    }
+}
@@ -1,15 +1,35 @@
+mod ai_paddle;
 mod bounce;
 mod bundles;
+mod controller;
 mod events;
+mod fixed_timestep;
+mod hot_reload;
+mod menu_focus;
 mod move_balls;
+mod obstacle;
 mod paddle;
+mod pointer;
+mod power_up;
+mod serve;
 mod winner;
 
 pub use self::{
+    ai_paddle::{predict_intercept, AiDifficulty, AiPaddle, AiPaddleSystem, AiProfile},
     bounce::BounceSystem,
+    controller::{ControllerSystem, ControllerSystemDesc, Controllers},
     events::{UiEventHandlerSystem, UiEventHandlerSystemDesc},
+    fixed_timestep::{
+        interpolate_transforms, restore_simulated_transforms, store_previous_transforms, FixedTimestep, Interpolated,
+    },
+    hot_reload::{HotReloadEvent, HotReloadSystem, HotReloadSystemDesc, WatchedFile, WatchedKind},
+    menu_focus::{MenuFocus, MenuFocusSystem, MenuFocusSystemDesc, Navigation},
     move_balls::MoveBallsSystem,
+    obstacle::{patrol, Obstacle, ObstacleSystem},
     paddle::PaddleSystem,
-    winner::{ScoreText, WinnerSystem},
+    pointer::{steer_pointer_paddles, PointerSystem, PointerSystemDesc, Pointers},
+    power_up::{split_velocity, Effect, PowerUp, PowerUpKind, PowerUpSystem, Stuck},
+    serve::{serve_velocity, ServeSystem, Serving},
+    winner::{MatchOver, ScoreText, WinnerSystem},
 };
 pub use bundles::PongBundle;
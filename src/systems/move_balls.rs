@@ -1,23 +1,23 @@
-use crate::Ball;
+use crate::{systems::FixedTimestep, Ball};
 use amethyst::{
-    core::{timing::Time, transform::Transform, SystemDesc},
+    core::{transform::Transform, SystemDesc},
     derive::SystemDesc,
     ecs::prelude::{Join, Read, ReadStorage, System, SystemData, World, WriteStorage},
 };
 
 /// This system is responsible for moving all balls according to their speed
-/// and the time passed.
+/// and the length of a simulation step.
 #[derive(SystemDesc)]
 pub struct MoveBallsSystem;
 
 impl<'s> System<'s> for MoveBallsSystem {
-    type SystemData = (ReadStorage<'s, Ball>, WriteStorage<'s, Transform>, Read<'s, Time>);
+    type SystemData = (ReadStorage<'s, Ball>, WriteStorage<'s, Transform>, Read<'s, FixedTimestep>);
 
-    fn run(&mut self, (balls, mut locals, time): Self::SystemData) {
+    fn run(&mut self, (balls, mut locals, timestep): Self::SystemData) {
         // Move every ball according to its speed, and the time passed.
         for (ball, local) in (&balls, &mut locals).join() {
-            local.prepend_translation_x(ball.velocity[0] * time.delta_seconds());
-            local.prepend_translation_y(ball.velocity[1] * time.delta_seconds());
+            local.prepend_translation_x(ball.velocity[0] * timestep.step());
+            local.prepend_translation_y(ball.velocity[1] * timestep.step());
         }
     }
 }
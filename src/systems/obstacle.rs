@@ -0,0 +1,111 @@
+use crate::systems::FixedTimestep;
+use amethyst::{
+    core::{transform::Transform, SystemDesc},
+    derive::SystemDesc,
+    ecs::prelude::{Component, DenseVecStorage, Join, Read, System, SystemData, World, WriteStorage},
+};
+
+/// A box of a level the balls bounce off, see `Level`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Obstacle {
+    pub width: f32,
+    pub height: f32,
+    /// Velocity of a moving obstacle, zero for a static one.
+    pub velocity: [f32; 2],
+    /// Lowest coordinates the centre of a moving obstacle reaches.
+    pub min: [f32; 2],
+    /// Highest coordinates the centre of a moving obstacle reaches.
+    pub max: [f32; 2],
+}
+
+impl Component for Obstacle {
+    type Storage = DenseVecStorage<Self>;
+}
+
+/// Moves `position` by `velocity * step`, turning around at the bounds from `min` to `max` along
+/// each axis. Returns the new position and velocity.
+pub fn patrol(position: [f32; 2], velocity: [f32; 2], min: [f32; 2], max: [f32; 2], step: f32) -> [[f32; 2]; 2] {
+    let mut position = position;
+    let mut velocity = velocity;
+    for axis in 0..2 {
+        position[axis] += velocity[axis] * step;
+        if position[axis] > max[axis] {
+            position[axis] = max[axis] - (position[axis] - max[axis]).min(max[axis] - min[axis]);
+            velocity[axis] = -velocity[axis].abs();
+        } else if position[axis] < min[axis] {
+            position[axis] = min[axis] + (min[axis] - position[axis]).min(max[axis] - min[axis]);
+            velocity[axis] = velocity[axis].abs();
+        }
+    }
+    [position, velocity]
+}
+
+/// This system moves the moving obstacles of a level back and forth between their bounds.
+#[derive(SystemDesc)]
+pub struct ObstacleSystem;
+
+impl<'s> System<'s> for ObstacleSystem {
+    type SystemData = (WriteStorage<'s, Obstacle>, WriteStorage<'s, Transform>, Read<'s, FixedTimestep>);
+
+    fn run(&mut self, (mut obstacles, mut transforms, timestep): Self::SystemData) {
+        for (obstacle, transform) in (&mut obstacles, &mut transforms).join() {
+            if obstacle.velocity == [0.0, 0.0] {
+                continue;
+            }
+            let position = [transform.translation().x, transform.translation().y];
+            let [position, velocity] =
+                patrol(position, obstacle.velocity, obstacle.min, obstacle.max, timestep.step());
+            transform.set_translation_x(position[0]);
+            transform.set_translation_y(position[1]);
+            obstacle.velocity = velocity;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use amethyst::{ecs::prelude::WorldExt, prelude::Builder};
+    use assert_approx_eq::assert_approx_eq;
+    use test_case::test_case;
+
+    #[test_case([50.0, 45.0], [0.0, 20.0], 0.5 => [[50.0, 55.0], [0.0, 20.0]] ; "moves")]
+    #[test_case([50.0, 65.0], [0.0, 20.0], 0.5 => [[50.0, 65.0], [0.0, -20.0]] ; "turns at the top")]
+    #[test_case([50.0, 25.0], [0.0, -20.0], 0.5 => [[50.0, 25.0], [0.0, 20.0]] ; "turns at the bottom")]
+    #[test_case([50.0, 45.0], [10.0, 0.0], 0.5 => [[50.0, 45.0], [-10.0, 0.0]] ; "without room to move")]
+    fn test_patrol(position: [f32; 2], velocity: [f32; 2], step: f32) -> [[f32; 2]; 2] {
+        patrol(position, velocity, [50.0, 20.0], [50.0, 70.0], step)
+    }
+
+    #[test]
+    fn move_obstacles() {
+        amethyst::start_logger(amethyst::LoggerConfig::default());
+        let test_result = crate::test_harness::IntegrationTestApplication::pong_base()
+            .with_setup(|world| {
+                let mut transform = Transform::default();
+                transform.set_translation_xyz(50.0, 45.0, 0.0);
+                world
+                    .create_entity()
+                    .with(Obstacle {
+                        width: 3.0,
+                        height: 14.0,
+                        velocity: [0.0, 20.0],
+                        min: [50.0, 17.0],
+                        max: [50.0, 73.0],
+                    })
+                    .with(transform)
+                    .build();
+            })
+            .with_system_single(ObstacleSystem, "obstacle_system", &[])
+            .with_assertion(|world| {
+                let step = FixedTimestep::default().step();
+                for (_, transform) in (&world.read_storage::<Obstacle>(), &world.read_storage::<Transform>()).join() {
+                    assert_approx_eq!(transform.translation().x, 50.0);
+                    assert_approx_eq!(transform.translation().y, 45.0 + 20.0 * step);
+                }
+            })
+            .run();
+        assert!(test_result.is_ok());
+    }
+}
@@ -1,47 +1,48 @@
-use crate::Paddle;
+use crate::{
+    config::PongConfig,
+    replay::PaddleInputs,
+    systems::{AiPaddle, FixedTimestep},
+    Paddle,
+};
 use amethyst::{
-    core::{timing::Time, transform::Transform, SystemDesc},
+    core::{transform::Transform, SystemDesc},
     derive::SystemDesc,
     ecs::prelude::{Join, Read, ReadStorage, System, SystemData, World, WriteStorage},
-    input::{InputHandler, StringBindings},
 };
 
-/// This system is responsible for moving all the paddles according to the user
-/// provided input.
+/// This system is responsible for moving all the paddles along their axis according to the user
+/// provided input, which `Pong` hands over as `PaddleInputs` for every simulation step.
+/// Paddles controlled by the computer are left to the `AiPaddleSystem`.
 #[derive(SystemDesc)]
 pub struct PaddleSystem;
 
 impl<'s> System<'s> for PaddleSystem {
     type SystemData = (
-        ReadStorage<'s, Paddle>,
+        WriteStorage<'s, Paddle>,
+        ReadStorage<'s, AiPaddle>,
         WriteStorage<'s, Transform>,
-        Read<'s, Time>,
-        Read<'s, InputHandler<StringBindings>>,
+        Read<'s, FixedTimestep>,
+        Read<'s, PaddleInputs>,
+        Read<'s, PongConfig>,
     );
 
-    fn run(&mut self, (paddles, mut transforms, time, input): Self::SystemData) {
-        use crate::Side;
-
+    fn run(&mut self, (mut paddles, ai_paddles, mut transforms, timestep, inputs, config): Self::SystemData) {
         // Iterate over all planks and move them according to the input the user
         // provided.
-        for (paddle, transform) in (&paddles, &mut transforms).join() {
-            let opt_movement = match paddle.side {
-                Side::Left => input.axis_value("left_paddle"),
-                Side::Right => input.axis_value("right_paddle"),
-            };
+        for (paddle, transform, _) in (&mut paddles, &mut transforms, !&ai_paddles).join() {
+            let opt_movement = inputs.axis_value(paddle.side);
+            let axis = paddle.side.axis();
+            let previous = transform.translation()[axis];
 
             if let Some(movement) = opt_movement {
-                use crate::ARENA_HEIGHT;
-                transform.prepend_translation_y(paddle.velocity * time.delta_seconds() * movement as f32);
+                let position = previous + paddle.velocity * timestep.step() * movement as f32;
 
                 // We make sure the paddle remains in the arena.
-                let paddle_y = transform.translation().y;
-                transform.set_translation_y(
-                    paddle_y
-                        .max(paddle.height * 0.5)
-                        .min(ARENA_HEIGHT - paddle.height * 0.5),
-                );
+                transform.translation_mut()[axis] = position
+                    .max(paddle.height * 0.5)
+                    .min(config.arena.length(axis) - paddle.height * 0.5);
             }
+            paddle.movement = (transform.translation()[axis] - previous) / timestep.step();
         }
     }
 }
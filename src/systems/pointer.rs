@@ -0,0 +1,269 @@
+use crate::{
+    bindings::GameBindings,
+    config::{ArenaConfig, PongConfig},
+    replay::PaddleInputs,
+    systems::FixedTimestep,
+    MatchSettings, Paddle, PaddleController, Side,
+};
+use amethyst::{
+    core::{math::Point3, transform::Transform, SystemDesc},
+    ecs::prelude::{Join, Read, ReadExpect, ReadStorage, System, SystemData, World, Write},
+    input::InputHandler,
+    renderer::Camera,
+    shrev::{EventChannel, ReaderId},
+    window::ScreenDimensions,
+    winit::{Event, TouchPhase, WindowEvent},
+};
+use std::collections::BTreeMap;
+
+/// World positions of the mouse cursor and of the fingers on the screen, which the paddles of
+/// `PaddleController::Pointer` players follow.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Pointers {
+    /// Position of the mouse cursor, while it is over the window.
+    pub mouse: Option<[f32; 2]>,
+    /// Positions of the touches, by touch id.
+    pub touches: BTreeMap<u64, [f32; 2]>,
+}
+
+impl Pointers {
+    /// Position along its axis the paddle on `side` follows. A touch on the side's half of the
+    /// arena takes precedence over the mouse cursor, so every player of a shared touch screen
+    /// controls their own paddle.
+    pub fn target(&self, side: Side, arena: &ArenaConfig) -> Option<f32> {
+        let axis = side.axis();
+        let across = 1 - axis;
+        let middle = arena.length(across) * 0.5;
+        let on_half = |position: &&[f32; 2]| match side {
+            Side::Left | Side::Bottom => position[across] < middle,
+            Side::Right | Side::Top => position[across] >= middle,
+        };
+        self.touches
+            .values()
+            .find(on_half)
+            .or_else(|| self.mouse.as_ref())
+            .map(|position| position[axis])
+    }
+}
+
+/// Sets the inputs of the paddles played with a pointer, moving each paddle towards its target as
+/// fast as it may move. The pointer is turned into inputs instead of moving the paddle directly,
+/// so a pointer player's match records and replays like any other.
+pub fn steer_pointer_paddles(world: &World, inputs: &mut PaddleInputs) {
+    let (pointers, paddles, transforms, match_settings, timestep, config): (
+        Option<Read<'_, Pointers>>,
+        ReadStorage<'_, Paddle>,
+        ReadStorage<'_, Transform>,
+        Read<'_, MatchSettings>,
+        Read<'_, FixedTimestep>,
+        Read<'_, PongConfig>,
+    ) = world.system_data();
+    let pointers = match pointers {
+        Some(pointers) => pointers,
+        None => return,
+    };
+    for (paddle, transform) in (&paddles, &transforms).join() {
+        if match_settings.controller(paddle.side) != PaddleController::Pointer {
+            continue;
+        }
+        if let Some(target) = pointers.target(paddle.side, &config.arena) {
+            let distance = target - transform.translation()[paddle.side.axis()];
+            let movement = distance / (paddle.velocity * timestep.step());
+            inputs.set_axis_value(paddle.side, Some(movement.max(-1.0).min(1.0)));
+        }
+    }
+}
+
+/// Builds a `PointerSystem` reading the window events.
+#[derive(Debug, Default)]
+pub struct PointerSystemDesc;
+
+impl<'a, 'b> SystemDesc<'a, 'b, PointerSystem> for PointerSystemDesc {
+    fn build(self, world: &mut World) -> PointerSystem {
+        <PointerSystem as System<'_>>::SystemData::setup(world);
+        let reader_id = world.fetch_mut::<EventChannel<Event>>().register_reader();
+        PointerSystem::new(reader_id)
+    }
+}
+
+/// This system converts the screen positions of the mouse cursor and the touches to positions in
+/// the arena, through the camera of the match, and keeps them in `Pointers`. Without a camera,
+/// outside of a match, there are no pointers.
+#[derive(Debug)]
+pub struct PointerSystem {
+    reader_id: ReaderId<Event>,
+    /// Physical screen positions of the touches, by touch id.
+    touches: BTreeMap<u64, (f32, f32)>,
+}
+
+impl PointerSystem {
+    pub fn new(reader_id: ReaderId<Event>) -> Self {
+        Self {
+            reader_id,
+            touches: BTreeMap::new(),
+        }
+    }
+}
+
+impl<'s> System<'s> for PointerSystem {
+    type SystemData = (
+        Read<'s, EventChannel<Event>>,
+        Read<'s, InputHandler<GameBindings>>,
+        ReadExpect<'s, ScreenDimensions>,
+        ReadStorage<'s, Camera>,
+        ReadStorage<'s, Transform>,
+        Write<'s, Pointers>,
+    );
+
+    fn run(&mut self, (events, input, screen, cameras, transforms, mut pointers): Self::SystemData) {
+        for event in events.read(&mut self.reader_id) {
+            if let Event::WindowEvent {
+                event: WindowEvent::Touch(touch),
+                ..
+            } = event
+            {
+                match touch.phase {
+                    TouchPhase::Started | TouchPhase::Moved => {
+                        let position = touch.location.to_physical(screen.hidpi_factor());
+                        self.touches.insert(touch.id, (position.x as f32, position.y as f32));
+                    }
+                    TouchPhase::Ended | TouchPhase::Cancelled => {
+                        self.touches.remove(&touch.id);
+                    }
+                }
+            }
+        }
+
+        *pointers = match (&cameras, &transforms).join().next() {
+            Some((camera, camera_transform)) => {
+                let to_world = |(x, y): (f32, f32)| {
+                    let point =
+                        camera.screen_to_world_point(Point3::new(x, y, 0.0), screen.diagonal(), camera_transform);
+                    [point.x, point.y]
+                };
+                Pointers {
+                    mouse: input.mouse_position().map(to_world),
+                    touches: self.touches.iter().map(|(id, position)| (*id, to_world(*position))).collect(),
+                }
+            }
+            None => Pointers::default(),
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use amethyst::{
+        ecs::prelude::{Builder, RunNow, WorldExt},
+        winit::{dpi::LogicalPosition, DeviceId, Touch, WindowId},
+    };
+    use assert_approx_eq::assert_approx_eq;
+
+    fn arena() -> ArenaConfig {
+        ArenaConfig {
+            width: 200.0,
+            height: 100.0,
+        }
+    }
+
+    #[test]
+    fn touches_steer_the_paddle_of_their_half() {
+        let mut pointers = Pointers {
+            mouse: Some([20.0, 30.0]),
+            touches: BTreeMap::new(),
+        };
+        pointers.touches.insert(1, [150.0, 80.0]);
+        assert_eq!(pointers.target(Side::Right, &arena()), Some(80.0));
+        assert_eq!(pointers.target(Side::Top, &arena()), Some(150.0));
+        // the mouse steers the paddles without a touch on their half
+        assert_eq!(pointers.target(Side::Left, &arena()), Some(30.0));
+        assert_eq!(pointers.target(Side::Bottom, &arena()), Some(20.0));
+
+        pointers.mouse = None;
+        assert_eq!(pointers.target(Side::Left, &arena()), None);
+    }
+
+    #[test]
+    fn pointer_paddle_moves_at_most_at_full_speed() {
+        amethyst::start_logger(amethyst::LoggerConfig::default());
+        let test_result = crate::test_harness::IntegrationTestApplication::pong_base()
+            .with_resource(FixedTimestep::default())
+            .with_setup(|world| {
+                world.write_resource::<MatchSettings>().left = PaddleController::Pointer;
+                for side in [Side::Left, Side::Right].iter() {
+                    let mut transform = Transform::default();
+                    transform.set_translation_xyz(0.0, 50.0, 0.0);
+                    world
+                        .create_entity()
+                        .with(Paddle {
+                            velocity: 60.0,
+                            side: *side,
+                            width: 4.0,
+                            height: 16.0,
+                            movement: 0.0,
+                        })
+                        .with(transform)
+                        .build();
+                }
+            })
+            .with_assertion(|world| {
+                let step = world.read_resource::<FixedTimestep>().step();
+                world.insert(Pointers {
+                    mouse: Some([10.0, 50.0 + 30.0 * step]),
+                    touches: BTreeMap::new(),
+                });
+
+                let mut inputs = PaddleInputs::default();
+                steer_pointer_paddles(world, &mut inputs);
+                assert_approx_eq!(inputs.axis_value(Side::Left).unwrap(), 0.5);
+                assert_eq!(inputs.axis_value(Side::Right), None);
+
+                world.insert(Pointers {
+                    mouse: Some([10.0, 0.0]),
+                    touches: BTreeMap::new(),
+                });
+                steer_pointer_paddles(world, &mut inputs);
+                assert_approx_eq!(inputs.axis_value(Side::Left).unwrap(), -1.0);
+            })
+            .run();
+        assert!(test_result.is_ok());
+    }
+
+    #[test]
+    fn touches_are_converted_through_the_camera() {
+        amethyst::start_logger(amethyst::LoggerConfig::default());
+        let test_result = crate::test_harness::IntegrationTestApplication::pong_base()
+            .with_setup(|world| {
+                let mut transform = Transform::default();
+                transform.set_translation_xyz(960.0, 640.0, 1.0);
+                world
+                    .create_entity()
+                    .with(Camera::standard_2d(1920.0, 1280.0))
+                    .with(transform)
+                    .build();
+            })
+            .with_assertion(|world| {
+                let mut system = PointerSystemDesc::default().build(world);
+                world.write_resource::<EventChannel<Event>>().single_write(Event::WindowEvent {
+                    window_id: unsafe { WindowId::dummy() },
+                    event: WindowEvent::Touch(Touch {
+                        device_id: unsafe { DeviceId::dummy() },
+                        phase: TouchPhase::Started,
+                        location: LogicalPosition::new(480.0, 320.0),
+                        id: 3,
+                    }),
+                });
+                system.run_now(world);
+
+                let pointers = world.read_resource::<Pointers>();
+                assert_eq!(pointers.mouse, None);
+                let touch = pointers.touches[&3];
+                // the y axis of the screen points down, the one of the arena up
+                assert_approx_eq!(touch[0], 480.0, 0.01);
+                assert_approx_eq!(touch[1], 960.0, 0.01);
+            })
+            .run();
+        assert!(test_result.is_ok());
+    }
+}
@@ -0,0 +1,594 @@
+use crate::{
+    config::{PongConfig, PowerUpConfig},
+    systems::{FixedTimestep, Interpolated, Serving},
+    Ball, Paddle, Side,
+};
+use amethyst::{
+    core::{math::Vector3, transform::Transform, Parent, SystemDesc},
+    derive::SystemDesc,
+    ecs::prelude::{
+        Component, DenseVecStorage, Entities, Entity, Join, Read, ReadStorage, System, SystemData, World,
+        WriteStorage,
+    },
+    renderer::SpriteRender,
+};
+use serde::{Deserialize, Serialize};
+
+/// What a power-up does once a ball collects it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PowerUpKind {
+    /// The paddle of the collecting player grows.
+    Grow,
+    /// The paddle of the opponent shrinks.
+    Shrink,
+    /// The collecting ball gets faster.
+    SpeedUp,
+    /// The collecting ball gets slower.
+    SlowDown,
+    /// Another ball splits off the collecting ball.
+    ExtraBall,
+    /// The paddle of the collecting player holds balls for a moment before returning them.
+    Sticky,
+}
+
+impl PowerUpKind {
+    pub const ALL: [Self; 6] = [
+        Self::Grow,
+        Self::Shrink,
+        Self::SpeedUp,
+        Self::SlowDown,
+        Self::ExtraBall,
+        Self::Sticky,
+    ];
+
+    /// Colour the power-up is tinted with while it lies in the arena.
+    pub const fn color(self) -> [f32; 3] {
+        match self {
+            Self::Grow => [0.2, 0.9, 0.2],
+            Self::Shrink => [0.9, 0.2, 0.2],
+            Self::SpeedUp => [1.0, 0.65, 0.0],
+            Self::SlowDown => [0.2, 0.6, 1.0],
+            Self::ExtraBall => [1.0, 1.0, 1.0],
+            Self::Sticky => [0.8, 0.3, 0.9],
+        }
+    }
+
+    /// Factor the height of an affected paddle is multiplied with.
+    fn height_factor(self, config: &PowerUpConfig) -> f32 {
+        match self {
+            Self::Grow => config.grow,
+            Self::Shrink => config.shrink,
+            _ => 1.0,
+        }
+    }
+
+    /// Factor the speed of an affected ball is multiplied with.
+    fn speed_factor(self, config: &PowerUpConfig) -> f32 {
+        match self {
+            Self::SpeedUp => config.speed_up,
+            Self::SlowDown => config.slow_down,
+            _ => 1.0,
+        }
+    }
+}
+
+/// A power-up lying in the arena. Balls collect it for the player whose paddle they touched last.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PowerUp {
+    pub kind: PowerUpKind,
+    pub radius: f32,
+}
+
+impl Component for PowerUp {
+    type Storage = DenseVecStorage<Self>;
+}
+
+/// Timed effect of a collected power-up. Every effect lives on an entity of its own below the root
+/// entity of the match, so it is removed together with the match.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Effect {
+    pub kind: PowerUpKind,
+    /// Player whose paddle is affected.
+    pub side: Side,
+    /// Ball whose speed is affected.
+    pub ball: Option<Entity>,
+    /// Seconds until the effect wears off.
+    pub remaining: f32,
+}
+
+impl Component for Effect {
+    type Storage = DenseVecStorage<Self>;
+}
+
+/// A ball held by a sticky paddle.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Stuck {
+    pub side: Side,
+    /// Position of the ball along the axis of the paddle, relative to its centre.
+    pub offset: f32,
+    /// Velocity the ball is released with.
+    pub velocity: [f32; 2],
+    /// Seconds until the ball is released.
+    pub remaining: f32,
+}
+
+impl Component for Stuck {
+    type Storage = DenseVecStorage<Self>;
+}
+
+/// Velocity of a ball splitting off a ball moving with `velocity`: the same speed, turned by 30
+/// degrees.
+pub fn split_velocity(velocity: [f32; 2]) -> [f32; 2] {
+    let (sin, cos) = 30f32.to_radians().sin_cos();
+    [velocity[0] * cos - velocity[1] * sin, velocity[0] * sin + velocity[1] * cos]
+}
+
+/// Position of a ball with `radius` splitting off a ball at `position`: two radii away along the
+/// `velocity` of the new ball, so the two balls do not collide and cancel the split.
+pub fn split_position(position: [f32; 2], velocity: [f32; 2], radius: f32) -> [f32; 2] {
+    let speed = (velocity[0] * velocity[0] + velocity[1] * velocity[1]).sqrt();
+    let direction = if speed > 0.0 {
+        [velocity[0] / speed, velocity[1] / speed]
+    } else {
+        [1.0, 0.0]
+    };
+    [
+        position[0] + direction[0] * 2.0 * radius,
+        position[1] + direction[1] * 2.0 * radius,
+    ]
+}
+
+/// `velocity` multiplied by `factor`, but no faster than `max_speed` unless it was before.
+fn scale_velocity(velocity: [f32; 2], factor: f32, max_speed: f32) -> [f32; 2] {
+    let speed = (velocity[0] * velocity[0] + velocity[1] * velocity[1]).sqrt();
+    let scaled = speed * factor;
+    let factor = if scaled > max_speed && scaled > speed {
+        speed.max(max_speed) / speed
+    } else {
+        factor
+    };
+    [velocity[0] * factor, velocity[1] * factor]
+}
+
+/// This system hands out the power-ups collected by the balls and keeps their effects up to date.
+///
+/// Effects are counted down every simulation step. Paddle sizes are derived from the effects in
+/// place, so a paddle is back to its configured size as soon as its last effect wore off. Paddles
+/// without a size effect are left alone. Speed effects are undone when they wear off, unless the
+/// rally of their ball ended before, without making the ball faster than `BounceConfig::max_speed`.
+/// Balls held by a sticky paddle follow it until they are released.
+#[derive(SystemDesc)]
+pub struct PowerUpSystem;
+
+impl<'s> System<'s> for PowerUpSystem {
+    type SystemData = (
+        Entities<'s>,
+        ReadStorage<'s, PowerUp>,
+        WriteStorage<'s, Effect>,
+        WriteStorage<'s, Stuck>,
+        WriteStorage<'s, Ball>,
+        ReadStorage<'s, Serving>,
+        WriteStorage<'s, Paddle>,
+        WriteStorage<'s, Transform>,
+        WriteStorage<'s, Interpolated>,
+        WriteStorage<'s, SpriteRender>,
+        WriteStorage<'s, Parent>,
+        Read<'s, FixedTimestep>,
+        Read<'s, PongConfig>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            entities,
+            power_ups,
+            mut effects,
+            mut stuck_balls,
+            mut balls,
+            servings,
+            mut paddles,
+            mut transforms,
+            mut interpolated,
+            mut sprites,
+            mut parents,
+            timestep,
+            config,
+        ): Self::SystemData,
+    ) {
+        let step = timestep.step();
+
+        // Count the effects down. A speed effect also ends with the rally of its ball: a ball that
+        // is served again moves at the serve speed already.
+        for (entity, effect) in (&entities, &mut effects).join() {
+            effect.remaining -= step;
+            let rally_over = effect
+                .ball
+                .map_or(false, |ball| !entities.is_alive(ball) || servings.contains(ball));
+            if effect.remaining > 0.0 && !rally_over {
+                continue;
+            }
+            if !rally_over {
+                if let Some(ball) = effect.ball {
+                    let factor = 1.0 / effect.kind.speed_factor(&config.power_ups);
+                    let max_speed = config.bounce.max_speed;
+                    if let Some(stuck) = stuck_balls.get_mut(ball) {
+                        stuck.velocity = scale_velocity(stuck.velocity, factor, max_speed);
+                    } else if let Some(ball) = balls.get_mut(ball) {
+                        ball.velocity = scale_velocity(ball.velocity, factor, max_speed);
+                    }
+                }
+            }
+            effect.remaining = 0.0;
+            entities.delete(entity).expect("effect entity is alive");
+        }
+
+        // Collect the power-ups hit by balls that touched a paddle.
+        let mut collected: Vec<(Entity, PowerUpKind, Entity, Side)> = Vec::new();
+        for (ball_entity, ball, ball_transform, _) in (&entities, &balls, &transforms, !&servings).join() {
+            let side = match ball.last_hit {
+                Some(side) => side,
+                None => continue,
+            };
+            for (entity, power_up, transform) in (&entities, &power_ups, &transforms).join() {
+                let delta = [
+                    transform.translation().x - ball_transform.translation().x,
+                    transform.translation().y - ball_transform.translation().y,
+                ];
+                let reach = ball.radius + power_up.radius;
+                if delta[0] * delta[0] + delta[1] * delta[1] < reach * reach
+                    && !collected.iter().any(|(collected, ..)| *collected == entity)
+                {
+                    collected.push((entity, power_up.kind, ball_entity, side));
+                }
+            }
+        }
+
+        for (power_up, kind, ball_entity, side) in collected {
+            entities.delete(power_up).expect("power-up entity is alive");
+            log::info!("{:?} player collected {:?}", side, kind);
+            let root = parents.get(ball_entity).map(|parent| parent.entity);
+            let affected_ball = match kind {
+                PowerUpKind::SpeedUp | PowerUpKind::SlowDown => {
+                    if let Some(ball) = balls.get_mut(ball_entity) {
+                        let factor = kind.speed_factor(&config.power_ups);
+                        ball.velocity = [ball.velocity[0] * factor, ball.velocity[1] * factor];
+                    }
+                    Some(ball_entity)
+                }
+                PowerUpKind::ExtraBall => {
+                    let split = balls.get(ball_entity).map(|ball| Ball {
+                        velocity: split_velocity(ball.velocity),
+                        radius: ball.radius,
+                        last_hit: ball.last_hit,
+                    });
+                    let transform = transforms.get(ball_entity).cloned();
+                    let sprite = sprites.get(ball_entity).cloned();
+                    if let (Some(split), Some(mut transform), Some(sprite)) = (split, transform, sprite) {
+                        let position = split_position(
+                            [transform.translation().x, transform.translation().y],
+                            split.velocity,
+                            split.radius,
+                        );
+                        transform.set_translation_x(position[0]);
+                        transform.set_translation_y(position[1]);
+                        let extra_ball = entities.create();
+                        balls.insert(extra_ball, split).expect("ball was just created");
+                        transforms.insert(extra_ball, transform).expect("ball was just created");
+                        interpolated
+                            .insert(extra_ball, Interpolated::new(position))
+                            .expect("ball was just created");
+                        sprites.insert(extra_ball, sprite).expect("ball was just created");
+                        if let Some(root) = root {
+                            parents
+                                .insert(extra_ball, Parent { entity: root })
+                                .expect("ball was just created");
+                        }
+                    }
+                    continue;
+                }
+                PowerUpKind::Grow | PowerUpKind::Shrink | PowerUpKind::Sticky => None,
+            };
+
+            let effect = entities.create();
+            effects
+                .insert(
+                    effect,
+                    Effect {
+                        kind,
+                        side: if kind == PowerUpKind::Shrink { side.opponent() } else { side },
+                        ball: affected_ball,
+                        remaining: config.power_ups.duration,
+                    },
+                )
+                .expect("effect was just created");
+            if let Some(root) = root {
+                parents.insert(effect, Parent { entity: root }).expect("effect was just created");
+            }
+        }
+
+        // Size the paddles according to the effects on them. The effects that wore off above are
+        // still joined until the world is maintained, so their paddle is sized back once.
+        for (paddle, transform) in (&mut paddles, &mut transforms).join() {
+            let sized_by = |effect: &&Effect| {
+                effect.side == paddle.side && (effect.kind == PowerUpKind::Grow || effect.kind == PowerUpKind::Shrink)
+            };
+            if !(&effects).join().any(|effect| sized_by(&effect)) {
+                continue;
+            }
+            let factor = (&effects)
+                .join()
+                .filter(sized_by)
+                .filter(|effect| effect.remaining > 0.0)
+                .map(|effect| effect.kind.height_factor(&config.power_ups))
+                .product::<f32>();
+            paddle.height = config.paddle.height * factor;
+            transform.set_scale(Vector3::new(1.0, factor, 1.0));
+        }
+
+        // Balls held by a sticky paddle move along with it until they are released.
+        let paddle_positions = (&paddles, &transforms)
+            .join()
+            .map(|(paddle, transform)| (paddle.side, transform.translation()[paddle.side.axis()]))
+            .collect::<Vec<_>>();
+        let mut released = Vec::new();
+        for (entity, ball, stuck, transform) in (&entities, &mut balls, &mut stuck_balls, &mut transforms).join() {
+            if let Some((_, paddle_position)) = paddle_positions.iter().find(|(side, _)| *side == stuck.side) {
+                transform.translation_mut()[stuck.side.axis()] = paddle_position + stuck.offset;
+            }
+            stuck.remaining -= step;
+            if stuck.remaining <= 0.0 {
+                ball.velocity = stuck.velocity;
+                released.push(entity);
+            }
+        }
+        for entity in released {
+            stuck_balls.remove(entity);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::states::{initialise_ball, initialise_paddles, initialise_power_up, load_sprite_sheet};
+    use amethyst::{ecs::prelude::WorldExt, prelude::Builder};
+    use assert_approx_eq::assert_approx_eq;
+    use test_case::test_case;
+
+    #[test_case([10.0, 0.0] => [8.66, 5.0] ; "to the right")]
+    #[test_case([-10.0, 0.0] => [-8.66, -5.0] ; "to the left")]
+    #[test_case([0.0, 10.0] => [-5.0, 8.66] ; "upwards")]
+    fn test_split_velocity(velocity: [f32; 2]) -> [f32; 2] {
+        let [x, y] = split_velocity(velocity);
+        [(x * 100.0).round() / 100.0, (y * 100.0).round() / 100.0]
+    }
+
+    #[test]
+    fn split_ball_does_not_touch_its_origin() {
+        let velocity = split_velocity([60.0, 0.0]);
+        let position = split_position([80.0, 45.0], velocity, 2.0);
+        let delta = [position[0] - 80.0, position[1] - 45.0];
+        assert_approx_eq!((delta[0] * delta[0] + delta[1] * delta[1]).sqrt(), 4.0);
+        assert_eq!(
+            crate::systems::bounce::collide_balls([[80.0, 45.0], position], [[60.0, 0.0], velocity], [2.0, 2.0]),
+            None
+        );
+    }
+
+    #[test_case([60.0, 0.0], 2.0, 100.0 => [100.0, 0.0] ; "capped")]
+    #[test_case([60.0, 0.0], 0.5, 100.0 => [30.0, 0.0] ; "slowed down")]
+    #[test_case([150.0, 0.0], 0.8, 100.0 => [120.0, 0.0] ; "already faster than the cap")]
+    fn test_scale_velocity(velocity: [f32; 2], factor: f32, max_speed: f32) -> [f32; 2] {
+        let [x, y] = scale_velocity(velocity, factor, max_speed);
+        [x.round(), y.round()]
+    }
+
+    /// Creates the paddles and a ball at `position`, last touched by the player on `last_hit`.
+    fn arena(world: &mut World, velocity: [f32; 2], position: [f32; 2], last_hit: Option<Side>) -> (Entity, Entity) {
+        let root_entity = world.create_entity().with(Transform::default()).build();
+        let sprite_sheet_handle = load_sprite_sheet(world);
+        initialise_paddles(world, root_entity, sprite_sheet_handle.clone());
+        let ball = initialise_ball(world, root_entity, sprite_sheet_handle, 2.0, velocity, Some(position));
+        world
+            .write_storage::<Ball>()
+            .get_mut(ball)
+            .expect("ball was just created")
+            .last_hit = last_hit;
+        (root_entity, ball)
+    }
+
+    fn paddle_height(world: &World, side: Side) -> f32 {
+        world
+            .read_storage::<Paddle>()
+            .join()
+            .find(|paddle| paddle.side == side)
+            .map(|paddle| paddle.height)
+            .expect("paddle exists")
+    }
+
+    #[test]
+    fn collect_grow_power_up() {
+        amethyst::start_logger(amethyst::LoggerConfig::default());
+        let test_result = crate::test_harness::IntegrationTestApplication::pong_base()
+            .with_setup(|world| {
+                let (root_entity, _) = arena(world, [0.0, 0.0], [80.0, 45.0], Some(Side::Left));
+                let sprite_sheet_handle = load_sprite_sheet(world);
+                initialise_power_up(world, root_entity, sprite_sheet_handle, PowerUpKind::Grow, [81.0, 45.0]);
+            })
+            .with_system_single(PowerUpSystem, "power_up_system", &[])
+            .with_assertion(|world| {
+                let config = PongConfig::default();
+                assert_eq!(0, world.read_storage::<PowerUp>().count());
+                let effects = world.read_storage::<Effect>();
+                let effect = effects.join().next().expect("power-up was collected");
+                assert_eq!((effect.kind, effect.side), (PowerUpKind::Grow, Side::Left));
+                assert_approx_eq!(paddle_height(world, Side::Left), config.paddle.height * config.power_ups.grow);
+                assert_approx_eq!(paddle_height(world, Side::Right), config.paddle.height);
+            })
+            .run();
+        assert!(test_result.is_ok());
+    }
+
+    #[test]
+    fn untouched_ball_does_not_collect() {
+        amethyst::start_logger(amethyst::LoggerConfig::default());
+        let test_result = crate::test_harness::IntegrationTestApplication::pong_base()
+            .with_setup(|world| {
+                let (root_entity, _) = arena(world, [0.0, 0.0], [80.0, 45.0], None);
+                let sprite_sheet_handle = load_sprite_sheet(world);
+                initialise_power_up(world, root_entity, sprite_sheet_handle, PowerUpKind::Shrink, [81.0, 45.0]);
+            })
+            .with_system_single(PowerUpSystem, "power_up_system", &[])
+            .with_assertion(|world| {
+                assert_eq!(1, world.read_storage::<PowerUp>().count());
+                assert_eq!(0, world.read_storage::<Effect>().count());
+            })
+            .run();
+        assert!(test_result.is_ok());
+    }
+
+    #[test]
+    fn collect_extra_ball_power_up() {
+        amethyst::start_logger(amethyst::LoggerConfig::default());
+        let test_result = crate::test_harness::IntegrationTestApplication::pong_base()
+            .with_setup(|world| {
+                let (root_entity, _) = arena(world, [60.0, 0.0], [80.0, 45.0], Some(Side::Left));
+                let sprite_sheet_handle = load_sprite_sheet(world);
+                initialise_power_up(world, root_entity, sprite_sheet_handle, PowerUpKind::ExtraBall, [81.0, 45.0]);
+            })
+            .with_system_single(PowerUpSystem, "power_up_system", &[])
+            .with_assertion(|world| {
+                let balls = world.read_storage::<Ball>();
+                let transforms = world.read_storage::<Transform>();
+                let positions = (&balls, &transforms)
+                    .join()
+                    .map(|(_, transform)| [transform.translation().x, transform.translation().y])
+                    .collect::<Vec<_>>();
+                assert_eq!(positions.len(), 2);
+                let delta = [positions[1][0] - positions[0][0], positions[1][1] - positions[0][1]];
+                assert_approx_eq!((delta[0] * delta[0] + delta[1] * delta[1]).sqrt(), 4.0);
+            })
+            .run();
+        assert!(test_result.is_ok());
+    }
+
+    #[test]
+    fn expired_effect_restores_paddle() {
+        amethyst::start_logger(amethyst::LoggerConfig::default());
+        let test_result = crate::test_harness::IntegrationTestApplication::pong_base()
+            .with_setup(|world| {
+                arena(world, [0.0, 0.0], [80.0, 45.0], None);
+                // a paddle without a size effect keeps whatever height it has
+                for paddle in (&mut world.write_storage::<Paddle>()).join() {
+                    paddle.height *= 2.0;
+                }
+                world
+                    .create_entity()
+                    .with(Effect {
+                        kind: PowerUpKind::Grow,
+                        side: Side::Right,
+                        ball: None,
+                        remaining: 0.001,
+                    })
+                    .build();
+            })
+            .with_system_single(PowerUpSystem, "power_up_system", &[])
+            .with_assertion(|world| {
+                let config = PongConfig::default();
+                assert_approx_eq!(paddle_height(world, Side::Left), config.paddle.height * 2.0);
+                assert_approx_eq!(paddle_height(world, Side::Right), config.paddle.height);
+            })
+            .run();
+        assert!(test_result.is_ok());
+    }
+
+    #[test]
+    fn expired_speed_effect_is_undone() {
+        amethyst::start_logger(amethyst::LoggerConfig::default());
+        let test_result = crate::test_harness::IntegrationTestApplication::pong_base()
+            .with_setup(|world| {
+                let (_, ball) = arena(world, [60.0, 0.0], [80.0, 45.0], Some(Side::Left));
+                world
+                    .create_entity()
+                    .with(Effect {
+                        kind: PowerUpKind::SpeedUp,
+                        side: Side::Left,
+                        ball: Some(ball),
+                        remaining: 0.001,
+                    })
+                    .build();
+            })
+            .with_system_single(PowerUpSystem, "power_up_system", &[])
+            .with_assertion(|world| {
+                let speed_up = PongConfig::default().power_ups.speed_up;
+                for ball in world.read_storage::<Ball>().join() {
+                    assert_approx_eq!(ball.velocity[0], 60.0 / speed_up);
+                    assert_approx_eq!(ball.velocity[1], 0.0);
+                }
+            })
+            .run();
+        assert!(test_result.is_ok());
+    }
+
+    #[test]
+    fn undone_slow_down_respects_max_speed() {
+        amethyst::start_logger(amethyst::LoggerConfig::default());
+        let test_result = crate::test_harness::IntegrationTestApplication::pong_base()
+            .with_setup(|world| {
+                let max_speed = PongConfig::default().bounce.max_speed;
+                // the slowed down ball was sped up to the cap by paddle hits in the meantime
+                let (_, ball) = arena(world, [max_speed, 0.0], [80.0, 45.0], Some(Side::Left));
+                world
+                    .create_entity()
+                    .with(Effect {
+                        kind: PowerUpKind::SlowDown,
+                        side: Side::Left,
+                        ball: Some(ball),
+                        remaining: 0.001,
+                    })
+                    .build();
+            })
+            .with_system_single(PowerUpSystem, "power_up_system", &[])
+            .with_assertion(|world| {
+                let max_speed = PongConfig::default().bounce.max_speed;
+                for ball in world.read_storage::<Ball>().join() {
+                    assert_approx_eq!(ball.velocity[0], max_speed);
+                }
+            })
+            .run();
+        assert!(test_result.is_ok());
+    }
+
+    #[test]
+    fn release_stuck_ball() {
+        amethyst::start_logger(amethyst::LoggerConfig::default());
+        let test_result = crate::test_harness::IntegrationTestApplication::pong_base()
+            .with_setup(|world| {
+                let (_, ball) = arena(world, [0.0, 0.0], [6.0, 40.0], Some(Side::Left));
+                world
+                    .write_storage::<Stuck>()
+                    .insert(
+                        ball,
+                        Stuck {
+                            side: Side::Left,
+                            offset: 2.0,
+                            velocity: [75.0, 10.0],
+                            remaining: 0.001,
+                        },
+                    )
+                    .expect("ball was just created");
+            })
+            .with_system_single(PowerUpSystem, "power_up_system", &[])
+            .with_assertion(|world| {
+                let arena = PongConfig::default().arena;
+                assert_eq!(0, world.read_storage::<Stuck>().count());
+                for (ball, transform) in (&world.read_storage::<Ball>(), &world.read_storage::<Transform>()).join() {
+                    assert_eq!(ball.velocity, [75.0, 10.0]);
+                    assert_approx_eq!(transform.translation().y, arena.height / 2.0 + 2.0);
+                }
+            })
+            .run();
+        assert!(test_result.is_ok());
+    }
+}
@@ -0,0 +1,162 @@
+use crate::{config::PongConfig, rng::GameRng, systems::FixedTimestep, Ball, MatchSettings, Side};
+use amethyst::{
+    core::SystemDesc,
+    derive::SystemDesc,
+    ecs::prelude::{Component, DenseVecStorage, Entities, Join, Read, System, SystemData, World, Write, WriteStorage},
+};
+use serde::{Deserialize, Serialize};
+
+/// Marks a ball that waits in the centre of the arena until it is served.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Serving {
+    /// Seconds until the ball is served.
+    pub countdown: f32,
+    /// Player the ball is served towards, the one who conceded the last point. `None` picks a
+    /// random side, e.g. for the first serve of a match.
+    pub towards: Option<Side>,
+}
+
+impl Serving {
+    pub const fn new(countdown: f32, towards: Option<Side>) -> Self {
+        Self { countdown, towards }
+    }
+}
+
+impl Component for Serving {
+    type Storage = DenseVecStorage<Self>;
+}
+
+/// Returns the velocity of a ball served towards the player on `towards`, at `angle` degrees
+/// from the straight line to their wall. Positive angles turn the serve upwards, or to the right
+/// for the top and bottom players.
+pub fn serve_velocity(speed: f32, towards: Side, angle: f32) -> [f32; 2] {
+    let normal = towards.normal();
+    let tangent = [normal[1].abs(), normal[0].abs()];
+    let angle = angle.to_radians();
+    let (across, along) = (speed * angle.cos(), speed * angle.sin());
+    [
+        -normal[0] * across + tangent[0] * along,
+        -normal[1] * across + tangent[1] * along,
+    ]
+}
+
+/// This system counts down every `Serving` ball and serves it once its countdown expires. The
+/// serve angle, and the side of a serve without a receiver out of the sides guarded in the
+/// `GameMode`, are drawn from the `GameRng`.
+#[derive(SystemDesc)]
+pub struct ServeSystem;
+
+impl<'s> System<'s> for ServeSystem {
+    type SystemData = (
+        Entities<'s>,
+        WriteStorage<'s, Ball>,
+        WriteStorage<'s, Serving>,
+        Read<'s, FixedTimestep>,
+        Read<'s, PongConfig>,
+        Read<'s, MatchSettings>,
+        Write<'s, GameRng>,
+    );
+
+    fn run(
+        &mut self,
+        (entities, mut balls, mut servings, timestep, config, match_settings, mut rng): Self::SystemData,
+    ) {
+        let sides = match_settings.mode.sides();
+        let speed = config.ball.velocity[0].hypot(config.ball.velocity[1]);
+        let mut served = Vec::new();
+        for (entity, ball, serving) in (&entities, &mut balls, &mut servings).join() {
+            serving.countdown -= timestep.step();
+            if serving.countdown > 0.0 {
+                continue;
+            }
+
+            let towards = serving.towards.unwrap_or_else(|| {
+                let index = (rng.gen_range(0.0, 1.0) * sides.len() as f32) as usize;
+                sides[index.min(sides.len() - 1)]
+            });
+            let angle = rng.gen_range(-config.serve.max_angle, config.serve.max_angle);
+            ball.velocity = serve_velocity(speed, towards, angle);
+            served.push(entity);
+        }
+
+        for entity in served {
+            servings.remove(entity);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::states::{initialise_ball, load_sprite_sheet};
+    use amethyst::{core::transform::Transform, ecs::prelude::WorldExt, prelude::Builder};
+    use assert_approx_eq::assert_approx_eq;
+    use test_case::test_case;
+
+    #[test_case(10.0, Side::Left, 0.0 => [-10.0, 0.0] ; "straight to the left")]
+    #[test_case(10.0, Side::Right, 0.0 => [10.0, 0.0] ; "straight to the right")]
+    #[test_case(10.0, Side::Right, 30.0 => [8.66, 5.0] ; "upwards")]
+    #[test_case(10.0, Side::Left, -30.0 => [-8.66, -5.0] ; "downwards")]
+    #[test_case(10.0, Side::Top, 0.0 => [0.0, 10.0] ; "straight to the top")]
+    #[test_case(10.0, Side::Bottom, 30.0 => [5.0, -8.66] ; "to the bottom and right")]
+    fn test_serve_velocity(speed: f32, towards: Side, angle: f32) -> [f32; 2] {
+        let velocity = serve_velocity(speed, towards, angle);
+        [
+            (velocity[0] * 100.0).round() / 100.0,
+            (velocity[1] * 100.0).round() / 100.0,
+        ]
+    }
+
+    fn serving_ball(world: &mut World, serving: Serving) {
+        let root_entity = world.create_entity().with(Transform::default()).build();
+        let sprite_sheet_handle = load_sprite_sheet(world);
+        let ball = initialise_ball(world, root_entity, sprite_sheet_handle, 2.0, [0.0, 0.0], None);
+        world
+            .write_storage::<Serving>()
+            .insert(ball, serving)
+            .expect("ball was just created");
+    }
+
+    #[test]
+    fn serve_towards_conceding_player() {
+        amethyst::start_logger(amethyst::LoggerConfig::default());
+        let test_result = crate::test_harness::IntegrationTestApplication::pong_base()
+            .with_resource(GameRng::from_seed(1))
+            .with_setup(|world| serving_ball(world, Serving::new(0.0, Some(Side::Left))))
+            .with_system_single(ServeSystem, "serve_system", &[])
+            .with_assertion(|world| {
+                let config = PongConfig::default();
+                let speed = config.ball.velocity[0].hypot(config.ball.velocity[1]);
+                let max_vertical = speed * config.serve.max_angle.to_radians().sin();
+
+                assert_eq!(0, world.read_storage::<Serving>().count());
+                for ball in world.read_storage::<Ball>().join() {
+                    assert!(ball.velocity[0] < 0.0);
+                    assert!(ball.velocity[1].abs() <= max_vertical);
+                    assert_approx_eq!(ball.velocity[0].hypot(ball.velocity[1]), speed, 1e-3);
+                }
+            })
+            .run();
+        assert!(test_result.is_ok());
+    }
+
+    #[test]
+    fn wait_for_countdown() {
+        amethyst::start_logger(amethyst::LoggerConfig::default());
+        let test_result = crate::test_harness::IntegrationTestApplication::pong_base()
+            .with_resource(GameRng::from_seed(1))
+            .with_setup(|world| serving_ball(world, Serving::new(1.0, None)))
+            .with_system_single(ServeSystem, "serve_system", &[])
+            .with_assertion(|world| {
+                let step = FixedTimestep::default().step();
+                for (ball, serving) in (&world.read_storage::<Ball>(), &world.read_storage::<Serving>()).join() {
+                    assert_eq!(ball.velocity, [0.0, 0.0]);
+                    assert_approx_eq!(serving.countdown, 1.0 - step);
+                }
+                assert_eq!(1, world.read_storage::<Serving>().count());
+            })
+            .run();
+        assert!(test_result.is_ok());
+    }
+}
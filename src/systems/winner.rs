@@ -1,23 +1,77 @@
-use crate::{audio::Sounds, Ball, ScoreBoard};
+use crate::{
+    audio::Sounds,
+    config::{ArenaConfig, PongConfig, UserSettings},
+    level::Level,
+    systems::{Interpolated, Serving},
+    Ball, MatchSettings, ScoreBoard, Side,
+};
 use amethyst::{
     assets::AssetStorage,
     audio::{output::Output, Source},
     core::{transform::Transform, SystemDesc},
     derive::SystemDesc,
-    ecs::prelude::{Entity, Join, Read, ReadExpect, System, SystemData, World, Write, WriteStorage},
+    ecs::prelude::{Entities, Entity, Join, Read, ReadExpect, System, SystemData, World, Write, WriteStorage},
+    shrev::EventChannel,
     ui::UiText,
     utils::fps_counter::FpsCounter,
 };
-/// This system is responsible for checking if a ball has moved into a left or
-/// a right edge. Points are distributed to the player on the other side, and
-/// the ball is reset.
+
+/// Sent once a point decides the match according to the configured `MatchRules`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MatchOver {
+    pub winner: Side,
+    /// Final score of every player, indexed by `Side::index`.
+    pub scores: Vec<i32>,
+}
+
+/// The side whose goal a ball at `position` has entered, if any. Only the sides guarded in the
+/// `GameMode` have a goal, and balls beside the goal of the `Level` bounce off the goal posts
+/// instead.
+pub fn conceding_side(
+    position: [f32; 2],
+    radius: f32,
+    sides: &[Side],
+    arena: &ArenaConfig,
+    level: &Level,
+) -> Option<Side> {
+    sides.iter().copied().find(|side| {
+        let axis = side.axis();
+        let depth = position[1 - axis];
+        // the level only narrows the goals on the left and right edges
+        let in_goal = axis == 0 || level.in_goal(position[axis], arena.length(axis));
+        in_goal
+            && match side {
+                Side::Left | Side::Bottom => depth <= radius,
+                Side::Right | Side::Top => depth >= arena.length(1 - axis) - radius,
+            }
+    })
+}
+
+/// Whether a ball at `position` has left the arena, which it can only do beside a goal of the
+/// `Level` by slipping past a goal post.
+pub fn left_arena(position: [f32; 2], arena: &ArenaConfig) -> bool {
+    position[0] <= 0.0 || position[0] >= arena.width || position[1] <= 0.0 || position[1] >= arena.height
+}
+
+/// This system is responsible for checking if a ball has moved into the goal
+/// of a player, see `conceding_side`. The point goes to the player who hit the
+/// ball last, or to the player on the other side if the ball went in off the
+/// conceding paddle or without touching any paddle. The ball is reset to the
+/// centre to be served towards the conceding player. Every ball scores on its
+/// own; while more than one ball is in play, a ball that scored is removed
+/// instead of being reset. A ball that left the arena beside a goal scores no
+/// point, but is reset or removed all the same. When a point decides the match
+/// a `MatchOver` event is sent.
 #[derive(SystemDesc)]
 pub struct WinnerSystem;
 
 impl<'s> System<'s> for WinnerSystem {
     type SystemData = (
+        Entities<'s>,
         WriteStorage<'s, Ball>,
+        WriteStorage<'s, Serving>,
         WriteStorage<'s, Transform>,
+        WriteStorage<'s, Interpolated>,
         WriteStorage<'s, UiText>,
         Write<'s, ScoreBoard>,
         Read<'s, AssetStorage<Source>>,
@@ -25,72 +79,113 @@ impl<'s> System<'s> for WinnerSystem {
         ReadExpect<'s, ScoreText>,
         Option<Read<'s, Output>>,
         Read<'s, FpsCounter>,
+        Read<'s, PongConfig>,
+        Read<'s, MatchSettings>,
+        Read<'s, Level>,
+        Write<'s, EventChannel<MatchOver>>,
+        Read<'s, UserSettings>,
     );
 
     fn run(
         &mut self,
-        (mut balls,
+        (entities,
+            mut balls,
+            mut servings,
             mut transforms,
+            mut interpolated,
             mut text,
             mut score_board,
             storage,
             sounds,
             score_text,
             audio_output,
-            fps_counter): Self::SystemData,
+            fps_counter,
+            config,
+            match_settings,
+            level,
+            mut match_over_events,
+            user_settings): Self::SystemData,
     ) {
         if let Some(text) = text.get_mut(score_text.fps_display) {
             let fps = fps_counter.sampled_fps();
             text.text = format!("FPS: {:.*}", 2, fps);
         }
 
-        for (ball, transform) in (&mut balls, &mut transforms).join() {
-            use crate::ARENA_WIDTH;
+        let mut balls_in_play = (&balls).join().count();
+        for (entity, ball, transform, interpolated) in
+            (&entities, &mut balls, &mut transforms, (&mut interpolated).maybe()).join()
+        {
+            if score_board.winner(&config.rules).is_some() {
+                // No more points once the match is decided, e.g. by another ball in this step.
+                break;
+            }
+            let position = [transform.translation().x, transform.translation().y];
+            let sides = match_settings.mode.sides();
 
-            let ball_x = transform.translation().x;
+            let conceded = conceding_side(position, ball.radius, sides, &config.arena, &level);
+            if conceded.is_none() && !left_arena(position, &config.arena) {
+                continue;
+            }
 
-            let did_hit = if ball_x <= ball.radius {
-                // Right player scored on the left side.
-                // We top the score at 999 to avoid text overlap.
-                score_board.score_right = (score_board.score_right + 1).min(999);
-                if let Some(text) = text.get_mut(score_text.p2_score) {
-                    text.text = score_board.score_right.to_string();
-                }
-                true
-            } else if ball_x >= ARENA_WIDTH - ball.radius {
-                // Left player scored on the right side.
-                // We top the score at 999 to avoid text overlap.
-                score_board.score_left = (score_board.score_left + 1).min(999);
-                if let Some(text) = text.get_mut(score_text.p1_score) {
-                    text.text = score_board.score_left.to_string();
+            if let Some(conceded) = conceded {
+                let scorer = ball
+                    .last_hit
+                    .filter(|side| *side != conceded && sides.contains(side))
+                    .unwrap_or_else(|| conceded.opponent());
+                let score = score_board.add_point(scorer);
+                if let Some(text) = score_text.scores.get(scorer.index()).and_then(|entity| text.get_mut(*entity)) {
+                    text.text = score.to_string();
                 }
-                true
             } else {
-                false
-            };
-
-            if did_hit {
-                // Reset the ball.
-                ball.velocity[0] = -ball.velocity[0];
-                transform.set_translation_x(ARENA_WIDTH / 2.0);
-
-                // Play audio.
-                if let Some(ref output) = audio_output {
-                    if let Some(sound) = storage.get(&sounds.score_sfx) {
-                        if cfg!(not(test)) {
-                            output.play_once(sound, 1.0);
-                        }
+                log::warn!("Ball left the arena beside the goal at {:?}, serving it again", position);
+            }
+
+            if balls_in_play > 1 {
+                // Extra balls of a multi-ball match leave the arena once they scored.
+                entities.delete(entity).expect("ball entity is alive");
+                balls_in_play -= 1;
+            } else {
+                // Reset the ball to the centre, it is served towards the player who conceded.
+                ball.velocity = [0.0, 0.0];
+                ball.last_hit = None;
+                transform.set_translation_x(config.arena.width / 2.0);
+                transform.set_translation_y(config.arena.height / 2.0);
+                servings
+                    .insert(entity, Serving::new(config.serve.countdown, conceded))
+                    .expect("ball entity is alive");
+                if let Some(interpolated) = interpolated {
+                    // don't render the ball sweeping across the arena
+                    interpolated.teleport([transform.translation().x, transform.translation().y]);
+                }
+            }
+            if conceded.is_none() {
+                continue;
+            }
+
+            // Play audio.
+            if let Some(ref output) = audio_output {
+                if let Some(sound) = storage.get(&sounds.score_sfx) {
+                    if cfg!(not(test)) {
+                        output.play_once(sound, user_settings.sfx_volume);
                     }
                 }
             }
+
+            if let Some(winner) = score_board.winner(&config.rules) {
+                log::info!("{:?} player won the match!", winner);
+                match_over_events.single_write(MatchOver {
+                    winner,
+                    scores: score_board.scores().to_vec(),
+                });
+            }
         }
     }
 }
 
 /// Stores the entities that are displaying the player score with `UiText`.
 pub struct ScoreText {
-    pub p1_score: Entity,
-    pub p2_score: Entity,
+    /// Score of every player, indexed by `Side::index`.
+    pub scores: Vec<Entity>,
     pub fps_display: Entity,
 }
 
@@ -103,8 +198,23 @@ mod tests {
         states::{initialise_ball, initialise_score, load_sprite_sheet},
         ScoreBoard,
     };
-    use amethyst::{assets::ProgressCounter, ecs::prelude::WorldExt, prelude::Builder, ui::UiCreator};
+    use crate::config::MatchRules;
+    use amethyst::{
+        assets::ProgressCounter, ecs::prelude::WorldExt, prelude::Builder, shrev::ReaderId, ui::UiCreator,
+    };
     use assert_approx_eq::assert_approx_eq;
+    use test_case::test_case;
+
+    #[test_case([1.0, 45.0], 2 => Some(Side::Left) ; "left goal")]
+    #[test_case([159.0, 45.0], 2 => Some(Side::Right) ; "right goal")]
+    #[test_case([80.0, 89.0], 2 => None ; "top wall of two players")]
+    #[test_case([80.0, 89.0], 4 => Some(Side::Top) ; "top goal")]
+    #[test_case([80.0, 1.0], 4 => Some(Side::Bottom) ; "bottom goal")]
+    #[test_case([80.0, 45.0], 4 => None ; "centre")]
+    fn test_conceding_side(position: [f32; 2], players: usize) -> Option<Side> {
+        let sides = &Side::ALL[..players];
+        conceding_side(position, 2.0, sides, &ArenaConfig::default(), &Level::default())
+    }
 
     #[test]
     fn reset_ball_on_hitting_left_side() {
@@ -113,7 +223,7 @@ mod tests {
             .with_setup(|world| {
                 let mut progress = ProgressCounter::default();
                 initialise_audio(world, &mut progress);
-                world.insert(ScoreBoard::new());
+                world.insert(ScoreBoard::new(2));
 
                 let ui_root = Some(world.exec(|mut creator: UiCreator<'_>| creator.create("ui/hud.ron", ())));
                 if let Some(ui_root) = ui_root {
@@ -129,7 +239,7 @@ mod tests {
                             world,
                             root_entity,
                             sprite_sheet,
-                            crate::BALL_RADIUS,
+                            PongConfig::default().ball.radius,
                             [-10.0, 0.0],
                             Some([0.0, 0.0]),
                         );
@@ -142,15 +252,66 @@ mod tests {
                 let transforms = world.read_storage::<Transform>();
                 assert_eq!(1, balls.count());
 
-                for (ball, transform) in (&balls, &transforms).join() {
-                    assert_approx_eq!(ball.velocity[0], 10.0);
-                    assert_approx_eq!(transform.translation().x, crate::ARENA_WIDTH / 2.0);
+                for (ball, serving, transform) in (&balls, &world.read_storage::<Serving>(), &transforms).join() {
+                    // The ball waits in the centre to be served to the conceding player.
+                    assert_eq!(ball.velocity, [0.0, 0.0]);
+                    assert_eq!(serving.towards, Some(Side::Left));
+                    assert_approx_eq!(serving.countdown, PongConfig::default().serve.countdown);
+                    assert_approx_eq!(transform.translation().x, PongConfig::default().arena.width / 2.0);
+                    assert_approx_eq!(transform.translation().y, PongConfig::default().arena.height / 2.0);
                 }
             })
             .run();
         assert!(test_result.is_ok());
     }
 
+    #[test_case([80.0, 45.0] => false ; "centre")]
+    #[test_case([-1.0, 10.0] => true ; "past the left wall")]
+    #[test_case([80.0, 90.5] => true ; "past the top wall")]
+    fn test_left_arena(position: [f32; 2]) -> bool {
+        left_arena(position, &ArenaConfig::default())
+    }
+
+    #[test]
+    fn no_point_beside_the_goal() {
+        amethyst::start_logger(amethyst::LoggerConfig::default());
+        let test_result = crate::test_harness::IntegrationTestApplication::pong_base()
+            .with_resource(Level {
+                goal_height: Some(30.0),
+                ..Level::default()
+            })
+            .with_setup(|world| {
+                let mut progress = ProgressCounter::default();
+                initialise_audio(world, &mut progress);
+                world.insert(ScoreBoard::new(2));
+
+                let ui_root = world.exec(|mut creator: UiCreator<'_>| creator.create("ui/hud.ron", ()));
+                initialise_score(world, ui_root);
+
+                let root_entity = world.create_entity().with(Transform::default()).build();
+                let sprite_sheet = load_sprite_sheet(world);
+                let radius = PongConfig::default().ball.radius;
+                initialise_ball(world, root_entity, sprite_sheet, radius, [-10.0, 0.0], Some([0.0, 10.0]));
+            })
+            .with_system_single(WinnerSystem, "", &[])
+            .with_assertion(|world| {
+                let score_board = world.read_resource::<ScoreBoard>();
+                assert_eq!(score_board.scores(), &[0, 0]);
+                // the ball slipped past the goal post, so it is served again without a point
+                let arena = PongConfig::default().arena;
+                let servings = world.read_storage::<Serving>();
+                let transforms = world.read_storage::<Transform>();
+                for (serving, transform) in (&servings, &transforms).join() {
+                    assert_eq!(serving.towards, None);
+                    assert_approx_eq!(transform.translation().x, arena.width / 2.0);
+                    assert_approx_eq!(transform.translation().y, arena.height / 2.0);
+                }
+                assert_eq!(1, servings.count());
+            })
+            .run();
+        assert!(test_result.is_ok());
+    }
+
     #[test]
     fn reset_ball_on_hitting_right_side() {
         amethyst::start_logger(amethyst::LoggerConfig::default());
@@ -158,7 +319,7 @@ mod tests {
             .with_setup(|world| {
                 let mut progress = ProgressCounter::default();
                 initialise_audio(world, &mut progress);
-                world.insert(ScoreBoard::new());
+                world.insert(ScoreBoard::new(2));
 
                 let ui_root = Some(world.exec(|mut creator: UiCreator<'_>| creator.create("ui/hud.ron", ())));
                 if let Some(ui_root) = ui_root {
@@ -174,9 +335,9 @@ mod tests {
                             world,
                             root_entity,
                             sprite_sheet,
-                            crate::BALL_RADIUS,
+                            PongConfig::default().ball.radius,
                             [10.0, 0.0],
-                            Some([crate::ARENA_WIDTH, 0.0]),
+                            Some([PongConfig::default().arena.width, 0.0]),
                         );
                     }
                 }
@@ -187,9 +348,134 @@ mod tests {
                 let transforms = world.read_storage::<Transform>();
                 assert_eq!(1, balls.count());
 
-                for (ball, transform) in (&balls, &transforms).join() {
-                    assert_approx_eq!(ball.velocity[0], -10.0);
-                    assert_approx_eq!(transform.translation().x, crate::ARENA_WIDTH / 2.0);
+                for (ball, serving, transform) in (&balls, &world.read_storage::<Serving>(), &transforms).join() {
+                    assert_eq!(ball.velocity, [0.0, 0.0]);
+                    assert_eq!(serving.towards, Some(Side::Right));
+                    assert_approx_eq!(transform.translation().x, PongConfig::default().arena.width / 2.0);
+                    assert_approx_eq!(transform.translation().y, PongConfig::default().arena.height / 2.0);
+                }
+            })
+            .run();
+        assert!(test_result.is_ok());
+    }
+
+    #[test]
+    fn remove_extra_ball_on_scoring() {
+        amethyst::start_logger(amethyst::LoggerConfig::default());
+        let test_result = crate::test_harness::IntegrationTestApplication::pong_base()
+            .with_setup(|world| {
+                let mut progress = ProgressCounter::default();
+                initialise_audio(world, &mut progress);
+                world.insert(ScoreBoard::new(2));
+
+                let ui_root = world.exec(|mut creator: UiCreator<'_>| creator.create("ui/hud.ron", ()));
+                initialise_score(world, ui_root);
+
+                let root_entity = world.create_entity().with(Transform::default()).build();
+                let sprite_sheet_handle = load_sprite_sheet(world);
+                initialise_ball(world, root_entity, sprite_sheet_handle.clone(), 2.0, [-10.0, 0.0], Some([0.0, 20.0]));
+                initialise_ball(world, root_entity, sprite_sheet_handle, 2.0, [10.0, 10.0], Some([40.0, 20.0]));
+            })
+            .with_system_single(WinnerSystem, "", &[])
+            .with_assertion(|world| {
+                world.maintain();
+                assert_eq!(world.read_resource::<ScoreBoard>().score(Side::Right), 1);
+
+                // The scoring ball left the arena, the other one is still in play.
+                let balls = world.read_storage::<Ball>();
+                assert_eq!(1, balls.count());
+                for ball in balls.join() {
+                    assert_eq!(ball.velocity, [10.0, 10.0]);
+                }
+                assert_eq!(0, world.read_storage::<Serving>().count());
+            })
+            .run();
+        assert!(test_result.is_ok());
+    }
+
+    #[test]
+    fn send_match_over_on_deciding_point() {
+        amethyst::start_logger(amethyst::LoggerConfig::default());
+        let config = PongConfig {
+            rules: MatchRules {
+                winning_score: 1,
+                win_by: 1,
+            },
+            ..PongConfig::default()
+        };
+        let test_result = crate::test_harness::IntegrationTestApplication::pong_base()
+            .with_resource(config)
+            .with_setup(|world| {
+                let mut progress = ProgressCounter::default();
+                initialise_audio(world, &mut progress);
+                world.insert(ScoreBoard::new(2));
+
+                let mut events = EventChannel::<MatchOver>::new();
+                let reader_id = events.register_reader();
+                world.insert(events);
+                world.insert(reader_id);
+
+                let ui_root = world.exec(|mut creator: UiCreator<'_>| creator.create("ui/hud.ron", ()));
+                initialise_score(world, ui_root);
+
+                let root_entity = world.create_entity().with(Transform::default()).build();
+                let sprite_sheet_handle = load_sprite_sheet(world);
+                initialise_ball(
+                    world,
+                    root_entity,
+                    sprite_sheet_handle,
+                    PongConfig::default().ball.radius,
+                    [10.0, 0.0],
+                    Some([PongConfig::default().arena.width, 0.0]),
+                );
+            })
+            .with_system_single(WinnerSystem, "", &[])
+            .with_assertion(|world| {
+                let mut reader_id = world.write_resource::<ReaderId<MatchOver>>();
+                let events = world.read_resource::<EventChannel<MatchOver>>();
+                let events = events.read(&mut reader_id).cloned().collect::<Vec<_>>();
+                assert_eq!(
+                    events,
+                    vec![MatchOver {
+                        winner: Side::Left,
+                        scores: vec![1, 0],
+                    }]
+                );
+            })
+            .run();
+        assert!(test_result.is_ok());
+    }
+
+    #[test]
+    fn last_hitter_scores_on_the_top_side() {
+        amethyst::start_logger(amethyst::LoggerConfig::default());
+        let test_result = crate::test_harness::IntegrationTestApplication::pong_base()
+            .with_resource(MatchSettings {
+                mode: crate::GameMode::FourPlayer,
+                ..MatchSettings::default()
+            })
+            .with_setup(|world| {
+                let mut progress = ProgressCounter::default();
+                initialise_audio(world, &mut progress);
+                world.insert(ScoreBoard::new(4));
+
+                let ui_root = world.exec(|mut creator: UiCreator<'_>| creator.create("ui/hud.ron", ()));
+                initialise_score(world, ui_root);
+
+                let root_entity = world.create_entity().with(Transform::default()).build();
+                let sprite_sheet = load_sprite_sheet(world);
+                let height = PongConfig::default().arena.height;
+                let ball = initialise_ball(world, root_entity, sprite_sheet, 2.0, [0.0, 10.0], Some([60.0, height]));
+                if let Some(ball) = world.write_storage::<Ball>().get_mut(ball) {
+                    ball.last_hit = Some(Side::Bottom);
+                }
+            })
+            .with_system_single(WinnerSystem, "", &[])
+            .with_assertion(|world| {
+                assert_eq!(world.read_resource::<ScoreBoard>().scores(), &[0, 0, 0, 1]);
+                assert_eq!(world.read_resource::<ScoreText>().scores.len(), 4);
+                for serving in world.read_storage::<Serving>().join() {
+                    assert_eq!(serving.towards, Some(Side::Top));
                 }
             })
             .run();
@@ -1,11 +1,23 @@
 #![allow(clippy::use_self)]
-use std::{any::Any, marker::PhantomData, panic, sync::Mutex};
+use std::{
+    any::Any,
+    marker::PhantomData,
+    panic,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
 
 use crate::{
+    audio::initialise_audio,
+    bindings::GameBindings,
+    config::{PongConfig, UserSettings},
     game_data::{CustomGameData, CustomGameDataBuilder},
     initialize_paths,
-    states::GameplayState,
-    Ball, GameStateEvent, GameStateEventReader, Paddle,
+    replay::Replay,
+    states::{GameplayState, ReplayState},
+    systems::{AiPaddle, Effect, FixedTimestep, Interpolated, Obstacle, PowerUp, Serving, Stuck},
+    test_harness::{ConditionBarrierResult, SendMockEvents},
+    Ball, GameStateEvent, GameStateEventReader, MatchSettings, Paddle, ScoreBoard,
 };
 use amethyst::{
     assets::AssetStorage,
@@ -13,9 +25,9 @@ use amethyst::{
     core::{shrev::EventChannel, transform::*, RunNowDesc, SystemBundle, SystemDesc},
     ecs::prelude::*,
     error::Error,
-    input::{BindingTypes, InputBundle, StringBindings},
+    input::{BindingTypes, InputBundle},
     prelude::*,
-    renderer::{Camera, SpriteRender, SpriteSheet, Texture},
+    renderer::{resources::Tint, Camera, SpriteRender, SpriteSheet, Texture},
     shred::Resource,
     ui::{FontAsset, UiBundle, UiText, UiTransform},
     utils::fps_counter::FpsCounterBundle,
@@ -193,22 +205,33 @@ impl IntegrationTestApplication {
         use amethyst::assets::Loader;
         Self::blank()
             .with_bundle(TransformBundle::new())
-            .with_bundle(InputBundle::<StringBindings>::new())
+            .with_bundle(InputBundle::<GameBindings>::new())
             .with_bundle(FpsCounterBundle::default())
-            .with_bundle(UiBundle::<StringBindings>::new())
+            .with_bundle(UiBundle::<GameBindings>::new())
             .with_resource(ScreenDimensions::new(1920, 1280, 1.0))
             .with_resource(AssetStorage::<Source>::default())
             .with_resource(AssetStorage::<Texture>::default())
             .with_resource(AssetStorage::<SpriteSheet>::default())
             .with_resource(AssetStorage::<FontAsset>::default())
             .with_resource(GameplayState::Paused)
+            .with_resource(PongConfig::default())
+            .with_resource(MatchSettings::default())
+            .with_resource(UserSettings::default())
             .with_resource(EventChannel::<crate::TestEvent>::with_capacity(8))
             .with_setup(|world| {
                 world.register::<Transform>();
                 world.register::<Parent>();
                 world.register::<SpriteRender>();
                 world.register::<Paddle>();
+                world.register::<AiPaddle>();
+                world.register::<Interpolated>();
                 world.register::<Ball>();
+                world.register::<Serving>();
+                world.register::<PowerUp>();
+                world.register::<Effect>();
+                world.register::<Stuck>();
+                world.register::<Obstacle>();
+                world.register::<Tint>();
                 world.register::<Camera>();
                 world.register::<UiTransform>();
                 world.register::<UiText>();
@@ -219,6 +242,53 @@ impl IntegrationTestApplication {
             })
     }
 
+    /// Returns an application that plays `replay` back and fails unless the match is decided in the
+    /// recorded step with the recorded score. This turns a replay file into a regression test for
+    /// the gameplay systems.
+    #[allow(dead_code)]
+    pub fn replay_regression(replay: Replay) -> Self {
+        use amethyst::assets::ProgressCounter;
+
+        // Slow frames drop steps, so the playback is awaited by its steps rather than its duration.
+        let steps = replay.steps;
+        let seconds = f64::from(steps) / f64::from(replay.config.simulation.tick_rate.max(1.0));
+        let timeout = Duration::from_secs(30 + 4 * seconds as u64);
+        let expected_scores = replay.scores.clone();
+        let started = Mutex::new(None);
+        Self::pong_base()
+            .with_setup(|world| {
+                let mut progress = ProgressCounter::default();
+                initialise_audio(world, &mut progress);
+            })
+            .with_state(move || {
+                SendMockEvents::test_state(move |_world| Box::new(ReplayState::new(replay.clone())))
+                    .with_condition_barrier(
+                        move |world| {
+                            // The barrier gives up on its own, since the one of `SendMockEvents`
+                            // quits without failing the test.
+                            let started = *started.lock().unwrap().get_or_insert_with(Instant::now);
+                            if replay_finished(world, steps) || started.elapsed() > timeout {
+                                ConditionBarrierResult::ResumeImmediately
+                            } else {
+                                ConditionBarrierResult::ContinueEvaluating
+                            }
+                        },
+                        timeout * 2,
+                    )
+                    .with_step(move |world| {
+                        assert!(
+                            replay_finished(world, steps),
+                            "the playback did not finish within {:?}",
+                            timeout
+                        );
+                        let simulated = world.read_resource::<FixedTimestep>().simulated_steps();
+                        assert_eq!(simulated, steps, "the match was not decided in the recorded step");
+                        assert_eq!(world.read_resource::<ScoreBoard>().scores(), expected_scores.as_slice());
+                    })
+                    .end_test()
+            })
+    }
+
     /// Returns an application with the Transform, Input, and UI bundles.
     ///
     /// This also adds a `ScreenDimensions` resource to the `World` so that UI calculations can be
@@ -285,7 +355,7 @@ impl IntegrationTestApplication {
     where
         S: State<CustomGameData<'static, 'static>, GameStateEvent> + 'static,
     {
-        let (_, _, assets_dir) = initialize_paths()?;
+        let assets_dir = initialize_paths()?.assets_dir;
         let mut application_builder = CoreApplication::build(assets_dir, first_state)?;
         {
             let world = &mut application_builder.world;
@@ -634,6 +704,17 @@ impl IntegrationTestApplication {
     }
 }
 
+/// Whether the match played back by a `ReplayState` is decided, or the playback ran out of its
+/// `steps`.
+fn replay_finished(world: &World, steps: u32) -> bool {
+    let decided = match (world.try_fetch::<PongConfig>(), world.try_fetch::<ScoreBoard>()) {
+        (Some(config), Some(score_board)) => score_board.winner(&config.rules).is_some(),
+        _ => false,
+    };
+    let simulated = world.try_fetch::<FixedTimestep>().map_or(0, |timestep| timestep.simulated_steps());
+    decided || simulated >= steps
+}
+
 #[cfg(test)]
 mod test {
     use std::marker::PhantomData;
@@ -1,15 +1,24 @@
 mod integration_test_application;
 mod raw_file_loader_source;
 mod send_mock_events;
+mod ui_helpers;
 
 pub use self::{
     integration_test_application::IntegrationTestApplication, raw_file_loader_source::RawFileLoaderSource,
     send_mock_events::ConditionBarrierResult, send_mock_events::SendMockEvents,
+    ui_helpers::{click, find_ui, wait_for_ui},
 };
 use crate::{game_data::CustomGameData, GameStateEvent};
 
 use crate::TestEvent;
 use amethyst::Trans;
+use std::path::PathBuf;
+
+/// A path called `name` below the temporary directory, unique to this test process.
+#[allow(dead_code)]
+pub fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("amethyst-2d-playground-{}-{}", std::process::id(), name))
+}
 
 pub fn handle_test_event(test_event: &TestEvent) -> Trans<CustomGameData<'static, 'static>, GameStateEvent> {
     match test_event {
@@ -0,0 +1,39 @@
+use crate::test_harness::ConditionBarrierResult;
+use amethyst::{
+    core::shrev::EventChannel,
+    ecs::prelude::{Entity, World, Write},
+    ui::{UiEvent, UiEventType, UiFinder},
+};
+
+/// Looks up the UI entity created from a prefab element with the given `id`.
+#[allow(dead_code)]
+pub fn find_ui(world: &mut World, id: &str) -> Option<Entity> {
+    let mut entity: Option<Entity> = None;
+    world.exec(|ui_finder: UiFinder<'_>| {
+        entity = ui_finder.find(id);
+    });
+    entity
+}
+
+/// Returns a condition barrier that waits until the UI element `id` has been created, which is
+/// once the state's UI prefab has finished loading.
+#[allow(dead_code)]
+pub fn wait_for_ui(id: &'static str) -> impl Fn(&mut World) -> ConditionBarrierResult + Send + Sync + 'static {
+    move |world| {
+        if find_ui(world, id).is_some() {
+            ConditionBarrierResult::ResumeImmediately
+        } else {
+            ConditionBarrierResult::ContinueEvaluating
+        }
+    }
+}
+
+/// Returns a step that clicks the button `id`, the way the mouse would.
+#[allow(dead_code)]
+pub fn click(id: &'static str) -> impl Fn(&mut World) + Send + Sync + 'static {
+    move |world| {
+        let button = find_ui(world, id).unwrap_or_else(|| panic!("Could not find button {}", id));
+        let mut events: Write<'_, EventChannel<UiEvent>> = world.system_data();
+        events.single_write(UiEvent::new(UiEventType::Click, button));
+    }
+}